@@ -4,13 +4,14 @@
 //! Continuous Double Auction engine with the existing energy trading infrastructure.
 
 use crate::blockchain::node::BlockchainNode;
+use crate::blockchain::transactions::{EnergyTransaction, EnergyTransactionEnvelope};
 use crate::infrastructure::database::DatabaseManager;
 use crate::infrastructure::grid::GridManager;
 use crate::runtime::continuous_double_auction::{
-    ContinuousDoubleAuction, MarketDepth, OrderBookEvent, TradeExecution
+    ContinuousDoubleAuction, MarketDepth, OrderBookEvent, SettlementStatus, TradeExecution
 };
 use crate::types::*;
-use crate::utils::SystemResult;
+use crate::utils::{SystemError, SystemResult};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::{broadcast, RwLock};
@@ -28,6 +29,8 @@ pub struct EnhancedTradingService {
     grid_manager: Option<Arc<GridManager>>,
     /// Real-time market data cache
     market_data_cache: Arc<RwLock<HashMap<String, MarketData>>>,
+    /// Executed trades awaiting finality of the transaction that records them, by trade ID
+    settlements: Arc<RwLock<HashMap<Uuid, TrackedSettlement>>>,
     /// Event listeners
     event_receiver: Arc<RwLock<Option<broadcast::Receiver<OrderBookEvent>>>>,
     /// Running state
@@ -50,6 +53,7 @@ impl EnhancedTradingService {
             database_manager: Some(database_manager),
             grid_manager: Some(grid_manager),
             market_data_cache: Arc::new(RwLock::new(HashMap::new())),
+            settlements: Arc::new(RwLock::new(HashMap::new())),
             event_receiver: Arc::new(RwLock::new(event_receiver)),
             running: Arc::new(RwLock::new(false)),
         })
@@ -66,6 +70,7 @@ impl EnhancedTradingService {
             database_manager: None,
             grid_manager: None,
             market_data_cache: Arc::new(RwLock::new(HashMap::new())),
+            settlements: Arc::new(RwLock::new(HashMap::new())),
             event_receiver: Arc::new(RwLock::new(event_receiver)),
             running: Arc::new(RwLock::new(false)),
         })
@@ -103,6 +108,9 @@ impl EnhancedTradingService {
     }
     
     /// Place a new energy order using CDA
+    ///
+    /// The returned trades are the terms the buyer and seller sign; the buyer records each on
+    /// chain through [`Self::submit_trade`].
     pub async fn place_order(&self, order: EnergyOrder) -> SystemResult<PlaceOrderResult> {
         // Validate order
         self.validate_order(&order).await?;
@@ -122,8 +130,8 @@ impl EnhancedTradingService {
             // Store trade in database
             self.store_trade(&trade).await?;
             
-            // Submit to blockchain
-            self.submit_trade_to_blockchain(&trade).await?;
+            // Await the parties' signed transaction
+            self.track_settlement(execution).await;
             
             trades.push(trade);
         }
@@ -234,7 +242,7 @@ impl EnhancedTradingService {
                 id: Uuid::new_v4(),
                 order_type: OrderType::Buy,
                 energy_amount: bid.total_quantity,
-                price_per_unit: bid.price as Balance,
+                price_per_unit: bid.price,
                 location: location.clone(),
                 energy_source: energy_source.clone(),
                 timestamp: bid.timestamp,
@@ -250,7 +258,7 @@ impl EnhancedTradingService {
                 id: Uuid::new_v4(),
                 order_type: OrderType::Sell,
                 energy_amount: ask.total_quantity,
-                price_per_unit: ask.price as Balance,
+                price_per_unit: ask.price,
                 location: location.clone(),
                 energy_source: energy_source.clone(),
                 timestamp: ask.timestamp,
@@ -276,7 +284,7 @@ impl EnhancedTradingService {
         Ok(EnergyTrade {
             trade_id: execution.trade_id.to_string(),
             energy_amount: execution.quantity,
-            price_per_unit: execution.price as Balance,
+            price_per_unit: execution.price,
             buyer_id: execution.buyer_id.clone(),
             seller_id: execution.seller_id.clone(),
             timestamp: execution.execution_time.timestamp() as u64,
@@ -286,12 +294,12 @@ impl EnhancedTradingService {
             id: execution.trade_id.to_string(),
             buy_order_id: execution.buy_order_id.to_string(),
             sell_order_id: execution.sell_order_id.to_string(),
-            price_per_kwh: execution.price as Balance,
-            total_price: (execution.price * execution.quantity) as Balance,
-            grid_fee: execution.fees.grid_fee as Balance,
+            price_per_kwh: execution.price,
+            total_price: execution.price.total(execution.quantity),
+            grid_fee: execution.fees.grid_fee,
             energy_source: execution.energy_source.clone(),
             carbon_offset: CarbonOffset {
-                offset_credits: execution.quantity * 0.5, // Simplified calculation
+                offset_credits: FixedPoint::from_millionths(execution.quantity.as_wh() as i64 * 500), // Simplified calculation: 0.5 per kWh
                 verified: true,
                 certification_body: "Thai Energy Authority".to_string(),
                 timestamp: execution.execution_time,
@@ -310,10 +318,10 @@ impl EnhancedTradingService {
         } else {
             let (best_bid, best_ask) = (depth.bids.first().map(|b| b.price), depth.asks.first().map(|a| a.price));
             match (best_bid, best_ask) {
-                (Some(bid), Some(ask)) => (bid + ask) / 2.0,
+                (Some(bid), Some(ask)) => bid.midpoint(ask),
                 (Some(bid), None) => bid,
                 (None, Some(ask)) => ask,
-                (None, None) => 5000.0, // Default price
+                (None, None) => TokenPrice::from_satang(5000), // Default price
             }
         };
         
@@ -351,8 +359,7 @@ impl EnhancedTradingService {
         
         let volume = trades.iter()
             .filter(|t| t.execution_time >= day_ago)
-            .map(|t| t.quantity)
-            .sum();
+            .fold(EnergyAmount::ZERO, |volume, t| volume.saturating_add(t.quantity));
         
         Ok(volume)
     }
@@ -367,12 +374,11 @@ impl EnhancedTradingService {
             .collect();
         
         if recent_trades.is_empty() {
-            return Ok((5000.0, 5000.0)); // Default range
+            return Ok((TokenPrice::from_satang(5000), TokenPrice::from_satang(5000))); // Default range
         }
         
-        let prices: Vec<TokenPrice> = recent_trades.iter().map(|t| t.price).collect();
-        let high = prices.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-        let low = prices.iter().fold(f64::INFINITY, |a, &b| a.min(b));
+        let high = recent_trades.iter().map(|t| t.price).max().unwrap_or_default();
+        let low = recent_trades.iter().map(|t| t.price).min().unwrap_or_default();
         
         Ok((high, low))
     }
@@ -397,11 +403,11 @@ impl EnhancedTradingService {
         let current_price = recent_trades.first().unwrap().price;
         let old_price = recent_trades.last().unwrap().price;
         
-        if old_price == 0.0 {
+        if old_price.is_zero() {
             return Ok(0.0);
         }
         
-        Ok((current_price - old_price) / old_price)
+        Ok((current_price.as_satang() as f64 - old_price.as_satang() as f64) / old_price.as_satang() as f64)
     }
     
     /// Start event processing background task
@@ -434,7 +440,7 @@ impl EnhancedTradingService {
                 // Handle trade execution
                 let trade = self.convert_execution_to_trade(&execution).await?;
                 self.store_trade(&trade).await?;
-                self.submit_trade_to_blockchain(&trade).await?;
+                self.track_settlement(execution).await;
             },
             _ => {
                 // Handle other events as needed
@@ -479,21 +485,82 @@ impl EnhancedTradingService {
         Ok(())
     }
     
-    /// Process settlements
-    async fn process_settlements(&self) -> SystemResult<()> {
-        // This would handle settlement processing
-        // For now, we'll just log that it's running
-        crate::utils::logging::log_info("EnhancedTradingService", "Settlements processed");
+    /// Submit the buyer's `ExecuteTrade` transaction recording a trade executed by this service
+    ///
+    /// The trade stays `Pending` until the block including the transaction is final, when
+    /// [`Self::process_settlements`] marks it `Settled`.
+    pub async fn submit_trade(&self, envelope: EnergyTransactionEnvelope) -> SystemResult<()> {
+        let blockchain_node = self.blockchain_node.as_ref().ok_or_else(|| {
+            SystemError::Internal("Enhanced trading service has no blockchain node".to_string())
+        })?;
+        let trade_id = match &envelope.transaction {
+            EnergyTransaction::ExecuteTrade { trade, .. } => Uuid::parse_str(&trade.trade_id).ok(),
+            _ => None,
+        };
+        let trade_id = match trade_id {
+            Some(trade_id) if self.settlements.read().await.contains_key(&trade_id) => trade_id,
+            _ => return Err(SystemError::Validation(format!(
+                "Transaction {} does not execute a trade of this market", hex::encode(envelope.hash)
+            ))),
+        };
+        
+        let tx_hash = envelope.hash;
+        blockchain_node.engine().submit_transaction(envelope).await.map_err(|e| {
+            SystemError::Trading(format!("Trade {} not submitted: {}", trade_id, e))
+        })?;
+        if let Some(settlement) = self.settlements.write().await.get_mut(&trade_id) {
+            settlement.tx_hash = Some(tx_hash);
+        }
+        Ok(())
+    }
+    
+    /// Track settlement of an executed trade until its `ExecuteTrade` transaction is final
+    async fn track_settlement(&self, mut execution: TradeExecution) {
+        execution.settlement_status = SettlementStatus::Pending;
+        self.settlements.write().await.entry(execution.trade_id)
+            .or_insert(TrackedSettlement { execution, tx_hash: None });
+    }
+    
+    /// Get the settlement status of a tracked trade
+    pub async fn settlement_status(&self, trade_id: &Uuid) -> Option<SettlementStatus> {
+        self.settlements.read().await.get(trade_id)
+            .map(|settlement| settlement.execution.settlement_status.clone())
+    }
+    
+    /// Mark pending trades `Settled` once the transaction recording them is final
+    pub async fn process_settlements(&self) -> SystemResult<()> {
+        let consensus = match &self.blockchain_node {
+            Some(blockchain_node) => blockchain_node.engine().consensus(),
+            None => return Ok(()),
+        };
+        
+        let mut settled = 0;
+        for settlement in self.settlements.write().await.values_mut() {
+            let Some(tx_hash) = &settlement.tx_hash else { continue };
+            if settlement.execution.settlement_status == SettlementStatus::Pending
+                && consensus.is_transaction_finalized(tx_hash).await
+            {
+                settlement.execution.settlement_status = SettlementStatus::Settled;
+                settled += 1;
+            }
+        }
+        
+        if settled > 0 {
+            crate::utils::logging::log_info(
+                "EnhancedTradingService",
+                &format!("Settled {} trades at finalized height {}", settled, consensus.finalized_height())
+            );
+        }
         Ok(())
     }
     
     /// Validate order
     async fn validate_order(&self, order: &EnergyOrder) -> SystemResult<()> {
-        if order.energy_amount <= 0.0 {
+        if order.energy_amount.is_zero() {
             return Err(crate::utils::SystemError::Validation("Invalid energy amount".to_string()));
         }
         
-        if (order.price_per_unit as f64) <= 0.0 {
+        if order.price_per_unit.is_zero() {
             return Err(crate::utils::SystemError::Validation("Invalid price".to_string()));
         }
         
@@ -514,13 +581,6 @@ impl EnhancedTradingService {
         Ok(())
     }
     
-    /// Submit trade to blockchain
-    async fn submit_trade_to_blockchain(&self, _trade: &EnergyTrade) -> SystemResult<()> {
-        // Implementation would submit to blockchain
-        // For now, always return OK
-        Ok(())
-    }
-    
     /// Verify order ownership
     async fn verify_order_ownership(&self, _order_id: Uuid, _account_id: &AccountId) -> SystemResult<()> {
         // Implementation would verify ownership
@@ -537,6 +597,13 @@ pub struct PlaceOrderResult {
     pub status: OrderStatus,
 }
 
+/// Executed trade together with the transaction that records it on chain, once submitted
+#[derive(Debug, Clone)]
+struct TrackedSettlement {
+    execution: TradeExecution,
+    tx_hash: Option<[u8; 32]>,
+}
+
 impl Clone for EnhancedTradingService {
     fn clone(&self) -> Self {
         Self {
@@ -545,6 +612,7 @@ impl Clone for EnhancedTradingService {
             database_manager: self.database_manager.clone(),
             grid_manager: self.grid_manager.clone(),
             market_data_cache: self.market_data_cache.clone(),
+            settlements: self.settlements.clone(),
             event_receiver: Arc::new(RwLock::new(None)), // Can't clone receiver
             running: self.running.clone(),
         }
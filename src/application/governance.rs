@@ -124,9 +124,9 @@ impl GovernanceService {
             no_votes: 0,
             abstain_votes: 0,
             total_voting_power: 0,
-            participation_rate: 0.0,
+            participation_rate: FixedPoint::ZERO,
             total_eligible: 0,
-            turnout_percentage: 0.0,
+            turnout_percentage: FixedPoint::ZERO,
         })
     }
     
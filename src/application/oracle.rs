@@ -74,7 +74,7 @@ impl OracleService {
             _ => 4000,
         };
         
-        Ok(base_price as f64)
+        Ok(TokenPrice::from_satang(base_price))
     }
     
     /// Get weather data for energy production forecasting
@@ -96,16 +96,16 @@ impl OracleService {
         // Fetch grid status from external APIs
         Ok(GridStatus {
             region: location.region.clone(),
-            current_load: 7500.0,
-            max_capacity: 10000.0,
-            health: 0.85,
+            current_load: EnergyAmount::from_wh(7_500_000),
+            max_capacity: EnergyAmount::from_wh(10_000_000),
+            health: FixedPoint::from_millionths(850_000),
             last_updated: crate::utils::now(),
             // Additional fields for compatibility
             location: location.clone(),
-            capacity: 10000.0,
+            capacity: EnergyAmount::from_wh(10_000_000),
             congestion_level: CongestionLevel::Medium,
-            stability_score: 0.85,
-            outage_risk: 0.15,
+            stability_score: FixedPoint::from_millionths(850_000),
+            outage_risk: FixedPoint::from_millionths(150_000),
             updated_at: crate::utils::now(),
         })
     }
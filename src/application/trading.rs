@@ -133,8 +133,8 @@ impl TradingService {
             volume_24h,
             price_change_24h: 0.0,
             trades_24h,
-            high_24h: current_price + 1000.0,
-            low_24h: current_price - 1000.0,
+            high_24h: current_price.checked_add(TokenPrice::from_satang(1000)).unwrap_or(current_price),
+            low_24h: current_price.saturating_sub(TokenPrice::from_satang(1000)),
             timestamp: crate::utils::now(),
         })
     }
@@ -245,12 +245,12 @@ impl TradingService {
     
     async fn calculate_current_price(&self, location: &GridLocation) -> SystemResult<TokenPrice> {
         // Calculate current market price
-        Ok(5000.0) // Default price
+        Ok(TokenPrice::from_satang(5000)) // Default price
     }
     
     async fn calculate_volume_24h(&self, location: &GridLocation) -> SystemResult<EnergyAmount> {
         // Calculate 24h volume
-        Ok(10000.0) // Default volume
+        Ok(EnergyAmount::from_wh(10_000_000)) // Default volume
     }
     
     async fn calculate_trades_24h(&self, location: &GridLocation) -> SystemResult<u32> {
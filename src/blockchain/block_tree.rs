@@ -0,0 +1,153 @@
+//! # Block Tree
+//!
+//! Competing blocks that do not extend the chain head, together with the post-state of
+//! recent blocks needed to validate them and to switch over to them.
+//!
+//! The canonical chain stays in `BlockchainState::blocks`; the tree holds the side
+//! branches hanging off it. Fork choice prefers the branch carrying the most authority
+//! signatures — the producer's signature on each block plus every precommit for it — and
+//! never leaves the finalized block. Forks more than [`MAX_FORK_DEPTH`] blocks below the
+//! head are not tracked.
+
+use crate::blockchain::consensus::{Authority, EnergyBlock};
+use crate::blockchain::transactions::{EnergyBalanceState, EnergyTransactionEnvelope};
+use crate::blockchain::liveness::ValidatorLiveness;
+use crate::blockchain::validator_set::ValidatorProposals;
+use crate::types::{AccountId, Hash};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Number of blocks below the head a competing branch may fork off
+pub const MAX_FORK_DEPTH: u32 = 64;
+
+/// Side branches and recent block post-states
+#[derive(Debug, Clone, Default)]
+pub struct BlockTree {
+    /// Validated blocks that are not part of the canonical chain, by hash
+    side_blocks: HashMap<Hash, EnergyBlock>,
+    /// Post-state of recent canonical blocks and of every side block, by block hash
+    checkpoints: HashMap<Hash, BlockCheckpoint>,
+}
+
+/// Chain state right after a block, used to validate children of that block
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockCheckpoint {
+    /// Number of the block
+    pub number: u32,
+    /// Account balances after the block
+    pub balances: HashMap<AccountId, EnergyBalanceState>,
+    /// Validator rotation index after the block
+    pub validator_index: usize,
+    /// Validator rotation round after the block
+    pub round: u64,
+    /// Validators in the rotation after the block, in rotation order
+    pub active_validators: Vec<AccountId>,
+    /// Registered authorities after the block, including slashed ones
+    pub authorities: HashMap<AccountId, Authority>,
+    /// Validator-set proposals under vote and passed changes after the block
+    pub validator_proposals: ValidatorProposals,
+    /// Produced and missed slots per authority after the block
+    pub liveness: HashMap<AccountId, ValidatorLiveness>,
+}
+
+/// Switch of the canonical chain to a heavier branch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainReorg {
+    /// Number of the last block both chains share
+    pub common_ancestor: u32,
+    /// Hashes of the blocks removed from the canonical chain, lowest first
+    pub retracted: Vec<Hash>,
+    /// Hashes of the blocks added to the canonical chain, lowest first
+    pub enacted: Vec<Hash>,
+    /// Transactions of retracted blocks that are not part of the new chain
+    pub orphaned_transactions: Vec<EnergyTransactionEnvelope>,
+}
+
+impl BlockTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a block is held as a side block
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.side_blocks.contains_key(hash)
+    }
+
+    /// Get a side block by hash
+    pub fn side_block(&self, hash: &Hash) -> Option<&EnergyBlock> {
+        self.side_blocks.get(hash)
+    }
+
+    /// Number of side blocks held
+    pub fn side_block_count(&self) -> usize {
+        self.side_blocks.len()
+    }
+
+    /// Get the post-state of a block
+    pub fn checkpoint(&self, hash: &Hash) -> Option<&BlockCheckpoint> {
+        self.checkpoints.get(hash)
+    }
+
+    /// Record the post-state of a block
+    pub fn insert_checkpoint(&mut self, hash: Hash, checkpoint: BlockCheckpoint) {
+        self.checkpoints.insert(hash, checkpoint);
+    }
+
+    /// Add a validated block that does not extend the canonical head
+    pub fn insert_side_block(&mut self, block: EnergyBlock, checkpoint: BlockCheckpoint) {
+        self.checkpoints.insert(block.header.hash.clone(), checkpoint);
+        self.side_blocks.insert(block.header.hash.clone(), block);
+    }
+
+    /// Keep a block a reorg removed from the canonical chain; its checkpoint is already held
+    pub fn insert_retracted(&mut self, block: EnergyBlock) {
+        self.side_blocks.insert(block.header.hash.clone(), block);
+    }
+
+    /// Side blocks no other side block builds on
+    pub fn tips(&self) -> Vec<&EnergyBlock> {
+        let parents: HashSet<&Hash> = self.side_blocks.values().map(|block| &block.header.parent_hash).collect();
+        self.side_blocks.values().filter(|block| !parents.contains(&block.header.hash)).collect()
+    }
+
+    /// Side blocks from the canonical chain up to `tip`, lowest first
+    ///
+    /// Returns `None` if the branch does not lead back to a block of `canonical`.
+    pub fn branch(&self, tip: &Hash, canonical: &[EnergyBlock]) -> Option<Vec<&EnergyBlock>> {
+        let mut branch = vec![self.side_blocks.get(tip)?];
+        while let Some(parent) = self.side_blocks.get(&branch[branch.len() - 1].header.parent_hash) {
+            branch.push(parent);
+        }
+        branch.reverse();
+
+        let first = &branch[0].header;
+        let ancestor = canonical.get(first.number.checked_sub(1)? as usize)?;
+        (ancestor.header.hash == first.parent_hash).then_some(branch)
+    }
+
+    /// Move the blocks of a branch out of the side tree
+    pub fn take_branch(&mut self, hashes: &[Hash]) -> Vec<EnergyBlock> {
+        hashes.iter().filter_map(|hash| self.side_blocks.remove(hash)).collect()
+    }
+
+    /// Drop side branches forking off below `floor` and checkpoints of canonical blocks below it
+    ///
+    /// `floor` is the lowest canonical block the chain may still be rewound to.
+    pub fn prune(&mut self, canonical: &[EnergyBlock], floor: u32) {
+        let keep: HashSet<Hash> = self.side_blocks.keys()
+            .filter(|hash| {
+                self.branch(hash, canonical)
+                    .is_some_and(|branch| branch[0].header.number > floor)
+            })
+            .cloned()
+            .collect();
+        self.side_blocks.retain(|hash, _| keep.contains(hash));
+
+        let side_blocks = &self.side_blocks;
+        self.checkpoints.retain(|hash, checkpoint| {
+            side_blocks.contains_key(hash)
+                || (checkpoint.number >= floor
+                    && canonical.get(checkpoint.number as usize).is_some_and(|block| block.header.hash == *hash))
+        });
+    }
+}
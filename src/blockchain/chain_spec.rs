@@ -0,0 +1,454 @@
+//! # Chain Specification
+//!
+//! A chain spec declares everything the genesis block is derived from: the network id, the
+//! genesis timestamp, the initial authorities with their public keys, the initial token
+//! balances per energy source and the initial currency balances in satang. It also fixes the fee, contract gas and limits and governance parameters
+//! every node of the network has to agree on, and the metering oracles and carbon credit
+//! issuers whose word the state transition takes. Nodes loading the same spec file derive an identical genesis
+//! block.
+//!
+//! `BlockchainConfig::chain_spec` names either the built-in development chain (`"dev"`) or
+//! the path of a JSON spec file. Spec files are generated with the `build-spec` command.
+
+use crate::blockchain::consensus::{
+    Authority, BlockHeader, ConsensusEngine, EnergyBlock, EnergyBlockStats, ValidatorSignature,
+};
+use crate::blockchain::contract_runtime::{GasSchedule, GAS_SCHEDULE_VERSION};
+use crate::blockchain::state_transition::TrustedParties;
+use crate::blockchain::state_trie::StateTrie;
+use crate::blockchain::transactions::EnergyBalanceState;
+use crate::blockchain::validator_set::{ValidatorCandidate, ValidatorSetChange};
+use crate::config::{BlockchainConfig, SmartContractConfig};
+use crate::types::{AccountId, Balance, EnergyAmount, EnergySource, FixedPoint, Hash, TokenPrice};
+use crate::utils::{SystemError, SystemResult};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// `chain_spec` value selecting the built-in development chain
+pub const DEVELOPMENT_CHAIN: &str = "dev";
+
+/// Genesis timestamp of the development chain, 2025-01-01T00:00:00Z
+const DEVELOPMENT_GENESIS_TIMESTAMP: u64 = 1_735_689_600;
+
+/// Metering oracle of the development chain; its key is derived from the name
+pub const DEVELOPMENT_ORACLE: &str = "GridMeteringOracle";
+
+/// Carbon credit issuer of the development chain; its key is derived from the name
+pub const DEVELOPMENT_CARBON_CREDIT_ISSUER: &str = "ThaiCarbonCreditRegistry";
+
+/// Account the genesis block is attributed to
+const GENESIS_VALIDATOR: &str = "GenesisValidator";
+
+/// Everything the genesis block and the chain-wide parameters are derived from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainSpec {
+    /// Human readable chain name
+    pub name: String,
+    /// Network id, used in place of `BlockchainConfig::network`
+    pub network_id: String,
+    /// Genesis block timestamp as unix seconds
+    pub genesis_timestamp: u64,
+    /// Initial authorities, scheduled in the given order
+    pub authorities: Vec<GenesisAuthority>,
+    /// Initial token balances per energy source
+    #[serde(default)]
+    pub balances: BTreeMap<AccountId, HashMap<EnergySource, Balance>>,
+    /// Initial currency balances in satang
+    #[serde(default)]
+    pub currency: BTreeMap<AccountId, Balance>,
+    /// Block timing every validator follows
+    #[serde(default)]
+    pub consensus: ConsensusParameters,
+    /// Fee parameters
+    pub fees: FeeParameters,
+    /// Gas charged for contract execution
+    #[serde(default)]
+    pub gas_schedule: GasSchedule,
+    /// Limits on contract deployment and execution
+    #[serde(default)]
+    pub contracts: ContractParameters,
+    /// Governance parameters
+    pub governance: GovernanceParameters,
+    /// Metering oracles whose signatures attest production reports
+    #[serde(default)]
+    pub oracles: Vec<GenesisAuthority>,
+    /// Accounts allowed to issue carbon credits
+    #[serde(default)]
+    pub carbon_credit_issuers: BTreeSet<AccountId>,
+}
+
+/// Genesis validator or metering oracle, identified by its public key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenesisAuthority {
+    /// Validator account ID
+    pub account_id: AccountId,
+    /// Validator name/organization
+    pub name: String,
+    /// Hex-encoded ed25519 public key; without one, blocks from the authority do not verify
+    #[serde(default)]
+    pub public_key: String,
+}
+
+/// Block timing of the chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsensusParameters {
+    /// Block production time in seconds
+    pub block_time: u64,
+    /// Seconds after the block time at which a missed slot passes to the next validator
+    pub slot_timeout: u64,
+}
+
+/// Fee parameters of the chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeParameters {
+    /// Gas limit for transactions
+    pub gas_limit: u64,
+    /// Gas price in tokens
+    pub gas_price: u128,
+}
+
+/// Contract limits of the chain, which every node has to enforce alike
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractParameters {
+    /// Maximum gas a single contract execution may use
+    pub max_gas_limit: u64,
+    /// Maximum contract code size in bytes
+    pub max_contract_size: usize,
+    /// Maximum storage size of a contract in bytes
+    pub max_storage_size: usize,
+    /// Whether WebAssembly contracts may be deployed and executed
+    pub enable_wasm: bool,
+}
+
+/// Governance parameters of the chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GovernanceParameters {
+    /// Blocks per validator-set epoch
+    pub validator_rotation_interval: u64,
+    /// Maximum number of validators
+    pub max_validators: u32,
+    /// Precommits needed to finalize a block
+    pub authority_threshold: u32,
+}
+
+impl ChainSpec {
+    /// Load the chain spec a node configuration names
+    pub fn load(config: &BlockchainConfig) -> SystemResult<Self> {
+        if config.chain_spec.is_empty() || config.chain_spec == DEVELOPMENT_CHAIN {
+            return Ok(Self::development(config));
+        }
+        let json = std::fs::read_to_string(&config.chain_spec).map_err(|e| {
+            SystemError::Configuration(format!("Failed to read chain spec {}: {}", config.chain_spec, e))
+        })?;
+        Self::from_json(&json)
+    }
+
+    /// Development chain with the Thai energy authorities as initial validators
+    ///
+    /// Takes its network id and parameters from the node configuration. The keys of the
+    /// authorities, the [`DEVELOPMENT_ORACLE`] and the [`DEVELOPMENT_CARBON_CREDIT_ISSUER`] are
+    /// derived from their names, so a node started with one of the names as its node key
+    /// validates for that authority. The configured authority threshold is capped at the
+    /// number of development authorities so the chain can finalize.
+    pub fn development(config: &BlockchainConfig) -> Self {
+        let development = |name| GenesisAuthority::development(name).expect("development names are valid node keys");
+        let authorities = ["ThaiEnergyAuthority", "GridAuthorityThailand", "RenewableEnergyAuth"]
+            .into_iter()
+            .map(development)
+            .collect::<Vec<_>>();
+        let authority_threshold = config.authority_threshold.min(authorities.len() as u32);
+
+        Self {
+            name: "Development".to_string(),
+            network_id: config.network.clone(),
+            genesis_timestamp: DEVELOPMENT_GENESIS_TIMESTAMP,
+            authorities,
+            balances: BTreeMap::new(),
+            currency: BTreeMap::new(),
+            consensus: ConsensusParameters {
+                block_time: config.block_time,
+                slot_timeout: config.slot_timeout,
+            },
+            fees: FeeParameters {
+                gas_limit: config.gas_limit,
+                gas_price: config.gas_price,
+            },
+            gas_schedule: config.smart_contract_vm.gas_schedule.clone(),
+            contracts: ContractParameters::from(&config.smart_contract_vm),
+            governance: GovernanceParameters {
+                validator_rotation_interval: config.validator_rotation_interval,
+                max_validators: config.max_validators,
+                authority_threshold,
+            },
+            oracles: vec![development(DEVELOPMENT_ORACLE)],
+            carbon_credit_issuers: BTreeSet::from([development(DEVELOPMENT_CARBON_CREDIT_ISSUER).account_id]),
+        }
+    }
+
+    /// Parse and validate a JSON chain spec
+    pub fn from_json(json: &str) -> SystemResult<Self> {
+        let spec: Self = serde_json::from_str(json)
+            .map_err(|e| SystemError::Configuration(format!("Invalid chain spec: {}", e)))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Serialize the chain spec as pretty-printed JSON
+    pub fn to_json(&self) -> SystemResult<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| SystemError::Internal(format!("Failed to serialize chain spec: {}", e)))
+    }
+
+    /// Check that the spec describes a chain nodes can start
+    pub fn validate(&self) -> SystemResult<()> {
+        if self.network_id.is_empty() {
+            return Err(SystemError::Configuration("Chain spec network id cannot be empty".to_string()));
+        }
+        if self.authorities.is_empty() {
+            return Err(SystemError::Configuration("Chain spec must declare at least one authority".to_string()));
+        }
+        let mut seen = HashSet::new();
+        for authority in &self.authorities {
+            if !seen.insert(&authority.account_id) {
+                return Err(SystemError::Configuration(format!(
+                    "Chain spec declares authority {} twice", authority.account_id
+                )));
+            }
+            authority.public_key_bytes()?;
+        }
+        if self.authorities.len() > self.governance.max_validators as usize {
+            return Err(SystemError::Configuration(format!(
+                "Chain spec declares {} authorities but allows at most {} validators",
+                self.authorities.len(), self.governance.max_validators
+            )));
+        }
+        if self.governance.authority_threshold == 0
+            || self.governance.authority_threshold as usize > self.authorities.len()
+        {
+            return Err(SystemError::Configuration(format!(
+                "Authority threshold {} must be between 1 and the {} genesis authorities",
+                self.governance.authority_threshold, self.authorities.len()
+            )));
+        }
+        if self.consensus.block_time == 0 {
+            return Err(SystemError::Configuration("Block time must be positive".to_string()));
+        }
+        if self.governance.validator_rotation_interval == 0 {
+            return Err(SystemError::Configuration("Validator rotation interval must be positive".to_string()));
+        }
+        let mut seen = HashSet::new();
+        for oracle in &self.oracles {
+            if !seen.insert(&oracle.account_id) {
+                return Err(SystemError::Configuration(format!(
+                    "Chain spec declares oracle {} twice", oracle.account_id
+                )));
+            }
+            oracle.verifying_key()?;
+        }
+        if self.gas_schedule.version != GAS_SCHEDULE_VERSION {
+            return Err(SystemError::Configuration(format!(
+                "Chain spec uses gas schedule version {} but this node charges by version {}",
+                self.gas_schedule.version, GAS_SCHEDULE_VERSION
+            )));
+        }
+        Ok(())
+    }
+
+    /// Node configuration with the chain-wide settings taken from the spec
+    pub fn apply_to(&self, config: &BlockchainConfig) -> BlockchainConfig {
+        BlockchainConfig {
+            network: self.network_id.clone(),
+            block_time: self.consensus.block_time,
+            slot_timeout: self.consensus.slot_timeout,
+            gas_limit: self.fees.gas_limit,
+            gas_price: self.fees.gas_price,
+            validator_rotation_interval: self.governance.validator_rotation_interval,
+            max_validators: self.governance.max_validators,
+            authority_threshold: self.governance.authority_threshold,
+            smart_contract_vm: SmartContractConfig {
+                max_gas_limit: self.contracts.max_gas_limit,
+                max_contract_size: self.contracts.max_contract_size,
+                enable_wasm: self.contracts.enable_wasm,
+                max_storage_size: self.contracts.max_storage_size,
+                gas_schedule: self.gas_schedule.clone(),
+            },
+            ..config.clone()
+        }
+    }
+
+    /// Oracles and carbon credit issuers the state transition trusts
+    pub fn trusted_parties(&self) -> SystemResult<TrustedParties> {
+        Ok(TrustedParties {
+            oracles: self.oracles.iter()
+                .map(|oracle| Ok((oracle.account_id.clone(), oracle.verifying_key()?)))
+                .collect::<SystemResult<_>>()?,
+            carbon_credit_issuers: self.carbon_credit_issuers.iter().cloned().collect(),
+        })
+    }
+
+    /// Genesis authorities, registered at the genesis timestamp
+    pub fn genesis_authorities(&self) -> SystemResult<Vec<Authority>> {
+        self.authorities.iter()
+            .map(|authority| {
+                Ok(Authority {
+                    registered_at: self.genesis_timestamp,
+                    ..Authority::new(
+                        authority.account_id.clone(),
+                        authority.name.clone(),
+                        Vec::new(),
+                        Vec::new(),
+                        authority.public_key_bytes()?,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Account balances right after the genesis block
+    pub fn genesis_balances(&self) -> HashMap<AccountId, EnergyBalanceState> {
+        let last_updated = UNIX_EPOCH + Duration::from_secs(self.genesis_timestamp);
+        let mut balances: HashMap<AccountId, EnergyBalanceState> = HashMap::new();
+        for (account_id, by_source) in &self.balances {
+            let state = balances.entry(account_id.clone()).or_insert_with(EnergyBalanceState::new);
+            for (source, amount) in by_source {
+                state.energy_balances.insert(source.clone(), *amount);
+                state.total_balance = state.total_balance.saturating_add(*amount);
+            }
+        }
+        for (account_id, amount) in &self.currency {
+            balances.entry(account_id.clone()).or_insert_with(EnergyBalanceState::new).currency_balance = *amount;
+        }
+        for state in balances.values_mut() {
+            state.last_updated = last_updated;
+        }
+        balances
+    }
+
+    /// Derive the genesis block
+    ///
+    /// The header commits to the initial balances through its state root and records the
+    /// initial authorities as its validator-set change.
+    pub fn genesis_block(&self) -> SystemResult<EnergyBlock> {
+        let authorities = self.authorities.iter()
+            .map(|authority| {
+                Ok(ValidatorCandidate {
+                    account_id: authority.account_id.clone(),
+                    name: authority.name.clone(),
+                    public_key: authority.public_key_bytes()?,
+                })
+            })
+            .collect::<SystemResult<Vec<_>>>()?;
+
+        let header = BlockHeader {
+            number: 0,
+            parent_hash: Hash::default(),
+            transaction_root: Hash::default(),
+            state_root: StateTrie::new().root_of(&self.genesis_balances()),
+            receipts_root: Hash::default(),
+            timestamp: self.genesis_timestamp,
+            validator: GENESIS_VALIDATOR.to_string(),
+            validator_set_change: Some(ValidatorSetChange { added: authorities, removed: Vec::new() }),
+            hash: Hash::default(),
+        };
+
+        let energy_stats = EnergyBlockStats {
+            total_energy_traded: EnergyAmount::ZERO,
+            energy_by_source: HashMap::new(),
+            trade_count: 0,
+            average_price: TokenPrice::ZERO,
+            gas_used: 0,
+            carbon_credits: FixedPoint::ZERO,
+        };
+
+        let validator_signature = ValidatorSignature {
+            validator: GENESIS_VALIDATOR.to_string(),
+            signature: vec![0; 64], // Genesis signature
+            timestamp: self.genesis_timestamp,
+        };
+
+        let mut block = EnergyBlock {
+            header,
+            transactions: Vec::new(),
+            receipts: Vec::new(),
+            energy_stats,
+            validator_signature,
+        };
+        block.header.hash = ConsensusEngine::calculate_block_hash(&block);
+        Ok(block)
+    }
+}
+
+impl Default for ConsensusParameters {
+    fn default() -> Self {
+        let config = BlockchainConfig::default();
+        Self {
+            block_time: config.block_time,
+            slot_timeout: config.slot_timeout,
+        }
+    }
+}
+
+impl From<&SmartContractConfig> for ContractParameters {
+    fn from(config: &SmartContractConfig) -> Self {
+        Self {
+            max_gas_limit: config.max_gas_limit,
+            max_contract_size: config.max_contract_size,
+            max_storage_size: config.max_storage_size,
+            enable_wasm: config.enable_wasm,
+        }
+    }
+}
+
+impl Default for ContractParameters {
+    fn default() -> Self {
+        Self::from(&SmartContractConfig::default())
+    }
+}
+
+impl GenesisAuthority {
+    /// Authority whose key is derived from `node_key`, for development and test chains
+    ///
+    /// Anyone knowing the node key can sign for the authority, so production chains declare
+    /// authorities by public key instead.
+    pub fn development(node_key: &str) -> SystemResult<Self> {
+        let keypair = crate::crypto::GridTokenXKeyPair::from_node_key(node_key)?;
+        Self::from_public_key(node_key, &hex::encode(keypair.export_public_key_bytes()))
+    }
+
+    /// Authority whose account ID is derived from its hex-encoded public key
+    pub fn from_public_key(name: &str, public_key: &str) -> SystemResult<Self> {
+        let key: [u8; 32] = hex::decode(public_key.trim_start_matches("0x")).ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| SystemError::Configuration(format!("Invalid public key for authority {}", name)))?;
+        let verifying_key = VerifyingKey::from_bytes(&key)
+            .map_err(|e| SystemError::Configuration(format!("Invalid public key for authority {}: {}", name, e)))?;
+        Ok(Self {
+            account_id: crate::crypto::derive_account_id(&verifying_key)?,
+            name: name.to_string(),
+            public_key: hex::encode(key),
+        })
+    }
+
+    /// Key an oracle's attestations verify against; unlike an authority, it must have one
+    fn verifying_key(&self) -> SystemResult<VerifyingKey> {
+        let key: [u8; 32] = self.public_key_bytes()?.try_into().map_err(|_| {
+            SystemError::Configuration(format!("Oracle {} has no public key", self.account_id))
+        })?;
+        VerifyingKey::from_bytes(&key)
+            .map_err(|e| SystemError::Configuration(format!("Invalid public key for oracle {}: {}", self.account_id, e)))
+    }
+
+    fn public_key_bytes(&self) -> SystemResult<Vec<u8>> {
+        let key = hex::decode(self.public_key.trim_start_matches("0x")).map_err(|e| {
+            SystemError::Configuration(format!("Invalid public key for authority {}: {}", self.account_id, e))
+        })?;
+        if !key.is_empty() && key.len() != 32 {
+            return Err(SystemError::Configuration(format!(
+                "Public key for authority {} must be 32 bytes, got {}", self.account_id, key.len()
+            )));
+        }
+        Ok(key)
+    }
+}
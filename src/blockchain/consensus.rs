@@ -2,31 +2,64 @@
 //! 
 //! Implements Proof-of-Authority (PoA) consensus mechanism for energy trading blockchain.
 
+use crate::blockchain::block_tree::{BlockCheckpoint, BlockTree, ChainReorg, MAX_FORK_DEPTH};
+use crate::blockchain::contract_runtime::ExecutionContext;
+use crate::blockchain::encoding;
+use crate::blockchain::finality::{FinalityGadget, Precommit, PrecommitRejection};
+use crate::blockchain::merkle::{self, TransactionProof};
+use crate::blockchain::slashing::EvidenceRejection;
+use crate::blockchain::smart_contracts::{ContractState, SmartContractVM};
+use crate::blockchain::state_trie::{AccountProof, StateTrie};
+use crate::blockchain::storage::BlockchainStorage;
+use crate::blockchain::sync::ChainStatus;
+use crate::blockchain::transaction_pool::{self, AdmissionRejection, TransactionPool};
+use crate::blockchain::state_transition::{
+    self, BlockContext, TransactionReceipt, TransactionRejection, TrustedParties,
+};
 use crate::blockchain::transactions::{EnergyTransactionEnvelope, EnergyBalanceState, EnergyTransaction};
+use crate::blockchain::chain_spec::ChainSpec;
+use crate::blockchain::liveness::{self, ValidatorLiveness};
+use crate::blockchain::validator_set::{ValidatorProposals, ValidatorSetChange, ValidatorSetGovernance};
 use crate::config::BlockchainConfig;
+use crate::crypto::GridTokenXKeyPair;
 use crate::types::*;
 use crate::utils::SystemResult;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
 use serde::{Deserialize, Serialize};
-use sha2::{Sha256, Digest};
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
-use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
+
+/// Number of produced blocks buffered for slow subscribers
+const PRODUCED_BLOCK_CHANNEL_CAPACITY: usize = 64;
+/// Number of chain reorganisations buffered for slow subscribers
+const REORG_CHANNEL_CAPACITY: usize = 16;
 
 /// Proof-of-Authority consensus engine
 pub struct ConsensusEngine {
     config: BlockchainConfig,
     /// Current blockchain state
     blockchain_state: Arc<RwLock<BlockchainState>>,
-    /// Authorized validators
-    validators: Arc<RwLock<HashSet<AccountId>>>,
+    /// Registered authorities keyed by validator account
+    authorities: Arc<RwLock<HashMap<AccountId, Authority>>>,
     /// Current validator (if this node is a validator)
     current_validator: Arc<RwLock<Option<AccountId>>>,
+    /// Keypair loaded from the configured node key, used to sign produced blocks
+    node_keypair: GridTokenXKeyPair,
     /// Validator rotation schedule
     validator_schedule: Arc<RwLock<ValidatorSchedule>>,
     /// Block production status
     is_producing: Arc<RwLock<bool>>,
+    /// Block store every accepted block is persisted to before it joins the chain
+    storage: Option<Arc<BlockchainStorage>>,
+    /// Announces every block this node produces, e.g. for gossiping it to peers
+    produced_blocks: broadcast::Sender<EnergyBlock>,
+    /// Precommits collected towards finality and the finalized head
+    finality: FinalityGadget,
+    /// Announces every switch of the canonical chain to another branch
+    reorgs: broadcast::Sender<ChainReorg>,
 }
 
 /// Blockchain state
@@ -38,12 +71,52 @@ pub struct BlockchainState {
     pub latest_block_hash: Hash,
     /// Account balances
     pub balances: HashMap<AccountId, EnergyBalanceState>,
-    /// Pending transactions
-    pub pending_transactions: Vec<EnergyTransactionEnvelope>,
+    /// Transactions waiting for inclusion in a block
+    pub pending_transactions: TransactionPool,
     /// Blockchain history
     pub blocks: Vec<EnergyBlock>,
+    /// Number of the block each included transaction was executed in
+    pub transaction_index: HashMap<[u8; 32], u32>,
+    /// Authenticated account state behind every block's state root
+    pub state_trie: StateTrie,
+    /// Competing branches and the post-state of recent blocks
+    pub block_tree: BlockTree,
+    /// Validator-set proposals and the epochs they take effect at
+    pub validator_set: ValidatorSetGovernance,
+    /// Produced and missed slots per authority
+    pub liveness: HashMap<AccountId, ValidatorLiveness>,
+    /// VM that contract deployments and calls are executed in
+    pub contract_vm: Arc<SmartContractVM>,
+    /// Oracles and carbon credit issuers of the chain spec
+    pub trusted_parties: Arc<TrustedParties>,
 }
 
+/// Chain state captured after a given block, used to restart without replaying from genesis
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    /// Height of the block the snapshot was taken after
+    pub height: u32,
+    /// Hash of that block
+    pub block_hash: Hash,
+    /// Account balances after the block
+    pub balances: HashMap<AccountId, EnergyBalanceState>,
+    /// Registered authorities
+    pub authorities: HashMap<AccountId, Authority>,
+    /// Validator rotation schedule
+    pub validator_schedule: ValidatorSchedule,
+    /// Total tokens held across all accounts, including staked tokens
+    pub token_supply: Balance,
+    /// Validator-set proposals under vote and passed changes awaiting an epoch boundary
+    #[serde(default)]
+    pub validator_proposals: ValidatorProposals,
+    /// Produced and missed slots per authority
+    #[serde(default)]
+    pub validator_liveness: HashMap<AccountId, ValidatorLiveness>,
+}
+
+/// Reputation of a newly registered authority, which is also the most it can hold
+pub const STARTING_REPUTATION: FixedPoint = FixedPoint::from_int(1);
+
 /// Authority validator information
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Authority {
@@ -53,8 +126,8 @@ pub struct Authority {
     pub name: String,
     /// Energy sector expertise
     pub expertise: Vec<EnergySource>,
-    /// Validator reputation score
-    pub reputation: f64,
+    /// Validator reputation score, from zero up to [`STARTING_REPUTATION`]
+    pub reputation: FixedPoint,
     /// Grid locations this validator can authorize
     pub authorized_regions: Vec<String>,
     /// Validator public key for signatures
@@ -66,7 +139,7 @@ pub struct Authority {
 }
 
 /// Validator schedule for block production
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ValidatorSchedule {
     /// Current active validators
     pub active_validators: Vec<AccountId>,
@@ -76,6 +149,13 @@ pub struct ValidatorSchedule {
     pub current_validator_index: usize,
     /// Block production interval (seconds)
     pub block_interval: u64,
+    /// Seconds after the block interval at which a missed slot passes to the next validator
+    /// (0 waits for the scheduled validator indefinitely)
+    #[serde(default)]
+    pub slot_timeout: u64,
+    /// Whether this node fills its slots with empty blocks when no transactions are pending
+    #[serde(default)]
+    pub produce_empty_blocks: bool,
     /// Last block production time
     pub last_block_time: u64,
 }
@@ -87,6 +167,8 @@ pub struct EnergyBlock {
     pub header: BlockHeader,
     /// Transactions in this block
     pub transactions: Vec<EnergyTransactionEnvelope>,
+    /// Execution receipts, one per transaction in the same order
+    pub receipts: Vec<TransactionReceipt>,
     /// Energy statistics for this block
     pub energy_stats: EnergyBlockStats,
     /// Validator signature
@@ -104,10 +186,15 @@ pub struct BlockHeader {
     pub transaction_root: Hash,
     /// State root hash
     pub state_root: Hash,
+    /// Merkle root of transaction receipts
+    pub receipts_root: Hash,
     /// Timestamp
     pub timestamp: u64,
     /// Block producer (validator)
     pub validator: AccountId,
+    /// Validator-set change taking effect after this block, only set at epoch boundaries
+    #[serde(default)]
+    pub validator_set_change: Option<ValidatorSetChange>,
     /// Block hash
     pub hash: Hash,
 }
@@ -132,16 +219,105 @@ pub struct EnergyBlockStats {
     pub energy_by_source: HashMap<EnergySource, EnergyAmount>,
     /// Number of trades
     pub trade_count: u32,
-    /// Average energy price
+    /// Average price per kWh of the energy traded
     pub average_price: TokenPrice,
     /// Total gas used
     pub gas_used: u64,
     /// Carbon credits generated
-    pub carbon_credits: u32,
+    pub carbon_credits: FixedPoint,
+}
+
+/// Maximum number of seconds a block timestamp may be ahead of local time
+const MAX_FUTURE_BLOCK_DRIFT: u64 = 30;
+
+/// Reason a block was rejected by the import pipeline
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum BlockRejection {
+    #[error("block {0} is already part of the chain")]
+    AlreadyImported(Hash),
+    #[error("expected block number {expected}, got {found}")]
+    InvalidBlockNumber { expected: u32, found: u32 },
+    #[error("parent hash {found} does not match chain head {expected}")]
+    ParentHashMismatch { expected: Hash, found: Hash },
+    #[error("timestamp {found} is earlier than parent timestamp {parent}")]
+    TimestampNotMonotonic { parent: u64, found: u64 },
+    #[error("timestamp {found} is too far ahead of local time {now}")]
+    TimestampInFuture { now: u64, found: u64 },
+    #[error("expected block from validator {expected:?}, got {found}")]
+    UnexpectedValidator { expected: Option<AccountId>, found: AccountId },
+    #[error("validator {0} is not a registered authority")]
+    UnknownAuthority(AccountId),
+    #[error("validator {0} is not an active authority")]
+    InactiveAuthority(AccountId),
+    #[error("validator {0} has no valid public key registered")]
+    MissingPublicKey(AccountId),
+    #[error("block hash does not match block contents")]
+    InvalidBlockHash,
+    #[error("validator signature is invalid")]
+    InvalidSignature,
+    #[error("transaction {index} cannot be included: {reason}")]
+    InvalidTransaction { index: usize, reason: TransactionRejection },
+    #[error("transaction {index} hash does not match its contents")]
+    TransactionHashMismatch { index: usize },
+    #[error("transaction root mismatch: expected {expected}, got {found}")]
+    TransactionRootMismatch { expected: Hash, found: Hash },
+    #[error("state root mismatch: expected {expected}, got {found}")]
+    StateRootMismatch { expected: Hash, found: Hash },
+    #[error("receipts root mismatch: expected {expected}, got {found}")]
+    ReceiptsRootMismatch { expected: Hash, found: Hash },
+    #[error("state of parent root {0} is not available")]
+    UnknownParentState(Hash),
+    #[error("block #{number} conflicts with finalized block #{finalized}")]
+    ConflictsWithFinalized { number: u32, finalized: u32 },
+    #[error("transaction {index} carries invalid evidence: {reason}")]
+    InvalidEvidence { index: usize, reason: EvidenceRejection },
+    #[error("expected validator set change {expected:?}, got {found:?}")]
+    ValidatorSetChangeMismatch { expected: Option<ValidatorSetChange>, found: Option<ValidatorSetChange> },
+}
+
+/// Outcome of importing a block received from another node
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockImportResult {
+    /// Block passed validation and joined the block tree; it extends the canonical chain
+    /// unless fork choice prefers another branch
+    Imported,
+    /// Block was rejected for the given reason
+    Rejected(BlockRejection),
+}
+
+/// Chain state a block is validated against: the head, or a recent block it forks off
+struct ParentState<'a> {
+    header: &'a BlockHeader,
+    balances: &'a HashMap<AccountId, EnergyBalanceState>,
+    /// Validator schedule right after the parent block
+    schedule: ValidatorSchedule,
+    /// Authorities right after the parent block
+    authorities: HashMap<AccountId, Authority>,
+    /// Validator-set proposals right after the parent block
+    validator_set: ValidatorSetGovernance,
+    /// Slot statistics right after the parent block
+    liveness: HashMap<AccountId, ValidatorLiveness>,
 }
 
 impl ConsensusEngine {
+    /// Create a consensus engine for the chain the configured chain spec describes
     pub async fn new(config: &BlockchainConfig) -> SystemResult<Self> {
+        Self::with_chain_spec(config, &ChainSpec::load(config)?).await
+    }
+    
+    /// Create a consensus engine on top of an existing genesis block
+    ///
+    /// The block must be the genesis block of the configured chain spec.
+    pub async fn with_genesis(config: &BlockchainConfig, genesis_block: EnergyBlock) -> SystemResult<Self> {
+        let spec = ChainSpec::load(config)?;
+        Self::check_genesis(&spec, &genesis_block)?;
+        Self::with_chain_spec(config, &spec).await
+    }
+    
+    /// Create a consensus engine for the chain starting at the genesis block of `spec`
+    ///
+    /// The chain-wide settings of the spec override those of `config`.
+    pub async fn with_chain_spec(config: &BlockchainConfig, spec: &ChainSpec) -> SystemResult<Self> {
         // Validate that only PoA is configured
         if config.consensus_algorithm != "proof_of_authority" {
             return Err(crate::utils::SystemError::Configuration(
@@ -155,49 +331,169 @@ impl ConsensusEngine {
             "✅ Initializing Proof-of-Authority consensus engine (PoW disabled)"
         );
         
-        let genesis_block = Self::create_genesis_block().await?;
-        let initial_state = BlockchainState {
-            block_height: 0,
-            latest_block_hash: genesis_block.header.hash.clone(),
-            balances: HashMap::new(),
-            pending_transactions: Vec::new(),
-            blocks: vec![genesis_block],
-        };
-        
-        // Initialize default energy authorities
-        let mut initial_validators = HashSet::new();
+        let config = &spec.apply_to(config);
+        let genesis_block = spec.genesis_block()?;
+        let genesis_balances = spec.genesis_balances();
+        let state_trie = StateTrie::new();
+        state_trie.root_of(&genesis_balances);
         
-        // Add Thai energy authorities as initial validators
-        let energy_authority = AccountId::from_str("ThaiEnergyAuthority").unwrap_or_default();
-        let grid_authority = AccountId::from_str("GridAuthorityThailand").unwrap_or_default();
-        let renewable_authority = AccountId::from_str("RenewableEnergyAuth").unwrap_or_default();
-        
-        initial_validators.insert(energy_authority.clone());
-        initial_validators.insert(grid_authority.clone());
-        initial_validators.insert(renewable_authority.clone());
+        let finality = FinalityGadget::new(genesis_block.header.hash.clone(), config.authority_threshold);
         
+        // The genesis authorities are scheduled in the order the chain spec declares them
+        let genesis_authorities = spec.genesis_authorities()?;
         let validator_schedule = ValidatorSchedule {
-            active_validators: vec![energy_authority, grid_authority, renewable_authority],
+            active_validators: genesis_authorities.iter().map(|authority| authority.account_id.clone()).collect(),
             round: 0,
             current_validator_index: 0,
             block_interval: config.block_time, // Use configured block time
+            slot_timeout: config.slot_timeout,
+            produce_empty_blocks: config.produce_empty_blocks,
             last_block_time: 0,
         };
+        let initial_authorities: HashMap<AccountId, Authority> = genesis_authorities.into_iter()
+            .map(|authority| (authority.account_id.clone(), authority))
+            .collect();
+        
+        let validator_set = ValidatorSetGovernance::new(config.validator_rotation_interval, config.max_validators);
+        let mut block_tree = BlockTree::new();
+        block_tree.insert_checkpoint(
+            genesis_block.header.hash.clone(),
+            Self::checkpoint(
+                0, genesis_balances.clone(), &validator_schedule, &initial_authorities, &validator_set, &HashMap::new(),
+            ),
+        );
+        let initial_state = BlockchainState {
+            block_height: 0,
+            latest_block_hash: genesis_block.header.hash.clone(),
+            balances: genesis_balances,
+            pending_transactions: TransactionPool::new(config),
+            blocks: vec![genesis_block],
+            transaction_index: HashMap::new(),
+            state_trie,
+            block_tree,
+            validator_set,
+            liveness: HashMap::new(),
+            contract_vm: Arc::new(SmartContractVM::new(&config.smart_contract_vm)),
+            trusted_parties: Arc::new(spec.trusted_parties()?),
+        };
+        
+        let node_keypair = GridTokenXKeyPair::from_node_key(&config.node_key)?;
         
         crate::utils::logging::log_info(
             "ConsensusEngine", 
             &format!("🏛️ Initialized with {} validators, block time: {}s", 
-                    initial_validators.len(), config.block_time)
+                    initial_authorities.len(), config.block_time)
         );
         
-        Ok(Self {
+        let engine = Self {
             config: config.clone(),
             blockchain_state: Arc::new(RwLock::new(initial_state)),
-            validators: Arc::new(RwLock::new(initial_validators)),
+            authorities: Arc::new(RwLock::new(initial_authorities)),
             current_validator: Arc::new(RwLock::new(None)),
+            node_keypair,
             validator_schedule: Arc::new(RwLock::new(validator_schedule)),
             is_producing: Arc::new(RwLock::new(false)),
-        })
+            storage: None,
+            produced_blocks: broadcast::channel(PRODUCED_BLOCK_CHANNEL_CAPACITY).0,
+            finality,
+            reorgs: broadcast::channel(REORG_CHANNEL_CAPACITY).0,
+        };
+        
+        if config.validator {
+            engine.initialize_validator().await?;
+        }
+        
+        Ok(engine)
+    }
+    
+    /// Create a consensus engine backed by a block store
+    ///
+    /// An empty store is initialised with the chain spec's genesis block; otherwise the stored
+    /// genesis is checked against it and every later block is replayed to rebuild the chain state.
+    /// The persisted finalized head and precommits are restored on top of the replayed chain.
+    pub async fn with_storage(config: &BlockchainConfig, storage: Arc<BlockchainStorage>) -> SystemResult<Self> {
+        Self::open_storage(config, storage, None).await
+    }
+    
+    /// Create a consensus engine backed by a block store for the chain starting at `genesis_block`
+    ///
+    /// Fails if the store already holds a chain with a different genesis.
+    pub async fn with_storage_and_genesis(
+        config: &BlockchainConfig,
+        storage: Arc<BlockchainStorage>,
+        genesis_block: EnergyBlock,
+    ) -> SystemResult<Self> {
+        Self::open_storage(config, storage, Some(genesis_block)).await
+    }
+    
+    async fn open_storage(
+        config: &BlockchainConfig,
+        storage: Arc<BlockchainStorage>,
+        genesis_block: Option<EnergyBlock>,
+    ) -> SystemResult<Self> {
+        let spec = ChainSpec::load(config)?;
+        if let Some(genesis_block) = &genesis_block {
+            Self::check_genesis(&spec, genesis_block)?;
+        }
+        let mut stored_blocks = storage.load_blocks().await?.into_iter();
+        let snapshot = storage.latest_snapshot().await?;
+        
+        let mut engine = match stored_blocks.next() {
+            Some(stored) => {
+                let expected = spec.genesis_block()?;
+                if stored.header.hash != expected.header.hash {
+                    return Err(crate::utils::SystemError::Configuration(format!(
+                        "Stored genesis block {} does not match expected genesis {}",
+                        stored.header.hash, expected.header.hash
+                    )));
+                }
+                Self::with_chain_spec(config, &spec).await?
+            }
+            None => {
+                let engine = Self::with_chain_spec(config, &spec).await?;
+                storage.append_block(&engine.genesis_block().await).await?;
+                engine
+            }
+        };
+        
+        let stored_blocks: Vec<EnergyBlock> = stored_blocks.collect();
+        let mut restored = 0;
+        if let Some(snapshot) = snapshot {
+            let height = snapshot.height;
+            match engine.restore_snapshot(snapshot, &stored_blocks).await {
+                Ok(()) => {
+                    restored = height as usize;
+                    crate::utils::logging::log_info(
+                        "ConsensusEngine",
+                        &format!("Restored state snapshot at block #{}", height)
+                    );
+                }
+                Err(reason) => crate::utils::logging::log_warning(
+                    "ConsensusEngine",
+                    &format!("Ignoring state snapshot at block #{}: {}", height, reason)
+                ),
+            }
+        }
+        
+        let mut replayed = 0;
+        for block in stored_blocks.into_iter().skip(restored) {
+            engine.replay_block(block).await?;
+            replayed += 1;
+        }
+        if replayed > 0 {
+            crate::utils::logging::log_info(
+                "ConsensusEngine",
+                &format!("Replayed {} stored blocks", replayed)
+            );
+        }
+        
+        {
+            let mut state = engine.blockchain_state.write().await;
+            engine.finality.restore_from(storage.clone(), &state).await?;
+            Self::prune_block_tree(&mut state, engine.finality.finalized_number());
+        }
+        engine.storage = Some(storage);
+        Ok(engine)
     }
     
     pub async fn start(&self) -> SystemResult<()> {
@@ -208,12 +504,11 @@ impl ConsensusEngine {
         crate::utils::logging::log_info(
             "ConsensusEngine",
             &format!("🏛️ Starting PoA consensus with {} validators", 
-                    self.validators.read().await.len())
+                    self.authorities.read().await.len())
         );
         
         // Check if this node is a validator
         if self.config.validator {
-            self.initialize_validator().await?;
             self.start_block_production().await?;
         }
         
@@ -231,19 +526,22 @@ impl ConsensusEngine {
     
     /// Initialize this node as a validator
     async fn initialize_validator(&self) -> SystemResult<()> {
-        // In a real implementation, this would load the validator's private key
-        // and register with the network
-        let validator_id = AccountId::from_str("LocalValidator").unwrap_or_default();
+        let validator_id = self.node_keypair.account_id().to_string();
         *self.current_validator.write().await = Some(validator_id.clone());
         
-        // Add to validator set if not already present
-        let mut validators = self.validators.write().await;
-        validators.insert(validator_id);
-        
-        crate::utils::logging::log_info(
-            "ConsensusEngine", 
-            "Node initialized as PoA validator"
-        );
+        // The authority set is chain state: a node only produces blocks once the genesis spec
+        // or an applied governance change makes it an authority
+        if self.authorities.read().await.contains_key(&validator_id) {
+            crate::utils::logging::log_info(
+                "ConsensusEngine", 
+                &format!("Node initialized as PoA validator {}", validator_id)
+            );
+        } else {
+            crate::utils::logging::log_warning(
+                "ConsensusEngine",
+                &format!("Node key {} is not an authority of this chain; waiting to be added by governance", validator_id)
+            );
+        }
         
         Ok(())
     }
@@ -254,32 +552,35 @@ impl ConsensusEngine {
         let validator_schedule = self.validator_schedule.clone();
         let blockchain_state = self.blockchain_state.clone();
         let current_validator = self.current_validator.clone();
+        let node_keypair = self.node_keypair.clone();
+        let authorities = self.authorities.clone();
+        let storage = self.storage.clone();
+        let produced_blocks = self.produced_blocks.clone();
+        let finality = self.finality.clone();
         
         *is_producing.write().await = true;
         
         tokio::spawn(async move {
             while *is_producing.read().await {
-                // Check if it's our turn to produce a block
-                if let Some(validator_id) = current_validator.read().await.as_ref() {
-                    let schedule = validator_schedule.read().await;
+                // Only validators produce blocks; whose slot it is gets checked on production
+                if current_validator.read().await.is_some() {
                     let current_time = SystemTime::now()
                         .duration_since(UNIX_EPOCH)
                         .unwrap()
                         .as_secs();
                     
-                    // Check if it's time to produce a block and if we're the designated validator
-                    if current_time >= schedule.last_block_time + schedule.block_interval {
-                        let current_validator_id = &schedule.active_validators[schedule.current_validator_index];
-                        
-                        if validator_id == current_validator_id {
-                            // Produce a new block
-                            if let Err(e) = Self::produce_block_static(
-                                &blockchain_state,
-                                &validator_schedule,
-                                validator_id.clone()
-                            ).await {
-                                log::error!("Failed to produce block: {}", e);
-                            }
+                    if validator_schedule.read().await.should_rotate(current_time) {
+                        // Produce a new block
+                        if let Err(e) = Self::produce_block_static(
+                            &blockchain_state,
+                            &validator_schedule,
+                            &node_keypair,
+                            &authorities,
+                            storage.as_deref(),
+                            &produced_blocks,
+                            &finality,
+                        ).await {
+                            log::error!("Failed to produce block: {}", e);
                         }
                     }
                 }
@@ -292,63 +593,159 @@ impl ConsensusEngine {
         Ok(())
     }
     
+    /// Produce a block now if this node is the scheduled validator
+    ///
+    /// Returns `None` when this node is not a validator, it is not our slot,
+    /// or there are no pending transactions and empty blocks are disabled.
+    pub async fn produce_block(&self) -> SystemResult<Option<EnergyBlock>> {
+        if self.current_validator.read().await.is_none() {
+            return Ok(None);
+        }
+        
+        Self::produce_block_static(
+            &self.blockchain_state,
+            &self.validator_schedule,
+            &self.node_keypair,
+            &self.authorities,
+            self.storage.as_deref(),
+            &self.produced_blocks,
+            &self.finality,
+        ).await
+    }
+    
     /// Static method for block production (to avoid async closure issues)
     async fn produce_block_static(
         blockchain_state: &Arc<RwLock<BlockchainState>>,
         validator_schedule: &Arc<RwLock<ValidatorSchedule>>,
-        validator_id: AccountId,
-    ) -> SystemResult<()> {
+        node_keypair: &GridTokenXKeyPair,
+        authorities: &Arc<RwLock<HashMap<AccountId, Authority>>>,
+        storage: Option<&BlockchainStorage>,
+        produced_blocks: &broadcast::Sender<EnergyBlock>,
+        finality: &FinalityGadget,
+    ) -> SystemResult<Option<EnergyBlock>> {
         let mut state = blockchain_state.write().await;
         let mut schedule = validator_schedule.write().await;
-        
-        if state.pending_transactions.is_empty() {
-            // No transactions to include, skip this block
-            return Ok(());
-        }
+        let mut authorities = authorities.write().await;
         
         let current_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
+        state.pending_transactions.prune_expired(current_time);
+        
+        if state.pending_transactions.is_empty() && !schedule.produce_empty_blocks {
+            // No transactions to include, skip this block
+            return Ok(None);
+        }
+        
+        let validator_id = node_keypair.account_id().to_string();
+        
+        // The slot passes on to the next validators while the scheduled one stays silent
+        let head = &state.blocks[state.blocks.len() - 1].header;
+        let missed_slots = schedule.missed_slots(head, current_time);
+        if schedule.slot_validator(missed_slots) != Some(&validator_id) {
+            return Ok(None);
+        }
+        
+        let context = BlockContext {
+            number: state.block_height + 1,
+            timestamp: current_time,
+            validator: validator_id.clone(),
+            trusted: state.trusted_parties.clone(),
+        };
+        
+        // Execute pending transactions against a copy of the state so the root reflects the
+        // post-block state. Transactions that cannot be included are left out of the block.
+        let mut post_balances = state.balances.clone();
+        let mut included = Vec::new();
+        let mut receipts = Vec::new();
+        let mut dropped = Vec::new();
+        let mut slashed = HashSet::new();
+        // Ready transactions in priority order, up to the block size limit
+        let candidates = state.pending_transactions.ready(|sender| {
+            state.balances.get(sender).map(|account| account.nonce).unwrap_or(0)
+        });
+        let mut block_size = 0;
+        for tx in &candidates {
+            let size = tx.encoded_size();
+            if block_size + size > state.pending_transactions.block_size_limit() {
+                continue;
+            }
+            if let Err(reason) = Self::check_evidence(&state, head, &authorities, context.number, tx, &mut slashed) {
+                crate::utils::logging::log_warning(
+                    "ConsensusEngine",
+                    &format!("Dropping transaction {}: {}", hex::encode(tx.hash), reason)
+                );
+                dropped.push(tx.hash);
+                continue;
+            }
+            match state_transition::apply_transaction(&mut post_balances, tx, &context, &state.contract_vm) {
+                Ok(receipt) => {
+                    block_size += size;
+                    included.push(tx.clone());
+                    receipts.push(receipt);
+                }
+                // Transactions ahead of the sender's nonce may become valid in a later block
+                Err(TransactionRejection::InvalidNonce { expected, found }) if found > expected => {}
+                Err(reason) => {
+                    crate::utils::logging::log_warning(
+                        "ConsensusEngine",
+                        &format!("Dropping transaction {}: {}", hex::encode(tx.hash), reason)
+                    );
+                    dropped.push(tx.hash);
+                }
+            }
+        }
+        for hash in &dropped {
+            state.pending_transactions.remove(hash);
+        }
+        
+        if included.is_empty() && !schedule.produce_empty_blocks {
+            return Ok(None);
+        }
+        
+        let parent = Self::head_parent(&state, &schedule, &authorities);
+        let state_root = Self::post_state_root(&state, &parent, &post_balances)
+            .map_err(|rejection| crate::utils::SystemError::Blockchain(rejection.to_string()))?;
         
         // Create new block
         let new_block = EnergyBlock {
             header: BlockHeader {
-                number: state.block_height + 1,
+                number: context.number,
                 parent_hash: state.latest_block_hash.clone(),
-                transaction_root: Self::calculate_merkle_root(&state.pending_transactions),
-                state_root: Self::calculate_state_root(&state.balances),
+                transaction_root: Self::calculate_merkle_root(&included),
+                state_root,
+                receipts_root: Self::calculate_receipts_root(&receipts),
                 timestamp: current_time,
                 validator: validator_id.clone(),
+                validator_set_change: state.validator_set.change_for(context.number, &authorities),
                 hash: String::new(), // Will be calculated after
             },
-            transactions: state.pending_transactions.clone(),
-            energy_stats: Self::calculate_energy_stats(&state.pending_transactions),
+            energy_stats: Self::calculate_energy_stats(&included),
+            transactions: included,
+            receipts,
             validator_signature: ValidatorSignature {
                 validator: validator_id,
-                signature: vec![0; 64], // Mock signature
+                signature: Vec::new(), // Will be signed after hashing
                 timestamp: current_time,
             },
         };
         
-        // Calculate block hash
+        // Calculate block hash and sign it with the node key
         let mut new_block = new_block;
         new_block.header.hash = Self::calculate_block_hash(&new_block);
+        new_block.validator_signature.signature = Self::sign_block_hash(&new_block.header.hash, node_keypair)?;
         
-        // Add block to chain
-        state.blocks.push(new_block.clone());
-        state.block_height += 1;
-        state.latest_block_hash = new_block.header.hash;
-        state.pending_transactions.clear();
-        
-        // Update validator schedule
-        schedule.current_validator_index = (schedule.current_validator_index + 1) % schedule.active_validators.len();
-        schedule.round = if schedule.current_validator_index == 0 { 
-            schedule.round + 1 
-        } else { 
-            schedule.round 
-        };
-        schedule.last_block_time = current_time;
+        if let Some(storage) = storage {
+            storage.append_block(&new_block).await?;
+        }
+        Self::append_block(&mut state, &mut schedule, &mut authorities, new_block.clone(), post_balances);
+        
+        if let Some(storage) = storage {
+            Self::snapshot_if_due(&state, &schedule, &authorities, storage).await;
+        }
+        finality.precommit(&new_block.header, node_keypair, &state, &authorities).await;
+        Self::prune_block_tree(&mut state, finality.finalized_number());
         
         crate::utils::logging::log_info(
             "ConsensusEngine",
@@ -357,215 +754,934 @@ impl ConsensusEngine {
                      new_block.header.validator)
         );
         
-        Ok(())
+        // Nobody listening is fine, e.g. when running without a network layer
+        let _ = produced_blocks.send(new_block.clone());
+        
+        Ok(Some(new_block))
     }
     
-    /// Add a new validator to the authority set (requires governance)
-    pub async fn add_validator(&self, validator_id: AccountId, authority: Authority) -> SystemResult<()> {
-        let mut validators = self.validators.write().await;
-        validators.insert(validator_id.clone());
-        
+    /// Import a block produced by another validator
+    ///
+    /// The block is checked against its parent, the validator schedule, the authority set
+    /// and by re-executing its transactions. Valid blocks extending the head are appended;
+    /// valid blocks on a competing branch are kept in the block tree, and the chain switches
+    /// over when fork choice prefers their branch.
+    pub async fn import_block(&self, block: EnergyBlock) -> SystemResult<BlockImportResult> {
+        let mut state = self.blockchain_state.write().await;
         let mut schedule = self.validator_schedule.write().await;
-        if !schedule.active_validators.contains(&validator_id) {
-            schedule.active_validators.push(validator_id.clone());
+        let mut authorities = self.authorities.write().await;
+        
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let finalized = self.finality.finalized_number();
+        
+        // Blocks that neither extend the head nor a recent block are checked against the
+        // head, so they are reported as out of order
+        let fork_parent = match block.header.parent_hash == state.latest_block_hash {
+            true => None,
+            false => Self::fork_parent(&state, &schedule, &block.header.parent_hash),
+        };
+        let extends_head = fork_parent.is_none();
+        let parent = fork_parent.unwrap_or_else(|| Self::head_parent(&state, &schedule, &authorities));
+        
+        let (post_balances, receipts) = match Self::validate_block(&state, &parent, &block, current_time, finalized) {
+            Ok(outcome) => outcome,
+            Err(rejection) => return Ok(BlockImportResult::Rejected(rejection)),
+        };
+        
+        // Keep our own receipts; the header commits to them through the receipts root
+        let mut block = block;
+        block.receipts = receipts;
+        
+        if !extends_head {
+            crate::utils::logging::log_info(
+                "ConsensusEngine",
+                &format!("Imported side block #{} ({}) from validator {}", block.header.number, block.header.hash, block.header.validator)
+            );
+            // The branch's validator-side effects only reach the shared state if it wins
+            let ParentState {
+                header: parent_header,
+                schedule: mut post_schedule,
+                authorities: mut post_authorities,
+                validator_set: mut post_validator_set,
+                liveness: mut post_liveness,
+                ..
+            } = parent;
+            Self::apply_validator_effects(
+                parent_header, &block, &mut post_schedule, &mut post_authorities, &mut post_validator_set, &mut post_liveness,
+            );
+            let checkpoint = Self::checkpoint(
+                block.header.number, post_balances, &post_schedule, &post_authorities, &post_validator_set, &post_liveness,
+            );
+            state.block_tree.insert_side_block(block, checkpoint);
+            self.apply_fork_choice(&mut state, &mut schedule, &mut authorities).await?;
+            Self::prune_block_tree(&mut state, self.finality.finalized_number());
+            return Ok(BlockImportResult::Imported);
         }
         
         crate::utils::logging::log_info(
             "ConsensusEngine",
-            &format!("Added new validator: {} ({})", validator_id, authority.name)
+            &format!("Imported block #{} from validator {}", block.header.number, block.header.validator)
         );
         
-        Ok(())
+        if let Some(storage) = &self.storage {
+            storage.append_block(&block).await?;
+        }
+        let header = block.header.clone();
+        Self::append_block(&mut state, &mut schedule, &mut authorities, block, post_balances);
+        
+        if let Some(storage) = &self.storage {
+            Self::snapshot_if_due(&state, &schedule, &authorities, storage).await;
+        }
+        
+        // Precommits may have arrived before the block; authorities add their own vote
+        self.precommit_or_advance(&header, &state, &authorities).await;
+        
+        // A heavier branch may have been waiting for precommits that only count now
+        self.apply_fork_choice(&mut state, &mut schedule, &mut authorities).await?;
+        Self::prune_block_tree(&mut state, self.finality.finalized_number());
+        
+        Ok(BlockImportResult::Imported)
     }
     
-    /// Remove a validator from the authority set (requires governance)
-    pub async fn remove_validator(&self, validator_id: &AccountId) -> SystemResult<()> {
-        let mut validators = self.validators.write().await;
-        validators.remove(validator_id);
+    /// Precommit to a block that joined the canonical chain if this node is an authority,
+    /// otherwise just count precommits that were waiting for it
+    async fn precommit_or_advance(
+        &self,
+        header: &BlockHeader,
+        state: &BlockchainState,
+        authorities: &HashMap<AccountId, Authority>,
+    ) {
+        if self.current_validator.read().await.is_some() {
+            self.finality.precommit(header, &self.node_keypair, state, authorities).await;
+        } else {
+            self.finality.advance(state, authorities).await;
+        }
+    }
+    
+    /// Switch to the branch fork choice prefers, if it is not the canonical chain
+    ///
+    /// Every branch is weighed from the finalized block up by its authority signatures;
+    /// ties go to the branch whose tip has the lowest hash, so all nodes pick the same one.
+    async fn apply_fork_choice(
+        &self,
+        state: &mut BlockchainState,
+        schedule: &mut ValidatorSchedule,
+        authorities: &mut HashMap<AccountId, Authority>,
+    ) -> SystemResult<Option<ChainReorg>> {
+        let finalized = self.finality.finalized_number();
+        let canonical: Vec<(u32, &Hash)> = state.blocks.iter()
+            .skip(finalized as usize + 1)
+            .map(|block| (block.header.number, &block.header.hash))
+            .collect();
         
-        let mut schedule = self.validator_schedule.write().await;
-        schedule.active_validators.retain(|v| v != validator_id);
+        let head_weight = self.finality.signature_weight(canonical.iter().copied(), authorities).await;
+        let mut best = (head_weight, Reverse(state.latest_block_hash.clone()));
+        let mut best_tip = None;
+        
+        for tip in state.block_tree.tips() {
+            let branch = match state.block_tree.branch(&tip.header.hash, &state.blocks) {
+                Some(branch) if branch[0].header.number > finalized => branch,
+                _ => continue,
+            };
+            let shared = (branch[0].header.number - 1 - finalized) as usize;
+            let blocks = canonical[..shared].iter().copied()
+                .chain(branch.iter().map(|block| (block.header.number, &block.header.hash)));
+            let candidate = (self.finality.signature_weight(blocks, authorities).await, Reverse(tip.header.hash.clone()));
+            if candidate > best {
+                best = candidate;
+                best_tip = Some(tip.header.hash.clone());
+            }
+        }
+        
+        match best_tip {
+            Some(tip) => self.reorganize(state, schedule, authorities, &tip).await.map(Some),
+            None => Ok(None),
+        }
+    }
+    
+    /// Make the side branch ending at `tip` the canonical chain
+    ///
+    /// The retracted blocks stay in the block tree so the chain can switch back. Their
+    /// transactions that the new branch does not include go back to the pending pool.
+    async fn reorganize(
+        &self,
+        state: &mut BlockchainState,
+        schedule: &mut ValidatorSchedule,
+        authorities: &mut HashMap<AccountId, Authority>,
+        tip: &Hash,
+    ) -> SystemResult<ChainReorg> {
+        let branch: Vec<EnergyBlock> = state.block_tree.branch(tip, &state.blocks)
+            .ok_or_else(|| crate::utils::SystemError::Blockchain(format!("Block {} is not on a side branch", tip)))?
+            .into_iter()
+            .cloned()
+            .collect();
+        let common_ancestor = branch[0].header.number - 1;
+        let checkpoint = state.block_tree.checkpoint(tip).cloned()
+            .ok_or_else(|| crate::utils::SystemError::Blockchain(format!("State after block {} is not available", tip)))?;
+        let ancestor_hash = &branch[0].header.parent_hash;
+        let ancestor = state.block_tree.checkpoint(ancestor_hash).cloned()
+            .ok_or_else(|| crate::utils::SystemError::Blockchain(format!("State after block {} is not available", ancestor_hash)))?;
+        
+        // Rewrite the store first, so a failure leaves the in-memory chain untouched
+        if let Some(storage) = &self.storage {
+            storage.truncate_blocks(common_ancestor).await?;
+            for block in &branch {
+                storage.append_block(block).await?;
+            }
+        }
+        
+        let enacted: Vec<Hash> = branch.iter().map(|block| block.header.hash.clone()).collect();
+        state.block_tree.take_branch(&enacted);
+        let retracted_blocks = state.blocks.split_off(common_ancestor as usize + 1);
+        
+        let enacted_transactions: HashSet<[u8; 32]> = branch.iter()
+            .flat_map(|block| block.transactions.iter().map(|tx| tx.hash))
+            .collect();
+        let orphaned_transactions: Vec<EnergyTransactionEnvelope> = retracted_blocks.iter()
+            .flat_map(|block| block.transactions.iter())
+            .filter(|tx| !enacted_transactions.contains(&tx.hash))
+            .cloned()
+            .collect();
+        
+        for tx in retracted_blocks.iter().flat_map(|block| block.transactions.iter()) {
+            state.transaction_index.remove(&tx.hash);
+        }
+        for block in &branch {
+            for tx in &block.transactions {
+                state.transaction_index.insert(tx.hash, block.header.number);
+            }
+        }
+        
+        // Orphaned transactions return to the pool; any it no longer admits are dropped
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        for block in &branch {
+            state.pending_transactions.remove_included(&block.transactions);
+        }
+        for tx in &orphaned_transactions {
+            let _ = state.pending_transactions.insert(tx.clone(), now);
+        }
+        
+        let retracted: Vec<Hash> = retracted_blocks.iter().map(|block| block.header.hash.clone()).collect();
+        for block in retracted_blocks {
+            state.block_tree.insert_retracted(block);
+        }
         
-        // Adjust current index if necessary
-        if schedule.current_validator_index >= schedule.active_validators.len() {
-            schedule.current_validator_index = 0;
+        let tip_header = branch[branch.len() - 1].header.clone();
+        state.blocks.extend(branch);
+        state.block_height = tip_header.number;
+        state.latest_block_hash = tip_header.hash.clone();
+        state.balances = checkpoint.balances;
+        
+        // Rebuild the authorities, rotation, validator-set proposals and slot counters from
+        // the common ancestor, so the retracted blocks' slashes, votes, changes and slots
+        // are undone, then replay the new branch
+        *authorities = ancestor.authorities;
+        schedule.active_validators = ancestor.active_validators;
+        state.validator_set.restore(ancestor.validator_proposals);
+        state.liveness = ancestor.liveness;
+        let ancestor_timestamp = state.blocks[common_ancestor as usize].header.timestamp;
+        schedule.restore(ancestor.validator_index, ancestor.round, ancestor_timestamp);
+        for number in common_ancestor + 1..=tip_header.number {
+            let BlockchainState { blocks, validator_set, liveness, .. } = &mut *state;
+            let (parent, block) = (&blocks[number as usize - 1].header, &blocks[number as usize]);
+            Self::apply_validator_effects(parent, block, schedule, authorities, validator_set, liveness);
         }
         
         crate::utils::logging::log_info(
             "ConsensusEngine",
-            &format!("Removed validator: {}", validator_id)
+            &format!(
+                "Reorganized chain at block #{}: {} blocks retracted, {} enacted, new head #{} ({})",
+                common_ancestor, retracted.len(), enacted.len(), tip_header.number, tip_header.hash
+            )
         );
         
-        Ok(())
+        if let Some(storage) = &self.storage {
+            Self::snapshot_if_due(state, schedule, authorities, storage).await;
+        }
+        for number in common_ancestor + 1..=tip_header.number {
+            let header = state.blocks[number as usize].header.clone();
+            self.precommit_or_advance(&header, state, authorities).await;
+        }
+        
+        let reorg = ChainReorg {
+            common_ancestor,
+            retracted,
+            enacted,
+            orphaned_transactions,
+        };
+        // Nobody listening is fine; the orphaned transactions are already back in the pool
+        let _ = self.reorgs.send(reorg.clone());
+        Ok(reorg)
     }
     
-    /// Get current validator set
-    pub async fn get_validators(&self) -> HashSet<AccountId> {
-        self.validators.read().await.clone()
+    /// Drop side branches and block post-states the chain can no longer be rewound to
+    fn prune_block_tree(state: &mut BlockchainState, finalized: u32) {
+        let floor = finalized.max(state.block_height.saturating_sub(MAX_FORK_DEPTH));
+        let BlockchainState { block_tree, blocks, .. } = state;
+        block_tree.prune(blocks, floor);
     }
     
-    /// Get validator schedule information
-    pub async fn get_validator_schedule(&self) -> ValidatorSchedule {
-        self.validator_schedule.read().await.clone()
+    /// The chain head as the parent of the next block
+    fn head_parent<'a>(
+        state: &'a BlockchainState,
+        schedule: &ValidatorSchedule,
+        authorities: &HashMap<AccountId, Authority>,
+    ) -> ParentState<'a> {
+        let head = &state.blocks[state.blocks.len() - 1];
+        ParentState {
+            header: &head.header,
+            balances: &state.balances,
+            schedule: schedule.clone(),
+            authorities: authorities.clone(),
+            validator_set: state.validator_set.clone(),
+            liveness: state.liveness.clone(),
+        }
     }
     
-    /// Verify validator signature on a block
-    pub fn verify_block_signature(&self, _block: &EnergyBlock) -> SystemResult<bool> {
-        // In a real implementation, this would verify the cryptographic signature
-        // For now, just check if the validator is in the authority set
-        // TODO: Implement actual signature verification with validator's public key
-        Ok(true)
+    /// A recent block other than the head as the parent of a competing block
+    ///
+    /// Returns `None` if the block is unknown or its post-state is no longer held.
+    fn fork_parent<'a>(state: &'a BlockchainState, schedule: &ValidatorSchedule, hash: &Hash) -> Option<ParentState<'a>> {
+        let checkpoint = state.block_tree.checkpoint(hash)?;
+        let header = match state.block_tree.side_block(hash) {
+            Some(block) => &block.header,
+            None => &state.blocks.get(checkpoint.number as usize)?.header,
+        };
+        
+        // Each branch has its own rotation, authorities and proposals; the slot timing and
+        // epoch rules are chain-wide
+        let mut parent_schedule = ValidatorSchedule {
+            active_validators: checkpoint.active_validators.clone(),
+            ..schedule.clone()
+        };
+        parent_schedule.restore(checkpoint.validator_index, checkpoint.round, header.timestamp);
+        let mut validator_set = state.validator_set.clone();
+        validator_set.restore(checkpoint.validator_proposals.clone());
+        Some(ParentState {
+            header,
+            balances: &checkpoint.balances,
+            schedule: parent_schedule,
+            authorities: checkpoint.authorities.clone(),
+            validator_set,
+            liveness: checkpoint.liveness.clone(),
+        })
     }
     
-    /// Create genesis block for PoA
-    async fn create_genesis_block() -> SystemResult<EnergyBlock> {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    /// Header of the block at `number` on the chain ending at `parent`, which may be a side branch
+    fn ancestor_header<'a>(state: &'a BlockchainState, parent: &'a BlockHeader, number: u32) -> Option<&'a BlockHeader> {
+        let mut header = parent;
+        while header.number > number {
+            match state.block_tree.side_block(&header.parent_hash) {
+                Some(block) => header = &block.header,
+                // Below the side blocks the branch runs along the canonical chain
+                None => return state.blocks.get(number as usize).map(|block| &block.header),
+            }
+        }
+        (header.number == number).then_some(header)
+    }
+    
+    /// Check the evidence a transaction carries, if any, for inclusion in block `number`
+    /// on top of `parent`
+    ///
+    /// `slashed` collects the offenders of evidence already in the block, as an authority
+    /// is slashed only once.
+    fn check_evidence(
+        state: &BlockchainState,
+        parent: &BlockHeader,
+        authorities: &HashMap<AccountId, Authority>,
+        number: u32,
+        tx: &EnergyTransactionEnvelope,
+        slashed: &mut HashSet<AccountId>,
+    ) -> Result<(), EvidenceRejection> {
+        let evidence = match &tx.transaction {
+            EnergyTransaction::ReportMisbehavior { evidence, .. } => evidence,
+            _ => return Ok(()),
+        };
+        let offender = evidence.verify(number, authorities, |height| Self::ancestor_header(state, parent, height))?;
+        if !slashed.insert(offender.clone()) {
+            return Err(EvidenceRejection::AlreadySlashed(offender.clone()));
+        }
+        Ok(())
+    }
+    
+    /// Run every import check against a block, returning the post-block balances and receipts on success
+    fn validate_block(
+        state: &BlockchainState,
+        parent: &ParentState,
+        block: &EnergyBlock,
+        current_time: u64,
+        finalized: u32,
+    ) -> Result<(HashMap<AccountId, EnergyBalanceState>, Vec<TransactionReceipt>), BlockRejection> {
+        let header = &block.header;
         
-        let genesis_validator = AccountId::from_str("GenesisValidator").unwrap_or_default();
+        if state.blocks.iter().any(|known| known.header.hash == header.hash) || state.block_tree.contains(&header.hash) {
+            return Err(BlockRejection::AlreadyImported(header.hash.clone()));
+        }
         
-        let header = BlockHeader {
-            number: 0,
-            parent_hash: Hash::default(),
-            transaction_root: Hash::default(),
-            state_root: Hash::default(),
-            timestamp,
-            validator: genesis_validator.clone(),
-            hash: Hash::default(),
-        };
+        // An unknown block at or below the finalized height can only be on another branch
+        if header.number <= finalized {
+            return Err(BlockRejection::ConflictsWithFinalized { number: header.number, finalized });
+        }
         
-        let energy_stats = EnergyBlockStats {
-            total_energy_traded: 0.0,
-            energy_by_source: HashMap::new(),
-            trade_count: 0,
-            average_price: 0.0,
-            gas_used: 0,
-            carbon_credits: 0,
-        };
+        let expected_number = parent.header.number + 1;
+        if header.number != expected_number {
+            return Err(BlockRejection::InvalidBlockNumber { expected: expected_number, found: header.number });
+        }
         
-        let validator_signature = ValidatorSignature {
-            validator: genesis_validator,
-            signature: vec![0; 64], // Genesis signature
-            timestamp,
-        };
+        if header.parent_hash != parent.header.hash {
+            return Err(BlockRejection::ParentHashMismatch {
+                expected: parent.header.hash.clone(),
+                found: header.parent_hash.clone(),
+            });
+        }
         
-        let mut block = EnergyBlock {
-            header,
-            transactions: Vec::new(),
-            energy_stats,
-            validator_signature,
+        let parent_timestamp = parent.header.timestamp;
+        if header.timestamp < parent_timestamp {
+            return Err(BlockRejection::TimestampNotMonotonic { parent: parent_timestamp, found: header.timestamp });
+        }
+        if header.timestamp > current_time + MAX_FUTURE_BLOCK_DRIFT {
+            return Err(BlockRejection::TimestampInFuture { now: current_time, found: header.timestamp });
+        }
+        
+        let missed_slots = parent.schedule.missed_slots(parent.header, header.timestamp);
+        let expected_validator = parent.schedule.slot_validator(missed_slots);
+        if expected_validator != Some(&header.validator) {
+            return Err(BlockRejection::UnexpectedValidator {
+                expected: expected_validator.cloned(),
+                found: header.validator.clone(),
+            });
+        }
+        
+        Self::validate_block_signature(block, &parent.authorities)?;
+        
+        let expected_change = parent.validator_set.change_for(header.number, &parent.authorities);
+        if header.validator_set_change != expected_change {
+            return Err(BlockRejection::ValidatorSetChangeMismatch {
+                expected: expected_change,
+                found: header.validator_set_change.clone(),
+            });
+        }
+        
+        let mut slashed = HashSet::new();
+        for (index, tx) in block.transactions.iter().enumerate() {
+            Self::check_evidence(state, parent.header, &parent.authorities, header.number, tx, &mut slashed)
+                .map_err(|reason| BlockRejection::InvalidEvidence { index, reason })?;
+        }
+        
+        Self::execute_block(state, parent, block)
+    }
+    
+    /// Re-execute a block's transactions on top of the current state and check its roots
+    fn execute_block(
+        state: &BlockchainState,
+        parent: &ParentState,
+        block: &EnergyBlock,
+    ) -> Result<(HashMap<AccountId, EnergyBalanceState>, Vec<TransactionReceipt>), BlockRejection> {
+        let header = &block.header;
+        
+        // Transaction hashes are sent along with the block, so they are checked before
+        // anything, including the transaction root, is derived from them
+        if let Some(index) = block.transactions.iter().position(|tx| !tx.has_valid_hash()) {
+            return Err(BlockRejection::TransactionHashMismatch { index });
+        }
+        let transaction_root = Self::calculate_merkle_root(&block.transactions);
+        if transaction_root != header.transaction_root {
+            return Err(BlockRejection::TransactionRootMismatch {
+                expected: transaction_root,
+                found: header.transaction_root.clone(),
+            });
+        }
+        
+        let context = BlockContext {
+            number: header.number,
+            timestamp: header.timestamp,
+            validator: header.validator.clone(),
+            trusted: state.trusted_parties.clone(),
         };
+        let mut post_balances = parent.balances.clone();
+        let receipts = state_transition::apply_transactions(&mut post_balances, &block.transactions, &context, &state.contract_vm)
+            .map_err(|(index, reason)| BlockRejection::InvalidTransaction { index, reason })?;
+        let state_root = Self::post_state_root(state, parent, &post_balances)?;
+        if state_root != header.state_root {
+            return Err(BlockRejection::StateRootMismatch {
+                expected: state_root,
+                found: header.state_root.clone(),
+            });
+        }
         
-        // Calculate genesis block hash
-        block.header.hash = Self::calculate_block_hash(&block);
+        let receipts_root = Self::calculate_receipts_root(&receipts);
+        if receipts_root != header.receipts_root {
+            return Err(BlockRejection::ReceiptsRootMismatch {
+                expected: receipts_root,
+                found: header.receipts_root.clone(),
+            });
+        }
         
-        Ok(block)
+        Ok((post_balances, receipts))
     }
     
-    /// Calculate block hash for PoA
-    fn calculate_block_hash(block: &EnergyBlock) -> Hash {
-        let mut data = Vec::new();
-        data.extend_from_slice(&block.header.number.to_be_bytes());
-        data.extend_from_slice(block.header.parent_hash.as_bytes());
-        data.extend_from_slice(block.header.transaction_root.as_bytes());
-        data.extend_from_slice(block.header.state_root.as_bytes());
-        data.extend_from_slice(&block.header.timestamp.to_be_bytes());
-        data.extend_from_slice(block.header.validator.as_bytes());
+    /// Re-apply a block loaded from storage
+    ///
+    /// Stored blocks were fully validated before they were written, so only chain linkage
+    /// and the execution results are checked again.
+    async fn replay_block(&self, block: EnergyBlock) -> SystemResult<()> {
+        let mut state = self.blockchain_state.write().await;
+        let mut schedule = self.validator_schedule.write().await;
+        let mut authorities = self.authorities.write().await;
+        
+        let expected_number = state.block_height + 1;
+        let linkage = if block.header.number != expected_number {
+            Err(BlockRejection::InvalidBlockNumber { expected: expected_number, found: block.header.number })
+        } else if block.header.parent_hash != state.latest_block_hash {
+            Err(BlockRejection::ParentHashMismatch {
+                expected: state.latest_block_hash.clone(),
+                found: block.header.parent_hash.clone(),
+            })
+        } else {
+            Ok(())
+        };
+        
+        let (post_balances, _receipts) = linkage
+            .and_then(|()| Self::execute_block(&state, &Self::head_parent(&state, &schedule, &authorities), &block))
+            .map_err(|rejection| crate::utils::SystemError::Blockchain(
+                format!("Stored block #{} is invalid: {}", block.header.number, rejection)
+            ))?;
         
-        let mut hasher = Sha256::new();
-        hasher.update(&data);
-        let hash_result = hasher.finalize();
-        hex::encode(hash_result)
+        Self::append_block(&mut state, &mut schedule, &mut authorities, block, post_balances);
+        Self::prune_block_tree(&mut state, 0);
+        Ok(())
     }
     
-    /// Calculate merkle root of transactions
-    fn calculate_merkle_root(transactions: &[EnergyTransactionEnvelope]) -> Hash {
-        if transactions.is_empty() {
-            return Hash::default();
+    /// Install a state snapshot on top of the stored blocks it covers
+    ///
+    /// `blocks` are the stored blocks after genesis. The blocks up to the snapshot height
+    /// are linked into the chain without re-executing them; the snapshot balances must
+    /// reproduce the state root committed in the header at that height.
+    async fn restore_snapshot(&self, snapshot: StateSnapshot, blocks: &[EnergyBlock]) -> Result<(), String> {
+        let height = snapshot.height as usize;
+        if height == 0 || height > blocks.len() {
+            return Err(format!("only {} blocks are stored", blocks.len()));
         }
         
-        let mut hashes: Vec<Hash> = transactions.iter()
-            .map(|tx| hex::encode(&tx.hash))
-            .collect();
+        let anchor = &blocks[height - 1].header;
+        if anchor.hash != snapshot.block_hash {
+            return Err(format!("block hash {} does not match stored block {}", snapshot.block_hash, anchor.hash));
+        }
+        let mut state = self.blockchain_state.write().await;
+        let state_root = state.state_trie.root_of(&snapshot.balances);
+        if state_root != anchor.state_root {
+            return Err(format!("state root {} does not match header state root {}", state_root, anchor.state_root));
+        }
         
-        while hashes.len() > 1 {
-            let mut next_level = Vec::new();
-            
-            for chunk in hashes.chunks(2) {
-                let combined = if chunk.len() == 2 {
-                    let mut data = Vec::new();
-                    data.extend_from_slice(chunk[0].as_bytes());
-                    data.extend_from_slice(chunk[1].as_bytes());
-                    let mut hasher = Sha256::new();
-                    hasher.update(&data);
-                    hex::encode(hasher.finalize())
-                } else {
-                    chunk[0].clone()
-                };
-                next_level.push(combined);
+        let mut parent_hash = state.latest_block_hash.clone();
+        for (index, block) in blocks[..height].iter().enumerate() {
+            if block.header.number as usize != index + 1 || block.header.parent_hash != parent_hash {
+                return Err(format!("stored block #{} does not extend the chain", block.header.number));
             }
-            
-            hashes = next_level;
+            parent_hash = block.header.hash.clone();
         }
         
-        hashes.into_iter().next().unwrap_or_default()
+        for block in &blocks[..height] {
+            for tx in &block.transactions {
+                state.transaction_index.insert(tx.hash, block.header.number);
+            }
+            state.blocks.push(block.clone());
+        }
+        state.block_height = snapshot.height;
+        state.latest_block_hash = snapshot.block_hash.clone();
+        state.balances = snapshot.balances;
+        state.validator_set.restore(snapshot.validator_proposals);
+        state.liveness = snapshot.validator_liveness;
+        let checkpoint = Self::checkpoint(
+            snapshot.height,
+            state.balances.clone(),
+            &snapshot.validator_schedule,
+            &snapshot.authorities,
+            &state.validator_set,
+            &state.liveness,
+        );
+        state.block_tree.insert_checkpoint(snapshot.block_hash, checkpoint);
+        *self.authorities.write().await = snapshot.authorities;
+        // Whether to produce empty blocks is up to this node, not the stored snapshot
+        *self.validator_schedule.write().await = ValidatorSchedule {
+            produce_empty_blocks: self.config.produce_empty_blocks,
+            ..snapshot.validator_schedule
+        };
+        
+        Ok(())
+    }
+    
+    /// Capture the current chain state
+    fn take_snapshot(
+        state: &BlockchainState,
+        schedule: &ValidatorSchedule,
+        authorities: &HashMap<AccountId, Authority>,
+    ) -> StateSnapshot {
+        let token_supply = state.balances.values()
+            .map(|balance| balance.total_balance.saturating_add(balance.staked_balance))
+            .fold(0, Balance::saturating_add);
+        
+        StateSnapshot {
+            height: state.block_height,
+            block_hash: state.latest_block_hash.clone(),
+            balances: state.balances.clone(),
+            authorities: authorities.clone(),
+            validator_schedule: schedule.clone(),
+            token_supply,
+            validator_proposals: state.validator_set.proposals().clone(),
+            validator_liveness: state.liveness.clone(),
+        }
     }
     
-    /// Calculate state root hash
-    fn calculate_state_root(balances: &HashMap<AccountId, EnergyBalanceState>) -> Hash {
-        let mut data = Vec::new();
+    /// Persist a snapshot if the chain just reached a snapshot height
+    ///
+    /// Failures are only logged: the block itself is already stored, and a missing
+    /// snapshot just means a longer replay on the next restart.
+    async fn snapshot_if_due(
+        state: &BlockchainState,
+        schedule: &ValidatorSchedule,
+        authorities: &HashMap<AccountId, Authority>,
+        storage: &BlockchainStorage,
+    ) {
+        if !storage.snapshot_due(state.block_height).await {
+            return;
+        }
+        if let Err(e) = storage.save_snapshot(&Self::take_snapshot(state, schedule, authorities)).await {
+            crate::utils::logging::log_warning(
+                "ConsensusEngine",
+                &format!("Failed to save state snapshot at block #{}: {}", state.block_height, e)
+            );
+        }
+    }
+    
+    /// Get a snapshot of the current chain state
+    pub async fn create_snapshot(&self) -> StateSnapshot {
+        let state = self.blockchain_state.read().await;
+        let schedule = self.validator_schedule.read().await;
+        let authorities = self.authorities.read().await;
+        Self::take_snapshot(&state, &schedule, &authorities)
+    }
+    
+    /// Append a validated block to the chain, advance the validator schedule, record the
+    /// slots it settles, apply the validator-set change the block records and slash the
+    /// authorities it carries evidence against
+    fn append_block(
+        state: &mut BlockchainState,
+        schedule: &mut ValidatorSchedule,
+        authorities: &mut HashMap<AccountId, Authority>,
+        block: EnergyBlock,
+        post_balances: HashMap<AccountId, EnergyBalanceState>,
+    ) {
+        state.block_height = block.header.number;
+        state.latest_block_hash = block.header.hash.clone();
+        state.balances = post_balances;
+        state.pending_transactions.remove_included(&block.transactions);
+        for tx in &block.transactions {
+            state.transaction_index.insert(tx.hash, block.header.number);
+        }
         
-        // Sort accounts for deterministic hash
-        let mut sorted_accounts: Vec<_> = balances.keys().collect();
-        sorted_accounts.sort();
+        let BlockchainState { blocks, validator_set, liveness, .. } = state;
+        let parent = &blocks[blocks.len() - 1].header;
+        Self::apply_validator_effects(parent, &block, schedule, authorities, validator_set, liveness);
         
-        for account in sorted_accounts {
-            if let Some(balance) = balances.get(account) {
-                data.extend_from_slice(account.as_bytes());
-                data.extend_from_slice(&balance.total_balance.to_be_bytes());
-                data.extend_from_slice(&balance.nonce.to_be_bytes());
+        state.block_tree.insert_checkpoint(
+            block.header.hash.clone(),
+            Self::checkpoint(
+                block.header.number, state.balances.clone(), schedule, authorities, &state.validator_set, &state.liveness,
+            ),
+        );
+        state.blocks.push(block);
+    }
+    
+    /// Move the rotation on past a block on top of `parent` and apply its validator-side
+    /// effects: the slots it settles, its validator-set proposals and votes, the change it
+    /// records and the authorities it carries evidence against
+    fn apply_validator_effects(
+        parent: &BlockHeader,
+        block: &EnergyBlock,
+        schedule: &mut ValidatorSchedule,
+        authorities: &mut HashMap<AccountId, Authority>,
+        validator_set: &mut ValidatorSetGovernance,
+        liveness: &mut HashMap<AccountId, ValidatorLiveness>,
+    ) {
+        let skipped = schedule.skipped_validators(parent, block.header.timestamp);
+        schedule.advance(block.header.timestamp, skipped.len());
+        liveness::record_block(liveness, authorities, &block.header, &skipped);
+        validator_set.record_block(block, authorities);
+        if let Some(change) = &block.header.validator_set_change {
+            Self::apply_validator_set_change(schedule, authorities, change, &block.header);
+        }
+        Self::apply_slashes(schedule, authorities, block);
+    }
+    
+    /// Post-state of the block at `number`, kept so competing blocks can build on it
+    fn checkpoint(
+        number: u32,
+        balances: HashMap<AccountId, EnergyBalanceState>,
+        schedule: &ValidatorSchedule,
+        authorities: &HashMap<AccountId, Authority>,
+        validator_set: &ValidatorSetGovernance,
+        liveness: &HashMap<AccountId, ValidatorLiveness>,
+    ) -> BlockCheckpoint {
+        BlockCheckpoint {
+            number,
+            balances,
+            validator_index: schedule.current_validator_index,
+            round: schedule.round,
+            active_validators: schedule.active_validators.clone(),
+            authorities: authorities.clone(),
+            validator_proposals: validator_set.proposals().clone(),
+            liveness: liveness.clone(),
+        }
+    }
+    
+    /// Switch to the validator set an epoch boundary block records
+    fn apply_validator_set_change(
+        schedule: &mut ValidatorSchedule,
+        authorities: &mut HashMap<AccountId, Authority>,
+        change: &ValidatorSetChange,
+        header: &BlockHeader,
+    ) {
+        for account_id in &change.removed {
+            authorities.remove(account_id);
+            schedule.remove(account_id);
+        }
+        for candidate in &change.added {
+            let authority = Authority {
+                registered_at: header.timestamp,
+                ..Authority::new(
+                    candidate.account_id.clone(),
+                    candidate.name.clone(),
+                    Vec::new(),
+                    Vec::new(),
+                    candidate.public_key.clone(),
+                )
+            };
+            authorities.insert(candidate.account_id.clone(), authority);
+            if !schedule.active_validators.contains(&candidate.account_id) {
+                schedule.active_validators.push(candidate.account_id.clone());
             }
         }
         
-        let mut hasher = Sha256::new();
-        hasher.update(&data);
-        hex::encode(hasher.finalize())
+        crate::utils::logging::log_info(
+            "ConsensusEngine",
+            &format!(
+                "Validator set changed after block #{}: {} added, {} removed, {} active",
+                header.number, change.added.len(), change.removed.len(), schedule.active_validators.len()
+            )
+        );
+    }
+    
+    /// Deactivate the authorities a block carries evidence against and drop them from the
+    /// rotation of its branch
+    ///
+    /// Authorities that are already inactive are left alone.
+    fn apply_slashes(
+        schedule: &mut ValidatorSchedule,
+        authorities: &mut HashMap<AccountId, Authority>,
+        block: &EnergyBlock,
+    ) {
+        for tx in &block.transactions {
+            let evidence = match &tx.transaction {
+                EnergyTransaction::ReportMisbehavior { evidence, .. } => evidence,
+                _ => continue,
+            };
+            let offender = evidence.offender();
+            let authority = match authorities.get_mut(offender) {
+                Some(authority) if authority.is_active => authority,
+                _ => continue,
+            };
+            authority.is_active = false;
+            authority.reputation = authority.reputation.saturating_sub(evidence.reputation_penalty()).max(FixedPoint::ZERO);
+            schedule.remove(offender);
+            
+            crate::utils::logging::log_warning(
+                "ConsensusEngine",
+                &format!("Slashed validator {} in block #{}", offender, block.header.number)
+            );
+        }
+    }
+    
+    /// Get current validator set
+    pub async fn get_validators(&self) -> HashSet<AccountId> {
+        self.authorities.read().await.keys().cloned().collect()
+    }
+    
+    /// Get registered authority information for a validator
+    pub async fn get_authority(&self, validator_id: &AccountId) -> Option<Authority> {
+        self.authorities.read().await.get(validator_id).cloned()
+    }
+    
+    /// Get the account ID this node signs blocks with
+    pub fn node_account_id(&self) -> AccountId {
+        self.node_keypair.account_id().to_string()
+    }
+    
+    /// Get validator schedule information
+    pub async fn get_validator_schedule(&self) -> ValidatorSchedule {
+        self.validator_schedule.read().await.clone()
+    }
+    
+    /// Get the produced and missed slots of every authority that had a slot
+    pub async fn get_validator_liveness(&self) -> HashMap<AccountId, ValidatorLiveness> {
+        self.blockchain_state.read().await.liveness.clone()
+    }
+    
+    /// Verify validator signature on a block
+    ///
+    /// Returns an error if the block was produced by an unknown or inactive authority,
+    /// and `Ok(false)` if the hash or signature does not check out.
+    pub async fn verify_block_signature(&self, block: &EnergyBlock) -> SystemResult<bool> {
+        let authorities = self.authorities.read().await;
+        match Self::validate_block_signature(block, &authorities) {
+            Ok(()) => Ok(true),
+            Err(BlockRejection::InvalidSignature) | Err(BlockRejection::InvalidBlockHash) => Ok(false),
+            Err(rejection) => Err(crate::utils::SystemError::Blockchain(rejection.to_string())),
+        }
+    }
+    
+    /// Check a block signature against a given authority set
+    fn validate_block_signature(
+        block: &EnergyBlock,
+        authorities: &HashMap<AccountId, Authority>,
+    ) -> Result<(), BlockRejection> {
+        let validator = &block.header.validator;
+        let authority = authorities.get(validator)
+            .ok_or_else(|| BlockRejection::UnknownAuthority(validator.clone()))?;
+        
+        if !authority.is_active {
+            return Err(BlockRejection::InactiveAuthority(validator.clone()));
+        }
+        
+        let public_key: [u8; 32] = authority.public_key.as_slice().try_into()
+            .map_err(|_| BlockRejection::MissingPublicKey(validator.clone()))?;
+        let verifying_key = VerifyingKey::from_bytes(&public_key)
+            .map_err(|_| BlockRejection::MissingPublicKey(validator.clone()))?;
+        
+        // The signature covers the block hash, so the hash itself must be genuine
+        if Self::calculate_block_hash(block) != block.header.hash {
+            return Err(BlockRejection::InvalidBlockHash);
+        }
+        
+        if block.validator_signature.validator != *validator {
+            return Err(BlockRejection::InvalidSignature);
+        }
+        
+        let signature = Signature::from_slice(&block.validator_signature.signature)
+            .map_err(|_| BlockRejection::InvalidSignature)?;
+        let message = hex::decode(&block.header.hash)
+            .map_err(|_| BlockRejection::InvalidBlockHash)?;
+        
+        verifying_key.verify(&message, &signature)
+            .map_err(|_| BlockRejection::InvalidSignature)
+    }
+    
+    /// Sign a block hash with the validator key
+    fn sign_block_hash(block_hash: &Hash, node_keypair: &GridTokenXKeyPair) -> SystemResult<Vec<u8>> {
+        let message = hex::decode(block_hash)
+            .map_err(|e| crate::utils::SystemError::Blockchain(format!("Invalid block hash: {}", e)))?;
+        Ok(node_keypair.sign(&message).to_bytes().to_vec())
+    }
+    
+    /// Check that a genesis block is the one a chain spec derives
+    fn check_genesis(spec: &ChainSpec, genesis_block: &EnergyBlock) -> SystemResult<()> {
+        let expected = spec.genesis_block()?;
+        if genesis_block.header.hash != expected.header.hash {
+            return Err(crate::utils::SystemError::Configuration(format!(
+                "Genesis block {} does not match chain spec genesis {}",
+                genesis_block.header.hash, expected.header.hash
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Calculate block hash for PoA
+    pub fn calculate_block_hash(block: &EnergyBlock) -> Hash {
+        Self::calculate_header_hash(&block.header)
+    }
+    
+    /// Calculate the hash a block header commits to; the block body is covered by its roots
+    pub fn calculate_header_hash(header: &BlockHeader) -> Hash {
+        let hash = encoding::hash(&(
+            header.number,
+            &header.parent_hash,
+            &header.transaction_root,
+            &header.state_root,
+            &header.receipts_root,
+            header.timestamp,
+            &header.validator,
+            &header.validator_set_change,
+        )).expect("block headers hold no floats and always encode");
+        hex::encode(hash)
+    }
+    
+    /// Calculate merkle root of transactions
+    ///
+    /// The root is built from the hashes the transactions carry; blocks from other nodes have
+    /// them checked against the transaction contents first.
+    pub fn calculate_merkle_root(transactions: &[EnergyTransactionEnvelope]) -> Hash {
+        let leaves: Vec<[u8; 32]> = transactions.iter().map(|tx| tx.hash).collect();
+        merkle::merkle_root(&leaves).map(hex::encode).unwrap_or_default()
+    }
+    
+    /// Calculate merkle root of transaction receipts
+    pub fn calculate_receipts_root(receipts: &[TransactionReceipt]) -> Hash {
+        let leaves: Vec<[u8; 32]> = receipts.iter()
+            .map(|receipt| encoding::hash(receipt).expect("receipts hold no floats and always encode"))
+            .collect();
+        merkle::merkle_root(&leaves).map(hex::encode).unwrap_or_default()
+    }
+    
+    /// Compute the state root after a block by updating the accounts it changed
+    fn post_state_root(
+        state: &BlockchainState,
+        parent: &ParentState,
+        post_balances: &HashMap<AccountId, EnergyBalanceState>,
+    ) -> Result<Hash, BlockRejection> {
+        let parent_root = parent.header.state_root.clone();
+        let changed = post_balances.iter()
+            .filter(|(account, balance)| parent.balances.get(*account) != Some(*balance));
+        
+        state.state_trie.update(&parent_root, changed)
+            .ok_or(BlockRejection::UnknownParentState(parent_root))
     }
     
     /// Calculate energy statistics for a block
     fn calculate_energy_stats(transactions: &[EnergyTransactionEnvelope]) -> EnergyBlockStats {
-        let mut total_energy_traded = 0.0f64;
-        let mut energy_by_source: HashMap<EnergySource, f64> = HashMap::new();
+        let mut total_energy_traded = EnergyAmount::ZERO;
+        let mut energy_by_source: HashMap<EnergySource, EnergyAmount> = HashMap::new();
         let mut trade_count = 0;
         let mut total_price = 0u128;
         let mut gas_used = 0;
-        let mut carbon_credits = 0u32;
+        let mut carbon_credits = FixedPoint::ZERO;
         
         for tx in transactions {
             gas_used += tx.get_gas_cost();
             
             match &tx.transaction {
                 EnergyTransaction::ExecuteTrade { trade, .. } => {
-                    total_energy_traded += trade.energy_amount;
-                    *energy_by_source.entry(trade.energy_source.clone()).or_insert(0.0) += trade.energy_amount;
+                    total_energy_traded = total_energy_traded.saturating_add(trade.energy_amount);
+                    let by_source = energy_by_source.entry(trade.energy_source.clone()).or_default();
+                    *by_source = by_source.saturating_add(trade.energy_amount);
                     trade_count += 1;
-                    total_price += trade.total_price;
-                    carbon_credits += trade.carbon_offset.offset_credits as u32;
+                    total_price = total_price.saturating_add(trade.total_price);
+                    carbon_credits = carbon_credits.saturating_add(trade.carbon_offset.offset_credits);
                 }
                 EnergyTransaction::ReportProduction { production_record, .. } => {
-                    *energy_by_source.entry(production_record.energy_type.clone()).or_insert(0.0) += production_record.amount;
+                    let by_source = energy_by_source.entry(production_record.energy_type.clone()).or_default();
+                    *by_source = by_source.saturating_add(production_record.amount);
                 }
                 _ => {}
             }
         }
         
-        let average_price = if trade_count > 0 {
-            (total_price / trade_count as u128) as TokenPrice
-        } else {
-            0.0
-        };
+        let average_price = TokenPrice::average(total_price, total_energy_traded).unwrap_or_default();
         
         EnergyBlockStats {
             total_energy_traded,
@@ -577,59 +1693,218 @@ impl ConsensusEngine {
         }
     }
     
-    /// Add transaction to pending pool
+    /// Admit a submitted transaction to the pool
+    ///
+    /// The transaction's signature, nonce and the sender's balance are checked against the
+    /// current chain state and the sender's pooled transactions; rejected transactions are
+    /// reported with the reason.
+    pub async fn admit_transaction(&self, tx: EnergyTransactionEnvelope) -> Result<(), AdmissionRejection> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut state = self.blockchain_state.write().await;
+        transaction_pool::check_admission(&tx, &state.balances, &state.pending_transactions)?;
+        state.pending_transactions.insert(tx, now)?;
+        Ok(())
+    }
+    
+    /// Add transaction to pending pool without admission checks, e.g. for locally built
+    /// transactions
     pub async fn add_transaction(&self, tx: EnergyTransactionEnvelope) -> SystemResult<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
         let mut state = self.blockchain_state.write().await;
-        state.pending_transactions.push(tx);
+        state.pending_transactions.insert(tx, now)
+            .map_err(|rejection| crate::utils::SystemError::Validation(rejection.to_string()))
+    }
+    
+    /// Transactions waiting in the pool, grouped by sender in nonce order
+    pub async fn pending_transactions(&self) -> Vec<EnergyTransactionEnvelope> {
+        self.blockchain_state.read().await.pending_transactions.transactions()
+    }
+    
+    /// Subscribe to blocks produced by this node
+    pub fn subscribe_produced_blocks(&self) -> broadcast::Receiver<EnergyBlock> {
+        self.produced_blocks.subscribe()
+    }
+    
+    /// Subscribe to switches of the canonical chain to another branch
+    pub fn subscribe_reorgs(&self) -> broadcast::Receiver<ChainReorg> {
+        self.reorgs.subscribe()
+    }
+    
+    /// Subscribe to finality precommits signed by this node
+    pub fn subscribe_precommits(&self) -> broadcast::Receiver<Precommit> {
+        self.finality.subscribe()
+    }
+    
+    /// Record a precommit received from another authority
+    ///
+    /// The precommit must be signed by an active authority's registered key. Precommits
+    /// for blocks we have not imported yet are buffered and counted once the block arrives.
+    ///
+    /// Precommits add weight to their block's branch, so a precommit may also make the
+    /// chain switch to a competing branch.
+    pub async fn import_precommit(&self, precommit: Precommit) -> Result<(), PrecommitRejection> {
+        let mut state = self.blockchain_state.write().await;
+        let mut schedule = self.validator_schedule.write().await;
+        let mut authorities = self.authorities.write().await;
+        self.finality.import(precommit, &state, &authorities).await?;
+        
+        if let Err(e) = self.apply_fork_choice(&mut state, &mut schedule, &mut authorities).await {
+            crate::utils::logging::log_warning(
+                "ConsensusEngine",
+                &format!("Failed to switch to the preferred branch: {}", e)
+            );
+        }
+        Self::prune_block_tree(&mut state, self.finality.finalized_number());
         Ok(())
     }
     
+    /// Get the number of the highest final block
+    pub fn finalized_height(&self) -> u32 {
+        self.finality.finalized_number()
+    }
+    
+    /// Get the hash of the highest final block
+    pub async fn finalized_hash(&self) -> Hash {
+        self.finality.finalized_hash().await
+    }
+    
+    /// Get the precommits collected so far for a block that is not final yet
+    pub async fn get_precommits(&self, block_number: u32, block_hash: &Hash) -> Vec<Precommit> {
+        self.finality.precommits_for(block_number, block_hash).await
+    }
+    
+    /// Check whether an included transaction's block has been finalized
+    pub async fn is_transaction_finalized(&self, tx_hash: &[u8; 32]) -> bool {
+        let state = self.blockchain_state.read().await;
+        state.transaction_index.get(tx_hash)
+            .is_some_and(|block_number| *block_number <= self.finality.finalized_number())
+    }
+    
+    /// Wait until the chain is final up to at least `height`
+    pub async fn wait_for_finality(&self, height: u32) -> SystemResult<()> {
+        let mut finalized = self.finality.watch();
+        finalized.wait_for(|finalized| *finalized >= height).await
+            .map(|_| ())
+            .map_err(|_| crate::utils::SystemError::Blockchain("Finality gadget has stopped".to_string()))
+    }
+    
     /// Get current blockchain state
     pub async fn get_blockchain_state(&self) -> BlockchainState {
         self.blockchain_state.read().await.clone()
     }
     
+    /// Get the genesis block this chain was started from
+    pub async fn genesis_block(&self) -> EnergyBlock {
+        self.blockchain_state.read().await.blocks[0].clone()
+    }
+    
+    /// Get the genesis hash and chain head announced to syncing peers
+    pub async fn chain_status(&self) -> ChainStatus {
+        let state = self.blockchain_state.read().await;
+        ChainStatus {
+            genesis_hash: state.blocks[0].header.hash.clone(),
+            best_number: state.block_height,
+            best_hash: state.latest_block_hash.clone(),
+        }
+    }
+    
+    /// Get up to `count` consecutive block headers starting at block `from`
+    pub async fn get_headers(&self, from: u32, count: u32) -> Vec<BlockHeader> {
+        let state = self.blockchain_state.read().await;
+        state.blocks.iter()
+            .skip(from as usize)
+            .take(count as usize)
+            .map(|block| block.header.clone())
+            .collect()
+    }
+    
+    /// Get the blocks with the given hashes, skipping unknown ones
+    ///
+    /// Blocks on side branches are served as well.
+    pub async fn get_blocks_by_hash(&self, hashes: &[Hash]) -> Vec<EnergyBlock> {
+        let state = self.blockchain_state.read().await;
+        let by_hash: HashMap<&Hash, &EnergyBlock> = state.blocks.iter()
+            .map(|block| (&block.header.hash, block))
+            .collect();
+        hashes.iter()
+            .filter_map(|hash| by_hash.get(hash).copied().or_else(|| state.block_tree.side_block(hash)).cloned())
+            .collect()
+    }
+    
+    /// Check whether a block is on the canonical chain or a side branch
+    pub async fn has_block(&self, hash: &Hash) -> bool {
+        let state = self.blockchain_state.read().await;
+        state.block_tree.contains(hash) || state.blocks.iter().any(|block| block.header.hash == *hash)
+    }
+    
+    /// Get the receipt of an included transaction by its hash
+    pub async fn get_transaction_receipt(&self, tx_hash: &[u8; 32]) -> Option<TransactionReceipt> {
+        let state = self.blockchain_state.read().await;
+        let block_number = *state.transaction_index.get(tx_hash)?;
+        let block = state.blocks.get(block_number as usize)?;
+        let position = block.transactions.iter().position(|tx| tx.hash == *tx_hash)?;
+        block.receipts.get(position).cloned()
+    }
+    
+    /// Prove that an included transaction is part of its block's transaction root
+    pub async fn get_transaction_proof(&self, tx_hash: &[u8; 32]) -> Option<TransactionProof> {
+        let state = self.blockchain_state.read().await;
+        let block_number = *state.transaction_index.get(tx_hash)?;
+        let block = state.blocks.get(block_number as usize)?;
+        let leaves: Vec<[u8; 32]> = block.transactions.iter().map(|tx| tx.hash).collect();
+        let index = leaves.iter().position(|leaf| leaf == tx_hash)?;
+        
+        Some(TransactionProof {
+            block_number,
+            block_hash: block.header.hash.clone(),
+            index: index as u32,
+            leaf_count: leaves.len() as u32,
+            siblings: merkle::merkle_path(&leaves, index)?.into_iter().map(hex::encode).collect(),
+        })
+    }
+    
+    /// Prove an account's state against the state root of the block at `height`
+    ///
+    /// Returns `None` for unknown heights and for heights whose state is no longer held,
+    /// such as blocks covered by a restored snapshot.
+    pub async fn get_account_proof(&self, account: &AccountId, height: u32) -> Option<AccountProof> {
+        let state = self.blockchain_state.read().await;
+        let block = state.blocks.get(height as usize)?;
+        state.state_trie.prove(&block.header.state_root, account)
+    }
+    
     /// Get account balance
     pub async fn get_account_balance(&self, account: &AccountId) -> Option<EnergyBalanceState> {
         let state = self.blockchain_state.read().await;
         state.balances.get(account).cloned()
     }
     
-    /// Apply transaction to blockchain state
-    #[allow(dead_code)]
-    async fn apply_transaction_to_state(state: &mut BlockchainState, tx: &EnergyTransactionEnvelope) {
-        // Remove from pending transactions
-        state.pending_transactions.retain(|pending_tx| pending_tx.hash != tx.hash);
-        
-        // Apply transaction effects to balances based on transaction type
-        match &tx.transaction {
-            EnergyTransaction::Transfer { from, to, amount, .. } => {
-                // Handle token transfer
-                if let Some(sender_balance) = state.balances.get_mut(from) {
-                    sender_balance.total_balance = sender_balance.total_balance.saturating_sub(*amount);
-                    sender_balance.increment_nonce();
-                }
-                
-                let receiver_balance = state.balances.entry(to.clone()).or_insert_with(EnergyBalanceState::new);
-                receiver_balance.total_balance = receiver_balance.total_balance.saturating_add(*amount);
-            }
-            EnergyTransaction::ExecuteTrade { trade, .. } => {
-                // Handle energy trade execution
-                if let Some(buyer_balance) = state.balances.get_mut(&trade.buyer_id) {
-                    buyer_balance.total_balance = buyer_balance.total_balance.saturating_sub(trade.total_price);
-                }
-                
-                if let Some(seller_balance) = state.balances.get_mut(&trade.seller_id) {
-                    seller_balance.total_balance = seller_balance.total_balance.saturating_add(trade.total_price);
-                }
-            }
-            _ => {
-                // For other transaction types, just increment nonce
-                let sender = tx.get_sender();
-                let balance_state = state.balances.entry(sender).or_insert_with(EnergyBalanceState::new);
-                balance_state.increment_nonce();
-            }
-        }
+    /// Code and storage of the contract deployed at `address` as of the chain head
+    pub async fn get_contract(&self, address: &AccountId) -> Option<ContractState> {
+        let state = self.blockchain_state.read().await;
+        state.balances.get(address)?.contract.clone()
+    }
+    
+    /// VM contract transactions are executed in
+    pub async fn contract_vm(&self) -> Arc<SmartContractVM> {
+        self.blockchain_state.read().await.contract_vm.clone()
+    }
+    
+    /// Run `call` against the chain head, which contracts read account balances from as needed
+    pub async fn with_contract_context<R>(&self, call: impl FnOnce(&ExecutionContext) -> R) -> R {
+        let state = self.blockchain_state.read().await;
+        let head = &state.blocks[state.blocks.len() - 1].header;
+        call(&ExecutionContext {
+            block_number: head.number,
+            timestamp: head.timestamp,
+            balances: &state.balances,
+        })
     }
 }
 
@@ -649,6 +1924,68 @@ impl ValidatorSchedule {
         let next_index = (self.current_validator_index + 1) % self.active_validators.len();
         self.active_validators.get(next_index)
     }
+    
+    /// Number of slots that passed to the next validator before a block at `timestamp`
+    /// on top of `parent`
+    ///
+    /// A slot opens `block_interval` seconds after the parent and lasts `slot_timeout`
+    /// seconds. The genesis timestamp predates the network start, so the first block is
+    /// always due from the first validator.
+    pub fn missed_slots(&self, parent: &BlockHeader, timestamp: u64) -> usize {
+        if self.slot_timeout == 0 || parent.number == 0 || self.active_validators.is_empty() {
+            return 0;
+        }
+        let overdue = timestamp.saturating_sub(parent.timestamp.saturating_add(self.block_interval));
+        (overdue / self.slot_timeout) as usize
+    }
+    
+    /// Validator due to produce once `missed_slots` slots have passed
+    pub fn slot_validator(&self, missed_slots: usize) -> Option<&AccountId> {
+        if self.active_validators.is_empty() {
+            return None;
+        }
+        self.active_validators.get((self.current_validator_index + missed_slots) % self.active_validators.len())
+    }
+    
+    /// Validators that missed their slot before a block at `timestamp` on top of `parent`
+    pub fn skipped_validators(&self, parent: &BlockHeader, timestamp: u64) -> Vec<AccountId> {
+        (0..self.missed_slots(parent, timestamp))
+            .filter_map(|missed| self.slot_validator(missed).cloned())
+            .collect()
+    }
+    
+    /// Move the rotation on after a block produced at `timestamp`, `missed_slots` slots
+    /// after the current validator's
+    fn advance(&mut self, timestamp: u64, missed_slots: usize) {
+        if !self.active_validators.is_empty() {
+            let next = self.current_validator_index + missed_slots + 1;
+            self.round += (next / self.active_validators.len()) as u64;
+            self.current_validator_index = next % self.active_validators.len();
+        } else {
+            self.round += 1;
+        }
+        self.last_block_time = timestamp;
+    }
+    
+    /// Drop a validator from the rotation, keeping the validators after it in their slots
+    fn remove(&mut self, validator: &AccountId) {
+        if let Some(position) = self.active_validators.iter().position(|active| active == validator) {
+            self.active_validators.remove(position);
+            if position < self.current_validator_index {
+                self.current_validator_index -= 1;
+            }
+            if self.current_validator_index >= self.active_validators.len() {
+                self.current_validator_index = 0;
+            }
+        }
+    }
+    
+    /// Reset the rotation to the position it had after another block
+    fn restore(&mut self, validator_index: usize, round: u64, last_block_time: u64) {
+        self.current_validator_index = if validator_index < self.active_validators.len() { validator_index } else { 0 };
+        self.round = round;
+        self.last_block_time = last_block_time;
+    }
 }
 
 impl Authority {
@@ -664,7 +2001,7 @@ impl Authority {
             account_id,
             name,
             expertise,
-            reputation: 1.0, // Start with neutral reputation
+            reputation: STARTING_REPUTATION,
             authorized_regions,
             public_key,
             is_active: true,
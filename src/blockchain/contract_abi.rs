@@ -0,0 +1,168 @@
+//! # Contract ABI Encoding
+//!
+//! Typed encoding of contract call arguments and return values. A contract function
+//! declares the types of its inputs and outputs; values are written one after another in
+//! declaration order, in the layout contract code reads with plain WebAssembly loads:
+//!
+//! | Type | Layout |
+//! |------|--------|
+//! | `bool` | 1 byte, 0 or 1 |
+//! | `u32`, `u64`, `u128`, `i64` | Fixed width, little-endian |
+//! | `string`, `bytes`, `address` | `u32` little-endian length, then the bytes |
+//!
+//! Strings and addresses must be valid UTF-8. Decoding rejects missing and trailing bytes.
+
+use crate::types::AccountId;
+use serde::{Deserialize, Serialize};
+
+/// Type of a contract function parameter or return value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AbiType {
+    Bool,
+    U32,
+    U64,
+    U128,
+    I64,
+    String,
+    Bytes,
+    Address,
+}
+
+/// Value of a contract function parameter or return value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AbiValue {
+    Bool(bool),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    I64(i64),
+    String(String),
+    Bytes(Vec<u8>),
+    Address(AccountId),
+}
+
+/// Reason values cannot be encoded or decoded for a function signature
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum AbiError {
+    #[error("expected {expected} values, got {found}")]
+    ArityMismatch { expected: usize, found: usize },
+    #[error("value {index} should be {expected:?}, got {found:?}")]
+    TypeMismatch { index: usize, expected: AbiType, found: AbiType },
+    #[error("value {index} is {length} bytes, longer than the encoding allows")]
+    TooLong { index: usize, length: usize },
+    #[error("value {index} is cut off")]
+    UnexpectedEnd { index: usize },
+    #[error("value {index} is not a valid {expected:?}")]
+    InvalidValue { index: usize, expected: AbiType },
+    #[error("{0} bytes left after the last value")]
+    TrailingBytes(usize),
+}
+
+impl AbiValue {
+    /// Type of the value
+    pub fn abi_type(&self) -> AbiType {
+        match self {
+            AbiValue::Bool(_) => AbiType::Bool,
+            AbiValue::U32(_) => AbiType::U32,
+            AbiValue::U64(_) => AbiType::U64,
+            AbiValue::U128(_) => AbiType::U128,
+            AbiValue::I64(_) => AbiType::I64,
+            AbiValue::String(_) => AbiType::String,
+            AbiValue::Bytes(_) => AbiType::Bytes,
+            AbiValue::Address(_) => AbiType::Address,
+        }
+    }
+}
+
+/// Encode `values`, checking them against the declared `types`
+pub fn encode(types: &[AbiType], values: &[AbiValue]) -> Result<Vec<u8>, AbiError> {
+    if types.len() != values.len() {
+        return Err(AbiError::ArityMismatch { expected: types.len(), found: values.len() });
+    }
+
+    let mut output = Vec::new();
+    for (index, (expected, value)) in types.iter().zip(values).enumerate() {
+        if value.abi_type() != *expected {
+            return Err(AbiError::TypeMismatch { index, expected: *expected, found: value.abi_type() });
+        }
+        match value {
+            AbiValue::Bool(value) => output.push(*value as u8),
+            AbiValue::U32(value) => output.extend_from_slice(&value.to_le_bytes()),
+            AbiValue::U64(value) => output.extend_from_slice(&value.to_le_bytes()),
+            AbiValue::U128(value) => output.extend_from_slice(&value.to_le_bytes()),
+            AbiValue::I64(value) => output.extend_from_slice(&value.to_le_bytes()),
+            AbiValue::String(value) | AbiValue::Address(value) => write_bytes(&mut output, index, value.as_bytes())?,
+            AbiValue::Bytes(value) => write_bytes(&mut output, index, value)?,
+        }
+    }
+    Ok(output)
+}
+
+/// Decode values of the declared `types` from `input`
+pub fn decode(types: &[AbiType], input: &[u8]) -> Result<Vec<AbiValue>, AbiError> {
+    let mut reader = Reader { input, index: 0 };
+    let mut values = Vec::with_capacity(types.len());
+    for (index, abi_type) in types.iter().enumerate() {
+        reader.index = index;
+        let value = match abi_type {
+            AbiType::Bool => match reader.take(1)?[0] {
+                0 => AbiValue::Bool(false),
+                1 => AbiValue::Bool(true),
+                _ => return Err(AbiError::InvalidValue { index, expected: *abi_type }),
+            },
+            AbiType::U32 => AbiValue::U32(u32::from_le_bytes(reader.take_array()?)),
+            AbiType::U64 => AbiValue::U64(u64::from_le_bytes(reader.take_array()?)),
+            AbiType::U128 => AbiValue::U128(u128::from_le_bytes(reader.take_array()?)),
+            AbiType::I64 => AbiValue::I64(i64::from_le_bytes(reader.take_array()?)),
+            AbiType::Bytes => AbiValue::Bytes(reader.take_prefixed()?.to_vec()),
+            AbiType::String | AbiType::Address => {
+                let text = String::from_utf8(reader.take_prefixed()?.to_vec())
+                    .map_err(|_| AbiError::InvalidValue { index, expected: *abi_type })?;
+                match abi_type {
+                    AbiType::String => AbiValue::String(text),
+                    _ => AbiValue::Address(text),
+                }
+            }
+        };
+        values.push(value);
+    }
+
+    if !reader.input.is_empty() {
+        return Err(AbiError::TrailingBytes(reader.input.len()));
+    }
+    Ok(values)
+}
+
+fn write_bytes(output: &mut Vec<u8>, index: usize, bytes: &[u8]) -> Result<(), AbiError> {
+    let length = u32::try_from(bytes.len()).map_err(|_| AbiError::TooLong { index, length: bytes.len() })?;
+    output.extend_from_slice(&length.to_le_bytes());
+    output.extend_from_slice(bytes);
+    Ok(())
+}
+
+/// Cursor over encoded values, tracking which value is being read for error reports
+struct Reader<'a> {
+    input: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], AbiError> {
+        if self.input.len() < length {
+            return Err(AbiError::UnexpectedEnd { index: self.index });
+        }
+        let (taken, rest) = self.input.split_at(length);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], AbiError> {
+        Ok(self.take(N)?.try_into().expect("took exactly N bytes"))
+    }
+
+    fn take_prefixed(&mut self) -> Result<&'a [u8], AbiError> {
+        let length = u32::from_le_bytes(self.take_array()?) as usize;
+        self.take(length)
+    }
+}
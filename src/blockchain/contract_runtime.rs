@@ -0,0 +1,502 @@
+//! # Contract Runtime
+//!
+//! Sandboxed WebAssembly execution of deployed contract code.
+//!
+//! A contract is a Wasm module exporting its linear `memory` and, for every function of its
+//! ABI, a Wasm function of the same name taking no parameters and returning nothing. An
+//! exported `deploy` function runs once when the contract is deployed. Contracts reach the
+//! chain only through the host functions they import from the `env` module:
+//!
+//! | Function | Signature | Effect |
+//! |----------|-----------|--------|
+//! | `input` | `(out_ptr, out_cap) -> len` | Arguments of the call |
+//! | `caller` | `(out_ptr, out_cap) -> len` | Account calling the contract |
+//! | `address` | `(out_ptr, out_cap) -> len` | Address of the contract itself |
+//! | `block_number` | `() -> i64` | Number of the chain head |
+//! | `block_timestamp` | `() -> i64` | Timestamp of the chain head |
+//! | `balance` | `(account_ptr, account_len, out_ptr)` | Balance as 16 little-endian bytes |
+//! | `storage_read` | `(key_ptr, key_len, out_ptr, out_cap) -> len` | Stored value, -1 if unset |
+//! | `storage_write` | `(key_ptr, key_len, value_ptr, value_len)` | Store a value |
+//! | `storage_remove` | `(key_ptr, key_len)` | Delete a value |
+//! | `emit_event` | `(topic_ptr, topic_len, data_ptr, data_len)` | Emit an event |
+//! | `return_value` | `(ptr, len)` | Set the call output |
+//! | `revert` | `(msg_ptr, msg_len)` | Abort the call with a message |
+//!
+//! Byte strings are passed as pointer and length into contract memory. Host functions
+//! returning one copy at most `out_cap` bytes to `out_ptr` and return the full length, so a
+//! contract can retry with a larger buffer. Storage keys, topics and accounts are UTF-8.
+//!
+//! Floating-point instructions are rejected and memory is capped, so every node executes a
+//! contract the same way. Execution is metered: every instruction and host function call is
+//! charged according to the chain's [`GasSchedule`], and a call running out of gas is aborted.
+//! Storage writes are applied only when the call succeeds.
+
+use crate::blockchain::smart_contracts::{ContractEvent, ContractState};
+use crate::blockchain::transactions::EnergyBalanceState;
+use crate::config::SmartContractConfig;
+use crate::types::{AccountId, Balance};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wasmi::errors::LinkerError;
+use wasmi::{
+    Caller, CompilationMode, Config, Engine, Error, Extern, ExternType, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TrapCode,
+};
+
+/// Module contracts import host functions from
+pub const HOST_MODULE: &str = "env";
+
+/// Export called once when a contract is deployed, if present
+pub const DEPLOY_ENTRY_POINT: &str = "deploy";
+
+/// Linear memory a contract may grow to, in bytes
+pub const MAX_MEMORY_SIZE: usize = 16 * 64 * 1024;
+
+/// Version of the gas schedule this node charges by
+pub const GAS_SCHEDULE_VERSION: u32 = 2;
+
+/// Host functions available to contracts
+const HOST_FUNCTIONS: [&str; 12] = [
+    "input", "caller", "address", "block_number", "block_timestamp", "balance",
+    "storage_read", "storage_write", "storage_remove", "emit_event", "return_value", "revert",
+];
+
+/// Reason contract code is not deployed or a contract call fails
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ContractError {
+    #[error("WebAssembly execution is disabled")]
+    WasmDisabled,
+    #[error("contract code of {size} bytes exceeds the {limit} byte limit")]
+    CodeTooLarge { size: usize, limit: usize },
+    #[error("invalid contract module: {0}")]
+    InvalidModule(String),
+    #[error("contract does not export {0}")]
+    MissingExport(String),
+    #[error("contract storage of {size} bytes would exceed the {limit} byte limit")]
+    StorageLimitExceeded { size: usize, limit: usize },
+    #[error("contract accessed memory out of bounds")]
+    MemoryAccess,
+    #[error("contract passed a string that is not valid UTF-8")]
+    InvalidString,
+    #[error("contract ran out of its {limit} gas")]
+    OutOfGas { limit: u64 },
+    #[error("contract reverted: {0}")]
+    Reverted(String),
+    #[error("contract trapped: {0}")]
+    Trap(String),
+}
+
+impl wasmi::errors::HostError for ContractError {}
+
+/// Gas charged for contract execution
+///
+/// One unit of gas pays for one executed Wasm instruction; structural instructions such as
+/// `block`, `end` and `drop` are free. Host functions charge on top of the instruction calling
+/// them. Instruction costs are those of the pinned interpreter release and change only with
+/// the schedule version, which every node of a chain has to support.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GasSchedule {
+    /// Schedule version, raised whenever a cost changes
+    pub version: u32,
+    /// Gas for every host function call
+    pub host_call: u64,
+    /// Gas per byte copied between contract memory and the host
+    pub per_byte: u64,
+    /// Gas for reading a storage value
+    pub storage_read: u64,
+    /// Gas for looking up the balance of an account
+    pub balance_read: u64,
+    /// Gas for writing or removing a storage value
+    pub storage_write: u64,
+    /// Gas per byte of key and value written to storage
+    pub storage_write_per_byte: u64,
+    /// Gas for emitting an event
+    pub event: u64,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        Self {
+            version: GAS_SCHEDULE_VERSION,
+            host_call: 50,
+            per_byte: 1,
+            storage_read: 200,
+            balance_read: 200,
+            storage_write: 5_000,
+            storage_write_per_byte: 50,
+            event: 500,
+        }
+    }
+}
+
+/// Chain state a contract call runs against
+#[derive(Clone, Copy)]
+pub struct ExecutionContext<'a> {
+    /// Number of the chain head
+    pub block_number: u32,
+    /// Timestamp of the chain head
+    pub timestamp: u64,
+    /// Balances of the chain, looked up as the contract asks for them
+    pub balances: &'a dyn AccountBalances,
+}
+
+impl Default for ExecutionContext<'_> {
+    fn default() -> Self {
+        Self { block_number: 0, timestamp: 0, balances: &() }
+    }
+}
+
+/// Account balances contracts can read
+pub trait AccountBalances {
+    /// Tokens `account` can spend
+    fn available_balance(&self, account: &AccountId) -> Balance;
+}
+
+/// No account holds tokens
+impl AccountBalances for () {
+    fn available_balance(&self, _account: &AccountId) -> Balance {
+        0
+    }
+}
+
+impl AccountBalances for HashMap<AccountId, Balance> {
+    fn available_balance(&self, account: &AccountId) -> Balance {
+        self.get(account).copied().unwrap_or(0)
+    }
+}
+
+impl AccountBalances for HashMap<AccountId, EnergyBalanceState> {
+    fn available_balance(&self, account: &AccountId) -> Balance {
+        self.get(account).map_or(0, EnergyBalanceState::get_total_available_balance)
+    }
+}
+
+/// A call into a deployed contract
+#[derive(Debug, Clone, Copy)]
+pub struct ContractCall<'a> {
+    /// Address of the contract
+    pub address: &'a AccountId,
+    /// Account calling the contract
+    pub caller: &'a AccountId,
+    /// Exported function to run
+    pub entry_point: &'a str,
+    /// Arguments of the call
+    pub input: &'a [u8],
+    /// Gas the call may use
+    pub gas_limit: u64,
+}
+
+/// Gas a contract call used and what came of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractExecution {
+    pub gas_used: u64,
+    pub outcome: Result<ContractOutcome, ContractError>,
+}
+
+/// Effects of a successful contract call
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractOutcome {
+    /// Value set through `return_value`
+    pub output: Vec<u8>,
+    /// Events in the order they were emitted
+    pub events: Vec<ContractEvent>,
+    /// Contract storage after the call
+    pub storage: HashMap<String, Vec<u8>>,
+}
+
+/// State of one contract call, reachable from host functions
+struct HostState<'a> {
+    caller: AccountId,
+    address: AccountId,
+    context: ExecutionContext<'a>,
+    input: Vec<u8>,
+    storage: HashMap<String, Vec<u8>>,
+    storage_size: usize,
+    max_storage_size: usize,
+    events: Vec<ContractEvent>,
+    output: Vec<u8>,
+    limits: StoreLimits,
+    schedule: GasSchedule,
+    gas_limit: u64,
+}
+
+/// Executes contract modules with the host functions of the chain
+pub struct ContractRuntime {
+    config: SmartContractConfig,
+    engine: Engine,
+}
+
+impl ContractRuntime {
+    pub fn new(config: &SmartContractConfig) -> Self {
+        let mut engine_config = Config::default();
+        engine_config.floats(false)
+            .consume_fuel(true)
+            .compilation_mode(CompilationMode::Eager);
+        Self {
+            config: config.clone(),
+            engine: Engine::new(&engine_config),
+        }
+    }
+
+    /// Check that code can be deployed as a contract exposing `entry_points`
+    pub fn validate(&self, code: &[u8], entry_points: &[&str]) -> Result<(), ContractError> {
+        self.compile(code).and_then(|module| {
+            for entry_point in entry_points {
+                entry_point_export(&module, entry_point)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Gas schedule calls are charged by
+    pub fn gas_schedule(&self) -> &GasSchedule {
+        &self.config.gas_schedule
+    }
+
+    /// Whether the code exports a function named `name`
+    pub fn exports_function(&self, code: &[u8], name: &str) -> bool {
+        self.compile(code).is_ok_and(|module| entry_point_export(&module, name).is_ok())
+    }
+
+    /// Run a call into `contract`, charging at most the call's gas limit
+    ///
+    /// Code that cannot be run is rejected before any gas is used.
+    pub fn execute(&self, contract: &ContractState, call: ContractCall<'_>, context: &ExecutionContext) -> ContractExecution {
+        let module = match self.compile(&contract.code).and_then(|module| {
+            entry_point_export(&module, call.entry_point)?;
+            Ok(module)
+        }) {
+            Ok(module) => module,
+            Err(e) => return ContractExecution { gas_used: 0, outcome: Err(e) },
+        };
+
+        let mut store = Store::new(&self.engine, HostState {
+            caller: call.caller.clone(),
+            address: call.address.clone(),
+            context: *context,
+            input: call.input.to_vec(),
+            storage: contract.storage.clone(),
+            storage_size: storage_size(&contract.storage),
+            max_storage_size: self.config.max_storage_size,
+            events: Vec::new(),
+            output: Vec::new(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_SIZE)
+                .memories(1)
+                .instances(1)
+                .build(),
+            schedule: self.config.gas_schedule.clone(),
+            gas_limit: call.gas_limit,
+        });
+        store.limiter(|state| &mut state.limits);
+        let linker = host_functions(&self.engine).expect("host functions have distinct names");
+        let result = store.set_fuel(call.gas_limit)
+            .and_then(|()| linker.instantiate_and_start(&mut store, &module))
+            .and_then(|instance| instance.get_typed_func::<(), ()>(&store, call.entry_point))
+            .and_then(|entry_point| entry_point.call(&mut store, ()));
+
+        let gas_used = call.gas_limit - store.get_fuel().unwrap_or(0);
+        let outcome = match result {
+            Ok(()) => {
+                let state = store.into_data();
+                Ok(ContractOutcome {
+                    output: state.output,
+                    events: state.events,
+                    storage: state.storage,
+                })
+            }
+            Err(e) => Err(contract_error(e, call.gas_limit)),
+        };
+        ContractExecution { gas_used, outcome }
+    }
+
+    fn compile(&self, code: &[u8]) -> Result<Module, ContractError> {
+        if !self.config.enable_wasm {
+            return Err(ContractError::WasmDisabled);
+        }
+        if code.len() > self.config.max_contract_size {
+            return Err(ContractError::CodeTooLarge { size: code.len(), limit: self.config.max_contract_size });
+        }
+        let module = Module::new(&self.engine, code).map_err(|e| ContractError::InvalidModule(e.to_string()))?;
+
+        for import in module.imports() {
+            if import.module() != HOST_MODULE || !HOST_FUNCTIONS.contains(&import.name()) {
+                return Err(ContractError::InvalidModule(format!("unknown import {}::{}", import.module(), import.name())));
+            }
+        }
+        if !module.exports().any(|export| export.name() == "memory" && matches!(export.ty(), ExternType::Memory(_))) {
+            return Err(ContractError::MissingExport("memory".to_string()));
+        }
+        Ok(module)
+    }
+}
+
+/// Total bytes of keys and values held in contract storage
+pub fn storage_size(storage: &HashMap<String, Vec<u8>>) -> usize {
+    storage.iter().map(|(key, value)| key.len() + value.len()).sum()
+}
+
+/// Check that `name` is exported as a function without parameters or results
+fn entry_point_export(module: &Module, name: &str) -> Result<(), ContractError> {
+    module.exports()
+        .find(|export| export.name() == name)
+        .and_then(|export| export.ty().func().cloned())
+        .filter(|ty| ty.params().is_empty() && ty.results().is_empty())
+        .map(|_| ())
+        .ok_or_else(|| ContractError::MissingExport(name.to_string()))
+}
+
+/// Recover the contract error behind a failed instantiation or call
+fn contract_error(error: Error, gas_limit: u64) -> ContractError {
+    if error.as_trap_code() == Some(TrapCode::OutOfFuel) {
+        return ContractError::OutOfGas { limit: gas_limit };
+    }
+    match error.downcast_ref::<ContractError>() {
+        Some(contract_error) => contract_error.clone(),
+        None => ContractError::Trap(error.to_string()),
+    }
+}
+
+/// Take `gas` from what the call has left, aborting it if that is not enough
+fn charge(caller: &mut Caller<'_, HostState<'_>>, gas: u64) -> Result<(), Error> {
+    let remaining = caller.get_fuel()?;
+    if gas > remaining {
+        caller.set_fuel(0)?;
+        return Err(Error::host(ContractError::OutOfGas { limit: caller.data().gas_limit }));
+    }
+    caller.set_fuel(remaining - gas)
+}
+
+/// Charge a host function call costing `cost` on top of the base host call price
+fn charge_call(caller: &mut Caller<'_, HostState<'_>>, cost: impl Fn(&GasSchedule) -> u64) -> Result<(), Error> {
+    let schedule = &caller.data().schedule;
+    let gas = schedule.host_call.saturating_add(cost(schedule));
+    charge(caller, gas)
+}
+
+fn memory(caller: &Caller<'_, HostState<'_>>) -> Result<Memory, Error> {
+    caller.get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Error::host(ContractError::MissingExport("memory".to_string())))
+}
+
+/// Copy `len` bytes at `ptr` out of contract memory, charging for every byte
+fn read_bytes(caller: &mut Caller<'_, HostState<'_>>, ptr: i32, len: i32) -> Result<Vec<u8>, Error> {
+    let memory = memory(caller)?;
+    let (ptr, len) = (ptr as u32 as usize, len as u32 as usize);
+    if ptr.saturating_add(len) > memory.data_size(&*caller) {
+        return Err(Error::host(ContractError::MemoryAccess));
+    }
+    let per_byte = caller.data().schedule.per_byte;
+    charge(caller, (len as u64).saturating_mul(per_byte))?;
+    let mut buffer = vec![0; len];
+    memory.read(&*caller, ptr, &mut buffer).map_err(|_| Error::host(ContractError::MemoryAccess))?;
+    Ok(buffer)
+}
+
+fn read_string(caller: &mut Caller<'_, HostState<'_>>, ptr: i32, len: i32) -> Result<String, Error> {
+    String::from_utf8(read_bytes(caller, ptr, len)?).map_err(|_| Error::host(ContractError::InvalidString))
+}
+
+fn write_bytes(caller: &mut Caller<'_, HostState<'_>>, ptr: i32, data: &[u8]) -> Result<(), Error> {
+    memory(caller)?
+        .write(caller, ptr as u32 as usize, data)
+        .map_err(|_| Error::host(ContractError::MemoryAccess))
+}
+
+/// Copy at most `out_cap` bytes of `data` to `out_ptr`, charging for every byte, and return
+/// the full length
+fn copy_out(caller: &mut Caller<'_, HostState<'_>>, data: &[u8], out_ptr: i32, out_cap: i32) -> Result<i32, Error> {
+    let copied = data.len().min(out_cap as u32 as usize);
+    let per_byte = caller.data().schedule.per_byte;
+    charge(caller, (copied as u64).saturating_mul(per_byte))?;
+    write_bytes(caller, out_ptr, &data[..copied])?;
+    Ok(data.len() as i32)
+}
+
+fn host_functions<'a>(engine: &Engine) -> Result<Linker<HostState<'a>>, LinkerError> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap(HOST_MODULE, "input", |mut caller: Caller<'_, HostState<'a>>, out_ptr: i32, out_cap: i32| {
+        charge_call(&mut caller, |_| 0)?;
+        let input = caller.data().input.clone();
+        copy_out(&mut caller, &input, out_ptr, out_cap)
+    })?;
+    linker.func_wrap(HOST_MODULE, "caller", |mut caller: Caller<'_, HostState<'a>>, out_ptr: i32, out_cap: i32| {
+        charge_call(&mut caller, |_| 0)?;
+        let account = caller.data().caller.clone();
+        copy_out(&mut caller, account.as_bytes(), out_ptr, out_cap)
+    })?;
+    linker.func_wrap(HOST_MODULE, "address", |mut caller: Caller<'_, HostState<'a>>, out_ptr: i32, out_cap: i32| {
+        charge_call(&mut caller, |_| 0)?;
+        let address = caller.data().address.clone();
+        copy_out(&mut caller, address.as_bytes(), out_ptr, out_cap)
+    })?;
+    linker.func_wrap(HOST_MODULE, "block_number", |mut caller: Caller<'_, HostState<'a>>| -> Result<i64, Error> {
+        charge_call(&mut caller, |_| 0)?;
+        Ok(caller.data().context.block_number as i64)
+    })?;
+    linker.func_wrap(HOST_MODULE, "block_timestamp", |mut caller: Caller<'_, HostState<'a>>| -> Result<i64, Error> {
+        charge_call(&mut caller, |_| 0)?;
+        Ok(caller.data().context.timestamp as i64)
+    })?;
+    linker.func_wrap(HOST_MODULE, "balance", |mut caller: Caller<'_, HostState<'a>>, account_ptr: i32, account_len: i32, out_ptr: i32| {
+        charge_call(&mut caller, |schedule| schedule.balance_read)?;
+        let account = read_string(&mut caller, account_ptr, account_len)?;
+        let balance = caller.data().context.balances.available_balance(&account);
+        write_bytes(&mut caller, out_ptr, &balance.to_le_bytes())
+    })?;
+    linker.func_wrap(HOST_MODULE, "storage_read", |mut caller: Caller<'_, HostState<'a>>, key_ptr: i32, key_len: i32, out_ptr: i32, out_cap: i32| {
+        charge_call(&mut caller, |schedule| schedule.storage_read)?;
+        let key = read_string(&mut caller, key_ptr, key_len)?;
+        match caller.data().storage.get(&key).cloned() {
+            Some(value) => copy_out(&mut caller, &value, out_ptr, out_cap),
+            None => Ok(-1),
+        }
+    })?;
+    linker.func_wrap(HOST_MODULE, "storage_write", |mut caller: Caller<'_, HostState<'a>>, key_ptr: i32, key_len: i32, value_ptr: i32, value_len: i32| {
+        let written = (key_len as u32 as u64).saturating_add(value_len as u32 as u64);
+        charge_call(&mut caller, |schedule| {
+            schedule.storage_write.saturating_add(written.saturating_mul(schedule.storage_write_per_byte))
+        })?;
+        let key = read_string(&mut caller, key_ptr, key_len)?;
+        let value = read_bytes(&mut caller, value_ptr, value_len)?;
+        let state = caller.data_mut();
+        let replaced = state.storage.get(&key).map_or(0, |old| key.len() + old.len());
+        let size = state.storage_size - replaced + key.len() + value.len();
+        if size > state.max_storage_size {
+            return Err(Error::host(ContractError::StorageLimitExceeded { size, limit: state.max_storage_size }));
+        }
+        state.storage_size = size;
+        state.storage.insert(key, value);
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "storage_remove", |mut caller: Caller<'_, HostState<'a>>, key_ptr: i32, key_len: i32| {
+        charge_call(&mut caller, |schedule| schedule.storage_write)?;
+        let key = read_string(&mut caller, key_ptr, key_len)?;
+        let state = caller.data_mut();
+        if let Some(old) = state.storage.remove(&key) {
+            state.storage_size -= key.len() + old.len();
+        }
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "emit_event", |mut caller: Caller<'_, HostState<'a>>, topic_ptr: i32, topic_len: i32, data_ptr: i32, data_len: i32| {
+        charge_call(&mut caller, |schedule| schedule.event)?;
+        let topic = read_string(&mut caller, topic_ptr, topic_len)?;
+        let data = read_bytes(&mut caller, data_ptr, data_len)?;
+        let state = caller.data_mut();
+        let contract = state.address.clone();
+        state.events.push(ContractEvent { contract, topic, data });
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "return_value", |mut caller: Caller<'_, HostState<'a>>, ptr: i32, len: i32| {
+        charge_call(&mut caller, |_| 0)?;
+        caller.data_mut().output = read_bytes(&mut caller, ptr, len)?;
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "revert", |mut caller: Caller<'_, HostState<'a>>, msg_ptr: i32, msg_len: i32| -> Result<(), Error> {
+        charge_call(&mut caller, |_| 0)?;
+        let message = String::from_utf8_lossy(&read_bytes(&mut caller, msg_ptr, msg_len)?).into_owned();
+        Err(Error::host(ContractError::Reverted(message)))
+    })?;
+    Ok(linker)
+}
@@ -0,0 +1,648 @@
+//! # Canonical Encoding
+//!
+//! Versioned binary encoding of consensus data: transactions, blocks and everything they
+//! contain. Every value has exactly one encoding, so hashes and signatures computed over it
+//! agree across nodes and implementations.
+//!
+//! An encoding starts with the [`ENCODING_VERSION`] byte, followed by the value:
+//! - integers are fixed-width big-endian, booleans a single `0`/`1` byte
+//! - strings, byte strings and sequences are prefixed with their length as a `u32`
+//! - options are a `0` tag or a `1` tag followed by the value
+//! - enum variants are their declaration index as a `u32`, followed by their fields
+//! - struct fields are written in declaration order, without names
+//! - map entries are sorted by the encoding of their keys, so hash maps encode the same
+//!   regardless of iteration order
+//! - floats are rejected: consensus data holds fractional figures as fixed-point integers,
+//!   see [`crate::types::FixedPoint`]
+//!
+//! The encoding is not self-describing; decoding needs the type that was encoded.
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Version of the encoding rules, written as the first byte of every encoding
+pub const ENCODING_VERSION: u8 = 1;
+
+/// Reason a value cannot be encoded or decoded
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum EncodingError {
+    #[error("unsupported encoding version {0}")]
+    UnsupportedVersion(u8),
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+    #[error("{0} trailing bytes after the encoded value")]
+    TrailingBytes(usize),
+    #[error("floats have no canonical encoding")]
+    Float,
+    #[error("length {0} exceeds the encodable maximum")]
+    LengthOverflow(usize),
+    #[error("{0}")]
+    Message(String),
+}
+
+impl ser::Error for EncodingError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        EncodingError::Message(msg.to_string())
+    }
+}
+
+impl de::Error for EncodingError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        EncodingError::Message(msg.to_string())
+    }
+}
+
+/// Encode a value canonically, prefixed with the encoding version
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, EncodingError> {
+    let mut encoder = Encoder { output: vec![ENCODING_VERSION] };
+    value.serialize(&mut encoder)?;
+    Ok(encoder.output)
+}
+
+/// Decode a value from its canonical encoding
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, EncodingError> {
+    let (version, body) = bytes.split_first().ok_or(EncodingError::UnexpectedEnd)?;
+    if *version != ENCODING_VERSION {
+        return Err(EncodingError::UnsupportedVersion(*version));
+    }
+    let mut decoder = Decoder { input: body };
+    let value = T::deserialize(&mut decoder)?;
+    if !decoder.input.is_empty() {
+        return Err(EncodingError::TrailingBytes(decoder.input.len()));
+    }
+    Ok(value)
+}
+
+/// SHA-256 of a value's canonical encoding
+pub fn hash<T: Serialize + ?Sized>(value: &T) -> Result<[u8; 32], EncodingError> {
+    Ok(Sha256::digest(encode(value)?).into())
+}
+
+/// Serializer writing the canonical encoding
+struct Encoder {
+    output: Vec<u8>,
+}
+
+impl Encoder {
+    fn write_len(&mut self, len: usize) -> Result<(), EncodingError> {
+        let len = u32::try_from(len).map_err(|_| EncodingError::LengthOverflow(len))?;
+        self.output.extend_from_slice(&len.to_be_bytes());
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Encoder {
+    type Ok = ();
+    type Error = EncodingError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = MapEncoder<'a>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<(), EncodingError> {
+        self.output.push(v as u8);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), EncodingError> {
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), EncodingError> {
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), EncodingError> {
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), EncodingError> {
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), EncodingError> {
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), EncodingError> {
+        self.output.push(v);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), EncodingError> {
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), EncodingError> {
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), EncodingError> {
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), EncodingError> {
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), EncodingError> {
+        Err(EncodingError::Float)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), EncodingError> {
+        Err(EncodingError::Float)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), EncodingError> {
+        self.serialize_u32(v as u32)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), EncodingError> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), EncodingError> {
+        self.write_len(v.len())?;
+        self.output.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), EncodingError> {
+        self.output.push(0);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), EncodingError> {
+        self.output.push(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), EncodingError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), EncodingError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str) -> Result<(), EncodingError> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), EncodingError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), EncodingError> {
+        self.serialize_u32(variant_index)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, EncodingError> {
+        let len = len.ok_or_else(|| EncodingError::Message("sequence length must be known".to_string()))?;
+        self.write_len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, EncodingError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, EncodingError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, EncodingError> {
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapEncoder<'a>, EncodingError> {
+        Ok(MapEncoder { encoder: self, entries: Vec::new(), key: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, EncodingError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, EncodingError> {
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for &mut Encoder {
+    type Ok = ();
+    type Error = EncodingError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodingError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), EncodingError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut Encoder {
+    type Ok = ();
+    type Error = EncodingError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodingError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), EncodingError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut Encoder {
+    type Ok = ();
+    type Error = EncodingError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodingError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), EncodingError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut Encoder {
+    type Ok = ();
+    type Error = EncodingError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodingError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), EncodingError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut Encoder {
+    type Ok = ();
+    type Error = EncodingError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), EncodingError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), EncodingError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut Encoder {
+    type Ok = ();
+    type Error = EncodingError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), EncodingError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), EncodingError> {
+        Ok(())
+    }
+}
+
+/// Collects map entries so they can be written sorted by key
+struct MapEncoder<'a> {
+    encoder: &'a mut Encoder,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    key: Option<Vec<u8>>,
+}
+
+impl MapEncoder<'_> {
+    fn encode_part<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, EncodingError> {
+        let mut encoder = Encoder { output: Vec::new() };
+        value.serialize(&mut encoder)?;
+        Ok(encoder.output)
+    }
+}
+
+impl ser::SerializeMap for MapEncoder<'_> {
+    type Ok = ();
+    type Error = EncodingError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), EncodingError> {
+        self.key = Some(Self::encode_part(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodingError> {
+        let key = self.key.take()
+            .ok_or_else(|| EncodingError::Message("map value without a key".to_string()))?;
+        self.entries.push((key, Self::encode_part(value)?));
+        Ok(())
+    }
+
+    fn end(mut self) -> Result<(), EncodingError> {
+        self.entries.sort();
+        self.encoder.write_len(self.entries.len())?;
+        for (key, value) in self.entries {
+            self.encoder.output.extend_from_slice(&key);
+            self.encoder.output.extend_from_slice(&value);
+        }
+        Ok(())
+    }
+}
+
+/// Deserializer reading the canonical encoding
+struct Decoder<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> Decoder<'de> {
+    fn take(&mut self, len: usize) -> Result<&'de [u8], EncodingError> {
+        if self.input.len() < len {
+            return Err(EncodingError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], EncodingError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn read_len(&mut self) -> Result<usize, EncodingError> {
+        Ok(u32::from_be_bytes(self.take_array()?) as usize)
+    }
+
+    fn read_bytes(&mut self) -> Result<&'de [u8], EncodingError> {
+        let len = self.read_len()?;
+        self.take(len)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Decoder<'de> {
+    type Error = EncodingError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, EncodingError> {
+        Err(EncodingError::Message("the canonical encoding is not self-describing".to_string()))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        match self.take(1)?[0] {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            other => Err(EncodingError::Message(format!("invalid boolean byte {}", other))),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_i8(i8::from_be_bytes(self.take_array()?))
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_i16(i16::from_be_bytes(self.take_array()?))
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_i32(i32::from_be_bytes(self.take_array()?))
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_i64(i64::from_be_bytes(self.take_array()?))
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_i128(i128::from_be_bytes(self.take_array()?))
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_u8(self.take(1)?[0])
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_u16(u16::from_be_bytes(self.take_array()?))
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_u32(u32::from_be_bytes(self.take_array()?))
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_u64(u64::from_be_bytes(self.take_array()?))
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_u128(u128::from_be_bytes(self.take_array()?))
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, EncodingError> {
+        Err(EncodingError::Float)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, EncodingError> {
+        Err(EncodingError::Float)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        let code = u32::from_be_bytes(self.take_array()?);
+        let c = char::from_u32(code)
+            .ok_or_else(|| EncodingError::Message(format!("invalid character {}", code)))?;
+        visitor.visit_char(c)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        let s = std::str::from_utf8(self.read_bytes()?)
+            .map_err(|e| EncodingError::Message(e.to_string()))?;
+        visitor.visit_borrowed_str(s)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_borrowed_bytes(self.read_bytes()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        match self.take(1)?[0] {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            other => Err(EncodingError::Message(format!("invalid option tag {}", other))),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        let len = self.read_len()?;
+        visitor.visit_seq(Counted { decoder: self, remaining: len })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_seq(Counted { decoder: self, remaining: len })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_seq(Counted { decoder: self, remaining: len })
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        let len = self.read_len()?;
+        visitor.visit_map(Counted { decoder: self, remaining: len })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EncodingError> {
+        visitor.visit_seq(Counted { decoder: self, remaining: fields.len() })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EncodingError> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EncodingError> {
+        self.deserialize_u32(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, EncodingError> {
+        Err(EncodingError::Message("the canonical encoding cannot skip values".to_string()))
+    }
+}
+
+/// Sequence, tuple, struct or map with a known number of elements
+struct Counted<'a, 'de> {
+    decoder: &'a mut Decoder<'de>,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Counted<'_, 'de> {
+    type Error = EncodingError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, EncodingError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.decoder).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Counted<'_, 'de> {
+    type Error = EncodingError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, EncodingError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.decoder).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, EncodingError> {
+        seed.deserialize(&mut *self.decoder)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Decoder<'de> {
+    type Error = EncodingError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), EncodingError> {
+        let index = u32::from_be_bytes(self.take_array()?);
+        let variant = seed.deserialize(IntoDeserializer::<EncodingError>::into_deserializer(index))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Decoder<'de> {
+    type Error = EncodingError;
+
+    fn unit_variant(self) -> Result<(), EncodingError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, EncodingError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_seq(Counted { decoder: self, remaining: len })
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, EncodingError> {
+        visitor.visit_seq(Counted { decoder: self, remaining: fields.len() })
+    }
+}
@@ -0,0 +1,399 @@
+//! # Finality
+//!
+//! PoA blocks become final through authority precommits. Every authority signs a
+//! precommit for each block it accepts onto its chain; once `authority_threshold`
+//! distinct active authorities have precommitted to a block, that block and all of
+//! its ancestors are final and may be relied upon, e.g. for trade settlement.
+//!
+//! A node backed by a block store persists the finalized head and the precommits above it
+//! alongside its blocks. After a restart it keeps refusing blocks that conflict with the
+//! finalized chain, and never precommits to a block conflicting with one it already voted for.
+
+use crate::blockchain::consensus::{Authority, BlockchainState, BlockHeader};
+use crate::blockchain::storage::BlockchainStorage;
+use crate::crypto::GridTokenXKeyPair;
+use crate::types::{AccountId, Hash};
+use crate::utils::SystemResult;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch, RwLock};
+
+/// Domain separation prefix for precommit signatures, so a block signature can never
+/// be replayed as a precommit
+const PRECOMMIT_DOMAIN: &[u8] = b"gridtokenx/precommit";
+/// Number of blocks ahead of the chain head precommits are buffered for
+pub const MAX_PRECOMMIT_LOOKAHEAD: u32 = 64;
+/// Number of locally signed precommits buffered for slow subscribers
+const PRECOMMIT_CHANNEL_CAPACITY: usize = 64;
+
+/// An authority's signed vote that a block should become final
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Precommit {
+    /// Number of the block voted for
+    pub block_number: u32,
+    /// Hash of the block voted for
+    pub block_hash: Hash,
+    /// Authority casting the vote
+    pub validator: AccountId,
+    /// Signature over the block number and hash
+    pub signature: Vec<u8>,
+}
+
+/// Reason a precommit was not counted
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PrecommitRejection {
+    #[error("validator {0} is not a registered authority")]
+    UnknownAuthority(AccountId),
+    #[error("validator {0} is not an active authority")]
+    InactiveAuthority(AccountId),
+    #[error("validator {0} has no valid public key registered")]
+    MissingPublicKey(AccountId),
+    #[error("precommit signature is invalid")]
+    InvalidSignature,
+    #[error("block {block_number} is at or below finalized height {finalized}")]
+    AlreadyFinalized { block_number: u32, finalized: u32 },
+    #[error("block {block_number} is too far ahead of chain head {head}")]
+    TooFarAhead { block_number: u32, head: u32 },
+    #[error("validator {validator} already precommitted to {existing} at block {block_number}")]
+    Equivocation { validator: AccountId, block_number: u32, existing: Hash },
+    #[error("precommit could not be persisted: {0}")]
+    Storage(String),
+}
+
+/// Finality state persisted with the block store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FinalityRecord {
+    /// Number of the highest final block
+    pub finalized_number: u32,
+    /// Hash of the highest final block
+    pub finalized_hash: Hash,
+    /// Precommits above the finalized height, including this node's own
+    pub precommits: Vec<Precommit>,
+}
+
+impl Precommit {
+    /// Message signed by a precommit for the given block
+    pub fn signing_message(block_number: u32, block_hash: &Hash) -> Vec<u8> {
+        let mut message = PRECOMMIT_DOMAIN.to_vec();
+        message.extend_from_slice(&block_number.to_be_bytes());
+        message.extend_from_slice(block_hash.as_bytes());
+        message
+    }
+
+    /// Sign a precommit for a block with the node key
+    pub fn sign(block_number: u32, block_hash: Hash, keypair: &GridTokenXKeyPair) -> Self {
+        let signature = keypair.sign(&Self::signing_message(block_number, &block_hash));
+        Self {
+            block_number,
+            block_hash,
+            validator: keypair.account_id().to_string(),
+            signature: signature.to_bytes().to_vec(),
+        }
+    }
+
+    /// Check the signature against the validator's registered public key
+    pub fn verify(&self, public_key: &[u8]) -> Result<(), PrecommitRejection> {
+        let public_key: [u8; 32] = public_key.try_into()
+            .map_err(|_| PrecommitRejection::MissingPublicKey(self.validator.clone()))?;
+        let verifying_key = VerifyingKey::from_bytes(&public_key)
+            .map_err(|_| PrecommitRejection::MissingPublicKey(self.validator.clone()))?;
+        let signature = Signature::from_slice(&self.signature)
+            .map_err(|_| PrecommitRejection::InvalidSignature)?;
+
+        verifying_key.verify(&Self::signing_message(self.block_number, &self.block_hash), &signature)
+            .map_err(|_| PrecommitRejection::InvalidSignature)
+    }
+}
+
+/// Collected precommits and the resulting finalized head
+#[derive(Debug, Clone)]
+pub struct FinalityTracker {
+    /// Precommits above the finalized height, by block number and validator
+    votes: BTreeMap<u32, HashMap<AccountId, Precommit>>,
+    /// Number of the highest final block
+    finalized_number: u32,
+    /// Hash of the highest final block
+    finalized_hash: Hash,
+}
+
+impl FinalityTracker {
+    /// Start tracking finality with the genesis block as the final head
+    pub fn new(genesis_hash: Hash) -> Self {
+        Self {
+            votes: BTreeMap::new(),
+            finalized_number: 0,
+            finalized_hash: genesis_hash,
+        }
+    }
+
+    /// Resume tracking from a persisted record
+    pub fn from_record(record: FinalityRecord) -> Self {
+        let mut votes: BTreeMap<u32, HashMap<AccountId, Precommit>> = BTreeMap::new();
+        for precommit in record.precommits {
+            votes.entry(precommit.block_number).or_default().insert(precommit.validator.clone(), precommit);
+        }
+        Self {
+            votes,
+            finalized_number: record.finalized_number,
+            finalized_hash: record.finalized_hash,
+        }
+    }
+
+    /// Capture the finalized head and the collected precommits for persisting
+    pub fn record(&self) -> FinalityRecord {
+        FinalityRecord {
+            finalized_number: self.finalized_number,
+            finalized_hash: self.finalized_hash.clone(),
+            precommits: self.votes.values().flat_map(|votes| votes.values().cloned()).collect(),
+        }
+    }
+
+    /// Number of the highest final block
+    pub fn finalized_number(&self) -> u32 {
+        self.finalized_number
+    }
+
+    /// Hash of the highest final block
+    pub fn finalized_hash(&self) -> &Hash {
+        &self.finalized_hash
+    }
+
+    /// Record a verified precommit
+    ///
+    /// A validator's first precommit at a height is kept; a second one for a different
+    /// block at the same height is reported as equivocation and not counted.
+    pub fn add_precommit(&mut self, precommit: Precommit, head: u32) -> Result<(), PrecommitRejection> {
+        if precommit.block_number <= self.finalized_number {
+            return Err(PrecommitRejection::AlreadyFinalized {
+                block_number: precommit.block_number,
+                finalized: self.finalized_number,
+            });
+        }
+        if precommit.block_number > head.saturating_add(MAX_PRECOMMIT_LOOKAHEAD) {
+            return Err(PrecommitRejection::TooFarAhead { block_number: precommit.block_number, head });
+        }
+
+        let votes = self.votes.entry(precommit.block_number).or_default();
+        if let Some(existing) = votes.get(&precommit.validator) {
+            if existing.block_hash != precommit.block_hash {
+                return Err(PrecommitRejection::Equivocation {
+                    validator: precommit.validator,
+                    block_number: precommit.block_number,
+                    existing: existing.block_hash.clone(),
+                });
+            }
+            return Ok(());
+        }
+        votes.insert(precommit.validator.clone(), precommit);
+        Ok(())
+    }
+
+    /// Precommits recorded for a block
+    pub fn precommits_for(&self, block_number: u32, block_hash: &Hash) -> Vec<Precommit> {
+        self.votes.get(&block_number)
+            .map(|votes| votes.values().filter(|vote| vote.block_hash == *block_hash).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Advance the finalized head to the highest chain block with enough precommits
+    ///
+    /// `chain` yields the number and hash of every block above the finalized head, and
+    /// `counts` decides whether a validator's vote is counted. Returns the new finalized
+    /// number if it moved.
+    pub fn try_finalize<'a>(
+        &mut self,
+        chain: impl Iterator<Item = (u32, &'a Hash)>,
+        threshold: usize,
+        counts: impl Fn(&AccountId) -> bool,
+    ) -> Option<u32> {
+        let mut finalized = None;
+        for (number, hash) in chain {
+            let votes = self.votes.get(&number)
+                .map(|votes| votes.values().filter(|vote| vote.block_hash == *hash && counts(&vote.validator)).count())
+                .unwrap_or(0);
+            if threshold > 0 && votes >= threshold {
+                finalized = Some((number, hash.clone()));
+            }
+        }
+
+        let (number, hash) = finalized?;
+        self.finalized_number = number;
+        self.finalized_hash = hash;
+        self.votes = self.votes.split_off(&(number + 1));
+        Some(number)
+    }
+}
+
+
+/// Finality state shared between the consensus engine and its block production task
+#[derive(Clone)]
+pub struct FinalityGadget {
+    /// Distinct authority precommits needed to finalize a block
+    threshold: usize,
+    tracker: Arc<RwLock<FinalityTracker>>,
+    /// Publishes the finalized height to waiters
+    finalized: Arc<watch::Sender<u32>>,
+    /// Announces every precommit this node signs, e.g. for gossiping it to peers
+    precommits: broadcast::Sender<Precommit>,
+    /// Block store the finality state is persisted to, if any
+    storage: Option<Arc<BlockchainStorage>>,
+}
+
+impl FinalityGadget {
+    pub fn new(genesis_hash: Hash, threshold: u32) -> Self {
+        Self {
+            threshold: threshold as usize,
+            tracker: Arc::new(RwLock::new(FinalityTracker::new(genesis_hash))),
+            finalized: Arc::new(watch::channel(0).0),
+            precommits: broadcast::channel(PRECOMMIT_CHANNEL_CAPACITY).0,
+            storage: None,
+        }
+    }
+
+    /// Resume from the finality state persisted in `storage` and persist there from now on
+    ///
+    /// `state` is the chain replayed from the same store, which must contain the persisted
+    /// finalized block.
+    pub async fn restore_from(&mut self, storage: Arc<BlockchainStorage>, state: &BlockchainState) -> SystemResult<()> {
+        if let Some(record) = storage.load_finality().await? {
+            let stored = state.blocks.get(record.finalized_number as usize).map(|block| &block.header.hash);
+            if stored != Some(&record.finalized_hash) {
+                return Err(crate::utils::SystemError::Blockchain(format!(
+                    "Finalized block #{} ({}) is not on the stored chain",
+                    record.finalized_number, record.finalized_hash
+                )));
+            }
+            crate::utils::logging::log_info(
+                "FinalityGadget",
+                &format!("Restored finalized block #{} with {} pending precommits", record.finalized_number, record.precommits.len())
+            );
+            self.finalized.send_replace(record.finalized_number);
+            *self.tracker.write().await = FinalityTracker::from_record(record);
+        }
+        self.storage = Some(storage);
+        Ok(())
+    }
+
+    /// Number of the highest final block
+    pub fn finalized_number(&self) -> u32 {
+        *self.finalized.borrow()
+    }
+
+    /// Hash of the highest final block
+    pub async fn finalized_hash(&self) -> Hash {
+        self.tracker.read().await.finalized_hash().clone()
+    }
+
+    /// Precommits collected so far for a block that is not final yet
+    pub async fn precommits_for(&self, block_number: u32, block_hash: &Hash) -> Vec<Precommit> {
+        self.tracker.read().await.precommits_for(block_number, block_hash)
+    }
+
+    /// Subscribe to precommits signed by this node
+    pub fn subscribe(&self) -> broadcast::Receiver<Precommit> {
+        self.precommits.subscribe()
+    }
+
+    /// Watch the finalized height
+    pub fn watch(&self) -> watch::Receiver<u32> {
+        self.finalized.subscribe()
+    }
+
+    /// Verify and record a precommit, then finalize whatever it completes
+    pub async fn import(
+        &self,
+        precommit: Precommit,
+        state: &BlockchainState,
+        authorities: &HashMap<AccountId, Authority>,
+    ) -> Result<(), PrecommitRejection> {
+        let authority = authorities.get(&precommit.validator)
+            .ok_or_else(|| PrecommitRejection::UnknownAuthority(precommit.validator.clone()))?;
+        if !authority.is_active {
+            return Err(PrecommitRejection::InactiveAuthority(precommit.validator.clone()));
+        }
+        precommit.verify(&authority.public_key)?;
+
+        let mut tracker = self.tracker.write().await;
+        tracker.add_precommit(precommit, state.block_height)?;
+        self.finalize(&mut tracker, state, authorities);
+        self.persist(&tracker).await
+    }
+
+    /// Sign our own precommit for a block that was just added to the chain
+    ///
+    /// The precommit is only announced once it is persisted, so the node cannot vote for a
+    /// conflicting block after a restart.
+    pub async fn precommit(
+        &self,
+        header: &BlockHeader,
+        keypair: &GridTokenXKeyPair,
+        state: &BlockchainState,
+        authorities: &HashMap<AccountId, Authority>,
+    ) {
+        let precommit = Precommit::sign(header.number, header.hash.clone(), keypair);
+        match self.import(precommit.clone(), state, authorities).await {
+            // Nobody listening is fine, e.g. when running without a network layer
+            Ok(()) => { let _ = self.precommits.send(precommit); }
+            Err(rejection) => crate::utils::logging::log_debug(
+                "FinalityGadget",
+                &format!("Not precommitting to block #{}: {}", header.number, rejection)
+            ),
+        }
+    }
+
+    /// Number of authority signatures on a run of blocks: the producer's signature on each
+    /// block plus every counted precommit for it
+    pub async fn signature_weight<'a>(
+        &self,
+        blocks: impl Iterator<Item = (u32, &'a Hash)>,
+        authorities: &HashMap<AccountId, Authority>,
+    ) -> usize {
+        let tracker = self.tracker.read().await;
+        blocks
+            .map(|(number, hash)| {
+                1 + tracker.precommits_for(number, hash).iter()
+                    .filter(|vote| authorities.get(&vote.validator).is_some_and(|authority| authority.is_active))
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Count precommits that arrived before their block, now that the chain has grown
+    pub async fn advance(&self, state: &BlockchainState, authorities: &HashMap<AccountId, Authority>) {
+        let mut tracker = self.tracker.write().await;
+        self.finalize(&mut tracker, state, authorities);
+        if let Err(rejection) = self.persist(&tracker).await {
+            crate::utils::logging::log_warning("FinalityGadget", &rejection.to_string());
+        }
+    }
+
+    async fn persist(&self, tracker: &FinalityTracker) -> Result<(), PrecommitRejection> {
+        match &self.storage {
+            Some(storage) => storage.save_finality(&tracker.record()).await
+                .map_err(|e| PrecommitRejection::Storage(e.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    fn finalize(
+        &self,
+        tracker: &mut FinalityTracker,
+        state: &BlockchainState,
+        authorities: &HashMap<AccountId, Authority>,
+    ) {
+        let chain = state.blocks.iter()
+            .skip(tracker.finalized_number() as usize + 1)
+            .map(|block| (block.header.number, &block.header.hash));
+        let counts = |validator: &AccountId| authorities.get(validator).is_some_and(|authority| authority.is_active);
+
+        if let Some(number) = tracker.try_finalize(chain, self.threshold, counts) {
+            crate::utils::logging::log_info(
+                "FinalityGadget",
+                &format!("Finalized block #{} ({})", number, tracker.finalized_hash())
+            );
+            self.finalized.send_replace(number);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! # Validator Liveness
+//!
+//! Every canonical block fills one slot of the validator rotation. When the scheduled
+//! validator does not produce within the slot timeout, the slot passes on to the next
+//! validator and the skipped validator has missed it. The produced and missed slots of every
+//! authority are counted as blocks join the canonical chain and feed into its reputation.
+//!
+//! Slot counters and reputation belong to the branch that settles the slots: a reorg rolls
+//! them back to the common ancestor and counts only the slots of the enacted blocks.
+
+use crate::blockchain::consensus::{Authority, BlockHeader, STARTING_REPUTATION};
+use crate::types::{AccountId, FixedPoint};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Reputation regained for producing a block in one's slot, up to the starting reputation
+pub const PRODUCED_SLOT_REWARD: FixedPoint = FixedPoint::from_millionths(10_000);
+/// Reputation lost for missing a slot
+pub const MISSED_SLOT_PENALTY: FixedPoint = FixedPoint::from_millionths(50_000);
+
+/// Slot statistics of one authority
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ValidatorLiveness {
+    /// Canonical blocks the authority produced
+    pub produced_slots: u64,
+    /// Slots that passed to the next validator because the authority did not produce
+    pub missed_slots: u64,
+    /// Number of the last canonical block the authority produced
+    pub last_produced: Option<u32>,
+}
+
+impl ValidatorLiveness {
+    /// Share of its slots the authority filled; 1.0 before its first slot
+    pub fn availability(&self) -> f64 {
+        let slots = self.produced_slots + self.missed_slots;
+        if slots == 0 {
+            return 1.0;
+        }
+        self.produced_slots as f64 / slots as f64
+    }
+}
+
+/// Record the slots a canonical block settles: `missed` passed over their slots before its
+/// producer filled one
+pub fn record_block(
+    liveness: &mut HashMap<AccountId, ValidatorLiveness>,
+    authorities: &mut HashMap<AccountId, Authority>,
+    header: &BlockHeader,
+    missed: &[AccountId],
+) {
+    for validator in missed {
+        liveness.entry(validator.clone()).or_default().missed_slots += 1;
+        if let Some(authority) = authorities.get_mut(validator) {
+            authority.reputation = authority.reputation.saturating_sub(MISSED_SLOT_PENALTY).max(FixedPoint::ZERO);
+        }
+    }
+
+    let producer = liveness.entry(header.validator.clone()).or_default();
+    producer.produced_slots += 1;
+    producer.last_produced = Some(header.number);
+    if let Some(authority) = authorities.get_mut(&header.validator) {
+        authority.reputation = authority.reputation.saturating_add(PRODUCED_SLOT_REWARD).min(STARTING_REPUTATION);
+    }
+}
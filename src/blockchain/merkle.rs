@@ -0,0 +1,120 @@
+//! # Merkle Trees
+//!
+//! Binary Merkle trees over 32-byte hashes, used for the transaction and receipt roots
+//! in block headers. Leaves and internal nodes are hashed with different prefixes so an
+//! internal node can never be passed off as a leaf. A node without a sibling is carried
+//! up to the next level unchanged.
+
+use crate::types::Hash;
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Digest};
+
+/// Domain separation prefix for leaf hashes
+const LEAF_PREFIX: u8 = 0x00;
+/// Domain separation prefix for internal node hashes
+const NODE_PREFIX: u8 = 0x01;
+
+/// Proof that a transaction is included in a block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionProof {
+    /// Number of the block containing the transaction
+    pub block_number: u32,
+    /// Hash of the block containing the transaction
+    pub block_hash: Hash,
+    /// Position of the transaction in the block
+    pub index: u32,
+    /// Number of transactions in the block
+    pub leaf_count: u32,
+    /// Sibling hashes from the leaf level up to the root
+    pub siblings: Vec<Hash>,
+}
+
+/// Compute the root of a tree over the given leaves, `None` for an empty tree
+pub fn merkle_root(leaves: &[[u8; 32]]) -> Option<[u8; 32]> {
+    let mut level: Vec<[u8; 32]> = leaves.iter().map(leaf_hash).collect();
+    if level.is_empty() {
+        return None;
+    }
+
+    while level.len() > 1 {
+        level = level.chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node_hash(left, right),
+                _ => pair[0],
+            })
+            .collect();
+    }
+
+    Some(level[0])
+}
+
+/// Sibling hashes needed to recompute the root from the leaf at `index`
+pub fn merkle_path(leaves: &[[u8; 32]], index: usize) -> Option<Vec<[u8; 32]>> {
+    if index >= leaves.len() {
+        return None;
+    }
+
+    let mut level: Vec<[u8; 32]> = leaves.iter().map(leaf_hash).collect();
+    let mut position = index;
+    let mut siblings = Vec::new();
+
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(position ^ 1) {
+            siblings.push(*sibling);
+        }
+        level = level.chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node_hash(left, right),
+                _ => pair[0],
+            })
+            .collect();
+        position /= 2;
+    }
+
+    Some(siblings)
+}
+
+/// Check that `tx_hash` is included under the transaction root `root`
+pub fn verify_transaction_proof(root: &Hash, tx_hash: &[u8; 32], proof: &TransactionProof) -> bool {
+    if proof.index >= proof.leaf_count {
+        return false;
+    }
+
+    let mut node = leaf_hash(tx_hash);
+    let mut position = proof.index as usize;
+    let mut width = proof.leaf_count as usize;
+    let mut siblings = proof.siblings.iter();
+
+    while width > 1 {
+        // The last node of an odd-sized level has no sibling and moves up unchanged
+        if position ^ 1 < width {
+            let Some(sibling) = siblings.next().and_then(decode_hash) else {
+                return false;
+            };
+            node = if position.is_multiple_of(2) { node_hash(&node, &sibling) } else { node_hash(&sibling, &node) };
+        }
+        position /= 2;
+        width = width.div_ceil(2);
+    }
+
+    siblings.next().is_none() && hex::encode(node) == *root
+}
+
+fn leaf_hash(leaf: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(leaf);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn decode_hash(hash: &Hash) -> Option<[u8; 32]> {
+    hex::decode(hash).ok()?.try_into().ok()
+}
@@ -3,62 +3,106 @@
 //! This module implements the core blockchain functionality including consensus,
 //! transaction pool, storage, and network layer.
 
+pub mod block_tree;
+pub mod chain_spec;
 pub mod consensus;
+pub mod contract_abi;
+pub mod contract_runtime;
+pub mod encoding;
+pub mod finality;
+pub mod liveness;
+pub mod merkle;
 pub mod transaction_pool;
 pub mod storage;
 pub mod network;
 pub mod node;
+pub mod ppa_contract;
 pub mod transactions;
+pub mod validator_set;
 pub mod smart_contracts;
+pub mod state_transition;
+pub mod state_trie;
+pub mod slashing;
+pub mod sync;
 
 use crate::config::BlockchainConfig;
 use crate::utils::SystemResult;
-use crate::blockchain::smart_contracts::{SmartContractVM, ContractABI, ContractExecutionResult};
-use crate::blockchain::transactions::{EnergyTransactionValidator, TransactionValidationResult, EnergyTransactionEnvelope};
+use crate::blockchain::contract_abi::AbiValue;
+use crate::blockchain::contract_runtime::ContractCall;
+use crate::blockchain::ppa_contract::{PowerPurchaseAgreement, PpaError, PpaTerms, PPA_CONTRACT_CODE};
+use crate::blockchain::smart_contracts::{SmartContractVM, ContractExecutionResult, ContractState};
+use crate::blockchain::consensus::{BlockImportResult, EnergyBlock};
+use crate::blockchain::state_transition::TransactionReceipt;
+use crate::blockchain::merkle::TransactionProof;
+use crate::blockchain::state_trie::AccountProof;
+use crate::blockchain::network::NetworkEvent;
+use crate::blockchain::transaction_pool::AdmissionRejection;
+use crate::blockchain::transactions::{EnergyTransaction, EnergyTransactionEnvelope};
 use crate::types::AccountId;
+use crate::utils::SystemError;
 use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::JoinHandle;
 
 /// Blockchain engine that coordinates all blockchain components
 pub struct BlockchainEngine {
     /// Consensus engine
     consensus: Arc<consensus::ConsensusEngine>,
-    /// Transaction pool
-    transaction_pool: Arc<transaction_pool::TransactionPool>,
     /// Storage layer
     storage: Arc<storage::BlockchainStorage>,
-    /// Smart contract VM
+    /// Smart contract VM, shared with the consensus engine
     smart_contract_vm: Arc<SmartContractVM>,
     /// Network layer
     network: Arc<network::NetworkLayer>,
     /// Node manager
     node_manager: Arc<node::NodeManager>,
+    /// Tasks relaying blocks and transactions between the network and the chain
+    relay_tasks: RwLock<Vec<JoinHandle<()>>>,
 }
 
 impl BlockchainEngine {
     /// Create new blockchain engine
     pub async fn new(config: &BlockchainConfig) -> SystemResult<Self> {
-        let consensus = Arc::new(consensus::ConsensusEngine::new(config).await?);
-        let transaction_pool = Arc::new(transaction_pool::TransactionPool::new(config).await?);
+        Self::build(config, None).await
+    }
+
+    /// Create a blockchain engine for the chain starting at a known genesis block
+    ///
+    /// Nodes of one network must share their genesis block to extend the same chain.
+    pub async fn with_genesis(config: &BlockchainConfig, genesis_block: EnergyBlock) -> SystemResult<Self> {
+        Self::build(config, Some(genesis_block)).await
+    }
+
+    async fn build(config: &BlockchainConfig, genesis_block: Option<EnergyBlock>) -> SystemResult<Self> {
+        // Every component runs with the chain-wide settings of the chain spec
+        let config = &chain_spec::ChainSpec::load(config)?.apply_to(config);
         let storage = Arc::new(storage::BlockchainStorage::new(config).await?);
-        let smart_contract_vm = Arc::new(SmartContractVM::new(1_000_000)); // 1M gas limit
+        let consensus = Arc::new(match genesis_block {
+            Some(genesis_block) => consensus::ConsensusEngine::with_storage_and_genesis(config, storage.clone(), genesis_block).await?,
+            None => consensus::ConsensusEngine::with_storage(config, storage.clone()).await?,
+        });
+        let smart_contract_vm = consensus.contract_vm().await;
         let network = Arc::new(network::NetworkLayer::new(config).await?);
-        let node_manager = Arc::new(node::NodeManager::new(config).await?);
+        network.set_sync_provider(consensus.clone()).await;
+        let node_manager = Arc::new(node::NodeManager::new(config, consensus.clone(), network.clone()).await?);
 
         Ok(Self {
             consensus,
-            transaction_pool,
             storage,
             smart_contract_vm,
             network,
             node_manager,
+            relay_tasks: RwLock::new(Vec::new()),
         })
     }
 
     /// Start the blockchain engine
     pub async fn start(&self) -> SystemResult<()> {
+        // Subscribe before anything can produce blocks so none go ungossiped
+        self.start_relays().await;
+
         // Start all components
         self.consensus.start().await?;
-        self.transaction_pool.start().await?;
         self.storage.start().await?;
         self.network.start().await?;
         self.node_manager.start().await?;
@@ -69,8 +113,10 @@ impl BlockchainEngine {
 
     /// Stop the blockchain engine
     pub async fn stop(&self) -> SystemResult<()> {
+        for task in self.relay_tasks.write().await.drain(..) {
+            task.abort();
+        }
         self.consensus.stop().await?;
-        self.transaction_pool.stop().await?;
         self.storage.stop().await?;
         self.network.stop().await?;
         self.node_manager.stop().await?;
@@ -84,9 +130,9 @@ impl BlockchainEngine {
         self.consensus.clone()
     }
 
-    /// Get transaction pool
-    pub fn transaction_pool(&self) -> Arc<transaction_pool::TransactionPool> {
-        self.transaction_pool.clone()
+    /// Transactions waiting in the consensus engine's pool for inclusion
+    pub async fn pending_transactions(&self) -> Vec<EnergyTransactionEnvelope> {
+        self.consensus.pending_transactions().await
     }
 
     /// Get storage layer
@@ -110,19 +156,18 @@ impl BlockchainEngine {
     }
 
     /// Submit transaction to blockchain
-    pub async fn submit_transaction(&self, transaction: EnergyTransactionEnvelope) -> SystemResult<()> {
-        // Validate transaction
-        let validator = EnergyTransactionValidator::new();
-        let validation_result = validator.validate_transaction(&transaction);
-        
-        if !matches!(validation_result, TransactionValidationResult::Valid) {
-            return Err(crate::utils::error::SystemError::Validation(
-                "Transaction validation failed".to_string()
-            ));
-        }
+    ///
+    /// Accepted transactions are queued for block production and gossiped to peers;
+    /// rejected ones are reported with the reason.
+    pub async fn submit_transaction(&self, transaction: EnergyTransactionEnvelope) -> Result<(), AdmissionRejection> {
+        self.consensus.admit_transaction(transaction.clone()).await?;
 
-        // Add to transaction pool
-        self.transaction_pool.add_transaction(transaction.clone()).await?;
+        if let Err(e) = self.network.broadcast_transaction(&transaction).await {
+            crate::utils::logging::log_warning(
+                "BlockchainEngine",
+                &format!("Transaction {} not gossiped: {}", hex::encode(transaction.hash), e)
+            );
+        }
 
         crate::utils::logging::log_info(
             "BlockchainEngine",
@@ -132,44 +177,203 @@ impl BlockchainEngine {
         Ok(())
     }
 
-    /// Deploy smart contract
-    pub async fn deploy_contract(
-        &self,
-        deployer: AccountId,
-        code: Vec<u8>,
-        abi: ContractABI,
-        constructor_args: Vec<u8>,
-    ) -> SystemResult<AccountId> {
-        let contract_address = self.smart_contract_vm
-            .deploy_contract(deployer, code, abi, constructor_args)
-            .await?;
+    /// Spawn the tasks that gossip produced blocks and precommits, and feed blocks,
+    /// transactions and precommits received from peers into the chain
+    async fn start_relays(&self) {
+        let mut produced_blocks = self.consensus.subscribe_produced_blocks();
+        let network = self.network.clone();
+        let outbound = tokio::spawn(async move {
+            loop {
+                match produced_blocks.recv().await {
+                    Ok(block) => {
+                        if let Err(e) = network.broadcast_block(&block).await {
+                            crate::utils::logging::log_warning(
+                                "BlockchainEngine",
+                                &format!("Block #{} not gossiped: {}", block.header.number, e)
+                            );
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let mut precommits = self.consensus.subscribe_precommits();
+        let network = self.network.clone();
+        let outbound_precommits = tokio::spawn(async move {
+            loop {
+                match precommits.recv().await {
+                    Ok(precommit) => {
+                        if let Err(e) = network.broadcast_precommit(&precommit).await {
+                            crate::utils::logging::log_warning(
+                                "BlockchainEngine",
+                                &format!("Precommit for block #{} not gossiped: {}", precommit.block_number, e)
+                            );
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let mut network_events = self.network.subscribe();
+        let consensus = self.consensus.clone();
+        let inbound = tokio::spawn(async move {
+            loop {
+                match network_events.recv().await {
+                    Ok(NetworkEvent::BlockReceived { source, block }) => {
+                        let block_number = block.header.number;
+                        match consensus.import_block(*block).await {
+                            Ok(BlockImportResult::Imported) => {}
+                            Ok(BlockImportResult::Rejected(reason)) => crate::utils::logging::log_debug(
+                                "BlockchainEngine",
+                                &format!("Gossiped block #{} from {} rejected: {}", block_number, source, reason)
+                            ),
+                            Err(e) => crate::utils::logging::log_warning(
+                                "BlockchainEngine",
+                                &format!("Failed to import gossiped block #{}: {}", block_number, e)
+                            ),
+                        }
+                    }
+                    Ok(NetworkEvent::TransactionReceived { source, transaction }) => {
+                        let tx_hash = hex::encode(transaction.hash);
+                        if let Err(e) = consensus.admit_transaction(*transaction).await {
+                            crate::utils::logging::log_debug(
+                                "BlockchainEngine",
+                                &format!("Gossiped transaction {} from {} not accepted: {}", tx_hash, source, e)
+                            );
+                        }
+                    }
+                    Ok(NetworkEvent::PrecommitReceived { source, precommit }) => {
+                        let block_number = precommit.block_number;
+                        if let Err(rejection) = consensus.import_precommit(precommit).await {
+                            crate::utils::logging::log_debug(
+                                "BlockchainEngine",
+                                &format!("Precommit for block #{} from {} not counted: {}", block_number, source, rejection)
+                            );
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        self.relay_tasks.write().await.extend([outbound, outbound_precommits, inbound]);
+    }
+
+    /// Import a block received from another node
+    ///
+    /// The block goes through full chain validation; rejected blocks are reported
+    /// with the reason instead of an error so callers can penalise the sender.
+    pub async fn import_block(&self, block: EnergyBlock) -> SystemResult<BlockImportResult> {
+        let block_number = block.header.number;
+        let result = self.consensus.import_block(block).await?;
+
+        if let BlockImportResult::Rejected(reason) = &result {
+            crate::utils::logging::log_warning(
+                "BlockchainEngine",
+                &format!("Block #{} rejected: {}", block_number, reason)
+            );
+        }
+
+        Ok(result)
+    }
+
+    /// Look up the execution receipt of an included transaction
+    pub async fn get_transaction_receipt(&self, tx_hash: &[u8; 32]) -> Option<TransactionReceipt> {
+        self.consensus.get_transaction_receipt(tx_hash).await
+    }
+
+    /// Prove that a transaction is included in a block
+    pub async fn get_transaction_proof(&self, tx_hash: &[u8; 32]) -> Option<TransactionProof> {
+        self.consensus.get_transaction_proof(tx_hash).await
+    }
+
+    /// Prove an account's state at a given block height
+    pub async fn get_account_proof(&self, account: &AccountId, height: u32) -> Option<AccountProof> {
+        self.consensus.get_account_proof(account, height).await
+    }
+
+    /// Submit a signed contract deployment, returning the address the contract will live at
+    ///
+    /// The module is checked before the transaction is admitted. The address is derived from the
+    /// deployer and the transaction nonce. The contract exists once the transaction is included
+    /// in a block; its receipt tells whether the constructor succeeded. Deployments built with
+    /// [`PpaTerms::deployment`](ppa_contract::PpaTerms::deployment) create a power purchase
+    /// agreement from the built-in template.
+    pub async fn deploy_contract(&self, transaction: EnergyTransactionEnvelope) -> SystemResult<AccountId> {
+        let EnergyTransaction::DeployContract { deployer, contract_code, abi, constructor_args, .. } = &transaction.transaction else {
+            return Err(SystemError::InvalidInput("Not a contract deployment".to_string()));
+        };
+        self.smart_contract_vm.check_code(contract_code, abi)
+            .map_err(|e| SystemError::Validation(format!("Contract rejected: {}", e)))?;
+        if contract_code == PPA_CONTRACT_CODE {
+            PpaTerms::from_args(constructor_args)
+                .map_err(|e| PpaError::InvalidTerms(e.to_string()))
+                .and_then(|terms| terms.validate(deployer))
+                .map_err(|e| SystemError::Validation(e.to_string()))?;
+        }
+        let contract_address = SmartContractVM::contract_address(deployer, transaction.nonce);
+        if self.consensus.get_contract(&contract_address).await.is_some() {
+            return Err(SystemError::Validation(format!("Contract already deployed at {}", contract_address)));
+        }
+
+        self.submit_transaction(transaction).await
+            .map_err(|rejection| SystemError::Validation(rejection.to_string()))?;
 
         crate::utils::logging::log_info(
             "BlockchainEngine",
-            &format!("Deployed contract {}", contract_address)
+            &format!("Submitted deployment of contract {}", contract_address)
         );
 
         Ok(contract_address)
     }
 
-    /// Execute smart contract function
+    /// Get the code and storage of a deployed contract as of the chain head
+    pub async fn get_contract(&self, contract_address: &AccountId) -> Option<ContractState> {
+        self.consensus.get_contract(contract_address).await
+    }
+
+    /// Get the agreement kept by a PPA contract as of the chain head
+    pub async fn get_power_purchase_agreement(&self, contract_address: &AccountId) -> Option<PowerPurchaseAgreement> {
+        self.consensus.get_contract(contract_address).await
+            .and_then(|contract| PowerPurchaseAgreement::from_contract(&contract))
+    }
+
+    /// Call a smart contract function against the chain head without changing any state
+    ///
+    /// Storage writes the call makes are discarded; calls that should change contract state
+    /// are submitted as `ExecuteContract` transactions. The arguments are checked against the
+    /// function's ABI, and the result carries the decoded return values. The gas limit is
+    /// capped at this node's maximum.
     pub async fn execute_contract_function(
         &self,
         contract_address: &AccountId,
         caller: &AccountId,
         function_name: &str,
-        args: &[u8],
+        args: &[AbiValue],
         gas_limit: u64,
     ) -> SystemResult<ContractExecutionResult> {
-        let result = self.smart_contract_vm
-            .execute_contract(
-                caller.clone(),
-                contract_address.clone(),
-                function_name.to_string(),
-                args.to_vec(),
-                gas_limit,
-            )
-            .await?;
+        let mut contract = self.consensus.get_contract(contract_address).await
+            .ok_or_else(|| SystemError::NotFound(format!("No contract deployed at {}", contract_address)))?;
+        let function = contract.abi.function(function_name)
+            .ok_or_else(|| SystemError::NotFound(format!("Function {} not found", function_name)))?;
+        let input = contract_abi::encode(&function.inputs, args)
+            .map_err(|e| SystemError::Validation(format!("Invalid arguments for {}: {}", function_name, e)))?;
+        let call = ContractCall {
+            address: contract_address,
+            caller,
+            entry_point: function_name,
+            input: &input,
+            gas_limit: gas_limit.min(self.smart_contract_vm.max_gas_limit()),
+        };
+        let result = self.consensus
+            .with_contract_context(|context| self.smart_contract_vm.execute_contract(&mut contract, call, context))
+            .await;
 
         crate::utils::logging::log_info(
             "BlockchainEngine",
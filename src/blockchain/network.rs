@@ -1,41 +1,629 @@
 //! # Network Layer
-//! 
+//!
 //! Implements P2P networking for blockchain communication.
+//!
+//! Nodes talk libp2p over TCP with noise encryption and yamux multiplexing. The peer
+//! identity is derived from the configured node key, so a node keeps the same peer ID
+//! across restarts. New blocks and transactions are gossiped on per-network gossipsub
+//! topics, as are finality precommits, and every received message is handed to
+//! subscribers as a [`NetworkEvent`].
+//! Block sync runs as a separate request/response protocol answered by the registered
+//! [`SyncProvider`]. Gossip messages and sync traffic are both sent in the canonical
+//! [`encoding`].
 
+use crate::blockchain::consensus::EnergyBlock;
+use crate::blockchain::encoding;
+use crate::blockchain::finality::Precommit;
+use crate::blockchain::sync::{SyncProvider, SyncRequest, SyncResponse, SYNC_PROTOCOL};
+use crate::blockchain::transactions::EnergyTransactionEnvelope;
 use crate::config::BlockchainConfig;
+use crate::crypto::GridTokenXKeyPair;
 use crate::utils::SystemResult;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, StreamExt};
+use libp2p::swarm::{NetworkBehaviour, SwarmEvent};
+use libp2p::{gossipsub, identify, identity, noise, request_response, tcp, yamux, Multiaddr, PeerId, StreamProtocol, Swarm};
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Digest};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
+
+/// Protocol version announced through identify
+const PROTOCOL_VERSION: &str = "/gridtokenx/1.0.0";
+/// Interval between attempts to reconnect to disconnected bootnodes
+const BOOTNODE_REDIAL_INTERVAL: Duration = Duration::from_secs(30);
+/// Number of network events buffered for slow subscribers
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+/// Time a peer has to answer a sync request
+const SYNC_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// P2P network layer
 pub struct NetworkLayer {
     config: BlockchainConfig,
     running: Arc<RwLock<bool>>,
+    /// libp2p identity derived from the node key
+    keypair: identity::Keypair,
+    /// Gossip topics of the configured network
+    topics: Topics,
+    /// Peers we currently hold a connection to
+    peers: Arc<RwLock<HashMap<PeerId, PeerInfo>>>,
+    /// Addresses the swarm is listening on
+    listen_addresses: Arc<RwLock<Vec<Multiaddr>>>,
+    /// Command channel into the running swarm task
+    commands: Arc<RwLock<Option<mpsc::UnboundedSender<NetworkCommand>>>>,
+    /// Fan-out of messages received from peers
+    events: broadcast::Sender<NetworkEvent>,
+    /// Chain data source answering sync requests from peers
+    sync_provider: Arc<RwLock<Option<Arc<dyn SyncProvider>>>>,
+}
+
+/// Entry in the peer table
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerInfo {
+    /// Remote peer ID
+    pub peer_id: PeerId,
+    /// Addresses the peer is connected on or announced through identify
+    pub addresses: Vec<Multiaddr>,
+    /// Agent version reported through identify
+    pub agent_version: Option<String>,
+    /// Gossip topics the peer has subscribed to
+    pub topics: Vec<String>,
+    /// Time the first connection to the peer was established
+    pub connected_at: u64,
+}
+
+/// Event raised by the network layer
+#[derive(Debug, Clone)]
+pub enum NetworkEvent {
+    /// A block was gossiped by a peer
+    BlockReceived { source: PeerId, block: Box<EnergyBlock> },
+    /// A transaction was gossiped by a peer
+    TransactionReceived { source: PeerId, transaction: Box<EnergyTransactionEnvelope> },
+    /// A finality precommit was gossiped by a peer
+    PrecommitReceived { source: PeerId, precommit: Precommit },
+    /// A connection to a new peer was established
+    PeerConnected(PeerId),
+    /// The last connection to a peer was closed
+    PeerDisconnected(PeerId),
+}
+
+/// Payload carried on the gossip topics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum GossipMessage {
+    Block(Box<EnergyBlock>),
+    Transaction(Box<EnergyTransactionEnvelope>),
+    Precommit(Precommit),
+}
+
+/// Request sent to the swarm task
+enum NetworkCommand {
+    Publish { topic: gossipsub::IdentTopic, data: Vec<u8> },
+    Request { peer: PeerId, request: SyncRequest, respond: oneshot::Sender<SystemResult<SyncResponse>> },
+    Shutdown,
+}
+
+/// Sync requests awaiting a response, by request ID
+type PendingRequests = HashMap<request_response::OutboundRequestId, oneshot::Sender<SystemResult<SyncResponse>>>;
+
+#[derive(NetworkBehaviour)]
+struct GridBehaviour {
+    gossipsub: gossipsub::Behaviour,
+    identify: identify::Behaviour,
+    sync: request_response::Behaviour<SyncCodec>,
+}
+
+/// Sync protocol codec writing requests and responses in the canonical encoding
+#[derive(Debug, Clone, Default)]
+struct SyncCodec;
+
+impl SyncCodec {
+    /// Largest sync request accepted from a peer, in bytes
+    const REQUEST_SIZE_MAXIMUM: u64 = 1024 * 1024;
+    /// Largest sync response accepted from a peer, in bytes
+    const RESPONSE_SIZE_MAXIMUM: u64 = 10 * 1024 * 1024;
+
+    async fn read<T, M>(io: &mut T, limit: u64) -> std::io::Result<M>
+    where
+        T: AsyncRead + Unpin + Send,
+        M: serde::de::DeserializeOwned,
+    {
+        let mut data = Vec::new();
+        io.take(limit).read_to_end(&mut data).await?;
+        encoding::decode(&data).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    async fn write<T, M>(io: &mut T, message: &M) -> std::io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+        M: Serialize,
+    {
+        let data = encoding::encode(message).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        io.write_all(&data).await
+    }
+}
+
+#[async_trait::async_trait]
+impl request_response::Codec for SyncCodec {
+    type Protocol = StreamProtocol;
+    type Request = SyncRequest;
+    type Response = SyncResponse;
+
+    async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> std::io::Result<SyncRequest>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        Self::read(io, Self::REQUEST_SIZE_MAXIMUM).await
+    }
+
+    async fn read_response<T>(&mut self, _: &StreamProtocol, io: &mut T) -> std::io::Result<SyncResponse>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        Self::read(io, Self::RESPONSE_SIZE_MAXIMUM).await
+    }
+
+    async fn write_request<T>(&mut self, _: &StreamProtocol, io: &mut T, request: SyncRequest) -> std::io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        Self::write(io, &request).await
+    }
+
+    async fn write_response<T>(&mut self, _: &StreamProtocol, io: &mut T, response: SyncResponse) -> std::io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        Self::write(io, &response).await
+    }
+}
+
+/// State shared between the network layer and its swarm task
+struct SwarmContext {
+    topics: Topics,
+    peers: Arc<RwLock<HashMap<PeerId, PeerInfo>>>,
+    listen_addresses: Arc<RwLock<Vec<Multiaddr>>>,
+    events: broadcast::Sender<NetworkEvent>,
+    sync_provider: Arc<RwLock<Option<Arc<dyn SyncProvider>>>>,
+}
+
+/// Gossip topics of one network
+#[derive(Clone)]
+struct Topics {
+    blocks: gossipsub::IdentTopic,
+    transactions: gossipsub::IdentTopic,
+    precommits: gossipsub::IdentTopic,
+}
+
+impl Topics {
+    fn new(network: &str) -> Self {
+        Self {
+            blocks: gossipsub::IdentTopic::new(format!("/gridtokenx/{}/blocks", network)),
+            transactions: gossipsub::IdentTopic::new(format!("/gridtokenx/{}/transactions", network)),
+            precommits: gossipsub::IdentTopic::new(format!("/gridtokenx/{}/precommits", network)),
+        }
+    }
 }
 
 impl NetworkLayer {
     pub async fn new(config: &BlockchainConfig) -> SystemResult<Self> {
+        let node_keypair = GridTokenXKeyPair::from_node_key(&config.node_key)?;
+        let keypair = identity::Keypair::ed25519_from_bytes(node_keypair.export_private_key_bytes())
+            .map_err(|e| crate::utils::SystemError::Configuration(format!("Invalid node key: {}", e)))?;
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+
         Ok(Self {
             config: config.clone(),
             running: Arc::new(RwLock::new(false)),
+            keypair,
+            topics: Topics::new(&config.network),
+            peers: Arc::new(RwLock::new(HashMap::new())),
+            listen_addresses: Arc::new(RwLock::new(Vec::new())),
+            commands: Arc::new(RwLock::new(None)),
+            events,
+            sync_provider: Arc::new(RwLock::new(None)),
         })
     }
-    
+
     pub async fn start(&self) -> SystemResult<()> {
         let mut running = self.running.write().await;
+        if *running {
+            return Ok(());
+        }
+
+        let mut swarm = self.build_swarm()?;
+
+        let listen_address: Multiaddr = format!("/ip4/0.0.0.0/tcp/{}", self.config.p2p_port)
+            .parse()
+            .map_err(|e| crate::utils::SystemError::Configuration(format!("Invalid P2P port: {}", e)))?;
+        swarm.listen_on(listen_address)
+            .map_err(|e| crate::utils::SystemError::Network(format!("Failed to listen: {}", e)))?;
+
+        let bootnodes = Self::parse_bootnodes(&self.config.bootnodes);
+        for bootnode in &bootnodes {
+            Self::dial(&mut swarm, bootnode);
+        }
+
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+        *self.commands.write().await = Some(command_tx);
+
+        let context = SwarmContext {
+            topics: self.topics.clone(),
+            peers: self.peers.clone(),
+            listen_addresses: self.listen_addresses.clone(),
+            events: self.events.clone(),
+            sync_provider: self.sync_provider.clone(),
+        };
+        tokio::spawn(Self::run_swarm(swarm, context, bootnodes, command_rx));
+
         *running = true;
-        
+
         crate::utils::logging::log_startup("Network Layer");
-        
+        crate::utils::logging::log_info(
+            "NetworkLayer",
+            &format!("Local peer ID {}", self.local_peer_id())
+        );
+
         Ok(())
     }
-    
+
     pub async fn stop(&self) -> SystemResult<()> {
         let mut running = self.running.write().await;
         *running = false;
-        
+
+        if let Some(commands) = self.commands.write().await.take() {
+            let _ = commands.send(NetworkCommand::Shutdown);
+        }
+        self.peers.write().await.clear();
+        self.listen_addresses.write().await.clear();
+
         crate::utils::logging::log_shutdown("Network Layer");
-        
+
         Ok(())
     }
+
+    /// Get the peer ID derived from the node key
+    pub fn local_peer_id(&self) -> PeerId {
+        self.keypair.public().to_peer_id()
+    }
+
+    /// Get the addresses the node is listening on
+    pub async fn listen_addresses(&self) -> Vec<Multiaddr> {
+        self.listen_addresses.read().await.clone()
+    }
+
+    /// Get the peer table
+    pub async fn peers(&self) -> Vec<PeerInfo> {
+        self.peers.read().await.values().cloned().collect()
+    }
+
+    /// Get the number of connected peers
+    pub async fn peer_count(&self) -> usize {
+        self.peers.read().await.len()
+    }
+
+    /// Subscribe to blocks, transactions and peer changes seen by the network layer
+    pub fn subscribe(&self) -> broadcast::Receiver<NetworkEvent> {
+        self.events.subscribe()
+    }
+
+    /// Register the chain data source that answers sync requests from peers
+    pub async fn set_sync_provider(&self, provider: Arc<dyn SyncProvider>) {
+        *self.sync_provider.write().await = Some(provider);
+    }
+
+    /// Send a sync request to a connected peer and wait for its response
+    pub async fn request(&self, peer: &PeerId, request: SyncRequest) -> SystemResult<SyncResponse> {
+        let (respond, response) = oneshot::channel();
+        {
+            let commands = self.commands.read().await;
+            let commands = commands.as_ref()
+                .ok_or_else(|| crate::utils::SystemError::Network("Network layer is not running".to_string()))?;
+            commands.send(NetworkCommand::Request { peer: *peer, request, respond })
+                .map_err(|_| crate::utils::SystemError::Network("Network task has stopped".to_string()))?;
+        }
+        response.await
+            .map_err(|_| crate::utils::SystemError::Network("Network task has stopped".to_string()))?
+    }
+
+    /// Gossip a block to the network
+    pub async fn broadcast_block(&self, block: &EnergyBlock) -> SystemResult<()> {
+        self.publish(&self.topics.blocks, &GossipMessage::Block(Box::new(block.clone()))).await
+    }
+
+    /// Gossip a transaction to the network
+    pub async fn broadcast_transaction(&self, transaction: &EnergyTransactionEnvelope) -> SystemResult<()> {
+        self.publish(&self.topics.transactions, &GossipMessage::Transaction(Box::new(transaction.clone()))).await
+    }
+
+    /// Gossip a finality precommit to the network
+    pub async fn broadcast_precommit(&self, precommit: &Precommit) -> SystemResult<()> {
+        self.publish(&self.topics.precommits, &GossipMessage::Precommit(precommit.clone())).await
+    }
+
+    async fn publish(&self, topic: &gossipsub::IdentTopic, message: &GossipMessage) -> SystemResult<()> {
+        let data = encoding::encode(message)
+            .map_err(|e| crate::utils::SystemError::Network(format!("Failed to encode gossip message: {}", e)))?;
+        let commands = self.commands.read().await;
+        let commands = commands.as_ref()
+            .ok_or_else(|| crate::utils::SystemError::Network("Network layer is not running".to_string()))?;
+        commands.send(NetworkCommand::Publish { topic: topic.clone(), data })
+            .map_err(|_| crate::utils::SystemError::Network("Network task has stopped".to_string()))
+    }
+
+    /// Build the libp2p swarm and subscribe to the gossip topics
+    fn build_swarm(&self) -> SystemResult<Swarm<GridBehaviour>> {
+        let network_error = |e: &dyn std::fmt::Display| crate::utils::SystemError::Network(e.to_string());
+
+        let mut swarm = libp2p::SwarmBuilder::with_existing_identity(self.keypair.clone())
+            .with_tokio()
+            .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)
+            .map_err(|e| network_error(&e))?
+            .with_behaviour(|key| {
+                // Identical payloads gossiped by different nodes are the same message
+                let gossipsub_config = gossipsub::ConfigBuilder::default()
+                    .heartbeat_interval(Duration::from_secs(1))
+                    .validation_mode(gossipsub::ValidationMode::Strict)
+                    .message_id_fn(|message| gossipsub::MessageId::from(Sha256::digest(&message.data).to_vec()))
+                    .build()?;
+                let gossipsub = gossipsub::Behaviour::new(
+                    gossipsub::MessageAuthenticity::Signed(key.clone()),
+                    gossipsub_config,
+                )?;
+                let identify = identify::Behaviour::new(
+                    identify::Config::new(PROTOCOL_VERSION.to_string(), key.public())
+                        .with_agent_version(format!("gridtokenx/{}", env!("CARGO_PKG_VERSION"))),
+                );
+                let sync = request_response::Behaviour::with_codec(
+                    SyncCodec,
+                    [(StreamProtocol::new(SYNC_PROTOCOL), request_response::ProtocolSupport::Full)],
+                    request_response::Config::default().with_request_timeout(SYNC_REQUEST_TIMEOUT),
+                );
+                Ok(GridBehaviour { gossipsub, identify, sync })
+            })
+            .map_err(|e| network_error(&e))?
+            .with_swarm_config(|config| config.with_idle_connection_timeout(Duration::from_secs(60)))
+            .build();
+
+        for topic in [&self.topics.blocks, &self.topics.transactions, &self.topics.precommits] {
+            swarm.behaviour_mut().gossipsub.subscribe(topic).map_err(|e| network_error(&e))?;
+        }
+
+        Ok(swarm)
+    }
+
+    /// Parse configured bootnode multiaddresses, skipping invalid entries
+    fn parse_bootnodes(bootnodes: &[String]) -> Vec<Multiaddr> {
+        bootnodes.iter()
+            .filter_map(|bootnode| match bootnode.parse::<Multiaddr>() {
+                Ok(address) => Some(address),
+                Err(e) => {
+                    crate::utils::logging::log_warning(
+                        "NetworkLayer",
+                        &format!("Ignoring invalid bootnode {}: {}", bootnode, e)
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Peer ID embedded in a `/p2p/<peer id>` address suffix
+    fn bootnode_peer_id(address: &Multiaddr) -> Option<PeerId> {
+        address.iter().find_map(|protocol| match protocol {
+            libp2p::multiaddr::Protocol::P2p(peer_id) => Some(peer_id),
+            _ => None,
+        })
+    }
+
+    fn dial(swarm: &mut Swarm<GridBehaviour>, address: &Multiaddr) {
+        if let Err(e) = swarm.dial(address.clone()) {
+            crate::utils::logging::log_warning(
+                "NetworkLayer",
+                &format!("Failed to dial {}: {}", address, e)
+            );
+        }
+    }
+
+    /// Drive the swarm until the layer is stopped
+    async fn run_swarm(
+        mut swarm: Swarm<GridBehaviour>,
+        context: SwarmContext,
+        bootnodes: Vec<Multiaddr>,
+        mut commands: mpsc::UnboundedReceiver<NetworkCommand>,
+    ) {
+        let mut pending_requests = PendingRequests::new();
+        let mut redial = tokio::time::interval(BOOTNODE_REDIAL_INTERVAL);
+        redial.tick().await;
+
+        loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(NetworkCommand::Publish { topic, data }) => {
+                        // Nobody listening and re-publishing a message we already relayed are both expected
+                        if let Err(e) = swarm.behaviour_mut().gossipsub.publish(topic.clone(), data) {
+                            crate::utils::logging::log_debug(
+                                "NetworkLayer",
+                                &format!("Message on {} not published: {}", topic, e)
+                            );
+                        }
+                    }
+                    Some(NetworkCommand::Request { peer, request, respond }) => {
+                        let request_id = swarm.behaviour_mut().sync.send_request(&peer, request);
+                        pending_requests.insert(request_id, respond);
+                    }
+                    Some(NetworkCommand::Shutdown) | None => break,
+                },
+                event = swarm.select_next_some() => match event {
+                    SwarmEvent::Behaviour(GridBehaviourEvent::Sync(event)) => {
+                        let provider = context.sync_provider.read().await.clone();
+                        Self::handle_sync_event(&mut swarm, event, provider.as_deref(), &mut pending_requests).await;
+                    }
+                    event => Self::handle_swarm_event(event, &context).await,
+                },
+                _ = redial.tick() => {
+                    let connected = context.peers.read().await;
+                    for bootnode in &bootnodes {
+                        match Self::bootnode_peer_id(bootnode) {
+                            Some(peer_id) if connected.contains_key(&peer_id) => {}
+                            _ => Self::dial(&mut swarm, bootnode),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle_sync_event(
+        swarm: &mut Swarm<GridBehaviour>,
+        event: request_response::Event<SyncRequest, SyncResponse>,
+        provider: Option<&dyn SyncProvider>,
+        pending_requests: &mut PendingRequests,
+    ) {
+        match event {
+            request_response::Event::Message { peer, message, .. } => match message {
+                request_response::Message::Request { request, channel, .. } => {
+                    // Dropping the channel tells the peer we cannot serve the request
+                    let Some(provider) = provider else { return };
+                    let response = provider.handle_sync_request(request).await;
+                    if swarm.behaviour_mut().sync.send_response(channel, response).is_err() {
+                        crate::utils::logging::log_debug(
+                            "NetworkLayer",
+                            &format!("Sync response to {} not delivered", peer)
+                        );
+                    }
+                }
+                request_response::Message::Response { request_id, response } => {
+                    if let Some(respond) = pending_requests.remove(&request_id) {
+                        let _ = respond.send(Ok(response));
+                    }
+                }
+            },
+            request_response::Event::OutboundFailure { peer, request_id, error, .. } => {
+                if let Some(respond) = pending_requests.remove(&request_id) {
+                    let _ = respond.send(Err(crate::utils::SystemError::Network(
+                        format!("Sync request to {} failed: {}", peer, error)
+                    )));
+                }
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                crate::utils::logging::log_debug(
+                    "NetworkLayer",
+                    &format!("Failed to answer sync request from {}: {}", peer, error)
+                );
+            }
+            request_response::Event::ResponseSent { .. } => {}
+        }
+    }
+
+    async fn handle_swarm_event(
+        event: SwarmEvent<GridBehaviourEvent>,
+        context: &SwarmContext,
+    ) {
+        let SwarmContext { topics, peers, listen_addresses, events, .. } = context;
+        match event {
+            SwarmEvent::NewListenAddr { address, .. } => {
+                crate::utils::logging::log_info("NetworkLayer", &format!("Listening on {}", address));
+                listen_addresses.write().await.push(address);
+            }
+            SwarmEvent::ExpiredListenAddr { address, .. } => {
+                listen_addresses.write().await.retain(|known| *known != address);
+            }
+            SwarmEvent::ConnectionEstablished { peer_id, endpoint, num_established, .. } => {
+                let mut peers = peers.write().await;
+                let peer = peers.entry(peer_id).or_insert_with(|| PeerInfo {
+                    peer_id,
+                    addresses: Vec::new(),
+                    agent_version: None,
+                    topics: Vec::new(),
+                    connected_at: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                });
+                let address = endpoint.get_remote_address().clone();
+                if !peer.addresses.contains(&address) {
+                    peer.addresses.push(address);
+                }
+                if num_established.get() == 1 {
+                    crate::utils::logging::log_info("NetworkLayer", &format!("Connected to peer {}", peer_id));
+                    let _ = events.send(NetworkEvent::PeerConnected(peer_id));
+                }
+            }
+            SwarmEvent::ConnectionClosed { peer_id, num_established: 0, .. } => {
+                peers.write().await.remove(&peer_id);
+                crate::utils::logging::log_info("NetworkLayer", &format!("Disconnected from peer {}", peer_id));
+                let _ = events.send(NetworkEvent::PeerDisconnected(peer_id));
+            }
+            SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
+                crate::utils::logging::log_warning(
+                    "NetworkLayer",
+                    &format!("Failed to connect to {:?}: {}", peer_id, error)
+                );
+            }
+            SwarmEvent::Behaviour(GridBehaviourEvent::Identify(identify::Event::Received { peer_id, info, .. })) => {
+                if let Some(peer) = peers.write().await.get_mut(&peer_id) {
+                    peer.agent_version = Some(info.agent_version);
+                    for address in info.listen_addrs {
+                        if !peer.addresses.contains(&address) {
+                            peer.addresses.push(address);
+                        }
+                    }
+                }
+            }
+            SwarmEvent::Behaviour(GridBehaviourEvent::Gossipsub(gossipsub::Event::Subscribed { peer_id, topic })) => {
+                if let Some(peer) = peers.write().await.get_mut(&peer_id) {
+                    let topic = topic.into_string();
+                    if !peer.topics.contains(&topic) {
+                        peer.topics.push(topic);
+                    }
+                }
+            }
+            SwarmEvent::Behaviour(GridBehaviourEvent::Gossipsub(gossipsub::Event::Unsubscribed { peer_id, topic })) => {
+                if let Some(peer) = peers.write().await.get_mut(&peer_id) {
+                    peer.topics.retain(|known| *known != topic.as_str());
+                }
+            }
+            SwarmEvent::Behaviour(GridBehaviourEvent::Gossipsub(gossipsub::Event::Message { propagation_source, message, .. })) => {
+                let event = match encoding::decode(&message.data) {
+                    Ok(GossipMessage::Block(block)) if message.topic == topics.blocks.hash() => {
+                        NetworkEvent::BlockReceived { source: propagation_source, block }
+                    }
+                    Ok(GossipMessage::Transaction(mut transaction)) if message.topic == topics.transactions.hash() => {
+                        // The hash is derived from the contents, never taken from the peer
+                        match transaction.compute_hash() {
+                            Ok(hash) => transaction.hash = hash,
+                            Err(e) => {
+                                crate::utils::logging::log_warning(
+                                    "NetworkLayer",
+                                    &format!("Dropping unhashable transaction from {}: {}", propagation_source, e)
+                                );
+                                return;
+                            }
+                        }
+                        NetworkEvent::TransactionReceived { source: propagation_source, transaction }
+                    }
+                    Ok(GossipMessage::Precommit(precommit)) if message.topic == topics.precommits.hash() => {
+                        NetworkEvent::PrecommitReceived { source: propagation_source, precommit }
+                    }
+                    Ok(_) => {
+                        crate::utils::logging::log_warning(
+                            "NetworkLayer",
+                            &format!("Dropping message on wrong topic {} from {}", message.topic, propagation_source)
+                        );
+                        return;
+                    }
+                    Err(e) => {
+                        crate::utils::logging::log_warning(
+                            "NetworkLayer",
+                            &format!("Dropping undecodable message from {}: {}", propagation_source, e)
+                        );
+                        return;
+                    }
+                };
+                let _ = events.send(event);
+            }
+            _ => {}
+        }
+    }
 }
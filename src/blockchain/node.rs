@@ -2,11 +2,21 @@
 //! 
 //! This module implements the main blockchain node that coordinates all blockchain operations.
 
+use crate::blockchain::block_tree::MAX_FORK_DEPTH;
+use crate::blockchain::consensus::{BlockHeader, BlockImportResult, BlockRejection, ConsensusEngine};
+use crate::blockchain::network::{NetworkEvent, NetworkLayer};
+use crate::blockchain::sync::{
+    ChainStatus, SyncRequest, SyncResponse, SyncState, SyncStatus, MAX_BLOCKS_PER_REQUEST, MAX_HEADERS_PER_REQUEST,
+};
 use crate::blockchain::BlockchainEngine;
 use crate::config::BlockchainConfig;
+use crate::types::Hash;
 use crate::utils::SystemResult;
+use libp2p::PeerId;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tokio::task::JoinHandle;
 
 /// Main blockchain node
 pub struct BlockchainNode {
@@ -59,16 +69,36 @@ impl BlockchainNode {
 }
 
 /// Node manager for blockchain operations
+///
+/// Runs the block sync state machine: peers are polled for their chain status, and when
+/// one is ahead the missing headers and blocks are downloaded from it and imported.
 pub struct NodeManager {
     config: BlockchainConfig,
     running: Arc<RwLock<bool>>,
+    consensus: Arc<ConsensusEngine>,
+    network: Arc<NetworkLayer>,
+    /// Current sync progress
+    sync_status: Arc<RwLock<SyncStatus>>,
+    /// Held while a sync round runs so rounds never overlap
+    sync_lock: Arc<Mutex<()>>,
+    /// Background task triggering sync rounds
+    sync_task: RwLock<Option<JoinHandle<()>>>,
 }
 
 impl NodeManager {
-    pub async fn new(config: &BlockchainConfig) -> SystemResult<Self> {
+    pub async fn new(
+        config: &BlockchainConfig,
+        consensus: Arc<ConsensusEngine>,
+        network: Arc<NetworkLayer>,
+    ) -> SystemResult<Self> {
         Ok(Self {
             config: config.clone(),
             running: Arc::new(RwLock::new(false)),
+            consensus,
+            network,
+            sync_status: Arc::new(RwLock::new(SyncStatus::default())),
+            sync_lock: Arc::new(Mutex::new(())),
+            sync_task: RwLock::new(None),
         })
     }
     
@@ -76,6 +106,30 @@ impl NodeManager {
         let mut running = self.running.write().await;
         *running = true;
         
+        let sync = self.sync_handle();
+        let mut network_events = self.network.subscribe();
+        let mut poll = tokio::time::interval(Duration::from_secs(self.config.block_time.max(1)));
+        let task = tokio::spawn(async move {
+            loop {
+                // Sync on a timer, when a peer connects, and when gossip shows we are behind
+                let triggered = tokio::select! {
+                    _ = poll.tick() => true,
+                    event = network_events.recv() => match event {
+                        Ok(NetworkEvent::PeerConnected(_)) => true,
+                        Ok(NetworkEvent::BlockReceived { block, .. }) => {
+                            block.header.number > sync.consensus.chain_status().await.best_number + 1
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => false,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                };
+                if triggered {
+                    sync.run().await;
+                }
+            }
+        });
+        *self.sync_task.write().await = Some(task);
+        
         crate::utils::logging::log_startup("Node Manager");
         
         Ok(())
@@ -85,8 +139,179 @@ impl NodeManager {
         let mut running = self.running.write().await;
         *running = false;
         
+        if let Some(task) = self.sync_task.write().await.take() {
+            task.abort();
+        }
+        
         crate::utils::logging::log_shutdown("Node Manager");
         
         Ok(())
     }
+    
+    /// Get the current sync progress
+    pub async fn sync_status(&self) -> SyncStatus {
+        let mut status = self.sync_status.read().await.clone();
+        status.current_height = self.consensus.chain_status().await.best_number;
+        status
+    }
+    
+    /// Run one sync round now and return the resulting status
+    pub async fn sync_once(&self) -> SyncStatus {
+        self.sync_handle().run().await;
+        self.sync_status().await
+    }
+    
+    fn sync_handle(&self) -> ChainSync {
+        ChainSync {
+            consensus: self.consensus.clone(),
+            network: self.network.clone(),
+            status: self.sync_status.clone(),
+            lock: self.sync_lock.clone(),
+        }
+    }
+}
+
+/// Everything a sync round needs, detached from the node manager so it can run in a task
+struct ChainSync {
+    consensus: Arc<ConsensusEngine>,
+    network: Arc<NetworkLayer>,
+    status: Arc<RwLock<SyncStatus>>,
+    lock: Arc<Mutex<()>>,
+}
+
+impl ChainSync {
+    /// Catch up with the best peer, recording the outcome in the sync status
+    async fn run(&self) {
+        let _guard = self.lock.lock().await;
+        
+        let outcome = match self.best_peer().await {
+            Some((peer, target)) => {
+                {
+                    let mut status = self.status.write().await;
+                    status.state = SyncState::Syncing;
+                    status.target_height = target.best_number;
+                    status.sync_peer = Some(peer.to_string());
+                }
+                crate::utils::logging::log_info(
+                    "NodeManager",
+                    &format!("Syncing to block #{} from peer {}", target.best_number, peer)
+                );
+                self.download(&peer, target.best_number).await
+            }
+            None => Ok(()),
+        };
+        
+        let mut status = self.status.write().await;
+        status.sync_peer = None;
+        match outcome {
+            Ok(()) => {
+                status.state = SyncState::Synced;
+                status.last_error = None;
+            }
+            Err(e) => {
+                crate::utils::logging::log_warning("NodeManager", &format!("Sync aborted: {}", e));
+                status.state = SyncState::Idle;
+                status.last_error = Some(e.to_string());
+            }
+        }
+    }
+    
+    /// Find the connected peer on our chain with the highest head above ours
+    async fn best_peer(&self) -> Option<(PeerId, ChainStatus)> {
+        let local = self.consensus.chain_status().await;
+        let mut best: Option<(PeerId, ChainStatus)> = None;
+        
+        for peer in self.network.peers().await {
+            let status = match self.network.request(&peer.peer_id, SyncRequest::Status).await {
+                Ok(SyncResponse::Status(status)) => status,
+                _ => continue,
+            };
+            if status.genesis_hash != local.genesis_hash || status.best_number <= local.best_number {
+                continue;
+            }
+            if best.as_ref().is_none_or(|(_, known)| status.best_number > known.best_number) {
+                best = Some((peer.peer_id, status));
+            }
+        }
+        
+        if let Some((_, status)) = &best {
+            self.status.write().await.target_height = status.best_number;
+        }
+        best
+    }
+    
+    /// Download and import blocks from `peer` until the chain reaches `target`
+    async fn download(&self, peer: &PeerId, target: u32) -> SystemResult<()> {
+        loop {
+            let head = self.consensus.chain_status().await;
+            if head.best_number >= target {
+                return Ok(());
+            }
+            
+            let mut headers = self.headers(peer, head.best_number + 1, target).await?;
+            
+            // A peer on another branch is followed from where its chain forks off ours,
+            // which can be no lower than our finalized block
+            if headers[0].parent_hash != head.best_hash {
+                let floor = self.consensus.finalized_height()
+                    .max(head.best_number.saturating_sub(MAX_FORK_DEPTH));
+                headers = self.headers(peer, floor + 1, target).await?;
+                let mut known = 0;
+                while known < headers.len() && self.consensus.has_block(&headers[known].hash).await {
+                    known += 1;
+                }
+                headers.drain(..known);
+                match headers.first() {
+                    None => return Err(sync_error(format!("peer {} has no blocks our fork choice prefers", peer))),
+                    Some(first) if !self.consensus.has_block(&first.parent_hash).await => {
+                        return Err(sync_error(format!("peer {} is on a chain that forks off below block #{}", peer, floor)));
+                    }
+                    Some(_) => {}
+                }
+            }
+            
+            // Only fetch bodies for headers that link up in order
+            for pair in headers.windows(2) {
+                if pair[1].number != pair[0].number + 1 || pair[1].parent_hash != pair[0].hash {
+                    return Err(sync_error(format!("peer {} sent headers that do not form a chain", peer)));
+                }
+            }
+            
+            for chunk in headers.chunks(MAX_BLOCKS_PER_REQUEST) {
+                let hashes: Vec<Hash> = chunk.iter().map(|header| header.hash.clone()).collect();
+                let blocks = match self.network.request(peer, SyncRequest::Blocks { hashes: hashes.clone() }).await? {
+                    SyncResponse::Blocks(blocks) => blocks,
+                    _ => return Err(sync_error(format!("peer {} sent an unexpected response", peer))),
+                };
+                if blocks.iter().map(|block| &block.header.hash).ne(hashes.iter()) {
+                    return Err(sync_error(format!("peer {} did not send the requested blocks", peer)));
+                }
+                
+                for block in blocks {
+                    let block_number = block.header.number;
+                    match self.consensus.import_block(block).await? {
+                        // Gossip may have delivered the block while we were downloading it
+                        BlockImportResult::Imported | BlockImportResult::Rejected(BlockRejection::AlreadyImported(_)) => {}
+                        BlockImportResult::Rejected(reason) => {
+                            return Err(sync_error(format!("block #{} from peer {} rejected: {}", block_number, peer, reason)));
+                        }
+                    }
+                    self.status.write().await.blocks_imported += 1;
+                }
+            }
+        }
+    }
+    
+    /// Ask `peer` for consecutive headers from block `from` up to `target`
+    async fn headers(&self, peer: &PeerId, from: u32, target: u32) -> SystemResult<Vec<BlockHeader>> {
+        let count = (target + 1).saturating_sub(from).clamp(1, MAX_HEADERS_PER_REQUEST);
+        match self.network.request(peer, SyncRequest::Headers { from, count }).await? {
+            SyncResponse::Headers(headers) if headers.first().is_some_and(|header| header.number == from) => Ok(headers),
+            _ => Err(sync_error(format!("peer {} sent no headers from block #{}", peer, from))),
+        }
+    }
+}
+
+fn sync_error(message: String) -> crate::utils::SystemError {
+    crate::utils::SystemError::Network(message)
 }
@@ -0,0 +1,575 @@
+//! # Power Purchase Agreement Contract
+//!
+//! Built-in contract template for bilateral power purchase agreements (PPAs): the seller
+//! delivers a fixed volume of energy in every delivery period and the buyer pays a fixed
+//! price per kWh delivered. The template is executed natively by every node instead of in
+//! the WebAssembly runtime, because settling it moves tokens and reads metering records.
+//!
+//! An agreement goes through these steps:
+//!
+//! 1. The buyer deploys [`PPA_CONTRACT_CODE`] with [`ppa_abi`], passing the
+//!    [`PpaTerms`] as constructor arguments. The collateral paying for every period at the
+//!    agreed price moves from the buyer into the contract account. The constructor runs only
+//!    as part of this deployment.
+//! 2. Before the first period starts, the seller calls `accept`, posting the bond named in
+//!    the terms. Until then the buyer may `cancel` and get the collateral back.
+//! 3. Once a period has ended, anyone may call `settle`. Energy delivered in the period is
+//!    the seller's production reported in blocks inside the period, up to the contracted
+//!    volume and to the energy tokens the seller still holds. Production counted toward one
+//!    agreement is not counted toward any other. The delivered tokens move from the seller
+//!    to the buyer, so the seller cannot sell them again, and the seller is paid for them
+//!    out of the collateral. Every kWh delivered short of the contracted volume is paid to
+//!    the buyer out of the bond at the shortfall penalty rate.
+//! 4. After the last period is settled, the remaining collateral goes back to the buyer and
+//!    the remaining bond to the seller.
+//!
+//! The agreement is kept in contract storage, so its state is covered by the state root.
+
+use crate::blockchain::contract_abi::{self, AbiError, AbiType, AbiValue};
+use crate::blockchain::contract_runtime::{ContractCall, ContractError, GasSchedule, DEPLOY_ENTRY_POINT};
+use crate::blockchain::encoding;
+use crate::blockchain::smart_contracts::{
+    ContractABI, ContractEvent, ContractExecutionResult, ContractFunction, ContractState,
+};
+use crate::blockchain::transactions::EnergyTransaction;
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+
+/// Code deployed to create a PPA contract
+///
+/// Not a WebAssembly module: the leading zero byte is followed by `native` rather than the
+/// Wasm magic number, so it cannot be mistaken for contract code.
+pub const PPA_CONTRACT_CODE: &[u8] = b"\0native:ppa:1";
+
+/// Storage key the agreement is kept under
+const AGREEMENT_KEY: &str = "agreement";
+
+/// Constructor argument types, in [`PpaTerms`] field order
+const TERMS_TYPES: [AbiType; 8] = [
+    AbiType::Address,
+    AbiType::U64,
+    AbiType::U64,
+    AbiType::U64,
+    AbiType::U64,
+    AbiType::U32,
+    AbiType::U64,
+    AbiType::U128,
+];
+
+/// Data of the `period_settled` event: period, delivered Wh, shortfall Wh, payment, penalty
+const SETTLEMENT_TYPES: [AbiType; 5] = [AbiType::U32, AbiType::U64, AbiType::U64, AbiType::U128, AbiType::U128];
+
+/// Terms of a power purchase agreement, agreed between the buyer deploying it and the seller
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PpaTerms {
+    /// Account delivering the energy and receiving the payments
+    pub seller: AccountId,
+    /// Price paid per kWh delivered
+    pub price: TokenPrice,
+    /// Energy contracted for every delivery period
+    pub volume: EnergyAmount,
+    /// Unix time the first delivery period starts
+    pub start: u64,
+    /// Length of a delivery period in seconds
+    pub period_length: u64,
+    /// Number of delivery periods
+    pub periods: u32,
+    /// Penalty per kWh the seller's production falls short of the contracted volume
+    pub shortfall_penalty: TokenPrice,
+    /// Bond the seller posts when accepting, out of which penalties are paid
+    pub seller_bond: Balance,
+}
+
+/// State of an agreement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PpaStatus {
+    /// Waiting for the seller to accept
+    Offered,
+    /// Accepted; delivery periods are being settled
+    Active,
+    /// Withdrawn by the buyer before the seller accepted
+    Cancelled,
+    /// Every delivery period was settled and the remaining funds returned
+    Completed,
+}
+
+/// A power purchase agreement as kept in the storage of its contract
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PowerPurchaseAgreement {
+    /// Account that deployed the agreement and pays for the energy
+    pub buyer: AccountId,
+    pub terms: PpaTerms,
+    pub status: PpaStatus,
+    /// Delivery periods settled so far, which are always the earliest ones
+    pub settled_periods: u32,
+    /// Buyer collateral still held by the contract
+    pub collateral: Balance,
+    /// Seller bond still held by the contract
+    pub bond: Balance,
+}
+
+/// Outcome of settling one delivery period
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeriodSettlement {
+    /// Index of the period, counting from zero
+    pub period: u32,
+    /// Energy moved from the seller to the buyer and paid for
+    pub delivered: EnergyAmount,
+    /// Contracted energy the seller did not deliver
+    pub shortfall: EnergyAmount,
+    /// Paid to the seller out of the collateral
+    pub payment: Balance,
+    /// Paid to the buyer out of the bond
+    pub penalty: Balance,
+}
+
+/// Tokens a PPA call moves between accounts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payment {
+    pub from: AccountId,
+    pub to: AccountId,
+    pub amount: Balance,
+}
+
+/// Energy tokens a PPA call moves from the seller to the buyer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub from: AccountId,
+    pub to: AccountId,
+    pub amount: Balance,
+}
+
+/// Result of a call into a PPA contract, with the tokens the call moves
+///
+/// The payments and deliveries are empty if the call failed; the caller of [`execute`]
+/// applies them.
+#[derive(Debug, Clone)]
+pub struct PpaExecution {
+    pub result: ContractExecutionResult,
+    pub payments: Vec<Payment>,
+    pub deliveries: Vec<Delivery>,
+}
+
+/// Metered production deliveries are settled against
+pub trait MeterReadings {
+    /// Count up to `limit` of the energy `account` reported producing in blocks with
+    /// timestamps in `[from, to)` toward an agreement, returning the amount counted
+    ///
+    /// Energy counted once is not counted again, so a unit of production settles one
+    /// agreement at most.
+    fn claim_production(&mut self, account: &AccountId, from: u64, to: u64, limit: EnergyAmount) -> EnergyAmount;
+
+    /// Energy the tokens `account` holds and has not locked are worth
+    fn available_energy(&self, account: &AccountId) -> EnergyAmount;
+}
+
+/// Reason a PPA call fails
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PpaError {
+    #[error("invalid agreement terms: {0}")]
+    InvalidTerms(String),
+    #[error("only the {role} may {action} the agreement")]
+    NotAllowed { role: &'static str, action: &'static str },
+    #[error("agreement is no longer open: {0:?}")]
+    NotOffered(PpaStatus),
+    #[error("delivery started at {start}, the offer can no longer be accepted")]
+    DeliveryStarted { start: u64 },
+    #[error("agreement is not active: {0:?}")]
+    NotActive(PpaStatus),
+    #[error("no unsettled delivery period has ended yet")]
+    NothingToSettle,
+    #[error("contract holds no agreement")]
+    MissingAgreement,
+    #[error("the PPA template has no function {0}")]
+    UnknownFunction(String),
+}
+
+/// ABI of the PPA template
+pub fn ppa_abi() -> ContractABI {
+    let function = |name: &str, outputs: &[AbiType]| ContractFunction {
+        name: name.to_string(),
+        inputs: vec![],
+        outputs: outputs.to_vec(),
+    };
+    ContractABI {
+        functions: vec![
+            function("accept", &[]),
+            function("cancel", &[]),
+            // Returns the number of periods the call settled
+            function("settle", &[AbiType::U32]),
+        ],
+    }
+}
+
+impl PpaTerms {
+    /// Constructor arguments of a PPA deployment with these terms
+    pub fn to_args(&self) -> Vec<u8> {
+        let values = [
+            AbiValue::Address(self.seller.clone()),
+            AbiValue::U64(self.price.as_satang()),
+            AbiValue::U64(self.volume.as_wh()),
+            AbiValue::U64(self.start),
+            AbiValue::U64(self.period_length),
+            AbiValue::U32(self.periods),
+            AbiValue::U64(self.shortfall_penalty.as_satang()),
+            AbiValue::U128(self.seller_bond),
+        ];
+        contract_abi::encode(&TERMS_TYPES, &values).expect("values match the constructor types")
+    }
+
+    /// Decode terms from constructor arguments
+    pub fn from_args(input: &[u8]) -> Result<Self, AbiError> {
+        match contract_abi::decode(&TERMS_TYPES, input)?.as_slice() {
+            [
+                AbiValue::Address(seller),
+                AbiValue::U64(price),
+                AbiValue::U64(volume),
+                AbiValue::U64(start),
+                AbiValue::U64(period_length),
+                AbiValue::U32(periods),
+                AbiValue::U64(shortfall_penalty),
+                AbiValue::U128(seller_bond),
+            ] => Ok(Self {
+                seller: seller.clone(),
+                price: TokenPrice::from_satang(*price),
+                volume: EnergyAmount::from_wh(*volume),
+                start: *start,
+                period_length: *period_length,
+                periods: *periods,
+                shortfall_penalty: TokenPrice::from_satang(*shortfall_penalty),
+                seller_bond: *seller_bond,
+            }),
+            _ => unreachable!("decoded values have the constructor types"),
+        }
+    }
+
+    /// Deployment of a PPA contract with these terms, offered by `buyer`
+    ///
+    /// `gas_limit` is the gas the constructor may use.
+    pub fn deployment(&self, buyer: &AccountId, gas_limit: u64) -> EnergyTransaction {
+        EnergyTransaction::DeployContract {
+            deployer: buyer.clone(),
+            contract_code: PPA_CONTRACT_CODE.to_vec(),
+            abi: ppa_abi(),
+            constructor_args: self.to_args(),
+            gas_limit,
+            signature: vec![],
+        }
+    }
+
+    /// Collateral the buyer locks: the price of the contracted volume of every period
+    pub fn collateral(&self) -> Option<Balance> {
+        self.price.total(self.volume).checked_mul(self.periods as Balance)
+    }
+
+    /// Unix time range `[from, to)` of a delivery period
+    pub fn period_window(&self, period: u32) -> (u64, u64) {
+        let from = self.start + period as u64 * self.period_length;
+        (from, from + self.period_length)
+    }
+
+    /// Check that `buyer` can offer these terms, returning the collateral it has to lock
+    pub fn validate(&self, buyer: &AccountId) -> Result<Balance, PpaError> {
+        let invalid = |reason: &str| Err(PpaError::InvalidTerms(reason.to_string()));
+        if self.seller == *buyer {
+            return invalid("buyer and seller must differ");
+        }
+        if self.volume.is_zero() || self.price.is_zero() {
+            return invalid("volume and price must be positive");
+        }
+        if self.volume.tokens().is_none() {
+            return invalid("volume must be a whole number of kWh");
+        }
+        if self.periods == 0 || self.period_length == 0 {
+            return invalid("there must be at least one delivery period of positive length");
+        }
+        let end = (self.periods as u64).checked_mul(self.period_length).and_then(|length| self.start.checked_add(length));
+        if end.is_none() {
+            return invalid("delivery ends after the last representable time");
+        }
+        self.collateral().map_or_else(|| invalid("collateral does not fit a balance"), Ok)
+    }
+}
+
+impl PowerPurchaseAgreement {
+    /// Agreement offered by `buyer`, and the payment locking its collateral in `contract`
+    pub fn offer(buyer: &AccountId, contract: &AccountId, terms: PpaTerms) -> Result<(Self, Payment), PpaError> {
+        let collateral = terms.validate(buyer)?;
+        let agreement = Self {
+            buyer: buyer.clone(),
+            terms,
+            status: PpaStatus::Offered,
+            settled_periods: 0,
+            collateral,
+            bond: 0,
+        };
+        let lock = Payment { from: buyer.clone(), to: contract.clone(), amount: collateral };
+        Ok((agreement, lock))
+    }
+
+    /// Agreement kept in a contract's storage, `None` if the contract is not a PPA
+    pub fn from_contract(contract: &ContractState) -> Option<Self> {
+        if contract.code != PPA_CONTRACT_CODE {
+            return None;
+        }
+        contract.storage.get(AGREEMENT_KEY).and_then(|bytes| encoding::decode(bytes).ok())
+    }
+
+    fn store(&self, contract: &mut ContractState) {
+        let bytes = encoding::encode(self).expect("agreements contain no floats");
+        contract.storage.insert(AGREEMENT_KEY.to_string(), bytes);
+    }
+
+    /// Seller accepts the offer at time `now`, posting its bond in `contract`
+    pub fn accept(&mut self, caller: &AccountId, contract: &AccountId, now: u64) -> Result<Payment, PpaError> {
+        if *caller != self.terms.seller {
+            return Err(PpaError::NotAllowed { role: "seller", action: "accept" });
+        }
+        if self.status != PpaStatus::Offered {
+            return Err(PpaError::NotOffered(self.status));
+        }
+        if now >= self.terms.start {
+            return Err(PpaError::DeliveryStarted { start: self.terms.start });
+        }
+        self.status = PpaStatus::Active;
+        self.bond = self.terms.seller_bond;
+        Ok(Payment { from: caller.clone(), to: contract.clone(), amount: self.bond })
+    }
+
+    /// Buyer withdraws an offer the seller has not accepted, releasing the collateral
+    pub fn cancel(&mut self, caller: &AccountId, contract: &AccountId) -> Result<Payment, PpaError> {
+        if *caller != self.buyer {
+            return Err(PpaError::NotAllowed { role: "buyer", action: "cancel" });
+        }
+        if self.status != PpaStatus::Offered {
+            return Err(PpaError::NotOffered(self.status));
+        }
+        self.status = PpaStatus::Cancelled;
+        let refund = Payment { from: contract.clone(), to: self.buyer.clone(), amount: self.collateral };
+        self.collateral = 0;
+        Ok(refund)
+    }
+
+    /// Settle every delivery period that ended by `now`, in order
+    ///
+    /// Returns the settled periods and the payments out of `contract`, including the
+    /// release of the remaining funds once the last period is settled. The energy delivered
+    /// in the returned periods is what the seller has to move to the buyer.
+    pub fn settle(
+        &mut self,
+        contract: &AccountId,
+        now: u64,
+        readings: &mut impl MeterReadings,
+    ) -> Result<(Vec<PeriodSettlement>, Vec<Payment>), PpaError> {
+        if self.status != PpaStatus::Active {
+            return Err(PpaError::NotActive(self.status));
+        }
+
+        let mut settlements = Vec::new();
+        let mut payments = Vec::new();
+        let mut pay = |to: &AccountId, amount: Balance| {
+            if amount > 0 {
+                payments.push(Payment { from: contract.clone(), to: to.clone(), amount });
+            }
+        };
+        let mut held = readings.available_energy(&self.terms.seller);
+        while self.settled_periods < self.terms.periods {
+            let period = self.settled_periods;
+            let (from, to) = self.terms.period_window(period);
+            if to > now {
+                break;
+            }
+
+            let delivered = readings.claim_production(&self.terms.seller, from, to, self.terms.volume.min(held));
+            held = held.saturating_sub(delivered);
+            let shortfall = self.terms.volume.saturating_sub(delivered);
+            let payment = self.terms.price.total(delivered).min(self.collateral);
+            let penalty = self.terms.shortfall_penalty.total(shortfall).min(self.bond);
+            self.collateral -= payment;
+            self.bond -= penalty;
+            self.settled_periods += 1;
+
+            pay(&self.terms.seller, payment);
+            pay(&self.buyer, penalty);
+            settlements.push(PeriodSettlement { period, delivered, shortfall, payment, penalty });
+        }
+        if settlements.is_empty() {
+            return Err(PpaError::NothingToSettle);
+        }
+
+        if self.settled_periods == self.terms.periods {
+            self.status = PpaStatus::Completed;
+            pay(&self.buyer, std::mem::take(&mut self.collateral));
+            pay(&self.terms.seller, std::mem::take(&mut self.bond));
+        }
+        Ok((settlements, payments))
+    }
+}
+
+/// Run the constructor of a PPA contract, creating the agreement from the terms in its input
+///
+/// Gas is charged according to `schedule`, and storage is written to `contract` only if the
+/// constructor succeeds.
+pub fn deploy(contract: &mut ContractState, constructor: ContractCall<'_>, schedule: &GasSchedule) -> PpaExecution {
+    let outcome = PpaTerms::from_args(constructor.input)
+        .map_err(|e| PpaError::InvalidTerms(e.to_string()))
+        .and_then(|terms| PowerPurchaseAgreement::offer(constructor.caller, constructor.address, terms))
+        .map(|(agreement, lock)| {
+            let offered = ContractEvent {
+                contract: constructor.address.clone(),
+                topic: "offered".to_string(),
+                data: agreement.terms.seller.as_bytes().to_vec(),
+            };
+            CallOutcome::new(agreement, vec![lock], offered)
+        });
+    finish(contract, constructor, &[], outcome, schedule)
+}
+
+/// Run a call into a PPA contract at block time `now`
+///
+/// Gas is charged per storage access, event and payment according to `schedule`. Storage is
+/// written to `contract` only if the call succeeds. The constructor cannot be called; it
+/// runs only through [`deploy`].
+pub fn execute(
+    contract: &mut ContractState,
+    call: ContractCall<'_>,
+    now: u64,
+    readings: &mut impl MeterReadings,
+    schedule: &GasSchedule,
+) -> PpaExecution {
+    let failure = |error: String| PpaExecution {
+        result: ContractExecutionResult::failure(0, error),
+        payments: vec![],
+        deliveries: vec![],
+    };
+    if call.entry_point == DEPLOY_ENTRY_POINT {
+        return failure(format!("The {} constructor runs only when the contract is deployed", DEPLOY_ENTRY_POINT));
+    }
+    let Some(function) = contract.abi.function(call.entry_point) else {
+        return failure(format!("Function {} not found", call.entry_point));
+    };
+    if let Err(e) = contract_abi::decode(&function.inputs, call.input) {
+        return failure(format!("Invalid arguments for {}: {}", function.name, e));
+    }
+    let outputs = function.outputs.clone();
+
+    let outcome = run(contract, call, now, readings);
+    finish(contract, call, &outputs, outcome, schedule)
+}
+
+/// Charge the gas of a PPA call and store the agreement if the call succeeded
+fn finish(
+    contract: &mut ContractState,
+    call: ContractCall<'_>,
+    outputs: &[AbiType],
+    outcome: Result<CallOutcome, PpaError>,
+    schedule: &GasSchedule,
+) -> PpaExecution {
+    let failure = |gas_used: u64, error: String| PpaExecution {
+        result: ContractExecutionResult::failure(gas_used, error),
+        payments: vec![],
+        deliveries: vec![],
+    };
+    let gas_used = schedule.host_call + schedule.storage_read + outcome.as_ref().map_or(0, |outcome| outcome.gas(schedule));
+    if gas_used > call.gas_limit {
+        return failure(call.gas_limit, ContractError::OutOfGas { limit: call.gas_limit }.to_string());
+    }
+
+    match outcome {
+        Ok(outcome) => {
+            outcome.agreement.store(contract);
+            let output = contract_abi::encode(outputs, &outcome.return_values).expect("return values match the PPA ABI");
+            PpaExecution {
+                result: ContractExecutionResult::success(gas_used, output, outcome.return_values, outcome.events),
+                payments: outcome.payments,
+                deliveries: outcome.deliveries,
+            }
+        }
+        Err(e) => failure(gas_used, ContractError::Reverted(e.to_string()).to_string()),
+    }
+}
+
+/// Effects of a successful PPA call
+struct CallOutcome {
+    agreement: PowerPurchaseAgreement,
+    payments: Vec<Payment>,
+    deliveries: Vec<Delivery>,
+    events: Vec<ContractEvent>,
+    return_values: Vec<AbiValue>,
+    /// Delivery periods whose production was claimed
+    periods_settled: usize,
+}
+
+impl CallOutcome {
+    fn new(agreement: PowerPurchaseAgreement, payments: Vec<Payment>, event: ContractEvent) -> Self {
+        Self { agreement, payments, deliveries: vec![], events: vec![event], return_values: vec![], periods_settled: 0 }
+    }
+
+    /// Gas for writing the agreement back, claiming metered production, emitting events and
+    /// moving tokens
+    fn gas(&self, schedule: &GasSchedule) -> u64 {
+        let stored = encoding::encode(&self.agreement).map_or(0, |bytes| bytes.len()) + AGREEMENT_KEY.len();
+        let events: u64 = self.events.iter()
+            .map(|event| schedule.event + schedule.per_byte * event.data.len() as u64)
+            .sum();
+        schedule.storage_write
+            + schedule.storage_write_per_byte * stored as u64
+            + (schedule.storage_read + schedule.storage_write) * self.periods_settled as u64
+            + schedule.host_call * (self.payments.len() + self.deliveries.len()) as u64
+            + events
+    }
+}
+
+fn run(contract: &ContractState, call: ContractCall<'_>, now: u64, readings: &mut impl MeterReadings) -> Result<CallOutcome, PpaError> {
+    let event = |topic: &str, data: Vec<u8>| ContractEvent {
+        contract: call.address.clone(),
+        topic: topic.to_string(),
+        data,
+    };
+
+    let mut agreement = PowerPurchaseAgreement::from_contract(contract).ok_or(PpaError::MissingAgreement)?;
+    match call.entry_point {
+        "accept" => {
+            let bond = agreement.accept(call.caller, call.address, now)?;
+            Ok(CallOutcome::new(agreement, vec![bond], event("accepted", vec![])))
+        }
+        "cancel" => {
+            let refund = agreement.cancel(call.caller, call.address)?;
+            Ok(CallOutcome::new(agreement, vec![refund], event("cancelled", vec![])))
+        }
+        "settle" => {
+            let (settlements, payments) = agreement.settle(call.address, now, readings)?;
+            let mut events: Vec<ContractEvent> = settlements.iter()
+                .map(|settlement| {
+                    let values = [
+                        AbiValue::U32(settlement.period),
+                        AbiValue::U64(settlement.delivered.as_wh()),
+                        AbiValue::U64(settlement.shortfall.as_wh()),
+                        AbiValue::U128(settlement.payment),
+                        AbiValue::U128(settlement.penalty),
+                    ];
+                    let data = contract_abi::encode(&SETTLEMENT_TYPES, &values).expect("values match the event types");
+                    event("period_settled", data)
+                })
+                .collect();
+            if agreement.status == PpaStatus::Completed {
+                events.push(event("completed", vec![]));
+            }
+            let delivered = settlements.iter().fold(EnergyAmount::ZERO, |total, settlement| total.saturating_add(settlement.delivered));
+            let tokens = delivered.tokens().expect("production and volumes are whole kWh");
+            let deliveries = match tokens {
+                0 => vec![],
+                amount => vec![Delivery { from: agreement.terms.seller.clone(), to: agreement.buyer.clone(), amount }],
+            };
+            Ok(CallOutcome {
+                agreement,
+                payments,
+                deliveries,
+                events,
+                return_values: vec![AbiValue::U32(settlements.len() as u32)],
+                periods_settled: settlements.len(),
+            })
+        }
+        other => Err(PpaError::UnknownFunction(other.to_string())),
+    }
+}
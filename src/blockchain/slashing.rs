@@ -0,0 +1,201 @@
+//! # Slashing
+//!
+//! Evidence that an authority misbehaved, submitted by any node as a
+//! `ReportMisbehavior` transaction. Evidence is checked against the offender's registered
+//! public key before it may be included in a block. On the branch carrying the block the
+//! offender is deactivated, loses reputation and is dropped from the block production
+//! rotation.
+//!
+//! Like other changes to the authority set, slashing belongs to the branch that includes the
+//! evidence: a reorg away from it reinstates the offender, and the evidence stays valid for
+//! inclusion on the new chain.
+
+use crate::blockchain::consensus::{Authority, BlockHeader, ConsensusEngine, EnergyBlock};
+use crate::blockchain::transactions::EnergyTransactionEnvelope;
+use crate::types::{AccountId, FixedPoint};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Reputation lost for signing two blocks at one height
+pub const DOUBLE_SIGN_PENALTY: FixedPoint = FixedPoint::from_millionths(500_000);
+/// Reputation lost for producing a block in another authority's slot
+pub const OUT_OF_TURN_PENALTY: FixedPoint = FixedPoint::from_millionths(250_000);
+/// Reputation lost for signing a block that breaks the block rules
+pub const INVALID_BLOCK_PENALTY: FixedPoint = FixedPoint::from_millionths(500_000);
+
+/// Block header together with the producer's signature over its hash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedHeader {
+    pub header: BlockHeader,
+    pub signature: Vec<u8>,
+}
+
+/// Proof that an authority broke the consensus rules
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MisbehaviorEvidence {
+    /// Two different blocks signed for the same height
+    DoubleSign { first: Box<SignedHeader>, second: Box<SignedHeader> },
+    /// A block signed for a slot the including chain scheduled for another authority
+    OutOfTurn { block: SignedHeader },
+    /// A signed block whose body breaks rules that hold regardless of chain state
+    InvalidBlock { block: SignedHeader, transactions: Vec<EnergyTransactionEnvelope> },
+}
+
+/// Reason evidence cannot be included in a block
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum EvidenceRejection {
+    #[error("validator {0} is not a registered authority")]
+    UnknownAuthority(AccountId),
+    #[error("validator {0} has already been slashed")]
+    AlreadySlashed(AccountId),
+    #[error("validator {0} has no valid public key registered")]
+    MissingPublicKey(AccountId),
+    #[error("block hash does not match block contents")]
+    InvalidBlockHash,
+    #[error("validator signature is invalid")]
+    InvalidSignature,
+    #[error("blocks are not two different blocks of one validator at one height")]
+    NotConflicting,
+    #[error("block #{0} is not below the block including the evidence")]
+    UnknownSlot(u32),
+    #[error("block #{number} was produced in the slot of validator {validator}")]
+    InTurn { number: u32, validator: AccountId },
+    #[error("transactions do not match the block's transaction root")]
+    BodyMismatch,
+    #[error("block breaks no block rule")]
+    NoFault,
+}
+
+impl SignedHeader {
+    /// Take the header and producer signature of a block
+    pub fn from_block(block: &EnergyBlock) -> Self {
+        Self {
+            header: block.header.clone(),
+            signature: block.validator_signature.signature.clone(),
+        }
+    }
+
+    /// Check the header hash and the signature against the producer's registered key
+    fn verify(&self, authority: &Authority) -> Result<(), EvidenceRejection> {
+        if ConsensusEngine::calculate_header_hash(&self.header) != self.header.hash {
+            return Err(EvidenceRejection::InvalidBlockHash);
+        }
+
+        let public_key: [u8; 32] = authority.public_key.as_slice().try_into()
+            .map_err(|_| EvidenceRejection::MissingPublicKey(authority.account_id.clone()))?;
+        let verifying_key = VerifyingKey::from_bytes(&public_key)
+            .map_err(|_| EvidenceRejection::MissingPublicKey(authority.account_id.clone()))?;
+        let signature = Signature::from_slice(&self.signature)
+            .map_err(|_| EvidenceRejection::InvalidSignature)?;
+        let message = hex::decode(&self.header.hash)
+            .map_err(|_| EvidenceRejection::InvalidBlockHash)?;
+
+        verifying_key.verify(&message, &signature)
+            .map_err(|_| EvidenceRejection::InvalidSignature)
+    }
+}
+
+impl MisbehaviorEvidence {
+    /// Evidence that the producer of two blocks at the same height signed both
+    pub fn double_sign(first: &EnergyBlock, second: &EnergyBlock) -> Self {
+        Self::DoubleSign {
+            first: Box::new(SignedHeader::from_block(first)),
+            second: Box::new(SignedHeader::from_block(second)),
+        }
+    }
+
+    /// Evidence that a block was produced outside its producer's slot
+    pub fn out_of_turn(block: &EnergyBlock) -> Self {
+        Self::OutOfTurn { block: SignedHeader::from_block(block) }
+    }
+
+    /// Evidence that a block's body breaks the block rules
+    pub fn invalid_block(block: &EnergyBlock) -> Self {
+        Self::InvalidBlock {
+            block: SignedHeader::from_block(block),
+            transactions: block.transactions.clone(),
+        }
+    }
+
+    /// Authority the evidence is against
+    pub fn offender(&self) -> &AccountId {
+        match self {
+            Self::DoubleSign { first, .. } => &first.header.validator,
+            Self::OutOfTurn { block } | Self::InvalidBlock { block, .. } => &block.header.validator,
+        }
+    }
+
+    /// Reputation the offender loses
+    pub fn reputation_penalty(&self) -> FixedPoint {
+        match self {
+            Self::DoubleSign { .. } => DOUBLE_SIGN_PENALTY,
+            Self::OutOfTurn { .. } => OUT_OF_TURN_PENALTY,
+            Self::InvalidBlock { .. } => INVALID_BLOCK_PENALTY,
+        }
+    }
+
+    /// Check the evidence for inclusion in block `number`
+    ///
+    /// `ancestor` yields the header of the including chain's block at a given height below
+    /// `number`. Returns the offender on success.
+    pub fn verify<'a>(
+        &self,
+        number: u32,
+        authorities: &HashMap<AccountId, Authority>,
+        ancestor: impl Fn(u32) -> Option<&'a BlockHeader>,
+    ) -> Result<&AccountId, EvidenceRejection> {
+        let offender = self.offender();
+        let authority = authorities.get(offender)
+            .ok_or_else(|| EvidenceRejection::UnknownAuthority(offender.clone()))?;
+        if !authority.is_active {
+            return Err(EvidenceRejection::AlreadySlashed(offender.clone()));
+        }
+
+        match self {
+            Self::DoubleSign { first, second } => {
+                if first.header.number != second.header.number
+                    || first.header.validator != second.header.validator
+                    || first.header.hash == second.header.hash
+                {
+                    return Err(EvidenceRejection::NotConflicting);
+                }
+                first.verify(authority)?;
+                second.verify(authority)?;
+            }
+            Self::OutOfTurn { block } => {
+                block.verify(authority)?;
+                let header = &block.header;
+                let slot = header.number.checked_sub(1)
+                    .filter(|_| header.number < number)
+                    .and_then(|parent| Some((ancestor(parent)?, ancestor(header.number)?)))
+                    .filter(|(parent, _)| parent.hash == header.parent_hash)
+                    .map(|(_, slot)| slot)
+                    .ok_or(EvidenceRejection::UnknownSlot(header.number))?;
+
+                // Rotation is fixed by the parent, so the including chain's block at this
+                // height was made by the authority scheduled for the slot
+                if slot.validator == header.validator {
+                    return Err(EvidenceRejection::InTurn { number: header.number, validator: slot.validator.clone() });
+                }
+            }
+            Self::InvalidBlock { block, transactions } => {
+                block.verify(authority)?;
+                if ConsensusEngine::calculate_merkle_root(transactions) != block.header.transaction_root {
+                    return Err(EvidenceRejection::BodyMismatch);
+                }
+
+                let mut seen = HashSet::new();
+                let fault = transactions.iter().any(|tx| {
+                    tx.get_gas_cost() > tx.gas_limit || !seen.insert(tx.hash)
+                });
+                if !fault {
+                    return Err(EvidenceRejection::NoFault);
+                }
+            }
+        }
+
+        Ok(offender)
+    }
+}
+
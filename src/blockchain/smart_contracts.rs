@@ -1,41 +1,62 @@
 //! # Smart Contract Module
 //! 
-//! Simple smart contract execution environment for energy trading.
+//! Smart contract execution environment for energy trading.
+//!
+//! Contracts are WebAssembly modules executed in the sandboxed [`ContractRuntime`]; see
+//! [`contract_runtime`](crate::blockchain::contract_runtime) for the host interface they are
+//! written against. Function arguments and return values are typed by the contract's ABI
+//! and encoded as described in [`contract_abi`](crate::blockchain::contract_abi).
+//!
+//! The built-in [power purchase agreement](crate::blockchain::ppa_contract) template is
+//! deployed like any other contract but executed natively by the state transition.
 
+use crate::blockchain::contract_abi::{self, AbiType, AbiValue};
+use crate::blockchain::contract_runtime::{ContractCall, ContractError, ContractRuntime, ExecutionContext, DEPLOY_ENTRY_POINT};
+use crate::blockchain::contract_runtime::GasSchedule;
+use crate::blockchain::encoding;
+use crate::blockchain::ppa_contract::{ppa_abi, PPA_CONTRACT_CODE};
+use crate::config::SmartContractConfig;
 use crate::types::*;
-use crate::utils::SystemResult;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tokio::sync::RwLock;
-use std::sync::Arc;
 
-/// Simple smart contract VM
+/// Smart contract VM
+///
+/// The VM holds no contract state of its own: contracts live in the account state of their
+/// address, so deployments and calls are executed by the state transition like any other
+/// transaction and their storage is covered by the state root.
 pub struct SmartContractVM {
     gas_limit: u64,
-    contracts: Arc<RwLock<HashMap<AccountId, ContractState>>>,
+    runtime: ContractRuntime,
 }
 
 /// Contract execution state
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ContractState {
     pub code: Vec<u8>,
     pub abi: ContractABI,
     pub storage: HashMap<String, Vec<u8>>,
-    pub balance: Balance,
 }
 
 /// Contract ABI definition
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ContractABI {
     pub functions: Vec<ContractFunction>,
 }
 
 /// Contract function definition
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ContractFunction {
     pub name: String,
-    pub inputs: Vec<String>,
-    pub outputs: Vec<String>,
+    pub inputs: Vec<AbiType>,
+    pub outputs: Vec<AbiType>,
+}
+
+impl ContractABI {
+    /// Look up a function by name
+    pub fn function(&self, name: &str) -> Option<&ContractFunction> {
+        self.functions.iter().find(|function| function.name == name)
+    }
 }
 
 /// Contract execution result
@@ -44,82 +65,174 @@ pub struct ContractExecutionResult {
     pub success: bool,
     pub gas_used: u64,
     pub output: Vec<u8>,
+    /// Output decoded with the function's ABI, empty if the call failed
+    pub return_values: Vec<AbiValue>,
+    pub events: Vec<ContractEvent>,
     pub error: Option<String>,
 }
 
+impl ContractExecutionResult {
+    pub(crate) fn success(gas_used: u64, output: Vec<u8>, return_values: Vec<AbiValue>, events: Vec<ContractEvent>) -> Self {
+        Self {
+            success: true,
+            gas_used,
+            output,
+            return_values,
+            events,
+            error: None,
+        }
+    }
+
+    pub(crate) fn failure(gas_used: u64, error: String) -> Self {
+        Self {
+            success: false,
+            gas_used,
+            output: vec![],
+            return_values: vec![],
+            events: vec![],
+            error: Some(error),
+        }
+    }
+}
+
+/// Event emitted by a contract during a call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractEvent {
+    /// Address of the emitting contract
+    pub contract: AccountId,
+    pub topic: String,
+    pub data: Vec<u8>,
+}
+
+impl std::fmt::Debug for SmartContractVM {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SmartContractVM")
+            .field("gas_limit", &self.gas_limit)
+            .finish_non_exhaustive()
+    }
+}
+
 impl SmartContractVM {
-    pub fn new(gas_limit: u64) -> Self {
+    pub fn new(config: &SmartContractConfig) -> Self {
         Self {
-            gas_limit,
-            contracts: Arc::new(RwLock::new(HashMap::new())),
+            gas_limit: config.max_gas_limit,
+            runtime: ContractRuntime::new(config),
         }
     }
 
-    pub async fn deploy_contract(
+    /// Most gas this node lets a read-only contract call use
+    pub fn max_gas_limit(&self) -> u64 {
+        self.gas_limit
+    }
+
+    /// Gas schedule contract execution is charged by
+    pub fn gas_schedule(&self) -> &GasSchedule {
+        self.runtime.gas_schedule()
+    }
+
+    /// Address of the contract `deployer` creates with the transaction of the given nonce
+    ///
+    /// Every deployment gets its own address, since a sender never reuses a nonce.
+    pub fn contract_address(deployer: &AccountId, nonce: u64) -> AccountId {
+        let hash = encoding::hash(&(deployer, nonce)).expect("account IDs and nonces always encode");
+        format!("contract_{}", hex::encode(&hash[..16]))
+    }
+
+    /// Check that a module can be deployed with the given ABI
+    ///
+    /// The module must be runnable by the contract runtime and export every function of its ABI.
+    /// The PPA template must be deployed with its own ABI.
+    pub fn check_code(&self, code: &[u8], abi: &ContractABI) -> Result<(), ContractError> {
+        if code == PPA_CONTRACT_CODE {
+            if *abi != ppa_abi() {
+                return Err(ContractError::InvalidModule("PPA template deployed with a different ABI".to_string()));
+            }
+            return Ok(());
+        }
+        let entry_points: Vec<&str> = abi.functions.iter().map(|function| function.name.as_str()).collect();
+        self.runtime.validate(code, &entry_points)
+    }
+
+    /// Create a contract from a module, running its constructor with the constructor arguments
+    ///
+    /// `constructor` calls the constructor export, normally [`DEPLOY_ENTRY_POINT`], from the
+    /// deployer at the new contract's address; modules without that export need no
+    /// constructor. Returns the constructor's result together with the new contract state,
+    /// which is `None` if the module is rejected or the constructor fails.
+    ///
+    /// [`DEPLOY_ENTRY_POINT`]: crate::blockchain::contract_runtime::DEPLOY_ENTRY_POINT
+    pub fn deploy_contract(
         &self,
-        deployer: AccountId,
         code: Vec<u8>,
         abi: ContractABI,
-        _constructor_args: Vec<u8>,
-    ) -> SystemResult<AccountId> {
-        let contract_address = format!("contract_{}", deployer);
-        
-        let contract_state = ContractState {
+        constructor: ContractCall<'_>,
+        context: &ExecutionContext,
+    ) -> (ContractExecutionResult, Option<ContractState>) {
+        if let Err(e) = self.check_code(&code, &abi) {
+            return (ContractExecutionResult::failure(0, format!("Contract rejected: {}", e)), None);
+        }
+
+        let mut contract = ContractState {
             code,
             abi,
             storage: HashMap::new(),
-            balance: 0,
         };
+        if !self.runtime.exports_function(&contract.code, constructor.entry_point) {
+            return (ContractExecutionResult::success(0, vec![], vec![], vec![]), Some(contract));
+        }
 
-        let mut contracts = self.contracts.write().await;
-        contracts.insert(contract_address.clone(), contract_state);
-
-        Ok(contract_address)
+        let execution = self.runtime.execute(&contract, constructor, context);
+        match execution.outcome {
+            Ok(outcome) => {
+                contract.storage = outcome.storage;
+                (ContractExecutionResult::success(execution.gas_used, outcome.output, vec![], outcome.events), Some(contract))
+            }
+            Err(e) => {
+                let error = format!("Contract constructor failed: {}", e);
+                (ContractExecutionResult::failure(execution.gas_used, error), None)
+            }
+        }
     }
 
-    pub async fn execute_contract(
+    /// Call the ABI function `call.entry_point` of a contract
+    ///
+    /// The input must decode as the function's inputs, and the call fails if its output does
+    /// not decode as the function's outputs. The call may use up to `call.gas_limit` gas.
+    /// Storage changes are written to `contract` only if the call succeeds; a call running
+    /// out of gas uses all of its gas. The constructor cannot be called again once deployed.
+    pub fn execute_contract(
         &self,
-        _caller: AccountId,
-        contract_address: AccountId,
-        function_name: String,
-        _args: Vec<u8>,
-        gas_limit: u64,
-    ) -> SystemResult<ContractExecutionResult> {
-        let contracts = self.contracts.read().await;
-        
-        if let Some(_contract) = contracts.get(&contract_address) {
-            // Simple execution simulation
-            let gas_used = std::cmp::min(gas_limit, self.gas_limit / 2);
-            
-            let result = match function_name.as_str() {
-                "get_balance" => ContractExecutionResult {
-                    success: true,
-                    gas_used,
-                    output: "1000".as_bytes().to_vec(),
-                    error: None,
-                },
-                "transfer" => ContractExecutionResult {
-                    success: true,
-                    gas_used,
-                    output: "true".as_bytes().to_vec(),
-                    error: None,
-                },
-                _ => ContractExecutionResult {
-                    success: false,
-                    gas_used,
-                    output: vec![],
-                    error: Some(format!("Function {} not found", function_name)),
-                },
-            };
-
-            Ok(result)
-        } else {
-            Ok(ContractExecutionResult {
-                success: false,
-                gas_used: 0,
-                output: vec![],
-                error: Some("Contract not found".to_string()),
-            })
+        contract: &mut ContractState,
+        call: ContractCall<'_>,
+        context: &ExecutionContext,
+    ) -> ContractExecutionResult {
+        if contract.code == PPA_CONTRACT_CODE {
+            return ContractExecutionResult::failure(0, "PPA contracts are only called through transactions".to_string());
+        }
+        if call.entry_point == DEPLOY_ENTRY_POINT {
+            return ContractExecutionResult::failure(0, format!("The {} constructor runs only when the contract is deployed", DEPLOY_ENTRY_POINT));
+        }
+        let Some(function) = contract.abi.function(call.entry_point) else {
+            return ContractExecutionResult::failure(0, format!("Function {} not found", call.entry_point));
+        };
+        if let Err(e) = contract_abi::decode(&function.inputs, call.input) {
+            return ContractExecutionResult::failure(0, format!("Invalid arguments for {}: {}", function.name, e));
+        }
+
+        let execution = self.runtime.execute(contract, call, context);
+        let outcome = match execution.outcome {
+            Ok(outcome) => outcome,
+            Err(e) => return ContractExecutionResult::failure(execution.gas_used, e.to_string()),
+        };
+        match contract_abi::decode(&function.outputs, &outcome.output) {
+            Ok(return_values) => {
+                contract.storage = outcome.storage;
+                ContractExecutionResult::success(execution.gas_used, outcome.output, return_values, outcome.events)
+            }
+            Err(e) => {
+                let error = format!("Invalid return value from {}: {}", function.name, e);
+                ContractExecutionResult::failure(execution.gas_used, error)
+            }
         }
     }
 }
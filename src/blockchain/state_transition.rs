@@ -0,0 +1,615 @@
+//! # State Transition Function
+//!
+//! Deterministically applies energy transactions to account state. The same code path
+//! is used when producing and when importing blocks, so every node that executes a block
+//! arrives at the same balances and state root.
+//!
+//! Contract deployments and calls run in the contract VM as part of the transition. The
+//! contract's code and storage are kept in the account state of its address, so a failed
+//! or out-of-gas call is discarded together with the rest of the transaction's effects.
+//! Contracts created from the built-in PPA template are executed natively; the payments they
+//! make move satang like any other and settle against the production reported in each block,
+//! whose tokens move from the seller to the buyer.
+//!
+//! Only production attested by a metering oracle of the chain spec is minted, and only the
+//! spec's carbon credit issuers may issue credits. A trade settles only at the price its
+//! terms work out to and only with the seller's signature over those terms.
+//!
+//! Energy tokens are worth one kWh each. Amounts that are not a whole number of kWh are
+//! rejected rather than rounded, and trades are paid for out of the separate currency
+//! balance in satang.
+
+use crate::blockchain::contract_runtime::{AccountBalances, ContractCall, ExecutionContext, DEPLOY_ENTRY_POINT};
+use crate::blockchain::ppa_contract::{self, MeterReadings, PpaExecution, PPA_CONTRACT_CODE};
+use crate::blockchain::smart_contracts::{ContractEvent, ContractExecutionResult, ContractState, SmartContractVM};
+use crate::blockchain::transactions::{
+    self, EnergyBalanceState, EnergyProductionRecord, EnergyTransaction, EnergyTransactionEnvelope,
+    MeteredProduction, SignatureRejection, StorageAction, ValidatorSignature,
+};
+use crate::blockchain::validator_set::ValidatorCandidate;
+use crate::types::*;
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Block-level context transactions are executed in
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockContext {
+    /// Number of the block being built or imported
+    pub number: u32,
+    /// Block timestamp, used instead of wall-clock time for state updates
+    pub timestamp: u64,
+    /// Block producer, credited with transaction fees
+    pub validator: AccountId,
+    /// Parties the chain spec trusts to attest production and issue carbon credits
+    pub trusted: Arc<TrustedParties>,
+}
+
+/// Accounts the chain trusts to vouch for what it cannot check itself
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrustedParties {
+    /// Metering oracles and the keys their production attestations verify against
+    pub oracles: HashMap<AccountId, VerifyingKey>,
+    /// Accounts allowed to issue carbon credits
+    pub carbon_credit_issuers: HashSet<AccountId>,
+}
+
+impl TrustedParties {
+    /// Whether a registered oracle attests that `producer` produced `record`
+    pub fn attests(&self, producer: &AccountId, record: &EnergyProductionRecord, attestations: &[ValidatorSignature]) -> bool {
+        let Ok(attestation_hash) = record.attestation_hash(producer) else {
+            return false;
+        };
+        attestations.iter().any(|attestation| {
+            let (Some(key), Ok(signature)) = (self.oracles.get(&attestation.validator), Signature::from_slice(&attestation.signature)) else {
+                return false;
+            };
+            key.verify_strict(&attestation_hash, &signature).is_ok()
+        })
+    }
+}
+
+/// Execution status of an included transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReceiptStatus {
+    /// Transaction effects were applied
+    Success,
+    /// Transaction was included and charged, but its effects were reverted
+    Failed,
+}
+
+/// Result of executing a single transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionReceipt {
+    /// Hash of the executed transaction
+    pub transaction_hash: [u8; 32],
+    /// Execution status
+    pub status: ReceiptStatus,
+    /// Gas consumed
+    pub gas_used: u64,
+    /// Fee debited from the sender
+    pub fee_charged: Balance,
+    /// Events emitted while executing the transaction, empty if it failed
+    pub events: Vec<TransactionEvent>,
+    /// Reason the transaction failed, if it did
+    pub error: Option<String>,
+}
+
+/// Event emitted by a successfully executed transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TransactionEvent {
+    /// Tokens moved between two accounts
+    TokensTransferred { from: AccountId, to: AccountId, energy_type: EnergySource, amount: Balance },
+    /// Satang paid from one account to another
+    PaymentMade { from: AccountId, to: AccountId, amount: Balance },
+    /// New tokens created from verified production
+    TokensMinted { account: AccountId, energy_type: EnergySource, amount: Balance },
+    /// Tokens destroyed by consumption
+    TokensBurned { account: AccountId, amount: Balance },
+    /// Tokens locked in storage
+    TokensLocked { account: AccountId, amount: Balance },
+    /// Tokens released from storage
+    TokensUnlocked { account: AccountId, amount: Balance },
+    /// Tokens staked behind a governance proposal
+    TokensStaked { account: AccountId, amount: Balance },
+    /// Both legs of a matched trade were settled
+    TradeSettled { trade_id: String, buyer: AccountId, seller: AccountId, energy_amount: Balance, total_price: Balance },
+    /// Order recorded on-chain
+    OrderPlaced { order_id: uuid::Uuid, trader: AccountId },
+    /// Order withdrawn by its trader
+    OrderCancelled { order_id: uuid::Uuid, trader: AccountId },
+    /// Carbon credits issued to an account
+    CarbonCreditsIssued { issuer: AccountId, recipient: AccountId, credits: u32 },
+    /// Account registered as an energy producer
+    ProducerRegistered { producer: AccountId },
+    /// Account registered as an energy consumer
+    ConsumerRegistered { consumer: AccountId },
+    /// Governance proposal submitted
+    ProposalSubmitted { proposal_id: uuid::Uuid, proposer: AccountId },
+    /// Vote cast on a governance proposal
+    VoteCast { proposal_id: uuid::Uuid, voter: AccountId },
+    /// Evidence against an authority was accepted; the consensus engine slashes it
+    MisbehaviorReported { reporter: AccountId, offender: AccountId },
+    /// Contract created at `contract`
+    ContractDeployed { deployer: AccountId, contract: AccountId },
+    /// Event emitted by contract code
+    ContractEventEmitted { contract: AccountId, topic: String, data: Vec<u8> },
+}
+
+impl From<ContractEvent> for TransactionEvent {
+    fn from(event: ContractEvent) -> Self {
+        TransactionEvent::ContractEventEmitted { contract: event.contract, topic: event.topic, data: event.data }
+    }
+}
+
+/// Reason a transaction cannot be included in a block at all
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum TransactionRejection {
+    #[error("invalid signature: {0}")]
+    Signature(#[from] SignatureRejection),
+    #[error("invalid nonce: expected {expected}, got {found}")]
+    InvalidNonce { expected: u64, found: u64 },
+    #[error("gas limit {limit} is below the intrinsic cost {required}")]
+    IntrinsicGasTooLow { required: u64, limit: u64 },
+    #[error("sender cannot pay fee {required}, available {available}")]
+    InsufficientFee { required: Balance, available: Balance },
+}
+
+/// Copy-on-write view over account balances, so failed transactions can be discarded
+struct StateOverlay<'a> {
+    base: &'a HashMap<AccountId, EnergyBalanceState>,
+    changes: HashMap<AccountId, EnergyBalanceState>,
+}
+
+impl<'a> StateOverlay<'a> {
+    fn new(base: &'a HashMap<AccountId, EnergyBalanceState>) -> Self {
+        Self::with_changes(base, HashMap::new())
+    }
+
+    fn with_changes(base: &'a HashMap<AccountId, EnergyBalanceState>, changes: HashMap<AccountId, EnergyBalanceState>) -> Self {
+        Self { base, changes }
+    }
+
+    fn account(&mut self, account: &AccountId) -> &mut EnergyBalanceState {
+        let base = self.base;
+        self.changes.entry(account.clone()).or_insert_with(|| {
+            base.get(account).cloned().unwrap_or_else(EnergyBalanceState::new)
+        })
+    }
+
+    fn get(&self, account: &AccountId) -> Option<&EnergyBalanceState> {
+        self.changes.get(account).or_else(|| self.base.get(account))
+    }
+
+    fn into_changes(self) -> HashMap<AccountId, EnergyBalanceState> {
+        self.changes
+    }
+}
+
+impl AccountBalances for StateOverlay<'_> {
+    fn available_balance(&self, account: &AccountId) -> Balance {
+        self.get(account).map_or(0, EnergyBalanceState::get_total_available_balance)
+    }
+}
+
+impl MeterReadings for StateOverlay<'_> {
+    fn claim_production(&mut self, account: &AccountId, from: u64, to: u64, limit: EnergyAmount) -> EnergyAmount {
+        if self.get(account).is_none() {
+            return EnergyAmount::ZERO;
+        }
+        self.account(account).claim_production(from, to, limit)
+    }
+
+    fn available_energy(&self, account: &AccountId) -> EnergyAmount {
+        let tokens = self.get(account).map_or(0, EnergyBalanceState::get_total_available_balance);
+        let kwh = u64::try_from(tokens).unwrap_or(u64::MAX).min(u64::MAX / WH_PER_KWH);
+        EnergyAmount::from_kwh(kwh).expect("capped to a representable amount")
+    }
+}
+
+/// Contract VM a transaction runs contract code in, and the gas that code may use
+struct ContractEnv<'a> {
+    vm: &'a SmartContractVM,
+    block: &'a BlockContext,
+    /// Nonce of the transaction, which contract addresses are derived from
+    nonce: u64,
+    gas_limit: u64,
+    gas_used: u64,
+}
+
+impl ContractEnv<'_> {
+    /// Context of a contract call reading balances, including the changes made so far, from `overlay`
+    fn context<'b>(&self, overlay: &'b StateOverlay) -> ExecutionContext<'b> {
+        ExecutionContext {
+            block_number: self.block.number,
+            timestamp: self.block.timestamp,
+            balances: overlay,
+        }
+    }
+
+    /// Make the payments and deliveries a PPA call requests, failing if the call failed
+    ///
+    /// Delivered tokens are taken from the seller's energy sources in canonical order.
+    fn pay_ppa(
+        &mut self,
+        overlay: &mut StateOverlay,
+        execution: PpaExecution,
+        events: &mut Vec<TransactionEvent>,
+    ) -> Result<ContractExecutionResult, String> {
+        self.gas_used = execution.result.gas_used;
+        if !execution.result.success {
+            return Err(execution.result.error.unwrap_or_default());
+        }
+        for payment in execution.payments.into_iter().filter(|payment| payment.amount > 0) {
+            overlay.account(&payment.from).pay(payment.amount).map_err(|e| e.to_string())?;
+            overlay.account(&payment.to).receive_payment(payment.amount);
+            events.push(TransactionEvent::PaymentMade { from: payment.from, to: payment.to, amount: payment.amount });
+        }
+        for delivery in execution.deliveries {
+            let mut remaining = delivery.amount;
+            for energy_type in EnergySource::ALL.iter() {
+                let amount = overlay.account(&delivery.from).get_available_balance(energy_type).min(remaining);
+                if amount == 0 {
+                    continue;
+                }
+                overlay.account(&delivery.from).transfer(energy_type.clone(), amount).map_err(|e| e.to_string())?;
+                overlay.account(&delivery.to).receive(energy_type.clone(), amount);
+                events.push(TransactionEvent::TokensTransferred {
+                    from: delivery.from.clone(),
+                    to: delivery.to.clone(),
+                    energy_type: energy_type.clone(),
+                    amount,
+                });
+                remaining -= amount;
+            }
+            if remaining > 0 {
+                return Err(format!("{} cannot deliver {} more tokens", delivery.from, remaining));
+            }
+        }
+        Ok(execution.result)
+    }
+}
+
+/// Apply every transaction of a block, failing on the first one that cannot be included
+pub fn apply_transactions(
+    balances: &mut HashMap<AccountId, EnergyBalanceState>,
+    transactions: &[EnergyTransactionEnvelope],
+    context: &BlockContext,
+    contracts: &SmartContractVM,
+) -> Result<Vec<TransactionReceipt>, (usize, TransactionRejection)> {
+    transactions.iter()
+        .enumerate()
+        .map(|(index, tx)| apply_transaction(balances, tx, context, contracts).map_err(|reason| (index, reason)))
+        .collect()
+}
+
+/// Apply a single transaction
+///
+/// Signature, nonce, intrinsic gas and fee checks decide whether the transaction may be
+/// included.
+/// Once included, the fee is always charged and the nonce advanced; the transaction's own
+/// effects are only committed if they succeed.
+///
+/// Contract code may use the gas left by the intrinsic cost, up to the contract gas limit
+/// the transaction names. The sender must be able to pay for all of it up front and is
+/// refunded whatever the code does not use.
+pub fn apply_transaction(
+    balances: &mut HashMap<AccountId, EnergyBalanceState>,
+    tx: &EnergyTransactionEnvelope,
+    context: &BlockContext,
+    contracts: &SmartContractVM,
+) -> Result<TransactionReceipt, TransactionRejection> {
+    tx.check_signature()?;
+    let sender = tx.get_sender();
+    let intrinsic_gas = tx.get_gas_cost();
+
+    if intrinsic_gas > tx.gas_limit {
+        return Err(TransactionRejection::IntrinsicGasTooLow { required: intrinsic_gas, limit: tx.gas_limit });
+    }
+    let contract_gas_limit = contract_gas_limit(&tx.transaction).min(tx.gas_limit - intrinsic_gas);
+    let max_gas = intrinsic_gas + contract_gas_limit;
+
+    let (expected_nonce, available) = balances.get(&sender)
+        .map(|state| (state.nonce, state.get_total_available_balance()))
+        .unwrap_or((0, 0));
+
+    if tx.nonce != expected_nonce {
+        return Err(TransactionRejection::InvalidNonce { expected: expected_nonce, found: tx.nonce });
+    }
+
+    let max_fee = (max_gas as Balance).checked_mul(tx.gas_price)
+        .filter(|fee| *fee <= available)
+        .ok_or(TransactionRejection::InsufficientFee {
+            required: (max_gas as Balance).saturating_mul(tx.gas_price),
+            available,
+        })?;
+
+    // Charge the maximum fee and advance the nonce; these stick even if execution fails
+    let mut overlay = StateOverlay::new(balances);
+    let sender_state = overlay.account(&sender);
+    sender_state.spend_any(max_fee)
+        .map_err(|_| TransactionRejection::InsufficientFee { required: max_fee, available })?;
+    sender_state.increment_nonce();
+    let charged = overlay.into_changes();
+
+    // Execute the transaction body on top of the charged state
+    let mut overlay = StateOverlay::with_changes(balances, charged.clone());
+    let mut contract_env = ContractEnv {
+        vm: contracts,
+        block: context,
+        nonce: tx.nonce,
+        gas_limit: contract_gas_limit,
+        gas_used: 0,
+    };
+    let mut events = Vec::new();
+    let (changes, status, error) = match execute(&mut overlay, &tx.transaction, &mut contract_env, &mut events) {
+        Ok(()) => (overlay.into_changes(), ReceiptStatus::Success, None),
+        Err(reason) => {
+            events.clear();
+            (charged, ReceiptStatus::Failed, Some(reason))
+        }
+    };
+
+    // Pay the block producer for the gas used and refund the rest of the maximum fee
+    let gas_used = intrinsic_gas + contract_env.gas_used;
+    let fee = gas_used as Balance * tx.gas_price;
+    let mut overlay = StateOverlay::with_changes(balances, changes);
+    if fee < max_fee {
+        overlay.account(&sender).receive(EnergySource::Mixed, max_fee - fee);
+    }
+    overlay.account(&context.validator).receive(EnergySource::Mixed, fee);
+    let changes = overlay.into_changes();
+
+    // Stamp touched accounts with block time so state is independent of the executing node
+    let block_time = UNIX_EPOCH + Duration::from_secs(context.timestamp);
+    for (account, mut state) in changes {
+        state.last_updated = block_time;
+        balances.insert(account, state);
+    }
+
+    Ok(TransactionReceipt {
+        transaction_hash: tx.hash,
+        status,
+        gas_used,
+        fee_charged: fee,
+        events,
+        error,
+    })
+}
+
+/// Gas the contract code run by a transaction may use at most
+fn contract_gas_limit(transaction: &EnergyTransaction) -> u64 {
+    match transaction {
+        EnergyTransaction::DeployContract { gas_limit, .. } | EnergyTransaction::ExecuteContract { gas_limit, .. } => *gas_limit,
+        _ => 0,
+    }
+}
+
+/// Execute the transaction-specific effects
+fn execute(
+    overlay: &mut StateOverlay,
+    transaction: &EnergyTransaction,
+    contracts: &mut ContractEnv,
+    events: &mut Vec<TransactionEvent>,
+) -> Result<(), String> {
+    match transaction {
+        EnergyTransaction::Transfer { from, to, amount, energy_type, .. } => {
+            if from == to {
+                return Err("Cannot transfer to self".to_string());
+            }
+            if *amount == 0 {
+                return Err("Transfer amount cannot be zero".to_string());
+            }
+            overlay.account(from).transfer(energy_type.clone(), *amount).map_err(|e| e.to_string())?;
+            overlay.account(to).receive(energy_type.clone(), *amount);
+            events.push(TransactionEvent::TokensTransferred {
+                from: from.clone(),
+                to: to.clone(),
+                energy_type: energy_type.clone(),
+                amount: *amount,
+            });
+        }
+        EnergyTransaction::ExecuteTrade { trade, seller_signature, .. } => {
+            if trade.buyer_id == trade.seller_id {
+                return Err("Buyer and seller must differ".to_string());
+            }
+            let energy = transactions::whole_tokens(trade.energy_amount).map_err(|e| e.to_string())?;
+            if energy == 0 {
+                return Err("Trade energy amount cannot be zero".to_string());
+            }
+            let total_price = trade.price_per_unit.total(trade.energy_amount);
+            if trade.total_price != total_price {
+                return Err(format!(
+                    "Total price of {} satang does not match {} at {}, which costs {} satang",
+                    trade.total_price, trade.energy_amount, trade.price_per_unit, total_price
+                ));
+            }
+            transactions::check_trade_signature(trade, seller_signature)
+                .map_err(|e| format!("Seller has not agreed to the trade: {}", e))?;
+
+            // Energy leg: seller delivers source-tagged energy tokens to the buyer
+            overlay.account(&trade.seller_id).transfer(trade.energy_source.clone(), energy).map_err(|e| e.to_string())?;
+            overlay.account(&trade.buyer_id).receive(trade.energy_source.clone(), energy);
+
+            // Payment leg: buyer pays the agreed total price in satang
+            overlay.account(&trade.buyer_id).pay(trade.total_price).map_err(|e| e.to_string())?;
+            overlay.account(&trade.seller_id).receive_payment(trade.total_price);
+            events.push(TransactionEvent::TradeSettled {
+                trade_id: trade.trade_id.clone(),
+                buyer: trade.buyer_id.clone(),
+                seller: trade.seller_id.clone(),
+                energy_amount: energy,
+                total_price: trade.total_price,
+            });
+        }
+        EnergyTransaction::ReportProduction { producer, production_record, validator_signatures } => {
+            if !production_record.verified {
+                return Err("Production record not verified".to_string());
+            }
+            if !contracts.block.trusted.attests(producer, production_record, validator_signatures) {
+                return Err("Production record is not attested by a registered metering oracle".to_string());
+            }
+            if production_record.amount.is_zero() {
+                return Err("Production amount cannot be zero".to_string());
+            }
+            let producer_state = overlay.account(producer);
+            let amount = producer_state.add_production(production_record.clone()).map_err(|e| e.to_string())?;
+            producer_state.metered_production.push(MeteredProduction {
+                reported_at: contracts.block.timestamp,
+                amount: production_record.amount,
+                claimed: EnergyAmount::ZERO,
+            });
+            events.push(TransactionEvent::TokensMinted {
+                account: producer.clone(),
+                energy_type: production_record.energy_type.clone(),
+                amount,
+            });
+        }
+        EnergyTransaction::ReportConsumption { consumer, consumption_record, .. } => {
+            let amount = transactions::whole_tokens(consumption_record.amount).map_err(|e| e.to_string())?;
+            let consumer_state = overlay.account(consumer);
+            consumer_state.spend_any(amount).map_err(|e| e.to_string())?;
+            consumer_state.consumption_history.push(consumption_record.clone());
+            events.push(TransactionEvent::TokensBurned { account: consumer.clone(), amount });
+        }
+        EnergyTransaction::GovernanceProposal { proposer, proposal, stake_amount, .. } => {
+            if let Some(candidate) = ValidatorCandidate::proposed(&proposal.proposal_type) {
+                candidate.verify().map_err(|e| e.to_string())?;
+            }
+            let proposer_state = overlay.account(proposer);
+            proposer_state.spend_any(*stake_amount).map_err(|e| e.to_string())?;
+            proposer_state.staked_balance = proposer_state.staked_balance.saturating_add(*stake_amount);
+            events.push(TransactionEvent::TokensStaked { account: proposer.clone(), amount: *stake_amount });
+            events.push(TransactionEvent::ProposalSubmitted { proposal_id: proposal.id, proposer: proposer.clone() });
+        }
+        EnergyTransaction::CarbonCredit { issuer, recipient, credits, .. } => {
+            if !contracts.block.trusted.carbon_credit_issuers.contains(issuer) {
+                return Err(format!("{} is not a registered carbon credit issuer", issuer));
+            }
+            if *credits == 0 {
+                return Err("Carbon credit amount cannot be zero".to_string());
+            }
+            if issuer == recipient {
+                return Err("Issuer cannot credit itself".to_string());
+            }
+            let recipient_state = overlay.account(recipient);
+            recipient_state.carbon_credits = recipient_state.carbon_credits.checked_add(*credits)
+                .ok_or_else(|| "Carbon credit overflow".to_string())?;
+            events.push(TransactionEvent::CarbonCreditsIssued {
+                issuer: issuer.clone(),
+                recipient: recipient.clone(),
+                credits: *credits,
+            });
+        }
+        EnergyTransaction::EnergyStorage { storage_operator, action, amount, .. } => {
+            let amount = transactions::whole_tokens(*amount).map_err(|e| e.to_string())?;
+            let operator_state = overlay.account(storage_operator);
+            match action {
+                StorageAction::Store { .. } => {
+                    operator_state.lock_any(amount).map_err(|e| e.to_string())?;
+                    events.push(TransactionEvent::TokensLocked { account: storage_operator.clone(), amount });
+                }
+                StorageAction::Release => {
+                    operator_state.unlock_any(amount).map_err(|e| e.to_string())?;
+                    events.push(TransactionEvent::TokensUnlocked { account: storage_operator.clone(), amount });
+                }
+                StorageAction::Reserve { .. } => {
+                    if operator_state.get_total_available_balance() < amount {
+                        return Err("Insufficient balance to reserve storage".to_string());
+                    }
+                }
+            }
+        }
+        EnergyTransaction::RegisterProducer { producer, capacity, energy_types, .. } => {
+            if capacity.is_zero() {
+                return Err("Producer capacity must be positive".to_string());
+            }
+            if energy_types.is_empty() {
+                return Err("Producer must declare at least one energy type".to_string());
+            }
+            events.push(TransactionEvent::ProducerRegistered { producer: producer.clone() });
+        }
+        EnergyTransaction::RegisterConsumer { consumer, consumption_pattern, .. } => {
+            if consumption_pattern.max_consumption < consumption_pattern.average_consumption {
+                return Err("Maximum consumption cannot be below average consumption".to_string());
+            }
+            events.push(TransactionEvent::ConsumerRegistered { consumer: consumer.clone() });
+        }
+        // Orders and votes are tracked by their own subsystems; on-chain they only
+        // pay fees, advance the sender nonce and leave an event behind.
+        EnergyTransaction::PlaceOrder { trader, order, .. } => {
+            events.push(TransactionEvent::OrderPlaced { order_id: order.id, trader: trader.clone() });
+        }
+        EnergyTransaction::CancelOrder { trader, order_id, .. } => {
+            events.push(TransactionEvent::OrderCancelled { order_id: *order_id, trader: trader.clone() });
+        }
+        EnergyTransaction::Vote { voter, proposal_id, .. } => {
+            events.push(TransactionEvent::VoteCast { proposal_id: *proposal_id, voter: voter.clone() });
+        }
+        EnergyTransaction::ReportMisbehavior { reporter, evidence } => {
+            events.push(TransactionEvent::MisbehaviorReported {
+                reporter: reporter.clone(),
+                offender: evidence.offender().clone(),
+            });
+        }
+        EnergyTransaction::DeployContract { deployer, contract_code, abi, constructor_args, .. } => {
+            let address = SmartContractVM::contract_address(deployer, contracts.nonce);
+            if overlay.get(&address).is_some_and(|account| account.contract.is_some()) {
+                return Err(format!("Contract already deployed at {}", address));
+            }
+            let constructor = ContractCall {
+                address: &address,
+                caller: deployer,
+                entry_point: DEPLOY_ENTRY_POINT,
+                input: constructor_args,
+                gas_limit: contracts.gas_limit,
+            };
+            let (result, contract) = if *contract_code == PPA_CONTRACT_CODE {
+                contracts.vm.check_code(contract_code, abi).map_err(|e| format!("Contract rejected: {}", e))?;
+                let mut contract = ContractState { code: contract_code.clone(), abi: abi.clone(), storage: HashMap::new() };
+                let execution = ppa_contract::deploy(&mut contract, constructor, contracts.vm.gas_schedule());
+                let result = contracts.pay_ppa(overlay, execution, events)?;
+                (result, Some(contract))
+            } else {
+                let context = contracts.context(overlay);
+                contracts.vm.deploy_contract(contract_code.clone(), abi.clone(), constructor, &context)
+            };
+            contracts.gas_used = result.gas_used;
+            let contract = contract.ok_or_else(|| result.error.unwrap_or_default())?;
+            overlay.account(&address).contract = Some(contract);
+            events.push(TransactionEvent::ContractDeployed { deployer: deployer.clone(), contract: address });
+            events.extend(result.events.into_iter().map(TransactionEvent::from));
+        }
+        EnergyTransaction::ExecuteContract { caller, contract_address, method, args, .. } => {
+            let mut contract = overlay.get(contract_address)
+                .and_then(|account| account.contract.clone())
+                .ok_or_else(|| format!("No contract deployed at {}", contract_address))?;
+            let call = ContractCall {
+                address: contract_address,
+                caller,
+                entry_point: method,
+                input: args,
+                gas_limit: contracts.gas_limit,
+            };
+            let result = if contract.code == PPA_CONTRACT_CODE {
+                let execution = ppa_contract::execute(&mut contract, call, contracts.block.timestamp, overlay, contracts.vm.gas_schedule());
+                contracts.pay_ppa(overlay, execution, events)?
+            } else {
+                let context = contracts.context(overlay);
+                let result = contracts.vm.execute_contract(&mut contract, call, &context);
+                contracts.gas_used = result.gas_used;
+                if !result.success {
+                    return Err(result.error.unwrap_or_default());
+                }
+                result
+            };
+            overlay.account(contract_address).contract = Some(contract);
+            events.extend(result.events.into_iter().map(TransactionEvent::from));
+        }
+        EnergyTransaction::UpdateGridStatus { .. } => {}
+    }
+
+    Ok(())
+}
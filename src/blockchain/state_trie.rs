@@ -0,0 +1,300 @@
+//! # State Trie
+//!
+//! Sparse Merkle tree over account state. Every account lives at the leaf addressed by
+//! the SHA-256 of its account id, and the leaf commits to the full `EnergyBalanceState`,
+//! including the code and storage of a contract deployed at the account.
+//! Nodes are stored by hash and never overwritten, so the tree under any past state root
+//! stays available for account proofs.
+
+use crate::blockchain::encoding;
+use crate::blockchain::transactions::EnergyBalanceState;
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Digest};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::UNIX_EPOCH;
+
+/// Depth of the tree, one level per bit of the account key
+const TREE_DEPTH: usize = 256;
+/// Domain separation prefix for leaf hashes
+const LEAF_PREFIX: u8 = 0x00;
+/// Domain separation prefix for internal node hashes
+const NODE_PREFIX: u8 = 0x01;
+
+/// Authenticated account state shared by all clones
+#[derive(Clone, Default)]
+pub struct StateTrie {
+    store: Arc<RwLock<TrieStore>>,
+}
+
+/// Content-addressed node storage
+#[derive(Default)]
+struct TrieStore {
+    /// Children of every non-empty internal node
+    nodes: HashMap<[u8; 32], ([u8; 32], [u8; 32])>,
+    /// Account state committed to by every leaf
+    leaves: HashMap<[u8; 32], (AccountId, EnergyBalanceState)>,
+}
+
+/// Proof that an account has a given state (or does not exist) under a state root
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountProof {
+    /// Account the proof is for
+    pub account: AccountId,
+    /// Account state, `None` for accounts that do not exist
+    pub account_state: Option<EnergyBalanceState>,
+    /// Bit `i` is set when the sibling at depth `i` is not an empty subtree
+    pub sibling_bitmap: Vec<u8>,
+    /// Non-empty sibling hashes ordered from the root down
+    pub siblings: Vec<Hash>,
+}
+
+impl std::fmt::Debug for StateTrie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let store = self.store.read().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("StateTrie")
+            .field("nodes", &store.nodes.len())
+            .field("leaves", &store.leaves.len())
+            .finish()
+    }
+}
+
+impl StateTrie {
+    /// Create an empty trie
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Root of a trie without accounts
+    pub fn empty_root() -> Hash {
+        hex::encode(empty_subtree(0))
+    }
+
+    /// Apply account updates on top of the tree under `root`, returning the new root
+    ///
+    /// Returns `None` if the nodes under `root` are not known to this trie.
+    pub fn update<'a>(
+        &self,
+        root: &Hash,
+        accounts: impl IntoIterator<Item = (&'a AccountId, &'a EnergyBalanceState)>,
+    ) -> Option<Hash> {
+        let mut root = decode_hash(root)?;
+        let mut store = self.store.write().unwrap_or_else(|e| e.into_inner());
+        if !store.contains(&root, 0) {
+            return None;
+        }
+
+        for (account, state) in accounts {
+            let key = account_key(account);
+            let leaf = leaf_hash(&key, state);
+            store.leaves.insert(leaf, (account.clone(), state.clone()));
+            root = store.insert(root, 0, &key, leaf);
+        }
+
+        Some(hex::encode(root))
+    }
+
+    /// Root of a trie holding exactly the given accounts
+    pub fn root_of(&self, balances: &HashMap<AccountId, EnergyBalanceState>) -> Hash {
+        self.update(&Self::empty_root(), balances.iter())
+            .expect("the empty root is always known")
+    }
+
+    /// Build a proof for `account` under `root`
+    ///
+    /// Returns `None` if the nodes under `root` are not known to this trie.
+    pub fn prove(&self, root: &Hash, account: &AccountId) -> Option<AccountProof> {
+        let mut node = decode_hash(root)?;
+        let store = self.store.read().unwrap_or_else(|e| e.into_inner());
+        let key = account_key(account);
+
+        let mut sibling_bitmap = vec![0u8; TREE_DEPTH / 8];
+        let mut siblings = Vec::new();
+        let mut depth = 0;
+        while depth < TREE_DEPTH && node != empty_subtree(depth) {
+            let (left, right) = *store.nodes.get(&node)?;
+            let (next, sibling) = if key_bit(&key, depth) { (right, left) } else { (left, right) };
+            if sibling != empty_subtree(depth + 1) {
+                sibling_bitmap[depth / 8] |= 0x80 >> (depth % 8);
+                siblings.push(hex::encode(sibling));
+            }
+            node = next;
+            depth += 1;
+        }
+
+        // Reaching an empty subtree before the leaf level proves the account is absent
+        let account_state = if node == empty_subtree(depth) {
+            None
+        } else {
+            Some(store.leaves.get(&node)?.1.clone())
+        };
+
+        Some(AccountProof {
+            account: account.clone(),
+            account_state,
+            sibling_bitmap,
+            siblings,
+        })
+    }
+}
+
+impl TrieStore {
+    /// Whether the subtree at `depth` with hash `node` can be walked
+    fn contains(&self, node: &[u8; 32], depth: usize) -> bool {
+        *node == empty_subtree(depth) || self.nodes.contains_key(node)
+    }
+
+    /// Set the leaf at `key` below `node`, returning the new subtree hash
+    fn insert(&mut self, node: [u8; 32], depth: usize, key: &[u8; 32], leaf: [u8; 32]) -> [u8; 32] {
+        if depth == TREE_DEPTH {
+            return leaf;
+        }
+
+        let (left, right) = if node == empty_subtree(depth) {
+            (empty_subtree(depth + 1), empty_subtree(depth + 1))
+        } else {
+            self.nodes[&node]
+        };
+
+        let (left, right) = if key_bit(key, depth) {
+            (left, self.insert(right, depth + 1, key, leaf))
+        } else {
+            (self.insert(left, depth + 1, key, leaf), right)
+        };
+
+        let hash = node_hash(&left, &right);
+        if hash != empty_subtree(depth) {
+            self.nodes.insert(hash, (left, right));
+        }
+        hash
+    }
+}
+
+/// Check an account proof against a state root
+pub fn verify_account_proof(state_root: &Hash, proof: &AccountProof) -> bool {
+    let Some(root) = decode_hash(state_root) else {
+        return false;
+    };
+    if proof.sibling_bitmap.len() != TREE_DEPTH / 8 {
+        return false;
+    }
+
+    let key = account_key(&proof.account);
+    let mut node = match &proof.account_state {
+        Some(state) => leaf_hash(&key, state),
+        None => empty_subtree(TREE_DEPTH),
+    };
+
+    let mut siblings = proof.siblings.iter().rev();
+    for depth in (0..TREE_DEPTH).rev() {
+        let sibling = if proof.sibling_bitmap[depth / 8] & (0x80 >> (depth % 8)) != 0 {
+            match siblings.next().and_then(decode_hash) {
+                Some(sibling) => sibling,
+                None => return false,
+            }
+        } else {
+            empty_subtree(depth + 1)
+        };
+        node = if key_bit(&key, depth) { node_hash(&sibling, &node) } else { node_hash(&node, &sibling) };
+    }
+
+    siblings.next().is_none() && node == root
+}
+
+/// Hash of an empty subtree whose root sits at `depth`
+fn empty_subtree(depth: usize) -> [u8; 32] {
+    static EMPTY: OnceLock<Vec<[u8; 32]>> = OnceLock::new();
+    EMPTY.get_or_init(|| {
+        let mut hashes = vec![[0u8; 32]; TREE_DEPTH + 1];
+        for depth in (0..TREE_DEPTH).rev() {
+            hashes[depth] = node_hash(&hashes[depth + 1], &hashes[depth + 1]);
+        }
+        hashes
+    })[depth]
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn leaf_hash(key: &[u8; 32], state: &EnergyBalanceState) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(key);
+    hasher.update(account_state_hash(state));
+    hasher.finalize().into()
+}
+
+/// Position of an account in the tree
+fn account_key(account: &AccountId) -> [u8; 32] {
+    Sha256::digest(account.as_bytes()).into()
+}
+
+/// Bit of `key` that selects the child at `depth`, most significant bit first
+fn key_bit(key: &[u8; 32], depth: usize) -> bool {
+    key[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+fn decode_hash(hash: &Hash) -> Option<[u8; 32]> {
+    hex::decode(hash).ok()?.try_into().ok()
+}
+
+/// Deterministic digest of every field of an account state
+fn account_state_hash(state: &EnergyBalanceState) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(state.total_balance.to_be_bytes());
+    for source in EnergySource::ALL.iter() {
+        hasher.update(state.energy_balances.get(source).copied().unwrap_or(0).to_be_bytes());
+        hasher.update(state.locked_balances.get(source).copied().unwrap_or(0).to_be_bytes());
+    }
+    hasher.update(state.staked_balance.to_be_bytes());
+    hasher.update(state.currency_balance.to_be_bytes());
+    hasher.update(state.carbon_credits.to_be_bytes());
+    hasher.update(state.nonce.to_be_bytes());
+    hasher.update(unix_seconds(state.last_updated).to_be_bytes());
+
+    hasher.update((state.production_history.len() as u64).to_be_bytes());
+    for record in &state.production_history {
+        hasher.update(record.amount.as_wh().to_be_bytes());
+        hasher.update([source_index(&record.energy_type)]);
+        hasher.update(unix_seconds(record.timestamp).to_be_bytes());
+        hasher.update([record.verified as u8]);
+        hasher.update(record.efficiency.as_millionths().to_be_bytes());
+        hasher.update((record.equipment_id.len() as u64).to_be_bytes());
+        hasher.update(record.equipment_id.as_bytes());
+    }
+
+    hasher.update((state.consumption_history.len() as u64).to_be_bytes());
+    for record in &state.consumption_history {
+        hasher.update(record.amount.as_wh().to_be_bytes());
+        hasher.update(unix_seconds(record.timestamp).to_be_bytes());
+        hasher.update([record.verified as u8]);
+    }
+
+    hasher.update((state.metered_production.len() as u64).to_be_bytes());
+    for production in &state.metered_production {
+        hasher.update(production.reported_at.to_be_bytes());
+        hasher.update(production.amount.as_wh().to_be_bytes());
+        hasher.update(production.claimed.as_wh().to_be_bytes());
+    }
+
+    // The canonical encoding covers the code, the ABI and every storage entry in key order
+    if let Some(contract) = &state.contract {
+        hasher.update(encoding::hash(contract).expect("contract state holds no floats and always encodes"));
+    }
+
+    hasher.finalize().into()
+}
+
+fn source_index(source: &EnergySource) -> u8 {
+    EnergySource::ALL.iter().position(|candidate| candidate == source).unwrap_or(u8::MAX as usize) as u8
+}
+
+fn unix_seconds(time: std::time::SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0)
+}
@@ -1,41 +1,480 @@
 //! # Storage Layer
-//! 
+//!
 //! Implements blockchain storage and state management.
+//!
+//! Blocks are kept in an append-only log file inside the configured data directory.
+//! Every record is length-prefixed and checksummed, and each append first goes through
+//! a single-record write-ahead log, so a crash in the middle of writing a block leaves
+//! either the previous chain or the complete new block on disk after recovery.
+//!
+//! State snapshots are written next to the block log every `snapshot_interval` blocks,
+//! so a restarting node only has to re-execute the blocks after the latest snapshot.
+//!
+//! A chain reorganisation truncates the log back to the common ancestor, dropping the
+//! snapshots of retracted heights, before the new branch is appended.
+//!
+//! The finalized head and the precommits collected above it are rewritten in place, through a
+//! temporary file, whenever they change.
 
+use crate::blockchain::consensus::{EnergyBlock, StateSnapshot};
+use crate::blockchain::finality::FinalityRecord;
 use crate::config::BlockchainConfig;
+use crate::types::Hash;
 use crate::utils::SystemResult;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Sha256, Digest};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// File holding the append-only block log
+const BLOCK_LOG_FILE: &str = "blocks.log";
+/// File holding the block currently being appended
+const WAL_FILE: &str = "blocks.wal";
+/// Directory holding state snapshots
+const SNAPSHOT_DIR: &str = "snapshots";
+/// File holding the finalized head and pending precommits
+const FINALITY_FILE: &str = "finality.bin";
+/// Number of most recent snapshots kept on disk
+const SNAPSHOTS_TO_KEEP: usize = 2;
+/// Length prefix plus SHA-256 checksum in front of every record
+const RECORD_HEADER_SIZE: usize = 4 + 32;
+
 /// Blockchain storage layer
 pub struct BlockchainStorage {
     config: BlockchainConfig,
     running: Arc<RwLock<bool>>,
+    /// On-disk block store, absent when no data directory is configured
+    store: Arc<RwLock<Option<BlockStore>>>,
+}
+
+/// Append-only, file-backed block store with in-memory indexes
+struct BlockStore {
+    /// Directory holding state snapshots
+    snapshot_dir: PathBuf,
+    /// Path of the write-ahead log
+    wal_path: PathBuf,
+    /// Path of the persisted finality state
+    finality_path: PathBuf,
+    /// Block log opened for reading and appending
+    log: File,
+    /// Offset of the end of the last complete record
+    end_offset: u64,
+    /// Record offset of every block, indexed by block height
+    offsets: Vec<u64>,
+    /// Block height by block hash
+    hash_index: HashMap<Hash, u32>,
+    /// Block height by transaction hash
+    transaction_index: HashMap<[u8; 32], u32>,
 }
 
 impl BlockchainStorage {
     pub async fn new(config: &BlockchainConfig) -> SystemResult<Self> {
+        let store = match &config.data_dir {
+            Some(data_dir) => Some(BlockStore::open(Path::new(data_dir))?),
+            None => None,
+        };
+
         Ok(Self {
             config: config.clone(),
             running: Arc::new(RwLock::new(false)),
+            store: Arc::new(RwLock::new(store)),
         })
     }
-    
+
     pub async fn start(&self) -> SystemResult<()> {
         let mut running = self.running.write().await;
         *running = true;
-        
+
         crate::utils::logging::log_startup("Blockchain Storage");
-        
+
         Ok(())
     }
-    
+
     pub async fn stop(&self) -> SystemResult<()> {
         let mut running = self.running.write().await;
         *running = false;
-        
+
         crate::utils::logging::log_shutdown("Blockchain Storage");
-        
+
+        Ok(())
+    }
+
+    /// Whether blocks are persisted to disk
+    pub async fn is_persistent(&self) -> bool {
+        self.store.read().await.is_some()
+    }
+
+    /// Number of blocks on disk, including genesis
+    pub async fn block_count(&self) -> u32 {
+        self.store.read().await.as_ref().map_or(0, |store| store.offsets.len() as u32)
+    }
+
+    /// Durably append the next block of the chain
+    pub async fn append_block(&self, block: &EnergyBlock) -> SystemResult<()> {
+        match self.store.write().await.as_mut() {
+            Some(store) => store.append(block),
+            None => Ok(()),
+        }
+    }
+
+    /// Drop every stored block above `height`, along with snapshots taken after it
+    pub async fn truncate_blocks(&self, height: u32) -> SystemResult<()> {
+        match self.store.write().await.as_mut() {
+            Some(store) => store.truncate(height),
+            None => Ok(()),
+        }
+    }
+
+    /// Read every stored block in height order, used to replay the chain on startup
+    pub async fn load_blocks(&self) -> SystemResult<Vec<EnergyBlock>> {
+        match self.store.write().await.as_mut() {
+            Some(store) => (0..store.offsets.len() as u32).map(|height| store.read_block(height)).collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Whether a state snapshot should be taken after the block at `height`
+    pub async fn snapshot_due(&self, height: u32) -> bool {
+        let interval = self.config.snapshot_interval;
+        interval > 0 && height > 0 && (height as u64).is_multiple_of(interval) && self.is_persistent().await
+    }
+
+    /// Durably write a state snapshot, pruning older ones
+    pub async fn save_snapshot(&self, snapshot: &StateSnapshot) -> SystemResult<()> {
+        match self.store.read().await.as_ref() {
+            Some(store) => store.save_snapshot(snapshot),
+            None => Ok(()),
+        }
+    }
+
+    /// Load the most recent readable snapshot, if any
+    pub async fn latest_snapshot(&self) -> SystemResult<Option<StateSnapshot>> {
+        match self.store.read().await.as_ref() {
+            Some(store) => store.latest_snapshot(),
+            None => Ok(None),
+        }
+    }
+
+    /// Durably replace the persisted finality state
+    pub async fn save_finality(&self, record: &FinalityRecord) -> SystemResult<()> {
+        match self.store.read().await.as_ref() {
+            Some(store) => store.save_finality(record),
+            None => Ok(()),
+        }
+    }
+
+    /// Load the persisted finality state, if any
+    pub async fn load_finality(&self) -> SystemResult<Option<FinalityRecord>> {
+        match self.store.read().await.as_ref() {
+            Some(store) => store.load_finality(),
+            None => Ok(None),
+        }
+    }
+
+    /// Get a block by height
+    pub async fn get_block_by_height(&self, height: u32) -> SystemResult<Option<EnergyBlock>> {
+        match self.store.write().await.as_mut() {
+            Some(store) if (height as usize) < store.offsets.len() => store.read_block(height).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Get a block by its hash
+    pub async fn get_block_by_hash(&self, hash: &Hash) -> SystemResult<Option<EnergyBlock>> {
+        match self.store.write().await.as_mut() {
+            Some(store) => match store.hash_index.get(hash).copied() {
+                Some(height) => store.read_block(height).map(Some),
+                None => Ok(None),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Get the block that included a transaction
+    pub async fn get_block_by_transaction(&self, tx_hash: &[u8; 32]) -> SystemResult<Option<EnergyBlock>> {
+        match self.store.write().await.as_mut() {
+            Some(store) => match store.transaction_index.get(tx_hash).copied() {
+                Some(height) => store.read_block(height).map(Some),
+                None => Ok(None),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+impl BlockStore {
+    /// Open the store in `dir`, recovering from an interrupted append if necessary
+    fn open(dir: &Path) -> SystemResult<Self> {
+        fs::create_dir_all(dir)?;
+
+        let log = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(BLOCK_LOG_FILE))?;
+
+        let snapshot_dir = dir.join(SNAPSHOT_DIR);
+        fs::create_dir_all(&snapshot_dir)?;
+
+        let mut store = Self {
+            snapshot_dir,
+            wal_path: dir.join(WAL_FILE),
+            finality_path: dir.join(FINALITY_FILE),
+            log,
+            end_offset: 0,
+            offsets: Vec::new(),
+            hash_index: HashMap::new(),
+            transaction_index: HashMap::new(),
+        };
+        store.recover()?;
+
+        crate::utils::logging::log_info(
+            "BlockchainStorage",
+            &format!("Opened block store at {} with {} blocks", dir.display(), store.offsets.len())
+        );
+
+        Ok(store)
+    }
+
+    /// Rebuild the indexes from the log, drop a torn tail record and redo a pending WAL entry
+    fn recover(&mut self) -> SystemResult<()> {
+        let mut contents = Vec::new();
+        self.log.seek(SeekFrom::Start(0))?;
+        self.log.read_to_end(&mut contents)?;
+
+        let mut offset = 0usize;
+        while let Some((block, record_len)) = decode_record::<EnergyBlock>(&contents[offset..]) {
+            if block.header.number as usize != self.offsets.len() {
+                break;
+            }
+            self.index_block(&block, offset as u64);
+            offset += record_len;
+        }
+
+        if offset < contents.len() {
+            crate::utils::logging::log_warning(
+                "BlockchainStorage",
+                &format!("Discarding {} bytes of incomplete block data", contents.len() - offset)
+            );
+            self.log.set_len(offset as u64)?;
+            self.log.sync_all()?;
+        }
+        self.end_offset = offset as u64;
+
+        // A complete WAL record for the next height means the crash happened before the
+        // append finished; anything else was either already appended or never committed.
+        if let Ok(wal) = fs::read(&self.wal_path) {
+            if let Some((block, _)) = decode_record::<EnergyBlock>(&wal) {
+                if block.header.number as usize == self.offsets.len() {
+                    self.write_to_log(&wal, &block)?;
+                }
+            }
+            self.clear_wal()?;
+        }
+
+        Ok(())
+    }
+
+    /// Append a block via the write-ahead log
+    fn append(&mut self, block: &EnergyBlock) -> SystemResult<()> {
+        if block.header.number as usize != self.offsets.len() {
+            return Err(crate::utils::error::SystemError::Blockchain(format!(
+                "Cannot store block #{} on top of {} stored blocks",
+                block.header.number,
+                self.offsets.len()
+            )));
+        }
+
+        let record = encode_record(block)?;
+
+        let mut wal = File::create(&self.wal_path)?;
+        wal.write_all(&record)?;
+        wal.sync_all()?;
+
+        self.write_to_log(&record, block)?;
+        self.clear_wal()
+    }
+
+    /// Cut the log after the block at `height`
+    fn truncate(&mut self, height: u32) -> SystemResult<()> {
+        let keep = height as usize + 1;
+        if keep >= self.offsets.len() {
+            return Ok(());
+        }
+
+        self.end_offset = self.offsets[keep];
+        self.log.set_len(self.end_offset)?;
+        self.log.sync_all()?;
+        self.offsets.truncate(keep);
+        self.hash_index.retain(|_, stored| *stored <= height);
+        self.transaction_index.retain(|_, stored| *stored <= height);
+
+        for path in self.snapshot_paths()? {
+            if snapshot_height(&path).is_some_and(|snapshot| snapshot > height) {
+                fs::remove_file(path)?;
+            }
+        }
+
+        crate::utils::logging::log_info(
+            "BlockchainStorage",
+            &format!("Truncated block store to block #{}", height)
+        );
+        Ok(())
+    }
+
+    /// Write an encoded record at the end of the log and index it
+    fn write_to_log(&mut self, record: &[u8], block: &EnergyBlock) -> SystemResult<()> {
+        self.log.seek(SeekFrom::Start(self.end_offset))?;
+        self.log.write_all(record)?;
+        self.log.sync_data()?;
+
+        self.index_block(block, self.end_offset);
+        self.end_offset += record.len() as u64;
+        Ok(())
+    }
+
+    fn clear_wal(&self) -> SystemResult<()> {
+        let wal = OpenOptions::new().write(true).create(true).truncate(true).open(&self.wal_path)?;
+        wal.sync_all()?;
+        Ok(())
+    }
+
+    fn index_block(&mut self, block: &EnergyBlock, offset: u64) {
+        self.offsets.push(offset);
+        self.hash_index.insert(block.header.hash.clone(), block.header.number);
+        for tx in &block.transactions {
+            self.transaction_index.insert(tx.hash, block.header.number);
+        }
+    }
+
+    /// Write a snapshot to a temporary file and rename it into place
+    fn save_snapshot(&self, snapshot: &StateSnapshot) -> SystemResult<()> {
+        let record = encode_record(snapshot)?;
+        let path = self.snapshot_dir.join(format!("snapshot-{:010}.bin", snapshot.height));
+        let temp_path = path.with_extension("tmp");
+
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&record)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)?;
+
+        for stale in self.snapshot_paths()?.into_iter().skip(SNAPSHOTS_TO_KEEP) {
+            fs::remove_file(stale)?;
+        }
+
+        crate::utils::logging::log_info(
+            "BlockchainStorage",
+            &format!("Saved state snapshot at block #{}", snapshot.height)
+        );
         Ok(())
     }
+
+    /// Write the finality state to a temporary file and rename it into place
+    fn save_finality(&self, record: &FinalityRecord) -> SystemResult<()> {
+        let temp_path = self.finality_path.with_extension("tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&encode_record(record)?)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &self.finality_path)?;
+        Ok(())
+    }
+
+    /// Persisted finality state
+    ///
+    /// Unlike a snapshot, an unreadable record is an error: starting without it could let
+    /// the node precommit to a block conflicting with one it already voted for.
+    fn load_finality(&self) -> SystemResult<Option<FinalityRecord>> {
+        let bytes = match fs::read(&self.finality_path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        decode_record::<FinalityRecord>(&bytes)
+            .map(|(record, _)| Some(record))
+            .ok_or_else(|| crate::utils::error::SystemError::Blockchain(format!(
+                "Finality record {} is corrupted", self.finality_path.display()
+            )))
+    }
+
+    /// Newest snapshot that decodes cleanly; corrupted files are skipped
+    fn latest_snapshot(&self) -> SystemResult<Option<StateSnapshot>> {
+        for path in self.snapshot_paths()? {
+            match decode_record::<StateSnapshot>(&fs::read(&path)?) {
+                Some((snapshot, _)) => return Ok(Some(snapshot)),
+                None => crate::utils::logging::log_warning(
+                    "BlockchainStorage",
+                    &format!("Ignoring unreadable snapshot {}", path.display())
+                ),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Snapshot files ordered from newest to oldest
+    fn snapshot_paths(&self) -> SystemResult<Vec<PathBuf>> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(&self.snapshot_dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "bin"))
+            .collect();
+        // Heights are zero-padded, so name order is height order
+        paths.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(paths)
+    }
+
+    /// Read the block stored at a given height
+    fn read_block(&mut self, height: u32) -> SystemResult<EnergyBlock> {
+        let offset = self.offsets[height as usize];
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        self.log.seek(SeekFrom::Start(offset))?;
+        self.log.read_exact(&mut header)?;
+
+        let payload_len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let mut record = header.to_vec();
+        record.resize(RECORD_HEADER_SIZE + payload_len, 0);
+        self.log.read_exact(&mut record[RECORD_HEADER_SIZE..])?;
+
+        decode_record::<EnergyBlock>(&record)
+            .map(|(block, _)| block)
+            .ok_or_else(|| crate::utils::error::SystemError::Blockchain(
+                format!("Stored block #{} is corrupted", height)
+            ))
+    }
+}
+
+/// Height encoded in a snapshot file name
+fn snapshot_height(path: &Path) -> Option<u32> {
+    path.file_stem()?.to_str()?.strip_prefix("snapshot-")?.parse().ok()
+}
+
+/// Encode a value as `[payload length][payload checksum][payload]`
+fn encode_record<T: Serialize>(value: &T) -> SystemResult<Vec<u8>> {
+    let payload = serde_json::to_vec(value)
+        .map_err(|e| crate::utils::error::SystemError::Internal(format!("Failed to encode record: {}", e)))?;
+
+    let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + payload.len());
+    record.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    record.extend_from_slice(&Sha256::digest(&payload));
+    record.extend_from_slice(&payload);
+    Ok(record)
+}
+
+/// Decode the record at the start of `bytes`, returning the value and record length
+///
+/// Returns `None` for truncated or corrupted records.
+fn decode_record<T: DeserializeOwned>(bytes: &[u8]) -> Option<(T, usize)> {
+    let header = bytes.get(..RECORD_HEADER_SIZE)?;
+    let payload_len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let payload = bytes.get(RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + payload_len)?;
+
+    if Sha256::digest(payload).as_slice() != &header[4..] {
+        return None;
+    }
+
+    let value = serde_json::from_slice(payload).ok()?;
+    Some((value, RECORD_HEADER_SIZE + payload_len))
 }
@@ -0,0 +1,113 @@
+//! # Block Sync Protocol
+//!
+//! Request/response messages nodes use to catch up with the chain after joining late
+//! or recovering from downtime. A syncing node asks its peers for their chain status,
+//! downloads the headers it is missing by height range, then fetches the block bodies
+//! by hash and imports them through the regular validation pipeline.
+
+use crate::blockchain::consensus::{BlockHeader, ConsensusEngine, EnergyBlock};
+use crate::types::Hash;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Protocol name negotiated for sync requests
+pub const SYNC_PROTOCOL: &str = "/gridtokenx/sync/2.0.0";
+/// Maximum number of headers served for a single request
+pub const MAX_HEADERS_PER_REQUEST: u32 = 128;
+/// Maximum number of block bodies served for a single request
+pub const MAX_BLOCKS_PER_REQUEST: usize = 16;
+
+/// Request sent to a peer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SyncRequest {
+    /// Ask for the peer's chain status
+    Status,
+    /// Ask for up to `count` consecutive headers starting at block `from`
+    Headers { from: u32, count: u32 },
+    /// Ask for the blocks with the given hashes
+    Blocks { hashes: Vec<Hash> },
+}
+
+/// Response to a [`SyncRequest`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SyncResponse {
+    Status(ChainStatus),
+    /// Consecutive headers; fewer than requested when the chain is shorter
+    Headers(Vec<BlockHeader>),
+    /// Requested blocks in request order; unknown hashes are skipped
+    Blocks(Vec<EnergyBlock>),
+}
+
+/// Summary of a node's chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainStatus {
+    /// Hash of the genesis block, identifying the chain
+    pub genesis_hash: Hash,
+    /// Number of the chain head
+    pub best_number: u32,
+    /// Hash of the chain head
+    pub best_hash: Hash,
+}
+
+/// Phase of the sync state machine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SyncState {
+    /// No peer has been asked yet, or the last attempt failed
+    Idle,
+    /// Downloading blocks from a peer ahead of us
+    Syncing,
+    /// No known peer has a longer chain
+    Synced,
+}
+
+/// Sync progress reported by the node manager
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    pub state: SyncState,
+    /// Local chain height
+    pub current_height: u32,
+    /// Height of the best chain seen on a peer
+    pub target_height: u32,
+    /// Peer blocks are downloaded from while syncing
+    pub sync_peer: Option<String>,
+    /// Blocks imported through sync since the node started
+    pub blocks_imported: u64,
+    /// Reason the last sync attempt was aborted
+    pub last_error: Option<String>,
+}
+
+impl Default for SyncStatus {
+    fn default() -> Self {
+        Self {
+            state: SyncState::Idle,
+            current_height: 0,
+            target_height: 0,
+            sync_peer: None,
+            blocks_imported: 0,
+            last_error: None,
+        }
+    }
+}
+
+/// Chain data served to syncing peers
+#[async_trait]
+pub trait SyncProvider: Send + Sync {
+    /// Answer a sync request from a peer
+    async fn handle_sync_request(&self, request: SyncRequest) -> SyncResponse;
+}
+
+#[async_trait]
+impl SyncProvider for ConsensusEngine {
+    async fn handle_sync_request(&self, request: SyncRequest) -> SyncResponse {
+        match request {
+            SyncRequest::Status => SyncResponse::Status(self.chain_status().await),
+            SyncRequest::Headers { from, count } => {
+                SyncResponse::Headers(self.get_headers(from, count.min(MAX_HEADERS_PER_REQUEST)).await)
+            }
+            SyncRequest::Blocks { mut hashes } => {
+                hashes.truncate(MAX_BLOCKS_PER_REQUEST);
+                SyncResponse::Blocks(self.get_blocks_by_hash(&hashes).await)
+            }
+        }
+    }
+}
@@ -1,80 +1,353 @@
 //! # Transaction Pool
-//! 
+//!
 //! Manages pending transactions before they are included in blocks.
+//!
+//! Transactions are queued per sender in nonce order. Those continuing the sender's account
+//! nonce without a gap are ready for the next block; later ones wait in the future queue until
+//! the gap is filled. Block production takes ready transactions by priority — gas price first,
+//! then transaction weight, then arrival — and a full pool makes room by evicting its
+//! lowest-priority transactions.
+//!
+//! Submitted transactions are admitted against the current chain state first: they must be
+//! signed by the key their sender account is derived from, must not reuse a nonce or run more
+//! than [`MAX_FUTURE_NONCES`] ahead of it, and the sender must be able to cover the maximum
+//! fee plus the tokens the transaction commits on top of what its other pooled transactions
+//! already spend.
 
 use crate::config::BlockchainConfig;
-use crate::utils::SystemResult;
-use crate::blockchain::transactions::EnergyTransactionEnvelope;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use std::collections::HashMap;
-use crate::types::Hash;
-
-/// Transaction pool for managing pending transactions
+use crate::blockchain::transactions::{
+    EnergyBalanceState, EnergyTransactionEnvelope, EnergyTransactionValidator, SignatureRejection,
+    TransactionValidationResult,
+};
+use crate::types::{AccountId, Balance};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+
+/// Percentage a replacement has to raise the gas price of the transaction it replaces by
+pub const REPLACEMENT_PRICE_BUMP: Balance = 10;
+
+/// How far ahead of its sender's account nonce a submitted transaction may be
+pub const MAX_FUTURE_NONCES: u64 = 64;
+
+/// Reason a transaction is not admitted to the pool
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PoolRejection {
+    #[error("transaction is already in the pool")]
+    AlreadyKnown,
+    #[error("replacement gas price {found} is below the required {required}")]
+    ReplacementUnderpriced { required: Balance, found: Balance },
+    #[error("transaction pool is full and the transaction does not outbid any pooled one")]
+    PoolFull,
+    #[error("transaction has no canonical encoding")]
+    Unencodable,
+}
+
+/// Reason a submitted transaction is not admitted
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum AdmissionRejection {
+    #[error("invalid transaction: {0}")]
+    Invalid(String),
+    #[error("invalid signature: {0}")]
+    Signature(#[from] SignatureRejection),
+    #[error("nonce {found} is already used, the account is at nonce {expected}")]
+    NonceTooLow { expected: u64, found: u64 },
+    #[error("nonce {found} is too far ahead of the account nonce {expected}")]
+    NonceTooHigh { expected: u64, found: u64 },
+    #[error("sender needs {required} to cover fee and amount, available {available}")]
+    InsufficientBalance { required: Balance, available: Balance },
+    #[error("sender needs {required} satang to pay, available {available}")]
+    InsufficientCurrency { required: Balance, available: Balance },
+    #[error(transparent)]
+    Pool(#[from] PoolRejection),
+}
+
+/// Check a submitted transaction against the current account state and the sender's other
+/// pooled transactions before it is pooled
+///
+/// Transactions up to [`MAX_FUTURE_NONCES`] ahead of the account nonce are admitted to wait in
+/// the future queue. A transaction replacing a pooled one only has to be affordable alongside
+/// the sender's remaining transactions.
+pub fn check_admission(
+    envelope: &EnergyTransactionEnvelope,
+    balances: &HashMap<AccountId, EnergyBalanceState>,
+    pool: &TransactionPool,
+) -> Result<(), AdmissionRejection> {
+    if let TransactionValidationResult::Invalid(reason) = EnergyTransactionValidator::new().validate_transaction(envelope) {
+        return Err(AdmissionRejection::Invalid(reason));
+    }
+    envelope.check_signature()?;
+
+    let sender = envelope.get_sender();
+    let (expected, available, currency) = balances.get(&sender)
+        .map(|account| (account.nonce, account.get_total_available_balance(), account.currency_balance))
+        .unwrap_or((0, 0, 0));
+    if envelope.nonce < expected {
+        return Err(AdmissionRejection::NonceTooLow { expected, found: envelope.nonce });
+    }
+    if envelope.nonce >= expected.saturating_add(MAX_FUTURE_NONCES) {
+        return Err(AdmissionRejection::NonceTooHigh { expected, found: envelope.nonce });
+    }
+
+    let (pending_spend, pending_payment) = pool.pending_spend(&sender, envelope.nonce);
+    let required = envelope.calculate_fee()
+        .saturating_add(envelope.committed_amount())
+        .saturating_add(pending_spend);
+    if required > available {
+        return Err(AdmissionRejection::InsufficientBalance { required, available });
+    }
+    let payment = envelope.committed_payment().saturating_add(pending_payment);
+    if payment > currency {
+        return Err(AdmissionRejection::InsufficientCurrency { required: payment, available: currency });
+    }
+    Ok(())
+}
+
+/// Block inclusion priority: gas price, then weight, then earlier arrival
+type Priority = (Balance, u64, Reverse<u64>);
+
+/// A pooled transaction with the time it was admitted
+#[derive(Debug, Clone)]
+struct PooledTransaction {
+    envelope: EnergyTransactionEnvelope,
+    /// Unix time (seconds) the transaction entered the pool
+    added_at: u64,
+    /// Arrival order, breaking ties between transactions of equal priority
+    sequence: u64,
+}
+
+impl PooledTransaction {
+    fn priority(&self) -> Priority {
+        (self.envelope.gas_price, self.envelope.get_weight(), Reverse(self.sequence))
+    }
+}
+
+/// Bounded, priority-ordered pool of pending transactions
+#[derive(Debug, Clone)]
 pub struct TransactionPool {
-    config: BlockchainConfig,
-    running: Arc<RwLock<bool>>,
-    transactions: Arc<RwLock<HashMap<Hash, EnergyTransactionEnvelope>>>,
+    /// Maximum number of pooled transactions
+    capacity: usize,
+    /// Seconds a transaction may wait for inclusion before it expires (0 keeps it indefinitely)
+    lifetime: u64,
+    /// Maximum total size in bytes of the transactions one block takes from the pool
+    block_size_limit: usize,
+    /// Pooled transactions per sender, keyed by nonce
+    senders: HashMap<AccountId, BTreeMap<u64, PooledTransaction>>,
+    /// Sender and nonce of every pooled transaction by hash
+    hashes: HashMap<[u8; 32], (AccountId, u64)>,
+    next_sequence: u64,
 }
 
 impl TransactionPool {
-    pub async fn new(config: &BlockchainConfig) -> SystemResult<Self> {
-        Ok(Self {
-            config: config.clone(),
-            running: Arc::new(RwLock::new(false)),
-            transactions: Arc::new(RwLock::new(HashMap::new())),
-        })
-    }
-    
-    pub async fn start(&self) -> SystemResult<()> {
-        let mut running = self.running.write().await;
-        *running = true;
-        
-        crate::utils::logging::log_startup("Transaction Pool");
-        
-        Ok(())
+    pub fn new(config: &BlockchainConfig) -> Self {
+        Self {
+            capacity: config.transaction_pool_size,
+            lifetime: config.transaction_lifetime,
+            block_size_limit: config.block_size_limit,
+            senders: HashMap::new(),
+            hashes: HashMap::new(),
+            next_sequence: 0,
+        }
     }
-    
-    pub async fn stop(&self) -> SystemResult<()> {
-        let mut running = self.running.write().await;
-        *running = false;
-        
-        crate::utils::logging::log_shutdown("Transaction Pool");
-        
-        Ok(())
+
+    /// Number of pooled transactions
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Maximum total size in bytes of the transactions one block takes from the pool
+    pub fn block_size_limit(&self) -> usize {
+        self.block_size_limit
+    }
+
+    /// Whether a transaction with the given hash is pooled
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.hashes.contains_key(hash)
+    }
+
+    /// Look up a pooled transaction by hash
+    pub fn get(&self, hash: &[u8; 32]) -> Option<&EnergyTransactionEnvelope> {
+        let (sender, nonce) = self.hashes.get(hash)?;
+        self.senders.get(sender)?.get(nonce).map(|pooled| &pooled.envelope)
+    }
+
+    /// All pooled transactions, grouped by sender in nonce order
+    pub fn transactions(&self) -> Vec<EnergyTransactionEnvelope> {
+        self.senders.values()
+            .flat_map(|queue| queue.values().map(|pooled| pooled.envelope.clone()))
+            .collect()
     }
-    
-    /// Add transaction to the pool
-    pub async fn add_transaction(&self, transaction: EnergyTransactionEnvelope) -> SystemResult<()> {
-        let mut transactions = self.transactions.write().await;
-        let transaction_hash = hex::encode(transaction.hash);
-        
-        // Check if transaction already exists
-        if transactions.contains_key(&transaction_hash) {
-            return Err(crate::utils::error::SystemError::Internal("Transaction already exists".to_string()));
+
+    /// Add a transaction at Unix time `now`
+    ///
+    /// A transaction with the same sender and nonce as a pooled one replaces it if it raises
+    /// the gas price by at least [`REPLACEMENT_PRICE_BUMP`] percent. When the pool is full the
+    /// lowest-priority transaction is evicted if the new one outbids it. Only the last queued
+    /// transaction of a sender is evicted, so no queue is left with a gap. The hash the
+    /// envelope carries is not trusted but recomputed from its contents.
+    pub fn insert(&mut self, mut envelope: EnergyTransactionEnvelope, now: u64) -> Result<(), PoolRejection> {
+        envelope.hash = envelope.compute_hash().map_err(|_| PoolRejection::Unencodable)?;
+        self.prune_expired(now);
+        let sender = envelope.get_sender();
+        let pooled = PooledTransaction {
+            envelope,
+            added_at: now,
+            sequence: self.next_sequence,
+        };
+
+        let nonce = pooled.envelope.nonce;
+        if self.hashes.get(&pooled.envelope.hash).is_some_and(|slot| *slot != (sender.clone(), nonce)) {
+            return Err(PoolRejection::AlreadyKnown);
+        }
+
+        let replaced = self.senders.get(&sender).and_then(|queue| queue.get(&nonce));
+        if let Some(replaced) = replaced {
+            let gas_price = replaced.envelope.gas_price;
+            let required = gas_price.saturating_add((gas_price.saturating_mul(REPLACEMENT_PRICE_BUMP) / 100).max(1));
+            if pooled.envelope.gas_price < required {
+                return Err(PoolRejection::ReplacementUnderpriced { required, found: pooled.envelope.gas_price });
+            }
+            crate::utils::logging::log_info(
+                "TransactionPool",
+                &format!("Transaction {} replaced by {}", hex::encode(replaced.envelope.hash), hex::encode(pooled.envelope.hash))
+            );
+            self.hashes.remove(&replaced.envelope.hash);
+        } else if self.len() >= self.capacity {
+            let (victim_sender, victim_nonce) = self.eviction_candidate(&sender, nonce, pooled.priority())
+                .ok_or(PoolRejection::PoolFull)?;
+            if let Some(evicted) = self.remove_entry(&victim_sender, victim_nonce) {
+                crate::utils::logging::log_info(
+                    "TransactionPool",
+                    &format!("Evicted transaction {} to make room", hex::encode(evicted.hash))
+                );
+            }
         }
-        
-        // Add transaction to pool
-        transactions.insert(transaction_hash, transaction);
-        
-        crate::utils::logging::log_info(
-            "TransactionPool",
-            "Added transaction to pool"
-        );
-        
+
+        self.next_sequence += 1;
+        self.hashes.insert(pooled.envelope.hash, (sender.clone(), nonce));
+        self.senders.entry(sender).or_default().insert(nonce, pooled);
         Ok(())
     }
-    
-    /// Remove transaction from pool
-    pub async fn remove_transaction(&self, hash: &str) -> SystemResult<()> {
-        let mut transactions = self.transactions.write().await;
-        transactions.remove(hash);
-        Ok(())
+
+    /// Remove a transaction by hash
+    pub fn remove(&mut self, hash: &[u8; 32]) -> Option<EnergyTransactionEnvelope> {
+        let (sender, nonce) = self.hashes.get(hash)?.clone();
+        self.remove_entry(&sender, nonce)
     }
-    
-    /// Get all transactions
-    pub async fn get_all_transactions(&self) -> Vec<EnergyTransactionEnvelope> {
-        let transactions = self.transactions.read().await;
-        transactions.values().cloned().collect()
+
+    /// Drop the transactions a block included, along with any other transaction of the same
+    /// senders at or below the included nonces, which can no longer be included
+    pub fn remove_included(&mut self, included: &[EnergyTransactionEnvelope]) {
+        for tx in included {
+            let sender = tx.get_sender();
+            let Some(queue) = self.senders.get_mut(&sender) else {
+                continue;
+            };
+            let pending = queue.split_off(&(tx.nonce + 1));
+            for stale in std::mem::replace(queue, pending).into_values() {
+                self.hashes.remove(&stale.envelope.hash);
+            }
+            if queue.is_empty() {
+                self.senders.remove(&sender);
+            }
+        }
+    }
+
+    /// Drop transactions that waited longer than the pool lifetime as of Unix time `now`
+    pub fn prune_expired(&mut self, now: u64) {
+        if self.lifetime == 0 {
+            return;
+        }
+        let expired: Vec<[u8; 32]> = self.senders.values()
+            .flat_map(|queue| queue.values())
+            .filter(|pooled| now.saturating_sub(pooled.added_at) > self.lifetime)
+            .map(|pooled| pooled.envelope.hash)
+            .collect();
+        for hash in expired {
+            self.remove(&hash);
+        }
+    }
+
+    /// Ready transactions in the order block production should include them
+    ///
+    /// `account_nonce` gives the next nonce of a sender's account. Each sender's transactions
+    /// stay in nonce order; between senders, the higher-priority transaction goes first.
+    pub fn ready(&self, account_nonce: impl Fn(&AccountId) -> u64) -> Vec<EnergyTransactionEnvelope> {
+        let mut heads: BinaryHeap<(Priority, &AccountId, u64)> = self.senders.iter()
+            .filter_map(|(sender, queue)| {
+                let nonce = account_nonce(sender);
+                queue.get(&nonce).map(|pooled| (pooled.priority(), sender, nonce))
+            })
+            .collect();
+
+        let mut ready = Vec::new();
+        while let Some((_, sender, nonce)) = heads.pop() {
+            let queue = &self.senders[sender];
+            ready.push(queue[&nonce].envelope.clone());
+            if let Some(next) = queue.get(&(nonce + 1)) {
+                heads.push((next.priority(), sender, nonce + 1));
+            }
+        }
+        ready
+    }
+
+    /// Transactions waiting for an earlier nonce of their sender, grouped by sender in nonce order
+    pub fn future(&self, account_nonce: impl Fn(&AccountId) -> u64) -> Vec<EnergyTransactionEnvelope> {
+        self.senders.iter()
+            .flat_map(|(sender, queue)| {
+                let mut next = account_nonce(sender);
+                queue.values().filter(move |pooled| {
+                    if pooled.envelope.nonce == next {
+                        next += 1;
+                        return false;
+                    }
+                    true
+                })
+            })
+            .map(|pooled| pooled.envelope.clone())
+            .collect()
+    }
+
+    /// The lowest-priority transaction among the last queued transaction of each sender that
+    /// a transaction of `sender` with `nonce` and `priority` outbids
+    ///
+    /// The sender's own earlier transaction is skipped, as evicting it would leave the new one
+    /// behind a gap.
+    fn eviction_candidate(&self, sender: &AccountId, nonce: u64, priority: Priority) -> Option<(AccountId, u64)> {
+        let mut tails: Vec<(Priority, &AccountId, u64)> = self.senders.iter()
+            .filter_map(|(tail_sender, queue)| {
+                queue.iter().next_back().map(|(tail_nonce, pooled)| (pooled.priority(), tail_sender, *tail_nonce))
+            })
+            .collect();
+        tails.sort_unstable();
+        tails.into_iter()
+            .find(|(_, tail_sender, tail_nonce)| !(*tail_sender == sender && *tail_nonce < nonce))
+            .filter(|(tail_priority, _, _)| *tail_priority < priority)
+            .map(|(_, tail_sender, tail_nonce)| (tail_sender.clone(), tail_nonce))
+    }
+
+    /// Fee plus committed tokens, and satang payments, of the pooled transactions of `sender`
+    /// other than the one at `nonce`
+    fn pending_spend(&self, sender: &AccountId, nonce: u64) -> (Balance, Balance) {
+        self.senders.get(sender).into_iter()
+            .flat_map(|queue| queue.iter())
+            .filter(|(pooled_nonce, _)| **pooled_nonce != nonce)
+            .fold((0, 0), |(spend, payment), (_, pooled)| (
+                spend.saturating_add(pooled.envelope.calculate_fee()).saturating_add(pooled.envelope.committed_amount()),
+                payment.saturating_add(pooled.envelope.committed_payment()),
+            ))
+    }
+
+    fn remove_entry(&mut self, sender: &AccountId, nonce: u64) -> Option<EnergyTransactionEnvelope> {
+        let queue = self.senders.get_mut(sender)?;
+        let removed = queue.remove(&nonce)?;
+        if queue.is_empty() {
+            self.senders.remove(sender);
+        }
+        self.hashes.remove(&removed.envelope.hash);
+        Some(removed.envelope)
     }
 }
@@ -6,9 +6,14 @@
 use crate::types::*;
 use crate::utils::SystemResult;
 use crate::config::BlockchainConfig;
+use crate::blockchain::encoding;
+use crate::blockchain::ppa_contract::{PpaTerms, PPA_CONTRACT_CODE};
+use crate::blockchain::slashing::MisbehaviorEvidence;
+use crate::blockchain::smart_contracts::{ContractABI, ContractState};
+use crate::crypto::GridTokenXKeyPair;
+use ed25519_dalek::{Signature, VerifyingKey, PUBLIC_KEY_LENGTH};
 use serde::{Deserialize, Serialize};
 use crate::types::AccountId;
-use sha2::{Sha256, Digest};
 use std::collections::HashMap;
 use std::time::SystemTime;
 
@@ -74,6 +79,7 @@ pub enum EnergyTransaction {
     DeployContract {
         deployer: AccountId,
         contract_code: Vec<u8>,
+        abi: ContractABI,
         constructor_args: Vec<u8>,
         gas_limit: u64,
         signature: Vec<u8>,
@@ -127,6 +133,11 @@ pub enum EnergyTransaction {
         location: GridLocation,
         signature: Vec<u8>,
     },
+    /// Report an authority that broke the consensus rules
+    ReportMisbehavior {
+        reporter: AccountId,
+        evidence: MisbehaviorEvidence,
+    },
 }
 
 /// Vote choice for governance
@@ -155,14 +166,73 @@ pub struct ValidatorSignature {
     pub timestamp: SystemTime,
 }
 
+impl ValidatorSignature {
+    /// Attestation by a metering oracle that `producer` produced `record`
+    ///
+    /// # Panics
+    ///
+    /// If the record cannot be encoded, i.e. its timestamp is before the Unix epoch.
+    pub fn attest(oracle: &GridTokenXKeyPair, producer: &AccountId, record: &EnergyProductionRecord) -> Self {
+        let attestation_hash = record.attestation_hash(producer).expect("attested records must have a canonical encoding");
+        Self {
+            validator: oracle.account_id().to_string(),
+            signature: oracle.sign(&attestation_hash).to_bytes().to_vec(),
+            timestamp: record.timestamp,
+        }
+    }
+}
+
+/// Seller's consent to a trade: its public key followed by its signature over the trade terms
+///
+/// # Panics
+///
+/// If the trade cannot be encoded.
+pub fn sign_trade(trade: &EnergyTrade, seller: &GridTokenXKeyPair) -> Vec<u8> {
+    let terms_hash = encoding::hash(trade).expect("trades must have a canonical encoding");
+    let mut consent = seller.export_public_key_bytes().to_vec();
+    consent.extend_from_slice(&seller.sign(&terms_hash).to_bytes());
+    consent
+}
+
+/// Check that `seller_signature` is the trade seller's consent to exactly these terms
+pub fn check_trade_signature(trade: &EnergyTrade, seller_signature: &[u8]) -> Result<(), SignatureRejection> {
+    if seller_signature.is_empty() {
+        return Err(SignatureRejection::Unsigned);
+    }
+    if seller_signature.len() < PUBLIC_KEY_LENGTH {
+        return Err(SignatureRejection::Malformed);
+    }
+    let (public_key, signature) = seller_signature.split_at(PUBLIC_KEY_LENGTH);
+    let terms_hash = encoding::hash(trade).map_err(|_| SignatureRejection::Malformed)?;
+    verify_signed_by(&trade.seller_id, public_key, signature, &terms_hash)
+}
+
+/// Check that `signature` over `message` was made with `public_key`, the key of `expected`
+fn verify_signed_by(expected: &AccountId, public_key: &[u8], signature: &[u8], message: &[u8]) -> Result<(), SignatureRejection> {
+    let public_key: [u8; PUBLIC_KEY_LENGTH] = public_key.try_into()
+        .map_err(|_| SignatureRejection::Malformed)?;
+    let verifying_key = VerifyingKey::from_bytes(&public_key)
+        .map_err(|_| SignatureRejection::Malformed)?;
+    let signature = Signature::from_slice(signature)
+        .map_err(|_| SignatureRejection::Malformed)?;
+
+    let signer = crate::crypto::derive_account_id(&verifying_key)
+        .map_err(|_| SignatureRejection::Malformed)?;
+    if signer != *expected {
+        return Err(SignatureRejection::SignerMismatch { sender: expected.clone(), signer });
+    }
+    verifying_key.verify_strict(message, &signature)
+        .map_err(|_| SignatureRejection::InvalidSignature)
+}
+
 /// Producer certification
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProducerCertification {
     pub certificate_id: String,
     pub issuing_authority: String,
     pub valid_until: SystemTime,
-    pub renewable_percentage: f32,
-    pub efficiency_rating: f32,
+    pub renewable_percentage: FixedPoint,
+    pub efficiency_rating: FixedPoint,
 }
 
 /// Consumer consumption pattern
@@ -179,7 +249,7 @@ pub struct ConsumptionPattern {
 pub struct CarbonVerification {
     pub verifier: AccountId,
     pub methodology: String,
-    pub co2_reduced: f64, // in tons
+    pub co2_reduced: FixedPoint, // in tons
     pub verification_standard: String,
     pub timestamp: SystemTime,
 }
@@ -200,7 +270,27 @@ pub struct EnergyTransactionEnvelope {
     pub gas_price: Balance,
     pub gas_limit: u64,
     pub timestamp: SystemTime,
+    /// Hash of the whole signed envelope, see [`EnergyTransactionEnvelope::compute_hash`]
     pub hash: [u8; 32],
+    /// Public key of the sender's account
+    #[serde(default)]
+    pub public_key: Vec<u8>,
+    /// Sender's ed25519 signature over the signing hash
+    #[serde(default)]
+    pub signature: Vec<u8>,
+}
+
+/// Reason a transaction signature does not authorize its sender
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SignatureRejection {
+    #[error("transaction is not signed")]
+    Unsigned,
+    #[error("malformed public key or signature")]
+    Malformed,
+    #[error("signed by {signer} instead of the sender {sender}")]
+    SignerMismatch { sender: AccountId, signer: AccountId },
+    #[error("signature does not match the transaction")]
+    InvalidSignature,
 }
 
 /// Energy production record with detailed validation
@@ -211,12 +301,29 @@ pub struct EnergyProductionRecord {
     pub location: GridLocation,
     pub timestamp: SystemTime,
     pub verified: bool,
-    pub efficiency: f32,
+    pub efficiency: FixedPoint,
     pub weather_conditions: Option<WeatherConditions>,
     pub equipment_id: String,
     pub quality_metrics: EnergyQualityMetrics,
 }
 
+impl EnergyProductionRecord {
+    /// Hash a metering oracle signs to attest that `producer` produced this record
+    pub fn attestation_hash(&self, producer: &AccountId) -> Result<[u8; 32], encoding::EncodingError> {
+        encoding::hash(&(producer, self))
+    }
+}
+
+/// Production as it reached the chain, which power purchase agreements settle against
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeteredProduction {
+    /// Timestamp of the block the production was reported in
+    pub reported_at: u64,
+    pub amount: EnergyAmount,
+    /// Part of the amount already counted toward an agreement
+    pub claimed: EnergyAmount,
+}
+
 /// Energy consumption record
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EnergyConsumptionRecord {
@@ -231,20 +338,20 @@ pub struct EnergyConsumptionRecord {
 /// Weather conditions affecting energy production
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WeatherConditions {
-    pub temperature: f32,
-    pub humidity: f32,
-    pub wind_speed: f32,
-    pub solar_irradiance: f32,
-    pub cloud_cover: f32,
+    pub temperature: FixedPoint,
+    pub humidity: FixedPoint,
+    pub wind_speed: FixedPoint,
+    pub solar_irradiance: FixedPoint,
+    pub cloud_cover: FixedPoint,
 }
 
 /// Energy quality metrics
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EnergyQualityMetrics {
-    pub voltage: f32,
-    pub frequency: f32,
-    pub power_factor: f32,
-    pub harmonic_distortion: f32,
+    pub voltage: FixedPoint,
+    pub frequency: FixedPoint,
+    pub power_factor: FixedPoint,
+    pub harmonic_distortion: FixedPoint,
 }
 
 /// Consumer type classification
@@ -278,6 +385,15 @@ pub struct EnergyBalanceState {
     pub nonce: u64,
     /// Last update timestamp
     pub last_updated: SystemTime,
+    /// Code and storage of the contract deployed at this account, if any
+    #[serde(default)]
+    pub contract: Option<ContractState>,
+    /// Satang held by the account, which trades and agreements are paid in
+    #[serde(default)]
+    pub currency_balance: Balance,
+    /// Production reported by the account, in the order it reached the chain
+    #[serde(default)]
+    pub metered_production: Vec<MeteredProduction>,
 }
 
 impl EnergyBalanceState {
@@ -293,6 +409,9 @@ impl EnergyBalanceState {
             carbon_credits: 0,
             nonce: 0,
             last_updated: SystemTime::now(),
+            contract: None,
+            currency_balance: 0,
+            metered_production: Vec::new(),
         }
     }
 
@@ -304,20 +423,41 @@ impl EnergyBalanceState {
     }
 
     /// Add energy balance from production
-    pub fn add_production(&mut self, production: EnergyProductionRecord) {
-        let amount = production.amount as Balance;
+    ///
+    /// Fails without changing the balance unless the amount is a whole number of kWh.
+    pub fn add_production(&mut self, production: EnergyProductionRecord) -> SystemResult<Balance> {
+        let amount = whole_tokens(production.amount)?;
         *self.energy_balances.entry(production.energy_type.clone()).or_insert(0) += amount;
         self.total_balance += amount;
         self.production_history.push(production);
         self.last_updated = SystemTime::now();
+        Ok(amount)
     }
 
     /// Remove energy balance from consumption
-    pub fn add_consumption(&mut self, consumption: EnergyConsumptionRecord) {
-        let amount = consumption.amount as Balance;
+    ///
+    /// Fails without changing the balance unless the amount is a whole number of kWh.
+    pub fn add_consumption(&mut self, consumption: EnergyConsumptionRecord) -> SystemResult<Balance> {
+        let amount = whole_tokens(consumption.amount)?;
         self.total_balance = self.total_balance.saturating_sub(amount);
         self.consumption_history.push(consumption);
         self.last_updated = SystemTime::now();
+        Ok(amount)
+    }
+
+    /// Pay `amount` satang out of the currency balance
+    pub fn pay(&mut self, amount: Balance) -> SystemResult<()> {
+        self.currency_balance = self.currency_balance.checked_sub(amount).ok_or_else(|| {
+            crate::utils::error::SystemError::Trading("Insufficient currency balance for payment".to_string())
+        })?;
+        self.last_updated = SystemTime::now();
+        Ok(())
+    }
+
+    /// Receive a payment of `amount` satang
+    pub fn receive_payment(&mut self, amount: Balance) {
+        self.currency_balance = self.currency_balance.saturating_add(amount);
+        self.last_updated = SystemTime::now();
     }
 
     /// Transfer energy to another account
@@ -373,45 +513,172 @@ impl EnergyBalanceState {
         Ok(())
     }
 
+    /// Get total available balance across all energy types
+    pub fn get_total_available_balance(&self) -> Balance {
+        EnergySource::ALL.iter()
+            .map(|energy_type| self.get_available_balance(energy_type))
+            .fold(0, Balance::saturating_add)
+    }
+
+    /// Spend tokens regardless of energy source
+    ///
+    /// Sources are drained in canonical order so every node debits the same sub-balances.
+    pub fn spend_any(&mut self, amount: Balance) -> SystemResult<()> {
+        if self.get_total_available_balance() < amount {
+            return Err(crate::utils::error::SystemError::Trading(
+                "Insufficient balance".to_string()
+            ));
+        }
+
+        let mut remaining = amount;
+        for energy_type in EnergySource::ALL.iter() {
+            if remaining == 0 {
+                break;
+            }
+            let debit = self.get_available_balance(energy_type).min(remaining);
+            if debit > 0 {
+                self.transfer(energy_type.clone(), debit)?;
+                remaining -= debit;
+            }
+        }
+        Ok(())
+    }
+
+    /// Lock tokens regardless of energy source, in canonical source order
+    pub fn lock_any(&mut self, amount: Balance) -> SystemResult<()> {
+        if self.get_total_available_balance() < amount {
+            return Err(crate::utils::error::SystemError::Trading(
+                "Insufficient balance to lock".to_string()
+            ));
+        }
+
+        let mut remaining = amount;
+        for energy_type in EnergySource::ALL.iter() {
+            if remaining == 0 {
+                break;
+            }
+            let lock = self.get_available_balance(energy_type).min(remaining);
+            if lock > 0 {
+                self.lock_balance(energy_type.clone(), lock)?;
+                remaining -= lock;
+            }
+        }
+        Ok(())
+    }
+
+    /// Unlock tokens previously locked with `lock_any`, in canonical source order
+    pub fn unlock_any(&mut self, amount: Balance) -> SystemResult<()> {
+        let total_locked = self.locked_balances.values().fold(0, |acc: Balance, locked| acc.saturating_add(*locked));
+        if total_locked < amount {
+            return Err(crate::utils::error::SystemError::Trading(
+                "Cannot unlock more than locked amount".to_string()
+            ));
+        }
+
+        let mut remaining = amount;
+        for energy_type in EnergySource::ALL.iter() {
+            if remaining == 0 {
+                break;
+            }
+            let unlock = self.locked_balances.get(energy_type).copied().unwrap_or(0).min(remaining);
+            if unlock > 0 {
+                self.unlock_balance(energy_type.clone(), unlock)?;
+                remaining -= unlock;
+            }
+        }
+        Ok(())
+    }
+
     /// Increment nonce
     pub fn increment_nonce(&mut self) {
         self.nonce += 1;
         self.last_updated = SystemTime::now();
     }
+
+    /// Count up to `limit` of the production reported in blocks with timestamps in
+    /// `[from, to)` toward an agreement, returning the amount counted
+    ///
+    /// Production is counted in the order it was reported, and never twice.
+    pub fn claim_production(&mut self, from: u64, to: u64, limit: EnergyAmount) -> EnergyAmount {
+        let mut claimed = EnergyAmount::ZERO;
+        for production in self.metered_production.iter_mut().filter(|production| (from..to).contains(&production.reported_at)) {
+            let claim = production.amount.saturating_sub(production.claimed).min(limit.saturating_sub(claimed));
+            production.claimed = production.claimed.saturating_add(claim);
+            claimed = claimed.saturating_add(claim);
+        }
+        claimed
+    }
+}
+
+/// Energy tokens `amount` is worth, failing unless it is a whole number of kWh
+pub(crate) fn whole_tokens(amount: EnergyAmount) -> SystemResult<Balance> {
+    amount.tokens().ok_or_else(|| {
+        crate::utils::error::SystemError::InvalidInput(format!("{} is not a whole number of tokens", amount))
+    })
 }
 
 impl EnergyTransactionEnvelope {
     /// Create new transaction envelope
+    ///
+    /// # Panics
+    ///
+    /// If the system clock is set before the Unix epoch, since such a timestamp has no
+    /// canonical encoding.
     pub fn new(transaction: EnergyTransaction, nonce: u64) -> Self {
-        let serialized = serde_json::to_vec(&transaction).unwrap_or_default();
-        let mut hasher = Sha256::new();
-        hasher.update(&serialized);
-        let hash_result = hasher.finalize();
-        
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&hash_result);
-
-        Self {
+        let mut envelope = Self {
             transaction,
             nonce,
             gas_price: 1000, // Default gas price
             gas_limit: 100000, // Default gas limit
             timestamp: SystemTime::now(),
-            hash,
+            hash: [0; 32],
+            public_key: Vec::new(),
+            signature: Vec::new(),
+        };
+        envelope.hash = envelope.compute_hash().expect("transactions built from a valid clock always encode");
+        envelope
+    }
+
+    /// Hash the sender signs: the transaction together with its nonce, gas terms and timestamp
+    pub fn signing_hash(&self) -> Result<[u8; 32], encoding::EncodingError> {
+        encoding::hash(&(&self.transaction, self.nonce, self.gas_price, self.gas_limit, &self.timestamp))
+    }
+
+    /// Hash identifying the transaction: the signed content together with the key and
+    /// signature, so two differently signed envelopes never share a hash
+    pub fn compute_hash(&self) -> Result<[u8; 32], encoding::EncodingError> {
+        encoding::hash(&(self.signing_hash()?, &self.public_key, &self.signature))
+    }
+
+    /// Sign the transaction with the sender's keypair and update its hash
+    ///
+    /// # Panics
+    ///
+    /// If the transaction cannot be encoded, i.e. its timestamp is before the Unix epoch.
+    pub fn sign(&mut self, keypair: &GridTokenXKeyPair) {
+        let signing_hash = self.signing_hash().expect("signed transactions must have a canonical encoding");
+        self.public_key = keypair.export_public_key_bytes().to_vec();
+        self.signature = keypair.sign(&signing_hash).to_bytes().to_vec();
+        self.hash = self.compute_hash().expect("signed transactions must have a canonical encoding");
+    }
+
+    /// Whether the envelope's hash matches its contents
+    pub fn has_valid_hash(&self) -> bool {
+        self.compute_hash().is_ok_and(|hash| hash == self.hash)
+    }
+
+    /// Check that the transaction is signed by the key its sender account is derived from
+    pub fn check_signature(&self) -> Result<(), SignatureRejection> {
+        if self.signature.is_empty() || self.public_key.is_empty() {
+            return Err(SignatureRejection::Unsigned);
         }
+        let signing_hash = self.signing_hash().map_err(|_| SignatureRejection::Malformed)?;
+        verify_signed_by(&self.get_sender(), &self.public_key, &self.signature, &signing_hash)
     }
 
     /// Verify transaction signature
     pub fn verify_signature(&self) -> SystemResult<bool> {
-        match &self.transaction {
-            EnergyTransaction::Transfer { from, to, amount, .. } => {
-                Ok(from != to && *amount > 0)
-            }
-            EnergyTransaction::PlaceOrder { order, .. } => {
-                Ok(order.energy_amount > 0.0 && order.price_per_unit > 0)
-            }
-            _ => Ok(true),
-        }
+        Ok(self.check_signature().is_ok())
     }
 
     /// Calculate transaction fee based on gas usage
@@ -437,6 +704,7 @@ impl EnergyTransactionEnvelope {
             EnergyTransaction::UpdateGridStatus { .. } => 30000,
             EnergyTransaction::CarbonCredit { .. } => 35000,
             EnergyTransaction::EnergyStorage { .. } => 40000,
+            EnergyTransaction::ReportMisbehavior { .. } => 30000,
         }
     }
 
@@ -454,6 +722,37 @@ impl EnergyTransactionEnvelope {
         }
     }
 
+    /// Tokens the sender transfers, burns, stakes or locks on top of the fee
+    ///
+    /// Energy amounts that are not a whole number of kWh commit nothing; executing them fails.
+    pub fn committed_amount(&self) -> Balance {
+        match &self.transaction {
+            EnergyTransaction::Transfer { amount, .. } => *amount,
+            EnergyTransaction::ReportConsumption { consumption_record, .. } => consumption_record.amount.tokens().unwrap_or(0),
+            EnergyTransaction::GovernanceProposal { stake_amount, .. } => *stake_amount,
+            EnergyTransaction::EnergyStorage { action: StorageAction::Store { .. } | StorageAction::Reserve { .. }, amount, .. } => {
+                amount.tokens().unwrap_or(0)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Satang the sender pays out of its currency balance
+    pub fn committed_payment(&self) -> Balance {
+        match &self.transaction {
+            EnergyTransaction::ExecuteTrade { trade, .. } => trade.total_price,
+            EnergyTransaction::DeployContract { contract_code, constructor_args, .. } if contract_code == PPA_CONTRACT_CODE => {
+                PpaTerms::from_args(constructor_args).ok().and_then(|terms| terms.collateral()).unwrap_or(0)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Size of the transaction as stored in a block, in bytes
+    pub fn encoded_size(&self) -> usize {
+        encoding::encode(self).map(|encoded| encoded.len()).unwrap_or(0)
+    }
+
     /// Get transaction sender account ID
     pub fn get_sender(&self) -> AccountId {
         match &self.transaction {
@@ -472,6 +771,7 @@ impl EnergyTransactionEnvelope {
             EnergyTransaction::UpdateGridStatus { authority, .. } => authority.clone(),
             EnergyTransaction::CarbonCredit { issuer, .. } => issuer.clone(),
             EnergyTransaction::EnergyStorage { storage_operator, .. } => storage_operator.clone(),
+            EnergyTransaction::ReportMisbehavior { reporter, .. } => reporter.clone(),
         }
     }
 }
@@ -513,15 +813,15 @@ impl EnergyTransactionValidator {
         // Transaction-specific validation
         match &transaction.transaction {
             EnergyTransaction::ExecuteTrade { trade, .. } => {
-                if trade.energy_amount == 0.0 {
+                if trade.energy_amount.is_zero() {
                     return TransactionValidationResult::Invalid("Energy amount cannot be zero".to_string());
                 }
-                if trade.price_per_unit == 0 {
+                if trade.price_per_unit.is_zero() {
                     return TransactionValidationResult::Invalid("Price per unit cannot be zero".to_string());
                 }
             }
             EnergyTransaction::ReportProduction { production_record, .. } => {
-                if production_record.amount == 0.0 {
+                if production_record.amount.is_zero() {
                     return TransactionValidationResult::Invalid("Production amount cannot be zero".to_string());
                 }
             }
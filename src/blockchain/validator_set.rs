@@ -0,0 +1,259 @@
+//! # Validator Set Governance
+//!
+//! Authorities join and leave the validator set through governance proposals decided
+//! on-chain. A validator-set proposal passes once more than half of the active authorities
+//! have voted for it before its voting deadline. Passed changes wait for the next epoch
+//! boundary, every `validator_rotation_interval` blocks. The boundary block records the
+//! change in its header, and every node switches to the new set right after that block.
+//!
+//! Additions that would grow the active set beyond `max_validators` stay queued until an
+//! authority leaves.
+
+use crate::blockchain::consensus::{Authority, EnergyBlock};
+use crate::blockchain::state_transition::ReceiptStatus;
+use crate::blockchain::transactions::{EnergyTransaction, VoteChoice};
+use crate::types::{AccountId, ProposalType};
+use crate::utils::{SystemError, SystemResult};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use uuid::Uuid;
+
+/// Authority joining the validator set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidatorCandidate {
+    /// Validator account ID
+    pub account_id: AccountId,
+    /// Validator name/organization
+    pub name: String,
+    /// Public key the validator signs blocks with
+    pub public_key: Vec<u8>,
+}
+
+/// Validator-set change an epoch boundary block records in its header
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ValidatorSetChange {
+    /// Authorities joining the set, in the order their proposals passed
+    pub added: Vec<ValidatorCandidate>,
+    /// Authorities leaving the set, in the order their proposals passed
+    pub removed: Vec<AccountId>,
+}
+
+/// Membership change a single proposal asks for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum MembershipChange {
+    Add(ValidatorCandidate),
+    Remove(AccountId),
+}
+
+/// Validator-set proposal still collecting votes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct OpenProposal {
+    change: MembershipChange,
+    /// Voting deadline as a unix timestamp
+    deadline: i64,
+    /// Authorities that voted for the proposal
+    approvals: BTreeSet<AccountId>,
+}
+
+/// Validator-set proposals under vote and passed changes waiting for an epoch boundary
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ValidatorProposals {
+    open: BTreeMap<Uuid, OpenProposal>,
+    /// Passed changes in the order they passed
+    passed: Vec<(Uuid, MembershipChange)>,
+}
+
+/// Epoch rules and the proposals deciding upcoming validator-set changes
+#[derive(Debug, Clone)]
+pub struct ValidatorSetGovernance {
+    /// Blocks per epoch; changes only take effect after a block at a multiple of it
+    epoch_length: u32,
+    /// Maximum number of active authorities
+    max_validators: usize,
+    proposals: ValidatorProposals,
+}
+
+impl ValidatorCandidate {
+    /// Candidate an `AddValidator` proposal asks to admit
+    pub fn proposed(proposal_type: &ProposalType) -> Option<Self> {
+        match proposal_type {
+            ProposalType::AddValidator { account_id, name, public_key } => Some(Self {
+                account_id: account_id.clone(),
+                name: name.clone(),
+                public_key: public_key.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Check that the public key is an ed25519 key and the account ID is derived from it,
+    /// like those of genesis authorities
+    pub fn verify(&self) -> SystemResult<()> {
+        let key: [u8; 32] = self.public_key.as_slice().try_into().map_err(|_| {
+            SystemError::Validation(format!("Validator {} needs a 32 byte public key", self.account_id))
+        })?;
+        let verifying_key = VerifyingKey::from_bytes(&key).map_err(|e| {
+            SystemError::Validation(format!("Invalid public key for validator {}: {}", self.account_id, e))
+        })?;
+        if crate::crypto::derive_account_id(&verifying_key)? != self.account_id {
+            return Err(SystemError::Validation(format!(
+                "Account {} is not derived from public key {}", self.account_id, hex::encode(key)
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl ValidatorSetChange {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl ValidatorProposals {
+    /// Number of proposals still collecting votes
+    pub fn open_count(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Number of passed changes waiting for an epoch boundary
+    pub fn passed_count(&self) -> usize {
+        self.passed.len()
+    }
+}
+
+impl ValidatorSetGovernance {
+    pub fn new(epoch_length: u64, max_validators: u32) -> Self {
+        Self {
+            epoch_length: epoch_length.clamp(1, u32::MAX as u64) as u32,
+            max_validators: max_validators as usize,
+            proposals: ValidatorProposals::default(),
+        }
+    }
+
+    /// Proposals under vote and passed changes
+    pub fn proposals(&self) -> &ValidatorProposals {
+        &self.proposals
+    }
+
+    /// Replace the proposals, e.g. with those of a state snapshot
+    pub fn restore(&mut self, proposals: ValidatorProposals) {
+        self.proposals = proposals;
+    }
+
+    /// Whether validator-set changes take effect after the block at `number`
+    pub fn is_epoch_boundary(&self, number: u32) -> bool {
+        number > 0 && number.is_multiple_of(self.epoch_length)
+    }
+
+    /// Change the block at `number` has to record, given the authorities before it
+    pub fn change_for(&self, number: u32, authorities: &HashMap<AccountId, Authority>) -> Option<ValidatorSetChange> {
+        if !self.is_epoch_boundary(number) {
+            return None;
+        }
+        let (change, _) = self.plan(authorities);
+        (!change.is_empty()).then_some(change)
+    }
+
+    /// Record the validator-set proposals and votes of a block joining the canonical chain
+    ///
+    /// At an epoch boundary the passed changes are settled first, against the authorities
+    /// the block was produced under. Returns the proposals that passed in this block.
+    pub fn record_block(&mut self, block: &EnergyBlock, authorities: &HashMap<AccountId, Authority>) -> Vec<Uuid> {
+        if self.is_epoch_boundary(block.header.number) {
+            let (_, deferred) = self.plan(authorities);
+            self.proposals.passed = deferred;
+        }
+
+        let timestamp = block.header.timestamp as i64;
+        for (tx, receipt) in block.transactions.iter().zip(&block.receipts) {
+            if receipt.status != ReceiptStatus::Success {
+                continue;
+            }
+            match &tx.transaction {
+                EnergyTransaction::GovernanceProposal { proposal, .. } => {
+                    let change = match &proposal.proposal_type {
+                        ProposalType::AddValidator { account_id, name, public_key } => MembershipChange::Add(ValidatorCandidate {
+                            account_id: account_id.clone(),
+                            name: name.clone(),
+                            public_key: public_key.clone(),
+                        }),
+                        ProposalType::RemoveValidator { account_id } => MembershipChange::Remove(account_id.clone()),
+                        _ => continue,
+                    };
+                    if self.proposals.passed.iter().any(|(id, _)| *id == proposal.id) {
+                        continue;
+                    }
+                    self.proposals.open.entry(proposal.id).or_insert(OpenProposal {
+                        change,
+                        deadline: proposal.voting_deadline.timestamp(),
+                        approvals: BTreeSet::new(),
+                    });
+                }
+                EnergyTransaction::Vote { voter, proposal_id, choice: VoteChoice::For, .. } => {
+                    let is_authority = authorities.get(voter).is_some_and(|authority| authority.is_active);
+                    if let Some(open) = self.proposals.open.get_mut(proposal_id) {
+                        if is_authority && timestamp <= open.deadline {
+                            open.approvals.insert(voter.clone());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let active: BTreeSet<&AccountId> = authorities.iter()
+            .filter(|(_, authority)| authority.is_active)
+            .map(|(account_id, _)| account_id)
+            .collect();
+        let passed: Vec<Uuid> = self.proposals.open.iter()
+            .filter(|(_, open)| open.approvals.iter().filter(|voter| active.contains(voter)).count() * 2 > active.len())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &passed {
+            if let Some(open) = self.proposals.open.remove(id) {
+                self.proposals.passed.push((*id, open.change));
+            }
+        }
+        self.proposals.open.retain(|_, open| open.deadline >= timestamp);
+        passed
+    }
+
+    /// Split the passed changes into the change to apply now and those deferred because
+    /// the validator set is full
+    fn plan(&self, authorities: &HashMap<AccountId, Authority>) -> (ValidatorSetChange, Vec<(Uuid, MembershipChange)>) {
+        let mut active: BTreeSet<&AccountId> = authorities.iter()
+            .filter(|(_, authority)| authority.is_active)
+            .map(|(account_id, _)| account_id)
+            .collect();
+        let mut change = ValidatorSetChange::default();
+        let mut deferred = Vec::new();
+
+        for (id, membership) in &self.proposals.passed {
+            match membership {
+                MembershipChange::Remove(account_id) => {
+                    active.remove(account_id);
+                    if let Some(position) = change.added.iter().position(|added| added.account_id == *account_id) {
+                        change.added.remove(position);
+                    } else if authorities.contains_key(account_id) && !change.removed.contains(account_id) {
+                        change.removed.push(account_id.clone());
+                    }
+                }
+                MembershipChange::Add(candidate) => {
+                    if active.contains(&candidate.account_id) {
+                        continue;
+                    }
+                    if active.len() >= self.max_validators {
+                        deferred.push((*id, membership.clone()));
+                        continue;
+                    }
+                    active.insert(&candidate.account_id);
+                    change.removed.retain(|removed| *removed != candidate.account_id);
+                    change.added.push(candidate.clone());
+                }
+            }
+        }
+        (change, deferred)
+    }
+}
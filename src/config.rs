@@ -3,6 +3,7 @@
 //! This module handles all system configuration including database, blockchain,
 //! grid integration, security, trading, governance, and oracle settings.
 
+use crate::blockchain::contract_runtime::GasSchedule;
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
 use std::env;
@@ -40,11 +41,17 @@ pub struct BlockchainConfig {
     pub rpc_port: u16,
     pub ws_port: u16,
     pub p2p_port: u16,
+    /// Chain spec file path, or "dev" for the built-in development chain
     pub chain_spec: String,
     /// Consensus algorithm (always PoA)
     pub consensus_algorithm: String,
     /// Block production time in seconds
     pub block_time: u64,
+    /// Seconds after the block time at which a missed slot passes to the next validator
+    /// (0 waits for the scheduled validator indefinitely)
+    pub slot_timeout: u64,
+    /// Produce blocks without transactions instead of skipping the slot
+    pub produce_empty_blocks: bool,
     /// Validator rotation interval in blocks
     pub validator_rotation_interval: u64,
     /// Maximum number of validators
@@ -55,8 +62,18 @@ pub struct BlockchainConfig {
     pub gas_limit: u64,
     /// Gas price in tokens
     pub gas_price: u128,
+    /// Maximum number of pending transactions held in the transaction pool
+    pub transaction_pool_size: usize,
+    /// Seconds a pending transaction may wait for inclusion before it expires (0 never expires)
+    pub transaction_lifetime: u64,
+    /// Maximum total size in bytes of the transactions a produced block includes
+    pub block_size_limit: usize,
     /// Smart contract VM settings
     pub smart_contract_vm: SmartContractConfig,
+    /// Directory for the on-disk block store; the chain is kept in memory only when unset
+    pub data_dir: Option<String>,
+    /// Blocks between state snapshots (0 disables snapshots)
+    pub snapshot_interval: u64,
 }
 
 /// Smart contract configuration
@@ -70,6 +87,9 @@ pub struct SmartContractConfig {
     pub enable_wasm: bool,
     /// Contract storage size limit
     pub max_storage_size: usize,
+    /// Gas charged for contract execution, taken from the chain spec
+    #[serde(default)]
+    pub gas_schedule: GasSchedule,
 }
 
 /// Grid integration configuration
@@ -285,7 +305,7 @@ impl BlockchainConfig {
                 .unwrap_or_else(|_| "30333".to_string())
                 .parse()?,
             chain_spec: env::var("BLOCKCHAIN_CHAIN_SPEC").unwrap_or_else(|_| 
-                "thai-energy-testnet.json".to_string()
+                "dev".to_string()
             ),
             consensus_algorithm: env::var("CONSENSUS_ALGORITHM").unwrap_or_else(|_| 
                 "proof_of_authority".to_string()
@@ -293,6 +313,12 @@ impl BlockchainConfig {
             block_time: env::var("BLOCK_TIME")
                 .unwrap_or_else(|_| "5".to_string())
                 .parse()?,
+            slot_timeout: env::var("BLOCKCHAIN_SLOT_TIMEOUT")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()?,
+            produce_empty_blocks: env::var("BLOCKCHAIN_PRODUCE_EMPTY_BLOCKS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
             validator_rotation_interval: env::var("VALIDATOR_ROTATION_INTERVAL")
                 .unwrap_or_else(|_| "15".to_string())
                 .parse()?,
@@ -308,6 +334,15 @@ impl BlockchainConfig {
             gas_price: env::var("BLOCKCHAIN_GAS_PRICE")
                 .unwrap_or_else(|_| "1000000000".to_string())
                 .parse()?,
+            transaction_pool_size: env::var("BLOCKCHAIN_TRANSACTION_POOL_SIZE")
+                .unwrap_or_else(|_| "4096".to_string())
+                .parse()?,
+            transaction_lifetime: env::var("BLOCKCHAIN_TRANSACTION_LIFETIME")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()?,
+            block_size_limit: env::var("BLOCKCHAIN_BLOCK_SIZE_LIMIT")
+                .unwrap_or_else(|_| "1048576".to_string())
+                .parse()?,
             smart_contract_vm: SmartContractConfig {
                 max_gas_limit: env::var("SMART_CONTRACT_MAX_GAS_LIMIT")
                     .unwrap_or_else(|_| "10000000".to_string())
@@ -321,7 +356,12 @@ impl BlockchainConfig {
                 max_storage_size: env::var("SMART_CONTRACT_MAX_STORAGE_SIZE")
                     .unwrap_or_else(|_| "10485760".to_string())
                     .parse()?,
+                gas_schedule: GasSchedule::default(),
             },
+            data_dir: env::var("BLOCKCHAIN_DATA_DIR").ok().filter(|s| !s.is_empty()),
+            snapshot_interval: env::var("BLOCKCHAIN_SNAPSHOT_INTERVAL")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()?,
         })
     }
 }
@@ -542,12 +582,19 @@ impl Default for BlockchainConfig {
             chain_spec: "dev".to_string(),
             consensus_algorithm: "proof_of_authority".to_string(),
             block_time: 6,
+            slot_timeout: 12,
+            produce_empty_blocks: false,
             validator_rotation_interval: 100,
             max_validators: 10,
             authority_threshold: 7,
             gas_limit: 1000000,
             gas_price: 1000000000,
+            transaction_pool_size: 4096,
+            transaction_lifetime: 3600,
+            block_size_limit: 1048576,
             smart_contract_vm: SmartContractConfig::default(),
+            data_dir: None,
+            snapshot_interval: 1000,
         }
     }
 }
@@ -559,6 +606,7 @@ impl Default for SmartContractConfig {
             max_contract_size: 1048576,
             enable_wasm: true,
             max_storage_size: 10485760,
+            gas_schedule: GasSchedule::default(),
         }
     }
 }
@@ -3,6 +3,7 @@
 //! This module provides secure key management, AccountId derivation, and transaction signing
 //! for the GridTokenX blockchain system.
 
+use crate::blockchain::encoding;
 use crate::utils::SystemResult;
 use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer, Verifier};
 use serde::{Deserialize, Serialize};
@@ -49,6 +50,34 @@ impl GridTokenXKeyPair {
         })
     }
     
+    /// Create keypair from a configured node key
+    ///
+    /// A 32-byte hex string (optionally `0x`-prefixed) is used as the private key directly.
+    /// Any other value is treated as a seed phrase and hashed with SHA256 so that
+    /// development configurations still yield a stable node identity.
+    pub fn from_node_key(node_key: &str) -> SystemResult<Self> {
+        let trimmed = node_key.trim();
+        let hex_part = trimmed.strip_prefix("0x").unwrap_or(trimmed);
+
+        let private_key_bytes: [u8; 32] = match hex::decode(hex_part) {
+            Ok(bytes) if bytes.len() == 32 => {
+                let mut key = [0u8; 32];
+                key.copy_from_slice(&bytes);
+                key
+            }
+            _ => {
+                if trimmed.is_empty() {
+                    return Err(crate::SystemError::Configuration("Node key cannot be empty".to_string()));
+                }
+                let mut hasher = Sha256::new();
+                hasher.update(trimmed.as_bytes());
+                hasher.finalize().into()
+            }
+        };
+
+        Self::from_private_key_bytes(&private_key_bytes)
+    }
+
     /// Get the AccountId
     pub fn account_id(&self) -> &str {
         &self.account_id
@@ -86,14 +115,13 @@ impl GridTokenXKeyPair {
     
     /// Sign a transaction and include AccountId verification
     pub fn sign_transaction<T: Serialize>(&self, transaction: &T) -> SystemResult<TransactionSignature> {
-        // Serialize transaction
-        let transaction_json = serde_json::to_string(transaction)
-            .map_err(|e| crate::SystemError::Internal(format!("Failed to serialize transaction: {}", e)))?;
-        let transaction_bytes = transaction_json.as_bytes();
+        // Encode transaction canonically
+        let transaction_bytes = encoding::encode(transaction)
+            .map_err(|e| crate::SystemError::Internal(format!("Failed to encode transaction: {}", e)))?;
         
         // Create message hash
         let mut hasher = Sha256::new();
-        hasher.update(transaction_bytes);
+        hasher.update(&transaction_bytes);
         hasher.update(self.account_id.as_bytes()); // Include AccountId
         let message_hash = hasher.finalize();
         
@@ -211,12 +239,11 @@ impl AccountManager {
         }
         
         // Recreate message hash
-        let transaction_json = serde_json::to_string(transaction)
-            .map_err(|e| crate::SystemError::Internal(format!("Failed to serialize transaction: {}", e)))?;
-        let transaction_bytes = transaction_json.as_bytes();
+        let transaction_bytes = encoding::encode(transaction)
+            .map_err(|e| crate::SystemError::Internal(format!("Failed to encode transaction: {}", e)))?;
         
         let mut hasher = Sha256::new();
-        hasher.update(transaction_bytes);
+        hasher.update(&transaction_bytes);
         hasher.update(signature.account_id.as_bytes());
         let message_hash = hasher.finalize();
         
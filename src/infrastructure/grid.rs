@@ -49,16 +49,16 @@ impl GridManager {
         // Simulate API call to grid monitoring system
         let status = GridStatus {
             region: location.region.clone(),
-            current_load: 7500.0,
-            max_capacity: 10000.0,
-            health: 0.85,
+            current_load: EnergyAmount::from_wh(7_500_000),
+            max_capacity: EnergyAmount::from_wh(10_000_000),
+            health: FixedPoint::from_millionths(850_000),
             last_updated: crate::utils::now(),
             // Additional fields for compatibility
             location: location.clone(),
-            capacity: 10000.0,
+            capacity: EnergyAmount::from_wh(10_000_000),
             congestion_level: CongestionLevel::Medium,
-            stability_score: 0.85,
-            outage_risk: 0.15,
+            stability_score: FixedPoint::from_millionths(850_000),
+            outage_risk: FixedPoint::from_millionths(150_000),
             updated_at: crate::utils::now(),
         };
         
@@ -68,14 +68,14 @@ impl GridManager {
     /// Check grid capacity for new load
     pub async fn check_capacity(&self, location: &GridLocation, load: EnergyAmount) -> SystemResult<bool> {
         let status = self.get_grid_status(location).await?;
-        Ok(status.current_load + load <= status.capacity)
+        Ok(status.current_load.checked_add(load).is_some_and(|total| total <= status.capacity))
     }
     
     /// Report energy production to grid
     pub async fn report_production(&self, location: &GridLocation, amount: EnergyAmount) -> SystemResult<()> {
         crate::utils::logging::log_info(
             "GridManager",
-            &format!("Reporting {} kWh production at {:?}", amount, location)
+            &format!("Reporting {} production at {:?}", amount, location)
         );
         
         // Send production data to grid management system
@@ -88,7 +88,7 @@ impl GridManager {
     pub async fn report_consumption(&self, location: &GridLocation, amount: EnergyAmount) -> SystemResult<()> {
         crate::utils::logging::log_info(
             "GridManager",
-            &format!("Reporting {} kWh consumption at {:?}", amount, location)
+            &format!("Reporting {} consumption at {:?}", amount, location)
         );
         
         // Send consumption data to grid management system
@@ -144,7 +144,7 @@ impl GridManager {
         // Simulate API call to grid management system
         crate::utils::logging::log_debug(
             "GridManager",
-            &format!("Sending {} data: {} kWh to {}", data_type, amount, self.config.endpoint)
+            &format!("Sending {} data: {} to {}", data_type, amount, self.config.endpoint)
         );
         
         // TODO: Implement actual HTTP client for grid API
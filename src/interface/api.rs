@@ -7,6 +7,7 @@ use crate::application::trading::TradingService;
 use crate::application::grid::GridService;
 use crate::application::governance::GovernanceService;
 use crate::application::oracle::OracleService;
+use crate::blockchain::node::BlockchainNode;
 use crate::infrastructure::security::SecurityManager;
 use crate::utils::SystemResult;
 use std::sync::Arc;
@@ -15,6 +16,7 @@ use tokio::sync::RwLock;
 /// Blockchain interface for direct blockchain interactions
 /// This provides programmatic access to blockchain functionality without HTTP layer
 pub struct BlockchainInterface {
+    blockchain_node: Arc<BlockchainNode>,
     trading_service: Arc<TradingService>,
     grid_service: Arc<GridService>,
     governance_service: Arc<GovernanceService>,
@@ -25,6 +27,7 @@ pub struct BlockchainInterface {
 
 impl BlockchainInterface {
     pub async fn new(
+        blockchain_node: Arc<BlockchainNode>,
         trading_service: Arc<TradingService>,
         grid_service: Arc<GridService>,
         governance_service: Arc<GovernanceService>,
@@ -32,6 +35,7 @@ impl BlockchainInterface {
         security_manager: Arc<SecurityManager>,
     ) -> SystemResult<Self> {
         Ok(Self {
+            blockchain_node,
             trading_service,
             grid_service,
             governance_service,
@@ -74,11 +78,15 @@ impl BlockchainInterface {
     
     /// Get blockchain status
     pub async fn get_blockchain_status(&self) -> SystemResult<BlockchainStatus> {
+        let consensus = self.blockchain_node.engine().consensus();
+        
         Ok(BlockchainStatus {
             system_status: "running".to_string(),
             service: "Thai Energy Trading Blockchain".to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
             timestamp: crate::utils::now().timestamp() as u64,
+            block_height: consensus.chain_status().await.best_number,
+            finalized_height: consensus.finalized_height(),
         })
     }
 }
@@ -90,4 +98,8 @@ pub struct BlockchainStatus {
     pub service: String,
     pub version: String,
     pub timestamp: u64,
+    /// Number of the chain head
+    pub block_height: u32,
+    /// Number of the highest block finalized by authority precommits
+    pub finalized_height: u32,
 }
@@ -44,8 +44,10 @@ impl<T> ApiResponse<T> {
 #[derive(Debug, Deserialize)]
 pub struct PlaceOrderRequest {
     pub order_type: OrderType,
-    pub energy_amount: EnergyAmount,
-    pub price_per_unit: Balance,
+    /// Energy amount in kWh
+    pub energy_amount: f64,
+    /// Price in THB per kWh
+    pub price_per_unit: f64,
     pub location: GridLocation,
     pub energy_source: Option<EnergySource>,
     pub account_id: AccountId,
@@ -93,14 +95,16 @@ pub struct PlaceOrderResponse {
     pub message: String,
 }
 
-/// Trade execution info for API response
+/// Trade execution info for API response, with prices and values in THB
 #[derive(Debug, Serialize)]
 pub struct TradeExecutionInfo {
     pub trade_id: Uuid,
-    pub price: TokenPrice,
-    pub quantity: EnergyAmount,
-    pub total_value: TokenPrice,
-    pub fees: TokenPrice,
+    /// Price in THB per kWh
+    pub price: f64,
+    /// Quantity in kWh
+    pub quantity: f64,
+    pub total_value: f64,
+    pub fees: f64,
     pub counterparty: String, // Anonymized counterparty info
     pub timestamp: String,
 }
@@ -109,16 +113,21 @@ impl From<&TradeExecution> for TradeExecutionInfo {
     fn from(execution: &TradeExecution) -> Self {
         Self {
             trade_id: execution.trade_id,
-            price: execution.price,
-            quantity: execution.quantity,
-            total_value: execution.price * execution.quantity,
-            fees: execution.fees.total_fee,
+            price: execution.price.as_thb_f64(),
+            quantity: execution.quantity.as_kwh_f64(),
+            total_value: satang_to_thb(execution.price.total(execution.quantity)),
+            fees: satang_to_thb(execution.fees.total_fee),
             counterparty: "***".to_string(), // Anonymized for privacy
             timestamp: execution.execution_time.to_rfc3339(),
         }
     }
 }
 
+/// Convert a satang amount to THB for API responses
+fn satang_to_thb(amount: Balance) -> f64 {
+    amount as f64 / SATANG_PER_THB as f64
+}
+
 /// CDA Trading API
 pub struct CDAApiService {
     trading_service: Arc<EnhancedTradingService>,
@@ -208,11 +217,17 @@ impl CDAApiService {
         request: PlaceOrderRequest,
         trading_service: Arc<EnhancedTradingService>,
     ) -> Result<impl Reply, warp::Rejection> {
+        let amounts = EnergyAmount::from_kwh_f64(request.energy_amount)
+            .and_then(|amount| Ok((amount, TokenPrice::from_thb_f64(request.price_per_unit)?)));
+        let (energy_amount, price_per_unit) = match amounts {
+            Ok(amounts) => amounts,
+            Err(e) => return Ok(warp::reply::json(&ApiResponse::<()>::error(&e.to_string()))),
+        };
         let order = EnergyOrder {
             id: Uuid::new_v4(),
             order_type: request.order_type,
-            energy_amount: request.energy_amount,
-            price_per_unit: request.price_per_unit,
+            energy_amount,
+            price_per_unit,
             location: request.location,
             energy_source: request.energy_source,
             timestamp: crate::utils::now(),
@@ -226,10 +241,10 @@ impl CDAApiService {
                 let executions: Vec<TradeExecutionInfo> = result.executions.iter()
                     .map(|trade| TradeExecutionInfo {
                         trade_id: Uuid::parse_str(&trade.trade_id).unwrap_or_default(),
-                        price: trade.price_per_unit as f64,
-                        quantity: trade.energy_amount,
-                        total_value: trade.total_price as f64,
-                        fees: trade.grid_fee as f64,
+                        price: trade.price_per_unit.as_thb_f64(),
+                        quantity: trade.energy_amount.as_kwh_f64(),
+                        total_value: satang_to_thb(trade.total_price),
+                        fees: satang_to_thb(trade.grid_fee),
                         counterparty: "***".to_string(),
                         timestamp: crate::utils::now().to_rfc3339(),
                     })
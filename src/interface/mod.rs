@@ -7,6 +7,7 @@ pub mod api;
 pub mod cda_api;
 
 use crate::application::*;
+use crate::blockchain::node::BlockchainNode;
 use crate::infrastructure::security::SecurityManager;
 use crate::utils::SystemResult;
 use std::sync::Arc;
@@ -18,6 +19,7 @@ pub struct InterfaceLayer {
 
 impl InterfaceLayer {
     pub async fn new(
+        blockchain_node: Arc<BlockchainNode>,
         trading_service: Arc<trading::TradingService>,
         grid_service: Arc<grid::GridService>,
         governance_service: Arc<governance::GovernanceService>,
@@ -25,6 +27,7 @@ impl InterfaceLayer {
         security_manager: Arc<SecurityManager>,
     ) -> SystemResult<Self> {
         let blockchain_interface = api::BlockchainInterface::new(
+            blockchain_node,
             trading_service,
             grid_service,
             governance_service,
@@ -98,6 +98,7 @@ impl ThaiEnergyTradingSystem {
         
         // Initialize blockchain interface
         let blockchain_interface = Arc::new(BlockchainInterface::new(
+            blockchain_node.clone(),
             trading_service.clone(),
             grid_service.clone(),
             governance_service.clone(),
@@ -279,6 +280,7 @@ impl ThaiEnergyTradingSystem {
     pub async fn validate_grid_load(&self, location: &GridLocation, additional_load: f64) -> Result<bool> {
         let status = self.grid_service.get_grid_status(location).await
             .map_err(|e| anyhow::anyhow!("Failed to get grid status: {}", e))?;
+        let additional_load = EnergyAmount::from_kwh_f64(additional_load)?;
         Ok(status.can_handle_load(additional_load))
     }
     
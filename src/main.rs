@@ -1,14 +1,86 @@
 //! # GridTokenX POC Blockchain
 //! 
 //! Executable for running the GridTokenX POC Blockchain system as a standalone application.
+//! The `build-spec` command writes a chain spec that every node of a new network loads.
 
-use thai_energy_trading_blockchain::{ThaiEnergyTradingSystem, SystemConfig};
+use thai_energy_trading_blockchain::{ThaiEnergyTradingSystem, SystemConfig, EnergySource};
+use thai_energy_trading_blockchain::blockchain::chain_spec::{
+    ChainSpec, ConsensusParameters, ContractParameters, FeeParameters, GenesisAuthority, GovernanceParameters,
+};
+use thai_energy_trading_blockchain::blockchain::contract_runtime::GasSchedule;
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::signal;
 use log::{info, error};
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let defaults = BlockchainConfig::default();
+    let block_time = defaults.block_time.to_string();
+    let slot_timeout = defaults.slot_timeout.to_string();
+    let gas_limit = defaults.gas_limit.to_string();
+    let gas_price = defaults.gas_price.to_string();
+    let rotation_interval = defaults.validator_rotation_interval.to_string();
+    let max_validators = defaults.max_validators.to_string();
+    let authority_threshold = defaults.authority_threshold.to_string();
+    let max_contract_gas = defaults.smart_contract_vm.max_gas_limit.to_string();
+    let max_contract_size = defaults.smart_contract_vm.max_contract_size.to_string();
+    let max_contract_storage = defaults.smart_contract_vm.max_storage_size.to_string();
+    
+    let matches = App::new("thai-energy-trading-blockchain")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand(SubCommand::with_name("build-spec")
+            .about("Generate a chain spec for a new network")
+            .arg(Arg::with_name("name").long("name").takes_value(true)
+                .default_value("Thai Energy Testnet").help("Chain name"))
+            .arg(Arg::with_name("network").long("network").takes_value(true).required(true)
+                .help("Network id"))
+            .arg(Arg::with_name("authority").long("authority").value_name("NAME=PUBLIC_KEY")
+                .takes_value(true).multiple(true).number_of_values(1).required(true)
+                .help("Initial authority and its hex-encoded ed25519 public key, in schedule order"))
+            .arg(Arg::with_name("oracle").long("oracle").value_name("NAME=PUBLIC_KEY")
+                .takes_value(true).multiple(true).number_of_values(1)
+                .help("Metering oracle and its hex-encoded ed25519 public key"))
+            .arg(Arg::with_name("carbon-credit-issuer").long("carbon-credit-issuer").value_name("ACCOUNT")
+                .takes_value(true).multiple(true).number_of_values(1)
+                .help("Account allowed to issue carbon credits"))
+            .arg(Arg::with_name("balance").long("balance").value_name("ACCOUNT=SOURCE:AMOUNT")
+                .takes_value(true).multiple(true).number_of_values(1)
+                .help("Initial token balance of an account for an energy source"))
+            .arg(Arg::with_name("currency").long("currency").value_name("ACCOUNT=SATANG")
+                .takes_value(true).multiple(true).number_of_values(1)
+                .help("Initial currency balance of an account in satang"))
+            .arg(Arg::with_name("genesis-timestamp").long("genesis-timestamp").takes_value(true)
+                .help("Genesis timestamp as unix seconds [default: now]"))
+            .arg(Arg::with_name("block-time").long("block-time").takes_value(true).default_value(&block_time))
+            .arg(Arg::with_name("slot-timeout").long("slot-timeout").takes_value(true).default_value(&slot_timeout))
+            .arg(Arg::with_name("gas-limit").long("gas-limit").takes_value(true).default_value(&gas_limit))
+            .arg(Arg::with_name("gas-price").long("gas-price").takes_value(true).default_value(&gas_price))
+            .arg(Arg::with_name("validator-rotation-interval").long("validator-rotation-interval")
+                .takes_value(true).default_value(&rotation_interval))
+            .arg(Arg::with_name("max-validators").long("max-validators").takes_value(true)
+                .default_value(&max_validators))
+            .arg(Arg::with_name("authority-threshold").long("authority-threshold").takes_value(true)
+                .default_value(&authority_threshold))
+            .arg(Arg::with_name("max-contract-gas").long("max-contract-gas").takes_value(true)
+                .default_value(&max_contract_gas).help("Maximum gas a contract execution may use"))
+            .arg(Arg::with_name("max-contract-size").long("max-contract-size").takes_value(true)
+                .default_value(&max_contract_size).help("Maximum contract code size in bytes"))
+            .arg(Arg::with_name("max-contract-storage").long("max-contract-storage").takes_value(true)
+                .default_value(&max_contract_storage).help("Maximum storage size of a contract in bytes"))
+            .arg(Arg::with_name("disable-wasm").long("disable-wasm")
+                .help("Reject WebAssembly contracts"))
+            .arg(Arg::with_name("output").long("output").short("o").takes_value(true)
+                .help("File to write the chain spec to [default: stdout]")))
+        .get_matches();
+    
+    if let Some(args) = matches.subcommand_matches("build-spec") {
+        return build_spec(args);
+    }
+    
     // Initialize logging
     env_logger::init();
     
@@ -41,3 +113,94 @@ async fn main() -> Result<()> {
     
     Ok(())
 }
+
+/// Write the chain spec described by the `build-spec` arguments
+fn build_spec(args: &ArgMatches) -> Result<()> {
+    let keyed = |arg| {
+        args.values_of(arg).into_iter().flatten()
+            .map(|value| {
+                let (name, public_key) = value.split_once('=')
+                    .ok_or_else(|| anyhow!("Expected NAME=PUBLIC_KEY, got {}", value))?;
+                Ok(GenesisAuthority::from_public_key(name, public_key)?)
+            })
+            .collect::<Result<Vec<_>>>()
+    };
+    let authorities = keyed("authority")?;
+    let oracles = keyed("oracle")?;
+    let carbon_credit_issuers = args.values_of("carbon-credit-issuer").into_iter().flatten().map(str::to_string).collect();
+    
+    let mut balances: BTreeMap<_, HashMap<_, _>> = BTreeMap::new();
+    for value in args.values_of("balance").into_iter().flatten() {
+        let (account_id, amount) = value.split_once('=')
+            .ok_or_else(|| anyhow!("Expected ACCOUNT=SOURCE:AMOUNT, got {}", value))?;
+        let (source, amount) = amount.split_once(':')
+            .ok_or_else(|| anyhow!("Expected ACCOUNT=SOURCE:AMOUNT, got {}", value))?;
+        let source: EnergySource = serde_json::from_value(serde_json::Value::String(source.to_string()))
+            .with_context(|| format!("Unknown energy source {}", source))?;
+        let amount: u128 = amount.parse().with_context(|| format!("Invalid amount in {}", value))?;
+        *balances.entry(account_id.to_string()).or_default().entry(source).or_insert(0) += amount;
+    }
+    
+    let mut currency = BTreeMap::new();
+    for value in args.values_of("currency").into_iter().flatten() {
+        let (account_id, amount) = value.split_once('=')
+            .ok_or_else(|| anyhow!("Expected ACCOUNT=SATANG, got {}", value))?;
+        let amount: u128 = amount.parse().with_context(|| format!("Invalid amount in {}", value))?;
+        *currency.entry(account_id.to_string()).or_insert(0) += amount;
+    }
+    
+    let genesis_timestamp = match args.value_of("genesis-timestamp") {
+        Some(timestamp) => timestamp.parse().context("Invalid genesis timestamp")?,
+        None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    };
+    
+    let spec = ChainSpec {
+        name: args.value_of("name").unwrap_or_default().to_string(),
+        network_id: args.value_of("network").unwrap_or_default().to_string(),
+        genesis_timestamp,
+        authorities,
+        balances,
+        currency,
+        consensus: ConsensusParameters {
+            block_time: parse_arg(args, "block-time")?,
+            slot_timeout: parse_arg(args, "slot-timeout")?,
+        },
+        fees: FeeParameters {
+            gas_limit: parse_arg(args, "gas-limit")?,
+            gas_price: parse_arg(args, "gas-price")?,
+        },
+        gas_schedule: GasSchedule::default(),
+        contracts: ContractParameters {
+            max_gas_limit: parse_arg(args, "max-contract-gas")?,
+            max_contract_size: parse_arg(args, "max-contract-size")?,
+            max_storage_size: parse_arg(args, "max-contract-storage")?,
+            enable_wasm: !args.is_present("disable-wasm"),
+        },
+        governance: GovernanceParameters {
+            validator_rotation_interval: parse_arg(args, "validator-rotation-interval")?,
+            max_validators: parse_arg(args, "max-validators")?,
+            authority_threshold: parse_arg(args, "authority-threshold")?,
+        },
+        oracles,
+        carbon_credit_issuers,
+    };
+    spec.validate()?;
+    
+    let json = spec.to_json()?;
+    match args.value_of("output") {
+        Some(path) => {
+            std::fs::write(path, json).with_context(|| format!("Failed to write chain spec to {}", path))?;
+            eprintln!("Chain spec written to {} (genesis {})", path, spec.genesis_block()?.header.hash);
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &ArgMatches, name: &str) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let value = args.value_of(name).unwrap_or_default();
+    value.parse().with_context(|| format!("Invalid --{}: {}", name, value))
+}
@@ -6,32 +6,37 @@ use super::types::*;
 use crate::types::*;
 use crate::utils::SystemResult;
 
+/// Basis points in a whole
+const BASIS_POINTS: Balance = 10_000;
+
 /// Fee calculator for CDA trades
 pub struct FeeCalculator {
-    maker_fee_rate: f64,
-    taker_fee_rate: f64,
-    grid_fee_rate: f64,
-    regulatory_fee_rate: f64,
+    /// Fee rates in basis points of the trade value
+    maker_fee_rate: u64,
+    taker_fee_rate: u64,
+    grid_fee_rate: u64,
+    regulatory_fee_rate: u64,
 }
 
 impl FeeCalculator {
     pub fn new() -> Self {
         Self {
-            maker_fee_rate: 0.001,  // 0.1%
-            taker_fee_rate: 0.002,  // 0.2%
-            grid_fee_rate: 0.005,   // 0.5%
-            regulatory_fee_rate: 0.0005, // 0.05%
+            maker_fee_rate: 10,     // 0.1%
+            taker_fee_rate: 20,     // 0.2%
+            grid_fee_rate: 50,      // 0.5%
+            regulatory_fee_rate: 5, // 0.05%
         }
     }
     
-    /// Calculate fees for a trade
+    /// Calculate fees for a trade, rounding each fee down to whole satang
     pub fn calculate_fees(&self, price: TokenPrice, quantity: EnergyAmount, is_taker: bool) -> SystemResult<TradeFees> {
-        let trade_value = price * quantity;
+        let trade_value = price.total(quantity);
+        let fee = |rate: u64| trade_value.saturating_mul(rate as Balance) / BASIS_POINTS;
         
-        let maker_fee = if is_taker { 0.0 } else { trade_value * self.maker_fee_rate };
-        let taker_fee = if is_taker { trade_value * self.taker_fee_rate } else { 0.0 };
-        let grid_fee = trade_value * self.grid_fee_rate;
-        let regulatory_fee = trade_value * self.regulatory_fee_rate;
+        let maker_fee = if is_taker { 0 } else { fee(self.maker_fee_rate) };
+        let taker_fee = if is_taker { fee(self.taker_fee_rate) } else { 0 };
+        let grid_fee = fee(self.grid_fee_rate);
+        let regulatory_fee = fee(self.regulatory_fee_rate);
         let total_fee = maker_fee + taker_fee + grid_fee + regulatory_fee;
         
         Ok(TradeFees {
@@ -43,8 +48,8 @@ impl FeeCalculator {
         })
     }
     
-    /// Update fee rates (for dynamic fee adjustment)
-    pub fn update_rates(&mut self, maker: f64, taker: f64, grid: f64, regulatory: f64) {
+    /// Update fee rates, given in basis points (for dynamic fee adjustment)
+    pub fn update_rates(&mut self, maker: u64, taker: u64, grid: u64, regulatory: u64) {
         self.maker_fee_rate = maker;
         self.taker_fee_rate = taker;
         self.grid_fee_rate = grid;
@@ -22,8 +22,8 @@ impl MarketDataManager {
     /// Generate market depth from order books
     pub fn generate_market_depth(
         &self,
-        bid_book: &BTreeMap<OrderedFloat, VecDeque<CDAOrder>>,
-        ask_book: &BTreeMap<OrderedFloat, VecDeque<CDAOrder>>,
+        bid_book: &BTreeMap<TokenPrice, VecDeque<CDAOrder>>,
+        ask_book: &BTreeMap<TokenPrice, VecDeque<CDAOrder>>,
         levels: usize,
     ) -> SystemResult<MarketDepth> {
         let mut bids = Vec::new();
@@ -31,11 +31,11 @@ impl MarketDataManager {
         
         // Build bid levels (highest first)
         for (price, orders) in bid_book.iter().rev().take(levels) {
-            let total_quantity: EnergyAmount = orders.iter().map(|o| o.remaining_quantity).sum();
+            let total_quantity = orders.iter().fold(EnergyAmount::ZERO, |total, o| total.saturating_add(o.remaining_quantity));
             let order_count = orders.len() as u32;
             
             bids.push(OrderBookLevel {
-                price: (*price).into(),
+                price: *price,
                 total_quantity,
                 order_count,
                 timestamp: crate::utils::now(),
@@ -44,11 +44,11 @@ impl MarketDataManager {
         
         // Build ask levels (lowest first)
         for (price, orders) in ask_book.iter().take(levels) {
-            let total_quantity: EnergyAmount = orders.iter().map(|o| o.remaining_quantity).sum();
+            let total_quantity = orders.iter().fold(EnergyAmount::ZERO, |total, o| total.saturating_add(o.remaining_quantity));
             let order_count = orders.len() as u32;
             
             asks.push(OrderBookLevel {
-                price: (*price).into(),
+                price: *price,
                 total_quantity,
                 order_count,
                 timestamp: crate::utils::now(),
@@ -56,19 +56,19 @@ impl MarketDataManager {
         }
         
         // Calculate spread and mid-price
-        let best_bid = bids.first().map(|b| b.price).unwrap_or(0.0);
-        let best_ask = asks.first().map(|a| a.price).unwrap_or(0.0);
-        let spread = if best_bid > 0.0 && best_ask > 0.0 { best_ask - best_bid } else { 0.0 };
-        let mid_price = if best_bid > 0.0 && best_ask > 0.0 { (best_bid + best_ask) / 2.0 } else { 0.0 };
+        let (spread, mid_price) = match (bids.first(), asks.first()) {
+            (Some(best_bid), Some(best_ask)) => (best_ask.price.saturating_sub(best_bid.price), best_bid.price.midpoint(best_ask.price)),
+            _ => (TokenPrice::ZERO, TokenPrice::ZERO),
+        };
         
-        let total_bid_volume: EnergyAmount = bids.iter().map(|b| b.total_quantity).sum();
-        let total_ask_volume: EnergyAmount = asks.iter().map(|a| a.total_quantity).sum();
+        let total_bid_volume = bids.iter().fold(EnergyAmount::ZERO, |total, b| total.saturating_add(b.total_quantity));
+        let total_ask_volume = asks.iter().fold(EnergyAmount::ZERO, |total, a| total.saturating_add(a.total_quantity));
         
         let now = chrono::Utc::now();
         let default_location = GridLocation {
             province: "Default".to_string(),
             district: "Default".to_string(),
-            coordinates: GridCoordinates { lat: 0.0, lng: 0.0 },
+            coordinates: GridCoordinates { lat: FixedPoint::ZERO, lng: FixedPoint::ZERO },
             region: "Default".to_string(),
             substation: "Default".to_string(),
             grid_code: "DEFAULT".to_string(),
@@ -5,15 +5,16 @@
 use super::types::*;
 use crate::types::*;
 use crate::utils::SystemResult;
+use std::cmp::Reverse;
 use std::collections::BTreeMap;
 use uuid::Uuid;
 
 /// Order matching engine with price-time priority
 pub struct MatchingEngine {
     /// Buy orders sorted by price (highest first) then time (earliest first)
-    buy_orders: BTreeMap<(OrderedFloat, i64), CDAOrder>,
+    buy_orders: BTreeMap<(Reverse<TokenPrice>, i64), CDAOrder>,
     /// Sell orders sorted by price (lowest first) then time (earliest first)  
-    sell_orders: BTreeMap<(OrderedFloat, i64), CDAOrder>,
+    sell_orders: BTreeMap<(TokenPrice, i64), CDAOrder>,
 }
 
 impl MatchingEngine {
@@ -35,8 +36,8 @@ impl MatchingEngine {
                 
                 // Add remaining quantity to buy order book if not IOC/FOK
                 if !executions.is_empty() || order.time_in_force != TimeInForce::ImmediateOrCancel {
-                    let remaining_amount = order.energy_amount - executions.iter().map(|e| e.energy_amount).sum::<f64>();
-                    if remaining_amount > 0.0 && order.time_in_force != TimeInForce::FillOrKill {
+                    let remaining_amount = Self::remaining_after(&order, &executions);
+                    if !remaining_amount.is_zero() && order.time_in_force != TimeInForce::FillOrKill {
                         let mut remaining_order = order.clone();
                         remaining_order.energy_amount = remaining_amount;
                         self.insert_buy_order(remaining_order);
@@ -49,8 +50,8 @@ impl MatchingEngine {
                 
                 // Add remaining quantity to sell order book if not IOC/FOK
                 if !executions.is_empty() || order.time_in_force != TimeInForce::ImmediateOrCancel {
-                    let remaining_amount = order.energy_amount - executions.iter().map(|e| e.energy_amount).sum::<f64>();
-                    if remaining_amount > 0.0 && order.time_in_force != TimeInForce::FillOrKill {
+                    let remaining_amount = Self::remaining_after(&order, &executions);
+                    if !remaining_amount.is_zero() && order.time_in_force != TimeInForce::FillOrKill {
                         let mut remaining_order = order.clone();
                         remaining_order.energy_amount = remaining_amount;
                         self.insert_sell_order(remaining_order);
@@ -83,11 +84,10 @@ impl MatchingEngine {
         let mut asks = Vec::new();
 
         // Aggregate buy orders by price
-        let mut bid_levels: BTreeMap<OrderedFloat, (f64, usize)> = BTreeMap::new();
+        let mut bid_levels: BTreeMap<TokenPrice, (EnergyAmount, usize)> = BTreeMap::new();
         for order in self.buy_orders.values() {
-            let price_key = OrderedFloat(order.price);
-            let (total_qty, count) = bid_levels.entry(price_key).or_insert((0.0, 0));
-            *total_qty += order.energy_amount;
+            let (total_qty, count) = bid_levels.entry(order.price).or_insert((EnergyAmount::ZERO, 0));
+            *total_qty = total_qty.saturating_add(order.energy_amount);
             *count += 1;
         }
 
@@ -95,7 +95,7 @@ impl MatchingEngine {
         let now = chrono::Utc::now();
         for (price, (total_quantity, order_count)) in bid_levels.iter().rev().take(levels) {
             bids.push(OrderBookLevel {
-                price: price.0,
+                price: *price,
                 total_quantity: *total_quantity,
                 order_count: *order_count as u32,
                 timestamp: now,
@@ -103,18 +103,17 @@ impl MatchingEngine {
         }
 
         // Aggregate sell orders by price
-        let mut ask_levels: BTreeMap<OrderedFloat, (f64, usize)> = BTreeMap::new();
+        let mut ask_levels: BTreeMap<TokenPrice, (EnergyAmount, usize)> = BTreeMap::new();
         for order in self.sell_orders.values() {
-            let price_key = OrderedFloat(order.price);
-            let (total_qty, count) = ask_levels.entry(price_key).or_insert((0.0, 0));
-            *total_qty += order.energy_amount;
+            let (total_qty, count) = ask_levels.entry(order.price).or_insert((EnergyAmount::ZERO, 0));
+            *total_qty = total_qty.saturating_add(order.energy_amount);
             *count += 1;
         }
 
         // Get top ask levels
         for (price, (total_quantity, order_count)) in ask_levels.iter().take(levels) {
             asks.push(OrderBookLevel {
-                price: price.0,
+                price: *price,
                 total_quantity: *total_quantity,
                 order_count: *order_count as u32,
                 timestamp: now,
@@ -122,18 +121,18 @@ impl MatchingEngine {
         }
 
         // Calculate market statistics
-        let best_bid = bids.first().map(|level| level.price).unwrap_or(0.0);
-        let best_ask = asks.first().map(|level| level.price).unwrap_or(0.0);
-        let spread = if best_bid > 0.0 && best_ask > 0.0 { best_ask - best_bid } else { 0.0 };
-        let mid_price = if best_bid > 0.0 && best_ask > 0.0 { (best_bid + best_ask) / 2.0 } else { 0.0 };
-        let total_bid_volume = bids.iter().map(|level| level.total_quantity).sum();
-        let total_ask_volume = asks.iter().map(|level| level.total_quantity).sum();
+        let (spread, mid_price) = match (bids.first(), asks.first()) {
+            (Some(best_bid), Some(best_ask)) => (best_ask.price.saturating_sub(best_bid.price), best_bid.price.midpoint(best_ask.price)),
+            _ => (TokenPrice::ZERO, TokenPrice::ZERO),
+        };
+        let total_bid_volume = bids.iter().fold(EnergyAmount::ZERO, |total, level| total.saturating_add(level.total_quantity));
+        let total_ask_volume = asks.iter().fold(EnergyAmount::ZERO, |total, level| total.saturating_add(level.total_quantity));
 
         // Create a default grid location (this should be passed as parameter in real usage)
         let grid_location = GridLocation {
             province: "Bangkok".to_string(),
             district: "Pathum Wan".to_string(),
-            coordinates: GridCoordinates { lat: 13.7563, lng: 100.5018 },
+            coordinates: GridCoordinates { lat: FixedPoint::from_millionths(13_756_300), lng: FixedPoint::from_millionths(100_501_800) },
             region: "Central".to_string(),
             substation: "Default".to_string(),
             grid_code: "DEFAULT".to_string(),
@@ -155,16 +154,21 @@ impl MatchingEngine {
     }
 
     /// Get best bid price
-    pub fn get_best_bid(&self) -> Option<f64> {
-        self.buy_orders.keys().next_back().map(|(price, _)| price.0)
+    pub fn get_best_bid(&self) -> Option<TokenPrice> {
+        self.buy_orders.keys().next().map(|(Reverse(price), _)| *price)
     }
 
     /// Get best ask price  
-    pub fn get_best_ask(&self) -> Option<f64> {
-        self.sell_orders.keys().next().map(|(price, _)| price.0)
+    pub fn get_best_ask(&self) -> Option<TokenPrice> {
+        self.sell_orders.keys().next().map(|(price, _)| *price)
     }
 
     // Private helper methods
+
+    /// Quantity of an order left once the given executions are filled
+    fn remaining_after(order: &CDAOrder, executions: &[TradeExecution]) -> EnergyAmount {
+        executions.iter().fold(order.energy_amount, |remaining, e| remaining.saturating_sub(e.energy_amount))
+    }
     
     fn match_buy_order(&mut self, buy_order: &CDAOrder) -> SystemResult<Vec<TradeExecution>> {
         let mut executions = Vec::new();
@@ -172,12 +176,12 @@ impl MatchingEngine {
 
         // Find matching sell orders (price <= buy_order.price)
         let matching_keys: Vec<_> = self.sell_orders
-            .range(..(OrderedFloat(buy_order.price + 0.0001), i64::MAX))
+            .range(..=(buy_order.price, i64::MAX))
             .map(|(key, _)| *key)
             .collect();
 
         for key in matching_keys {
-            if remaining_amount <= 0.0 {
+            if remaining_amount.is_zero() {
                 break;
             }
 
@@ -208,15 +212,15 @@ impl MatchingEngine {
                 };
 
                 executions.push(execution);
-                remaining_amount -= execution_amount;
+                remaining_amount = remaining_amount.saturating_sub(execution_amount);
 
                 // Update or remove the sell order
                 if sell_order.energy_amount <= execution_amount {
                     self.sell_orders.remove(&key);
                 } else {
                     let mut updated_order = sell_order.clone();
-                    updated_order.energy_amount -= execution_amount;
-                    updated_order.filled_amount += execution_amount;
+                    updated_order.energy_amount = updated_order.energy_amount.saturating_sub(execution_amount);
+                    updated_order.filled_amount = updated_order.filled_amount.saturating_add(execution_amount);
                     self.sell_orders.insert(key, updated_order);
                 }
             }
@@ -231,12 +235,12 @@ impl MatchingEngine {
 
         // Find matching buy orders (price >= sell_order.price)
         let matching_keys: Vec<_> = self.buy_orders
-            .range((OrderedFloat(sell_order.price), i64::MIN)..)
+            .range(..=(Reverse(sell_order.price), i64::MAX))
             .map(|(key, _)| *key)
             .collect();
 
         for key in matching_keys {
-            if remaining_amount <= 0.0 {
+            if remaining_amount.is_zero() {
                 break;
             }
 
@@ -267,15 +271,15 @@ impl MatchingEngine {
                 };
 
                 executions.push(execution);
-                remaining_amount -= execution_amount;
+                remaining_amount = remaining_amount.saturating_sub(execution_amount);
 
                 // Update or remove the buy order
                 if buy_order.energy_amount <= execution_amount {
                     self.buy_orders.remove(&key);
                 } else {
                     let mut updated_order = buy_order.clone();
-                    updated_order.energy_amount -= execution_amount;
-                    updated_order.filled_amount += execution_amount;
+                    updated_order.energy_amount = updated_order.energy_amount.saturating_sub(execution_amount);
+                    updated_order.filled_amount = updated_order.filled_amount.saturating_add(execution_amount);
                     self.buy_orders.insert(key, updated_order);
                 }
             }
@@ -285,12 +289,12 @@ impl MatchingEngine {
     }
 
     fn insert_buy_order(&mut self, order: CDAOrder) {
-        let key = (OrderedFloat(-order.price), order.priority_timestamp.timestamp_nanos_opt().unwrap_or(0));
+        let key = (Reverse(order.price), order.priority_timestamp.timestamp_nanos_opt().unwrap_or(0));
         self.buy_orders.insert(key, order);
     }
 
     fn insert_sell_order(&mut self, order: CDAOrder) {
-        let key = (OrderedFloat(order.price), order.priority_timestamp.timestamp_nanos_opt().unwrap_or(0));
+        let key = (order.price, order.priority_timestamp.timestamp_nanos_opt().unwrap_or(0));
         self.sell_orders.insert(key, order);
     }
 
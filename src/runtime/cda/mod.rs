@@ -44,7 +44,7 @@ impl ContinuousDoubleAuction {
         let cda_order = CDAOrder {
             base: order.clone(),
             original_quantity: order.energy_amount,
-            filled_quantity: 0.0,
+            filled_quantity: EnergyAmount::ZERO,
             remaining_quantity: order.energy_amount,
             is_hidden: false,
             time_in_force: TimeInForce::GoodTillCancelled,
@@ -55,10 +55,10 @@ impl ContinuousDoubleAuction {
             account_id: order.account_id,
             order_type: order.order_type,
             energy_amount: order.energy_amount,
-            price: order.price_per_unit as f64,
+            price: order.price_per_unit,
             grid_location: order.location,
             energy_source: order.energy_source.unwrap_or(EnergySource::Mixed),
-            filled_amount: 0.0,
+            filled_amount: EnergyAmount::ZERO,
             minimum_fill: None,
             post_only: false,
         };
@@ -106,10 +106,10 @@ impl ContinuousDoubleAuction {
             Ok(MarketDepth {
                 bids: Vec::new(),
                 asks: Vec::new(),
-                spread: 0.0,
-                mid_price: 0.0,
-                total_bid_volume: 0.0,
-                total_ask_volume: 0.0,
+                spread: TokenPrice::ZERO,
+                mid_price: TokenPrice::ZERO,
+                total_bid_volume: EnergyAmount::ZERO,
+                total_ask_volume: EnergyAmount::ZERO,
                 timestamp: now,
                 // Additional fields for compatibility  
                 grid_location: grid_location.clone(),
@@ -119,7 +119,7 @@ impl ContinuousDoubleAuction {
     }
 
     /// Get best bid and ask prices
-    pub async fn get_best_prices(&self, grid_location: &GridLocation) -> SystemResult<(Option<f64>, Option<f64>)> {
+    pub async fn get_best_prices(&self, grid_location: &GridLocation) -> SystemResult<(Option<TokenPrice>, Option<TokenPrice>)> {
         let engines = self.matching_engines.read().await;
         
         if let Some(engine) = engines.get(grid_location) {
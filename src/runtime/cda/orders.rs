@@ -3,6 +3,7 @@
 //! Order lifecycle management and validation for the Continuous Double Auction system.
 
 use super::types::*;
+use crate::types::EnergyAmount;
 use crate::utils::SystemResult;
 use chrono::Utc;
 use std::collections::{BTreeMap, HashMap};
@@ -46,10 +47,10 @@ impl OrderManager {
     }
     
     /// Update order quantities after partial fill
-    pub fn update_order_quantities(&mut self, order_id: &Uuid, filled_quantity: f64) -> SystemResult<()> {
+    pub fn update_order_quantities(&mut self, order_id: &Uuid, filled_quantity: EnergyAmount) -> SystemResult<()> {
         if let Some(order) = self.orders.get_mut(order_id) {
-            order.filled_quantity += filled_quantity;
-            order.remaining_quantity -= filled_quantity;
+            order.filled_quantity = order.filled_quantity.saturating_add(filled_quantity);
+            order.remaining_quantity = order.remaining_quantity.saturating_sub(filled_quantity);
         }
         Ok(())
     }
@@ -90,14 +90,14 @@ pub struct CDAOrder {
     pub post_only: bool,
 }
 
-/// Trading fees breakdown
+/// Trading fees breakdown, in satang
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TradeFees {
-    pub maker_fee: TokenPrice,
-    pub taker_fee: TokenPrice,
-    pub grid_fee: TokenPrice,
-    pub regulatory_fee: TokenPrice,
-    pub total_fee: TokenPrice,
+    pub maker_fee: Balance,
+    pub taker_fee: Balance,
+    pub grid_fee: Balance,
+    pub regulatory_fee: Balance,
+    pub total_fee: Balance,
 }
 
 /// Time-in-Force options
@@ -123,36 +123,6 @@ pub enum OrderBookEvent {
     MarketDepthUpdate(MarketDepth),
 }
 
-/// Wrapper for ordered float comparisons
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub struct OrderedFloat(pub f64);
-
-impl Eq for OrderedFloat {}
-
-impl std::cmp::Ord for OrderedFloat {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.partial_cmp(&other.0).unwrap_or(std::cmp::Ordering::Equal)
-    }
-}
-
-impl std::cmp::PartialOrd for OrderedFloat {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl From<f64> for OrderedFloat {
-    fn from(value: f64) -> Self {
-        OrderedFloat(value)
-    }
-}
-
-impl From<OrderedFloat> for f64 {
-    fn from(value: OrderedFloat) -> Self {
-        value.0
-    }
-}
-
 /// Order status
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum OrderStatus {
@@ -27,9 +27,9 @@ use uuid::Uuid;
 /// Main Continuous Double Auction Engine - Optimized Implementation
 pub struct ContinuousDoubleAuction {
     /// Buy orders organized by price (highest first) then time
-    bid_book: Arc<RwLock<BTreeMap<OrderedFloat, VecDeque<CDAOrder>>>>,
+    bid_book: Arc<RwLock<BTreeMap<TokenPrice, VecDeque<CDAOrder>>>>,
     /// Sell orders organized by price (lowest first) then time
-    ask_book: Arc<RwLock<BTreeMap<OrderedFloat, VecDeque<CDAOrder>>>>,
+    ask_book: Arc<RwLock<BTreeMap<TokenPrice, VecDeque<CDAOrder>>>>,
     /// Order lifecycle manager
     order_manager: Arc<RwLock<OrderManager>>,
     /// Trade execution history (limited to recent trades for memory efficiency)
@@ -119,11 +119,11 @@ impl ContinuousDoubleAuction {
     /// Validate order before processing
     fn validate_order(&self, order: &CDAOrder) -> SystemResult<()> {
         // Basic validation
-        if order.remaining_quantity <= 0.0 {
+        if order.remaining_quantity.is_zero() {
             return Err(crate::utils::SystemError::InvalidOrder("Order quantity must be positive".to_string()));
         }
         
-        if order.price <= 0.0 {
+        if order.price.is_zero() {
             return Err(crate::utils::SystemError::InvalidOrder("Order price must be positive".to_string()));
         }
         
@@ -163,7 +163,7 @@ impl ContinuousDoubleAuction {
         Ok(CDAOrder {
             base: base_order.clone(),
             original_quantity: base_order.energy_amount,
-            filled_quantity: 0.0,
+            filled_quantity: EnergyAmount::ZERO,
             remaining_quantity: base_order.energy_amount,
             is_hidden: false,
             time_in_force: TimeInForce::GTC,
@@ -174,10 +174,10 @@ impl ContinuousDoubleAuction {
             account_id: base_order.account_id,
             order_type: base_order.order_type,
             energy_amount: base_order.energy_amount,
-            price: base_order.price_per_unit as f64,
+            price: base_order.price_per_unit,
             grid_location: base_order.location,
             energy_source: base_order.energy_source.unwrap_or(EnergySource::Solar),
-            filled_amount: 0.0,
+            filled_amount: EnergyAmount::ZERO,
             minimum_fill: None,
             post_only: false,
         })
@@ -195,7 +195,7 @@ impl ContinuousDoubleAuction {
         };
         
         // Add remaining order to book if needed
-        if order.remaining_quantity > 0.0 && !matches!(order.time_in_force, TimeInForce::IOC | TimeInForce::FOK) {
+        if !order.remaining_quantity.is_zero() && !matches!(order.time_in_force, TimeInForce::IOC | TimeInForce::FOK) {
             self.add_to_book(order).await?;
         }
         
@@ -213,28 +213,28 @@ impl ContinuousDoubleAuction {
         let mut ask_book = self.ask_book.write().await;
         
         // Use efficient price range query
-        let order_price = OrderedFloat::from(order.base.price_per_unit as f64);
+        let order_price = order.base.price_per_unit;
         let matching_prices: Vec<_> = ask_book.range(..=order_price).map(|(p, _)| *p).collect();
         
         for price in matching_prices {
-            if order.remaining_quantity <= 0.0 { break; }
+            if order.remaining_quantity.is_zero() { break; }
             
             if let Some(ask_orders) = ask_book.get_mut(&price) {
                 // Process matches at this price level
                 while let Some(mut ask_order) = ask_orders.pop_front() {
-                    if order.remaining_quantity <= 0.0 { break; }
+                    if order.remaining_quantity.is_zero() { break; }
                     
                     if self.market_data_manager.are_orders_compatible(&order.base, &ask_order.base) {
                         let trade_quantity = order.remaining_quantity.min(ask_order.remaining_quantity);
-                        let execution = self.create_execution(order, &mut ask_order, price.into(), trade_quantity, true).await?;
+                        let execution = self.create_execution(order, &mut ask_order, price, trade_quantity, true).await?;
                         executions.push(execution);
                         
                         // Update quantities
-                        order.remaining_quantity -= trade_quantity;
-                        ask_order.remaining_quantity -= trade_quantity;
+                        order.remaining_quantity = order.remaining_quantity.saturating_sub(trade_quantity);
+                        ask_order.remaining_quantity = ask_order.remaining_quantity.saturating_sub(trade_quantity);
                         
                         // Put back if not fully filled
-                        if ask_order.remaining_quantity > 0.0 {
+                        if !ask_order.remaining_quantity.is_zero() {
                             ask_orders.push_front(ask_order);
                         }
                     } else {
@@ -257,28 +257,28 @@ impl ContinuousDoubleAuction {
         let mut bid_book = self.bid_book.write().await;
         
         // Use efficient price range query (reverse for bids)
-        let order_price = OrderedFloat::from(order.base.price_per_unit as f64);
+        let order_price = order.base.price_per_unit;
         let matching_prices: Vec<_> = bid_book.range(order_price..).map(|(p, _)| *p).collect();
         
         for price in matching_prices.into_iter().rev() {
-            if order.remaining_quantity <= 0.0 { break; }
+            if order.remaining_quantity.is_zero() { break; }
             
             if let Some(bid_orders) = bid_book.get_mut(&price) {
                 // Process matches at this price level
                 while let Some(mut bid_order) = bid_orders.pop_front() {
-                    if order.remaining_quantity <= 0.0 { break; }
+                    if order.remaining_quantity.is_zero() { break; }
                     
                     if self.market_data_manager.are_orders_compatible(&order.base, &bid_order.base) {
                         let trade_quantity = order.remaining_quantity.min(bid_order.remaining_quantity);
-                        let execution = self.create_execution(&mut bid_order, order, price.into(), trade_quantity, false).await?;
+                        let execution = self.create_execution(&mut bid_order, order, price, trade_quantity, false).await?;
                         executions.push(execution);
                         
                         // Update quantities
-                        order.remaining_quantity -= trade_quantity;
-                        bid_order.remaining_quantity -= trade_quantity;
+                        order.remaining_quantity = order.remaining_quantity.saturating_sub(trade_quantity);
+                        bid_order.remaining_quantity = bid_order.remaining_quantity.saturating_sub(trade_quantity);
                         
                         // Put back if not fully filled
-                        if bid_order.remaining_quantity > 0.0 {
+                        if !bid_order.remaining_quantity.is_zero() {
                             bid_orders.push_front(bid_order);
                         }
                     } else {
@@ -300,8 +300,8 @@ impl ContinuousDoubleAuction {
         &self,
         buy_order: &mut CDAOrder,
         sell_order: &mut CDAOrder,
-        price: f64,
-        quantity: f64,
+        price: TokenPrice,
+        quantity: EnergyAmount,
         is_aggressive_buy: bool,
     ) -> SystemResult<TradeExecution> {
         let fees = self.fee_calculator.calculate_fees(price, quantity, is_aggressive_buy)?;
@@ -332,7 +332,7 @@ impl ContinuousDoubleAuction {
     
     /// Add order to appropriate book
     async fn add_to_book(&self, order: CDAOrder) -> SystemResult<()> {
-        let price = OrderedFloat::from(order.base.price_per_unit as f64);
+        let price = order.base.price_per_unit;
         
         // Add to order manager
         {
@@ -358,7 +358,7 @@ impl ContinuousDoubleAuction {
     
     /// Remove order from book
     async fn remove_from_book(&self, order: &CDAOrder) -> SystemResult<()> {
-        let price = OrderedFloat::from(order.base.price_per_unit as f64);
+        let price = order.base.price_per_unit;
         
         match order.base.order_type {
             OrderType::Buy => {
@@ -431,7 +431,7 @@ impl ContinuousDoubleAuction {
 // Re-export types for external use
 pub use crate::runtime::cda::types::{
     MarketDepth, OrderBookEvent, TradeExecution, CDAOrder, TradeFees, 
-    TimeInForce, MatchingAlgorithm, OrderBookLevel, OrderedFloat, OrderStatus, SettlementStatus
+    TimeInForce, MatchingAlgorithm, OrderBookLevel, OrderStatus, SettlementStatus
 };
 
 impl Clone for ContinuousDoubleAuction {
@@ -84,7 +84,7 @@ impl EnergyTradingPallet {
         
         crate::utils::logging::log_info(
             "EnergyTradingPallet",
-            &format!("Order placed: {} {} kWh at {} THB/kWh", 
+            &format!("Order placed: {} {} at {}", 
                      order.id, 
                      order.energy_amount, 
                      order.price_per_unit)
@@ -237,8 +237,8 @@ impl EnergyTradingPallet {
         let trade_id = Uuid::new_v4();
         let energy_amount = buy_order.energy_amount.min(sell_order.energy_amount);
         let price_per_kwh = sell_order.price_per_unit; // Use seller's price
-        let total_price = energy_amount as Balance * price_per_kwh;
-        let grid_fee = (total_price as f64 * 0.05) as Balance; // 5% grid fee
+        let total_price = price_per_kwh.total(energy_amount);
+        let grid_fee = total_price * 5 / 100; // 5% grid fee
         
         let trade = EnergyTrade {
             trade_id: trade_id.to_string(),
@@ -258,7 +258,7 @@ impl EnergyTradingPallet {
             grid_fee,
             energy_source: sell_order.energy_source.clone().unwrap_or(EnergySource::Solar),
             carbon_offset: CarbonOffset {
-                offset_credits: energy_amount as f64 * 0.5, // Simplified calculation
+                offset_credits: FixedPoint::from_millionths(energy_amount.as_wh() as i64 * 500), // Simplified calculation: 0.5 per kWh
                 verified: false,
                 certification_body: "Thai Energy Authority".to_string(),
                 timestamp: crate::utils::now(),
@@ -281,7 +281,7 @@ impl EnergyTradingPallet {
         
         crate::utils::logging::log_info(
             "EnergyTradingPallet",
-            &format!("Trade executed: {} kWh at {} THB/kWh", 
+            &format!("Trade executed: {} at {}", 
                      trade.energy_amount, 
                      trade.price_per_kwh)
         );
@@ -299,7 +299,7 @@ impl EnergyTradingPallet {
                 buy_order.status = OrderStatus::Filled;
             } else {
                 buy_order.status = OrderStatus::PartiallyFilled;
-                buy_order.energy_amount -= trade.energy_amount;
+                buy_order.energy_amount = buy_order.energy_amount.saturating_sub(trade.energy_amount);
             }
             buy_order.timestamp = crate::utils::now();
         }
@@ -310,7 +310,7 @@ impl EnergyTradingPallet {
                 sell_order.status = OrderStatus::Filled;
             } else {
                 sell_order.status = OrderStatus::PartiallyFilled;
-                sell_order.energy_amount -= trade.energy_amount;
+                sell_order.energy_amount = sell_order.energy_amount.saturating_sub(trade.energy_amount);
             }
             sell_order.timestamp = crate::utils::now();
         }
@@ -321,7 +321,7 @@ impl EnergyTradingPallet {
     /// Validate an order before placement
     async fn validate_order(&self, order: &EnergyOrder) -> SystemResult<()> {
         // Check energy amount
-        if !crate::utils::is_valid_energy_amount(order.energy_amount as u64) {
+        if !crate::utils::is_valid_energy_amount(order.energy_amount) {
             return Err(crate::utils::SystemError::Validation(
                 "Invalid energy amount".to_string()
             ));
@@ -19,8 +19,8 @@ pub struct TokenSystem {
     total_supply: Arc<RwLock<Balance>>,
     /// Maximum token supply (10 million tokens)
     max_supply: Balance,
-    /// Energy token exchange rates
-    exchange_rates: Arc<RwLock<HashMap<EnergySource, f64>>>,
+    /// Energy token exchange rates in basis points (10,000 = 1:1)
+    exchange_rates: Arc<RwLock<HashMap<EnergySource, u64>>>,
     /// Energy production validators
     production_validators: Arc<RwLock<HashMap<AccountId, ProductionValidator>>>,
 }
@@ -40,12 +40,12 @@ impl TokenSystem {
         let mut exchange_rates = HashMap::new();
         
         // Set initial exchange rates for different energy sources
-        exchange_rates.insert(EnergySource::Solar, 1.0);      // 1:1 base rate
-        exchange_rates.insert(EnergySource::Wind, 1.0);       // 1:1 base rate
-        exchange_rates.insert(EnergySource::Hydro, 1.1);      // 10% premium for hydro
-        exchange_rates.insert(EnergySource::Biomass, 0.9);    // 10% discount for biomass
-        exchange_rates.insert(EnergySource::NaturalGas, 0.8); // 20% discount for natural gas
-        exchange_rates.insert(EnergySource::Mixed, 0.95);     // 5% discount for mixed
+        exchange_rates.insert(EnergySource::Solar, 10_000);     // 1:1 base rate
+        exchange_rates.insert(EnergySource::Wind, 10_000);      // 1:1 base rate
+        exchange_rates.insert(EnergySource::Hydro, 11_000);     // 10% premium for hydro
+        exchange_rates.insert(EnergySource::Biomass, 9_000);    // 10% discount for biomass
+        exchange_rates.insert(EnergySource::NaturalGas, 8_000); // 20% discount for natural gas
+        exchange_rates.insert(EnergySource::Mixed, 9_500);      // 5% discount for mixed
         
         Ok(Self {
             balances: Arc::new(RwLock::new(HashMap::new())),
@@ -138,12 +138,17 @@ impl TokenSystem {
         
         // Calculate token amount based on energy type and exchange rate
         let exchange_rates = self.exchange_rates.read().await;
-        let rate = exchange_rates.get(&production_record.energy_type).copied().unwrap_or(1.0);
-        let token_amount = (production_record.amount as f64 * rate) as Balance;
+        let rate = exchange_rates.get(&production_record.energy_type).copied().unwrap_or(10_000);
+        let token_amount = production_record.amount.scale_bps(rate).tokens().ok_or_else(|| {
+            crate::utils::error::SystemError::Trading(format!(
+                "{} at the {:?} exchange rate is not a whole number of tokens",
+                production_record.amount, production_record.energy_type
+            ))
+        })?;
         
         // Check total supply limit
         let mut total_supply = self.total_supply.write().await;
-        if *total_supply + token_amount > self.max_supply {
+        if total_supply.checked_add(token_amount).is_none_or(|supply| supply > self.max_supply) {
             return Err(crate::utils::error::SystemError::Trading(
                 "Minting would exceed maximum supply".to_string()
             ));
@@ -152,7 +157,7 @@ impl TokenSystem {
         // Mint tokens
         let mut balances = self.balances.write().await;
         let producer_balance = balances.entry(producer.clone()).or_insert_with(EnergyBalanceState::new);
-        producer_balance.add_production(production_record.clone());
+        producer_balance.add_production(production_record.clone())?;
         
         *total_supply += token_amount;
         
@@ -171,7 +176,11 @@ impl TokenSystem {
         consumer: &AccountId,
         consumption_record: EnergyConsumptionRecord,
     ) -> SystemResult<()> {
-        let token_amount = consumption_record.amount as Balance;
+        let token_amount = consumption_record.amount.tokens().ok_or_else(|| {
+            crate::utils::error::SystemError::Trading(format!(
+                "{} is not a whole number of tokens", consumption_record.amount
+            ))
+        })?;
         
         let mut balances = self.balances.write().await;
         let consumer_balance = balances.entry(consumer.clone()).or_insert_with(EnergyBalanceState::new);
@@ -185,7 +194,7 @@ impl TokenSystem {
         
         // Burn tokens (reduce from mixed energy type)
         consumer_balance.transfer(EnergySource::Mixed, token_amount)?;
-        consumer_balance.add_consumption(consumption_record.clone());
+        consumer_balance.add_consumption(consumption_record.clone())?;
         
         // Reduce total supply
         let mut total_supply = self.total_supply.write().await;
@@ -277,14 +286,14 @@ impl TokenSystem {
         Ok(true)
     }
     
-    /// Get energy exchange rate
-    pub async fn get_exchange_rate(&self, energy_type: &EnergySource) -> f64 {
+    /// Get energy exchange rate in basis points
+    pub async fn get_exchange_rate(&self, energy_type: &EnergySource) -> u64 {
         let rates = self.exchange_rates.read().await;
-        rates.get(energy_type).copied().unwrap_or(1.0)
+        rates.get(energy_type).copied().unwrap_or(10_000)
     }
     
-    /// Update exchange rate
-    pub async fn update_exchange_rate(&self, energy_type: EnergySource, rate: f64) -> SystemResult<()> {
+    /// Update exchange rate, given in basis points
+    pub async fn update_exchange_rate(&self, energy_type: EnergySource, rate: u64) -> SystemResult<()> {
         let mut rates = self.exchange_rates.write().await;
         rates.insert(energy_type.clone(), rate);
         
@@ -342,9 +351,8 @@ impl TokenSystem {
                 let mut rates_lock = rates.write().await;
                 for (_energy_type, rate) in rates_lock.iter_mut() {
                     // Add small random fluctuation
-                    let fluctuation = (rand::random::<f64>() - 0.5) * 0.1; // ±5%
-                    *rate *= 1.0 + fluctuation;
-                    *rate = rate.max(0.1).min(2.0); // Keep within reasonable bounds
+                    let fluctuation = rand::random::<u64>() % 1_001; // 0..=10% in basis points
+                    *rate = (*rate * (9_500 + fluctuation) / 10_000).clamp(1_000, 20_000); // ±5%, within reasonable bounds
                 }
             }
         });
@@ -377,14 +385,21 @@ impl TokenSystem {
         self.transfer_energy(from, to, amount, EnergySource::Mixed).await
     }
     
+    /// Energy worth `tokens` at one token per kWh
+    fn energy_for_tokens(tokens: Balance) -> SystemResult<EnergyAmount> {
+        u64::try_from(tokens).ok().and_then(EnergyAmount::from_kwh).ok_or_else(|| {
+            crate::utils::error::SystemError::Trading(format!("Token amount {} is out of range", tokens))
+        })
+    }
+    
     pub async fn mint(&self, account: &AccountId, amount: Balance) -> SystemResult<()> {
         let production_record = EnergyProductionRecord {
-            amount: amount as EnergyAmount,
+            amount: Self::energy_for_tokens(amount)?,
             energy_type: EnergySource::Mixed,
             location: GridLocation {
                 province: "default".to_string(),
                 district: "default".to_string(),
-                coordinates: GridCoordinates { lat: 0.0, lng: 0.0 },
+                coordinates: GridCoordinates { lat: FixedPoint::ZERO, lng: FixedPoint::ZERO },
                 region: "default".to_string(),
                 substation: "default".to_string(),
                 grid_code: "default".to_string(),
@@ -392,14 +407,14 @@ impl TokenSystem {
             },
             timestamp: std::time::SystemTime::now(),
             verified: true,
-            efficiency: 0.85,
+            efficiency: FixedPoint::from_millionths(850_000),
             weather_conditions: None,
             equipment_id: "default".to_string(),
             quality_metrics: crate::blockchain::transactions::EnergyQualityMetrics {
-                voltage: 230.0,
-                frequency: 50.0,
-                power_factor: 0.95,
-                harmonic_distortion: 0.05,
+                voltage: FixedPoint::from_int(230),
+                frequency: FixedPoint::from_int(50),
+                power_factor: FixedPoint::from_millionths(950_000),
+                harmonic_distortion: FixedPoint::from_millionths(50_000),
             },
         };
         
@@ -408,11 +423,11 @@ impl TokenSystem {
     
     pub async fn burn(&self, account: &AccountId, amount: Balance) -> SystemResult<()> {
         let consumption_record = EnergyConsumptionRecord {
-            amount: amount as EnergyAmount,
+            amount: Self::energy_for_tokens(amount)?,
             location: GridLocation {
                 province: "default".to_string(),
                 district: "default".to_string(),
-                coordinates: GridCoordinates { lat: 0.0, lng: 0.0 },
+                coordinates: GridCoordinates { lat: FixedPoint::ZERO, lng: FixedPoint::ZERO },
                 region: "default".to_string(),
                 substation: "default".to_string(),
                 grid_code: "default".to_string(),
@@ -336,13 +336,13 @@ impl TpsTestEngine {
         let order = EnergyOrder {
             id: Uuid::new_v4(),
             order_type: if user_id % 2 == 0 { OrderType::Buy } else { OrderType::Sell },
-            energy_amount: 50.0 + (user_id as f64 * 10.0),
-            price_per_unit: 100000 + (user_id as u128 * 1000), // Token price
+            energy_amount: EnergyAmount::from_wh(50_000 + user_id as u64 * 10_000),
+            price_per_unit: TokenPrice::from_satang(100000 + user_id as u64 * 1000),
             energy_source: Some(EnergySource::Solar),
             location: GridLocation {
                 province: "Bangkok".to_string(),
                 district: "Chatuchak".to_string(),
-                coordinates: GridCoordinates { lat: 13.8, lng: 100.55 },
+                coordinates: GridCoordinates { lat: FixedPoint::from_millionths(13_800_000), lng: FixedPoint::from_millionths(100_550_000) },
                 region: "Central".to_string(),
                 substation: "BKK-001".to_string(),
                 grid_code: "TH-BKK-001".to_string(),
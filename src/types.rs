@@ -13,14 +13,234 @@ pub type AccountId = String;
 /// Hash type used throughout the system
 pub type Hash = String;
 
-/// Balance type for token amounts (1 token = 1 kWh)
+/// Balance type for token amounts (1 token = 1 kWh) and payments in satang
 pub type Balance = u128;
 
-/// Energy amount in kWh
-pub type EnergyAmount = f64;
+/// Watt-hours in a kWh
+pub const WH_PER_KWH: u64 = 1_000;
 
-/// Token price per kWh
-pub type TokenPrice = f64;
+/// Satang in a Thai baht
+pub const SATANG_PER_THB: u64 = 100;
+
+/// Energy amount, held as a whole number of watt-hours
+///
+/// Amounts are exact integers so every node computes the same result; fractional kWh figures
+/// are only accepted and produced at API boundaries through [`EnergyAmount::from_kwh_f64`] and
+/// [`EnergyAmount::as_kwh_f64`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EnergyAmount(u64);
+
+impl EnergyAmount {
+    pub const ZERO: EnergyAmount = EnergyAmount(0);
+
+    pub const fn from_wh(wh: u64) -> Self {
+        EnergyAmount(wh)
+    }
+
+    /// Whole kWh, `None` if the amount does not fit
+    pub fn from_kwh(kwh: u64) -> Option<Self> {
+        kwh.checked_mul(WH_PER_KWH).map(EnergyAmount)
+    }
+
+    /// Convert a kWh figure received from outside the system, rounding to the nearest Wh
+    pub fn from_kwh_f64(kwh: f64) -> crate::utils::SystemResult<Self> {
+        let wh = (kwh * WH_PER_KWH as f64).round();
+        if !wh.is_finite() || wh < 0.0 || wh > u64::MAX as f64 {
+            return Err(crate::utils::SystemError::InvalidInput(format!("Invalid energy amount {} kWh", kwh)));
+        }
+        Ok(EnergyAmount(wh as u64))
+    }
+
+    pub const fn as_wh(self) -> u64 {
+        self.0
+    }
+
+    /// The amount in kWh, for display and API responses
+    pub fn as_kwh_f64(self) -> f64 {
+        self.0 as f64 / WH_PER_KWH as f64
+    }
+
+    /// Energy tokens the amount is worth, one per kWh
+    ///
+    /// `None` unless the amount is a whole number of kWh, so no fraction of a token is ever
+    /// minted, burned or moved by rounding.
+    pub fn tokens(self) -> Option<Balance> {
+        self.0.is_multiple_of(WH_PER_KWH).then_some((self.0 / WH_PER_KWH) as Balance)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(EnergyAmount)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(EnergyAmount)
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        EnergyAmount(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        EnergyAmount(self.0.saturating_sub(other.0))
+    }
+
+    /// Scale by a factor given in basis points (1/10,000), rounding down
+    pub fn scale_bps(self, bps: u64) -> Self {
+        EnergyAmount((self.0 as u128 * bps as u128 / 10_000).min(u64::MAX as u128) as u64)
+    }
+}
+
+impl std::fmt::Display for EnergyAmount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:03} kWh", self.0 / WH_PER_KWH, self.0 % WH_PER_KWH)
+    }
+}
+
+/// Price of one kWh, held as a whole number of satang
+///
+/// The price of an [`EnergyAmount`] is computed exactly and rounded down to whole satang by
+/// [`TokenPrice::total`]; the result is what a trade settles as a [`Balance`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TokenPrice(u64);
+
+impl TokenPrice {
+    pub const ZERO: TokenPrice = TokenPrice(0);
+
+    pub const fn from_satang(satang: u64) -> Self {
+        TokenPrice(satang)
+    }
+
+    /// Convert a THB/kWh figure received from outside the system, rounding to the nearest satang
+    pub fn from_thb_f64(thb: f64) -> crate::utils::SystemResult<Self> {
+        let satang = (thb * SATANG_PER_THB as f64).round();
+        if !satang.is_finite() || satang < 0.0 || satang > u64::MAX as f64 {
+            return Err(crate::utils::SystemError::InvalidInput(format!("Invalid price {} THB/kWh", thb)));
+        }
+        Ok(TokenPrice(satang as u64))
+    }
+
+    pub const fn as_satang(self) -> u64 {
+        self.0
+    }
+
+    /// The price in THB/kWh, for display and API responses
+    pub fn as_thb_f64(self) -> f64 {
+        self.0 as f64 / SATANG_PER_THB as f64
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Price in satang of `amount` at this rate, rounded down
+    pub fn total(self, amount: EnergyAmount) -> Balance {
+        // A u64 product of two u64 factors cannot overflow a u128
+        self.0 as Balance * amount.as_wh() as Balance / WH_PER_KWH as Balance
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(TokenPrice)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(TokenPrice)
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        TokenPrice(self.0.saturating_sub(other.0))
+    }
+
+    /// Rate per kWh a total price in satang works out to for `amount`, rounded down
+    pub fn average(total: Balance, amount: EnergyAmount) -> Option<Self> {
+        if amount.is_zero() {
+            return None;
+        }
+        let satang = total.checked_mul(WH_PER_KWH as Balance)? / amount.as_wh() as Balance;
+        u64::try_from(satang).ok().map(TokenPrice)
+    }
+
+    /// Midpoint of two prices, rounded down
+    pub fn midpoint(self, other: Self) -> Self {
+        TokenPrice(((self.0 as u128 + other.0 as u128) / 2) as u64)
+    }
+}
+
+impl std::fmt::Display for TokenPrice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:02} THB/kWh", self.0 / SATANG_PER_THB, self.0 % SATANG_PER_THB)
+    }
+}
+
+/// Millionths in a unit of a [`FixedPoint`] value
+const FIXED_POINT_SCALE: i64 = 1_000_000;
+
+/// Signed decimal with six fractional digits, held as a whole number of millionths
+///
+/// Measurements and ratios carried in consensus data use this instead of floats so every
+/// node encodes them the same; fractional figures are only accepted and produced at API
+/// boundaries through [`FixedPoint::from_f64`] and [`FixedPoint::as_f64`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FixedPoint(i64);
+
+impl FixedPoint {
+    pub const ZERO: FixedPoint = FixedPoint(0);
+
+    pub const fn from_millionths(millionths: i64) -> Self {
+        FixedPoint(millionths)
+    }
+
+    /// Whole number of units
+    pub const fn from_int(value: i32) -> Self {
+        FixedPoint(value as i64 * FIXED_POINT_SCALE)
+    }
+
+    /// Convert a figure received from outside the system, rounding to the nearest millionth
+    pub fn from_f64(value: f64) -> crate::utils::SystemResult<Self> {
+        let millionths = (value * FIXED_POINT_SCALE as f64).round();
+        if !millionths.is_finite() || millionths < i64::MIN as f64 || millionths > i64::MAX as f64 {
+            return Err(crate::utils::SystemError::InvalidInput(format!("Invalid fixed-point value {}", value)));
+        }
+        Ok(FixedPoint(millionths as i64))
+    }
+
+    pub const fn as_millionths(self) -> i64 {
+        self.0
+    }
+
+    /// The value as a float, for display and API responses
+    pub fn as_f64(self) -> f64 {
+        self.0 as f64 / FIXED_POINT_SCALE as f64
+    }
+
+    /// Whole units, rounded towards zero
+    pub const fn trunc(self) -> i64 {
+        self.0 / FIXED_POINT_SCALE
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        FixedPoint(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        FixedPoint(self.0.saturating_sub(other.0))
+    }
+}
+
+impl std::fmt::Display for FixedPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let magnitude = self.0.unsigned_abs();
+        let scale = FIXED_POINT_SCALE as u64;
+        write!(f, "{}{}.{:06}", sign, magnitude / scale, magnitude % scale)
+    }
+}
 
 /// Trade status enumeration
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -40,7 +260,7 @@ pub struct EnergyTrade {
     /// Energy amount traded
     pub energy_amount: EnergyAmount,
     /// Price per unit
-    pub price_per_unit: Balance,
+    pub price_per_unit: TokenPrice,
     /// Buyer ID
     pub buyer_id: String,
     /// Seller ID
@@ -55,7 +275,8 @@ pub struct EnergyTrade {
     pub id: String,
     pub buy_order_id: String,
     pub sell_order_id: String,
-    pub price_per_kwh: Balance,
+    pub price_per_kwh: TokenPrice,
+    /// Total price in satang
     pub total_price: Balance,
     pub grid_fee: Balance,
     pub energy_source: EnergySource,
@@ -81,11 +302,18 @@ pub struct GridLocation {
     pub meter_id: String,
 }
 
-/// Grid coordinates structure
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Grid coordinates structure, in degrees
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct GridCoordinates {
-    pub lat: f64,
-    pub lng: f64,
+    pub lat: FixedPoint,
+    pub lng: FixedPoint,
+}
+
+impl GridCoordinates {
+    /// Convert a latitude and longitude in degrees received from outside the system
+    pub fn from_degrees(lat: f64, lng: f64) -> crate::utils::SystemResult<Self> {
+        Ok(Self { lat: FixedPoint::from_f64(lat)?, lng: FixedPoint::from_f64(lng)? })
+    }
 }
 
 impl Eq for GridLocation {}
@@ -98,9 +326,7 @@ impl std::hash::Hash for GridLocation {
         self.substation.hash(state);
         self.grid_code.hash(state);
         self.meter_id.hash(state);
-        // Hash coordinately as ordered bytes to handle f64
-        self.coordinates.lat.to_bits().hash(state);
-        self.coordinates.lng.to_bits().hash(state);
+        self.coordinates.hash(state);
     }
 }
 
@@ -127,6 +353,21 @@ pub enum EnergySource {
     Mixed,
 }
 
+impl EnergySource {
+    /// Every energy source in canonical order, used wherever iteration must be deterministic
+    pub const ALL: [EnergySource; 9] = [
+        EnergySource::Solar,
+        EnergySource::Wind,
+        EnergySource::Hydro,
+        EnergySource::Biomass,
+        EnergySource::NaturalGas,
+        EnergySource::Nuclear,
+        EnergySource::Coal,
+        EnergySource::Gas,
+        EnergySource::Mixed,
+    ];
+}
+
 /// Producer type classification
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProducerType {
@@ -143,26 +384,26 @@ pub struct GridStatus {
     /// Region identifier
     pub region: String,
     /// Current load
-    pub current_load: f64,
+    pub current_load: EnergyAmount,
     /// Maximum capacity
-    pub max_capacity: f64,
+    pub max_capacity: EnergyAmount,
     /// Grid health score
-    pub health: f64,
+    pub health: FixedPoint,
     /// Last updated timestamp
     pub last_updated: DateTime<Utc>,
     /// Additional fields for compatibility
     pub location: GridLocation,
-    pub capacity: f64,
+    pub capacity: EnergyAmount,
     pub congestion_level: CongestionLevel,
-    pub stability_score: f64,
-    pub outage_risk: f64,
+    pub stability_score: FixedPoint,
+    pub outage_risk: FixedPoint,
     pub updated_at: DateTime<Utc>,
 }
 
 impl GridStatus {
     /// Check if grid can handle additional load
-    pub fn can_handle_load(&self, additional_load: f64) -> bool {
-        (self.current_load + additional_load) <= self.max_capacity
+    pub fn can_handle_load(&self, additional_load: EnergyAmount) -> bool {
+        self.current_load.checked_add(additional_load).is_some_and(|load| load <= self.max_capacity)
     }
 }
 
@@ -185,7 +426,7 @@ pub struct EnergyOrder {
     /// Energy amount
     pub energy_amount: EnergyAmount,
     /// Price per unit
-    pub price_per_unit: Balance,
+    pub price_per_unit: TokenPrice,
     /// Location
     pub location: GridLocation,
     /// Energy source
@@ -287,7 +528,7 @@ pub struct QualityMetrics {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CarbonOffset {
     /// Offset credits
-    pub offset_credits: f64,
+    pub offset_credits: FixedPoint,
     /// Verification status
     pub verified: bool,
     /// Certification body
@@ -329,6 +570,16 @@ pub enum ProposalType {
     RegulationChange,
     FeatureUpdate,
     Emergency,
+    /// Register a new authority in the validator set
+    AddValidator {
+        account_id: AccountId,
+        name: String,
+        public_key: Vec<u8>,
+    },
+    /// Remove an authority from the validator set
+    RemoveValidator {
+        account_id: AccountId,
+    },
 }
 
 /// Proposal status enumeration
@@ -354,11 +605,11 @@ pub struct VotingResults {
     /// Total voting power
     pub total_voting_power: Balance,
     /// Participation rate
-    pub participation_rate: f64,
+    pub participation_rate: FixedPoint,
     /// Total eligible voters
     pub total_eligible: u32,
     /// Turnout percentage
-    pub turnout_percentage: f64,
+    pub turnout_percentage: FixedPoint,
 }
 
 /// Vote choice enumeration
@@ -223,6 +223,7 @@ pub mod time {
 /// Validation utilities
 /// Validation utilities
 pub mod validation {
+    use crate::types::{EnergyAmount, FixedPoint, TokenPrice, WH_PER_KWH};
     use crate::utils::string_to_account_id;
     
     /// Validate email format
@@ -232,13 +233,13 @@ pub mod validation {
     }
     
     /// Validate energy amount
-    pub fn is_valid_energy_amount(amount: u64) -> bool {
-        amount > 0 && amount <= 1_000_000 // Max 1 million kWh
+    pub fn is_valid_energy_amount(amount: EnergyAmount) -> bool {
+        !amount.is_zero() && amount <= EnergyAmount::from_wh(1_000_000 * WH_PER_KWH) // Max 1 million kWh
     }
     
     /// Validate price
-    pub fn is_valid_price(price: u128) -> bool {
-        price > 0 && price <= 100_000_000 // Max 100 million tokens
+    pub fn is_valid_price(price: TokenPrice) -> bool {
+        !price.is_zero() && price <= TokenPrice::from_satang(100_000_000) // Max 1 million THB/kWh
     }
     
     /// Validate grid location
@@ -247,8 +248,8 @@ pub mod validation {
         !location.district.is_empty() &&
         !location.substation.is_empty() &&
         !location.grid_code.is_empty() &&
-        location.coordinates.lat >= -90.0 && location.coordinates.lat <= 90.0 &&
-        location.coordinates.lng >= -180.0 && location.coordinates.lng <= 180.0
+        (FixedPoint::from_int(-90)..=FixedPoint::from_int(90)).contains(&location.coordinates.lat) &&
+        (FixedPoint::from_int(-180)..=FixedPoint::from_int(180)).contains(&location.coordinates.lng)
     }
     
     /// Validate account ID format
@@ -537,7 +538,7 @@ pub mod error {
 
 /// Testing utilities
 pub mod testing {
-    use crate::types::GridCoordinates;
+    use crate::types::{EnergyAmount, FixedPoint, GridCoordinates, TokenPrice};
     use crate::AccountId;
     use crate::utils::{generate_order_id, time};
     
@@ -553,7 +554,7 @@ pub mod testing {
             district: "Pathum Wan".to_string(),
             substation: "Siam".to_string(),
             grid_code: "BKK-001".to_string(),
-            coordinates: GridCoordinates { lat: 13.7463, lng: 100.5352 },
+            coordinates: GridCoordinates { lat: FixedPoint::from_millionths(13_746_300), lng: FixedPoint::from_millionths(100_535_200) },
             region: "Central".to_string(),
             meter_id: "METER-001".to_string(),
         }
@@ -565,8 +566,8 @@ pub mod testing {
             id: generate_order_id(),
             account_id: create_test_account_id(),
             order_type: crate::types::OrderType::Buy,
-            energy_amount: 100.0,
-            price_per_unit: 5000,
+            energy_amount: EnergyAmount::from_wh(100_000),
+            price_per_unit: TokenPrice::from_satang(5000),
             energy_source: Some(crate::types::EnergySource::Solar),
             location: create_test_grid_location(),
             timestamp: time::now(),
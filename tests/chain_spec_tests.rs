@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::SystemTime;
+use thai_energy_trading_blockchain::blockchain::chain_spec::{
+    ChainSpec, ConsensusParameters, ContractParameters, FeeParameters, GenesisAuthority, GovernanceParameters,
+    DEVELOPMENT_ORACLE,
+};
+use thai_energy_trading_blockchain::blockchain::consensus::{BlockImportResult, ConsensusEngine};
+use thai_energy_trading_blockchain::blockchain::contract_runtime::{GasSchedule, GAS_SCHEDULE_VERSION};
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    ValidatorSignature,
+};
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use thai_energy_trading_blockchain::*;
+
+const AUTHORITY_KEYS: [&str; 2] = ["spec-a", "spec-b"];
+
+fn authority(key: &str) -> GenesisAuthority {
+    let keypair = GridTokenXKeyPair::from_node_key(key).unwrap();
+    GenesisAuthority::from_public_key(key, &hex::encode(keypair.export_public_key_bytes())).unwrap()
+}
+
+fn spec() -> ChainSpec {
+    ChainSpec {
+        name: "Spec Testnet".to_string(),
+        network_id: "spec-testnet".to_string(),
+        genesis_timestamp: 1_700_000_000,
+        authorities: AUTHORITY_KEYS.iter().map(|key| authority(key)).collect(),
+        oracles: vec![authority(DEVELOPMENT_ORACLE)],
+        carbon_credit_issuers: BTreeSet::new(),
+        balances: BTreeMap::from([
+            ("alice".to_string(), HashMap::from([(EnergySource::Solar, 500), (EnergySource::Wind, 250)])),
+        ]),
+        currency: BTreeMap::from([("alice".to_string(), 10_000), ("bob".to_string(), 2_500)]),
+        consensus: ConsensusParameters { block_time: 5, slot_timeout: 10 },
+        fees: FeeParameters { gas_limit: 2_000_000, gas_price: 0 },
+        gas_schedule: GasSchedule::default(),
+        contracts: ContractParameters {
+            max_gas_limit: 500_000,
+            max_contract_size: 65_536,
+            max_storage_size: 1_048_576,
+            enable_wasm: false,
+        },
+        governance: GovernanceParameters {
+            validator_rotation_interval: 50,
+            max_validators: 5,
+            authority_threshold: 2,
+        },
+    }
+}
+
+/// Write the spec to a file and configure a node to load it
+fn node_config(dir: &tempfile::TempDir, spec: &ChainSpec, node_key: &str) -> BlockchainConfig {
+    let path = dir.path().join("chain-spec.json");
+    std::fs::write(&path, spec.to_json().unwrap()).unwrap();
+    BlockchainConfig {
+        node_key: node_key.to_string(),
+        chain_spec: path.to_string_lossy().into_owned(),
+        ..BlockchainConfig::default()
+    }
+}
+
+/// Account of the keypair derived from `key`
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+#[tokio::test]
+async fn test_nodes_loading_one_spec_share_genesis() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = spec();
+    let producer = ConsensusEngine::new(&node_config(&dir, &spec, AUTHORITY_KEYS[0])).await.unwrap();
+    let importer = ConsensusEngine::new(&node_config(&dir, &spec, AUTHORITY_KEYS[1])).await.unwrap();
+
+    let genesis = producer.genesis_block().await;
+    assert_eq!(genesis, importer.genesis_block().await);
+    assert_eq!(genesis, spec.genesis_block().unwrap());
+    assert_eq!(genesis.header.timestamp, spec.genesis_timestamp);
+
+    let alice = importer.get_account_balance(&"alice".to_string()).await.unwrap();
+    assert_eq!(alice.total_balance, 750);
+    assert_eq!(alice.energy_balances.get(&EnergySource::Wind), Some(&250));
+    assert_eq!(alice.currency_balance, 10_000);
+    assert_eq!(importer.get_account_balance(&"bob".to_string()).await.unwrap().currency_balance, 2_500);
+    assert_eq!(genesis.header.state_root, importer.get_blockchain_state().await.state_trie.root_of(&spec.genesis_balances()));
+
+    // The spec's authorities sign blocks without any out-of-band key registration
+    producer.add_transaction(mint_envelope("bob", EnergyAmount::from_wh(100_000), 0)).await.unwrap();
+    let block = producer.produce_block().await.unwrap().unwrap();
+    assert_eq!(block.header.validator, spec.authorities[0].account_id);
+    assert_eq!(importer.import_block(block).await.unwrap(), BlockImportResult::Imported);
+}
+
+#[tokio::test]
+async fn test_spec_overrides_chain_wide_settings() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = ChainSpec { gas_schedule: GasSchedule { storage_write: 9_000, ..GasSchedule::default() }, ..spec() };
+    let config = node_config(&dir, &spec, AUTHORITY_KEYS[0]);
+    let applied = spec.apply_to(&config);
+    assert_eq!(applied.smart_contract_vm.gas_schedule, spec.gas_schedule);
+    assert_eq!(ContractParameters::from(&applied.smart_contract_vm), spec.contracts);
+    assert_eq!(applied.network, "spec-testnet");
+    assert_eq!(applied.gas_limit, 2_000_000);
+    assert_eq!(applied.max_validators, 5);
+    assert_eq!(applied.node_key, config.node_key);
+
+    let engine = ConsensusEngine::new(&config).await.unwrap();
+    let mut validators: Vec<_> = engine.get_validators().await.into_iter().collect();
+    validators.sort();
+    let mut expected: Vec<_> = spec.authorities.iter().map(|authority| authority.account_id.clone()).collect();
+    expected.sort();
+    assert_eq!(validators, expected);
+}
+
+#[tokio::test]
+async fn test_genesis_of_another_spec_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = spec();
+    let other = ChainSpec { genesis_timestamp: spec.genesis_timestamp + 1, ..spec.clone() };
+    assert_ne!(spec.genesis_block().unwrap().header.hash, other.genesis_block().unwrap().header.hash);
+
+    let config = node_config(&dir, &spec, AUTHORITY_KEYS[0]);
+    let result = ConsensusEngine::with_genesis(&config, other.genesis_block().unwrap()).await;
+    assert!(matches!(result, Err(SystemError::Configuration(_))));
+    assert!(ConsensusEngine::with_genesis(&config, spec.genesis_block().unwrap()).await.is_ok());
+}
+
+#[test]
+fn test_spec_json_round_trip_and_validation() {
+    let spec = spec();
+    assert_eq!(ChainSpec::from_json(&spec.to_json().unwrap()).unwrap(), spec);
+
+    let duplicate = ChainSpec {
+        authorities: vec![authority(AUTHORITY_KEYS[0]), authority(AUTHORITY_KEYS[0])],
+        ..spec.clone()
+    };
+    assert!(matches!(ChainSpec::from_json(&duplicate.to_json().unwrap()), Err(SystemError::Configuration(_))));
+
+    let mut bad_key = spec.clone();
+    bad_key.authorities[0].public_key = "abcd".to_string();
+    assert!(bad_key.validate().is_err());
+
+    let duplicate_oracle = ChainSpec {
+        oracles: vec![authority(DEVELOPMENT_ORACLE), authority(DEVELOPMENT_ORACLE)],
+        ..spec.clone()
+    };
+    assert!(duplicate_oracle.validate().is_err());
+
+    let mut bad_oracle_key = spec.clone();
+    bad_oracle_key.oracles[0].public_key = "abcd".to_string();
+    assert!(bad_oracle_key.validate().is_err());
+
+    let too_many = ChainSpec {
+        governance: GovernanceParameters { max_validators: 1, authority_threshold: 1, ..spec.governance.clone() },
+        ..spec.clone()
+    };
+    assert!(too_many.validate().is_err());
+
+    // Two authorities can never reach a threshold of three, however many validators are allowed
+    let unreachable_threshold = ChainSpec {
+        governance: GovernanceParameters { authority_threshold: 3, ..spec.governance.clone() },
+        ..spec.clone()
+    };
+    assert!(matches!(
+        ChainSpec::from_json(&unreachable_threshold.to_json().unwrap()),
+        Err(SystemError::Configuration(_))
+    ));
+
+    let unknown_schedule = ChainSpec {
+        gas_schedule: GasSchedule { version: GAS_SCHEDULE_VERSION + 1, ..GasSchedule::default() },
+        ..spec.clone()
+    };
+    assert!(unknown_schedule.validate().is_err());
+    assert!(GenesisAuthority::from_public_key("nobody", "not-hex").is_err());
+}
@@ -0,0 +1,465 @@
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::{
+    BlockImportResult, BlockRejection, ConsensusEngine, EnergyBlock,
+};
+use thai_energy_trading_blockchain::blockchain::state_transition::{
+    ReceiptStatus, TransactionEvent, TransactionRejection,
+};
+use thai_energy_trading_blockchain::blockchain::merkle::verify_transaction_proof;
+use thai_energy_trading_blockchain::blockchain::state_trie::verify_account_proof;
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    SignatureRejection, ValidatorSignature,
+};
+use std::time::SystemTime;
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use thai_energy_trading_blockchain::*;
+
+fn validator_config(node_key: &str) -> BlockchainConfig {
+    BlockchainConfig {
+        node_key: node_key.to_string(),
+        validator: true,
+        ..BlockchainConfig::default()
+    }
+}
+
+/// Chain whose only authority is the node with `node_key`
+fn single_authority_spec(node_key: &str) -> ChainSpec {
+    ChainSpec {
+        authorities: vec![GenesisAuthority::development(node_key).unwrap()],
+        ..ChainSpec::development(&BlockchainConfig::default())
+    }
+}
+
+/// Create a consensus engine where the local node is the only scheduled validator
+async fn single_validator_engine(node_key: &str) -> ConsensusEngine {
+    ConsensusEngine::with_chain_spec(&validator_config(node_key), &single_authority_spec(node_key)).await.unwrap()
+}
+
+/// Create a non-validating node on the chain of the producer with `producer_key`
+async fn importer_for(producer_key: &str) -> ConsensusEngine {
+    let config = BlockchainConfig { validator: false, ..BlockchainConfig::default() };
+    ConsensusEngine::with_chain_spec(&config, &single_authority_spec(producer_key)).await.unwrap()
+}
+
+/// Re-hash and re-sign a modified block so only the targeted check can fail
+fn resign(mut block: EnergyBlock, producer_key: &str) -> EnergyBlock {
+    let keypair = GridTokenXKeyPair::from_node_key(producer_key).unwrap();
+    block.header.hash = ConsensusEngine::calculate_block_hash(&block);
+    block.validator_signature.signature = keypair.sign(&hex::decode(&block.header.hash).unwrap()).to_bytes().to_vec();
+    block
+}
+
+/// Account of the keypair derived from `key`
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+/// Transfer between the accounts of `from` and `to`, signed by `from`
+fn transfer_envelope(from: &str, to: &str, amount: Balance, nonce: u64) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::Transfer {
+            from: account(from),
+            to: account(to),
+            amount,
+            energy_type: EnergySource::Solar,
+            grid_location: utils::testing::create_test_grid_location(),
+        },
+        nonce,
+    );
+    envelope.gas_price = 1;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(from).unwrap());
+    envelope
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+#[tokio::test]
+async fn test_produced_block_is_signed_by_node_key() {
+    let engine = single_validator_engine("validator-one").await;
+    engine.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(1000_000), 0)).await.unwrap();
+
+    let block = engine.produce_block().await.unwrap().expect("block should be produced");
+
+    let keypair = GridTokenXKeyPair::from_node_key("validator-one").unwrap();
+    assert_eq!(block.header.validator, keypair.account_id());
+    assert_eq!(block.validator_signature.signature.len(), 64);
+    assert!(engine.verify_block_signature(&block).await.unwrap());
+
+    // A forged signature must not verify
+    let mut forged = block.clone();
+    forged.validator_signature.signature = vec![0; 64];
+    assert!(!engine.verify_block_signature(&forged).await.unwrap());
+
+    // Tampering with the header invalidates the signed hash
+    let mut tampered = block.clone();
+    tampered.header.timestamp += 1;
+    assert!(!engine.verify_block_signature(&tampered).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_block_from_other_validator_verifies_with_registered_key() {
+    let producer = single_validator_engine("validator-two").await;
+    producer.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(1000_000), 0)).await.unwrap();
+    let block = producer.produce_block().await.unwrap().unwrap();
+
+    // Unknown authorities are rejected outright
+    let outsider = ConsensusEngine::with_chain_spec(&validator_config("validator-three"), &single_authority_spec("validator-three")).await.unwrap();
+    assert!(outsider.verify_block_signature(&block).await.is_err());
+
+    let importer = ConsensusEngine::with_chain_spec(&validator_config("validator-three"), &single_authority_spec("validator-two")).await.unwrap();
+    assert!(importer.verify_block_signature(&block).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_validator_outside_the_spec_does_not_schedule_itself() {
+    let spec = single_authority_spec("validator-four");
+    let outsider = ConsensusEngine::with_chain_spec(&validator_config("validator-five"), &spec).await.unwrap();
+
+    // The authority set and schedule come from the spec alone, whatever the node key
+    assert_eq!(outsider.get_validators().await, [spec.authorities[0].account_id.clone()].into());
+    assert_eq!(outsider.get_validator_schedule().await.active_validators, vec![spec.authorities[0].account_id.clone()]);
+    assert_eq!(outsider.genesis_block().await, spec.genesis_block().unwrap());
+    outsider.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(1000_000), 0)).await.unwrap();
+    assert!(outsider.produce_block().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_import_valid_blocks_in_order() {
+    let producer = single_validator_engine("producer-a").await;
+    let importer = importer_for("producer-a").await;
+
+    producer.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(100_000_000), 0)).await.unwrap();
+    producer.add_transaction(transfer_envelope("alice", "bob", 50_000, 1)).await.unwrap();
+    let first = producer.produce_block().await.unwrap().unwrap();
+    assert_eq!(first.transactions.len(), 2);
+    producer.add_transaction(transfer_envelope("bob", "carol", 5, 0)).await.unwrap();
+    let second = producer.produce_block().await.unwrap().unwrap();
+
+    assert_eq!(importer.import_block(first.clone()).await.unwrap(), BlockImportResult::Imported);
+    assert_eq!(importer.import_block(second.clone()).await.unwrap(), BlockImportResult::Imported);
+
+    let state = importer.get_blockchain_state().await;
+    assert_eq!(state.block_height, 2);
+    assert_eq!(state.latest_block_hash, second.header.hash);
+    assert_eq!(state.balances, producer.get_blockchain_state().await.balances);
+    assert_eq!(state.balances[&account("bob")].total_balance, 50_000 - 21_000 - 5);
+    assert_eq!(state.balances[&account("carol")].total_balance, 5);
+
+    // Re-importing a known block is rejected
+    assert_eq!(
+        importer.import_block(first.clone()).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::AlreadyImported(first.header.hash))
+    );
+}
+
+#[tokio::test]
+async fn test_import_rejects_invalid_blocks() {
+    let producer = single_validator_engine("producer-b").await;
+    let importer = importer_for("producer-b").await;
+
+    producer.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(1000_000), 0)).await.unwrap();
+    let block = producer.produce_block().await.unwrap().unwrap();
+
+    let mut wrong_number = block.clone();
+    wrong_number.header.number = 5;
+    assert!(matches!(
+        importer.import_block(resign(wrong_number, "producer-b")).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::InvalidBlockNumber { expected: 1, found: 5 })
+    ));
+
+    let mut wrong_parent = block.clone();
+    wrong_parent.header.parent_hash = "00".repeat(32);
+    assert!(matches!(
+        importer.import_block(resign(wrong_parent, "producer-b")).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::ParentHashMismatch { .. })
+    ));
+
+    let mut stale = block.clone();
+    stale.header.timestamp = 0;
+    assert!(matches!(
+        importer.import_block(resign(stale, "producer-b")).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::TimestampNotMonotonic { .. })
+    ));
+
+    let mut forged = block.clone();
+    forged.validator_signature.signature = vec![1; 64];
+    assert_eq!(
+        importer.import_block(forged).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::InvalidSignature)
+    );
+
+    let mut extra_tx = block.clone();
+    extra_tx.transactions.push(transfer_envelope("carol", "dave", 1, 0));
+    assert!(matches!(
+        importer.import_block(resign(extra_tx, "producer-b")).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::TransactionRootMismatch { .. })
+    ));
+
+    let mut wrong_state = block.clone();
+    wrong_state.header.state_root = "11".repeat(32);
+    assert!(matches!(
+        importer.import_block(resign(wrong_state, "producer-b")).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::StateRootMismatch { .. })
+    ));
+
+    let mut wrong_receipts = block.clone();
+    wrong_receipts.header.receipts_root = "22".repeat(32);
+    assert!(matches!(
+        importer.import_block(resign(wrong_receipts, "producer-b")).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::ReceiptsRootMismatch { .. })
+    ));
+
+    // A block from a validator outside the schedule is rejected before signature checks
+    let outsider = single_validator_engine("outsider").await;
+    outsider.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(1000_000), 0)).await.unwrap();
+    let mut foreign = outsider.produce_block().await.unwrap().unwrap();
+    foreign.header.parent_hash = block.header.parent_hash.clone();
+    assert!(matches!(
+        importer.import_block(resign(foreign, "outsider")).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::UnexpectedValidator { .. })
+    ));
+
+    // A transaction hash that does not match the transaction is caught before the root
+    let mut wrong_tx_hash = block.clone();
+    wrong_tx_hash.transactions[0].hash = [3; 32];
+    wrong_tx_hash.header.transaction_root = ConsensusEngine::calculate_merkle_root(&wrong_tx_hash.transactions);
+    assert_eq!(
+        importer.import_block(resign(wrong_tx_hash, "producer-b")).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::TransactionHashMismatch { index: 0 })
+    );
+
+    // Transactions that cannot be executed invalidate the block
+    let mut unfunded = block.clone();
+    unfunded.transactions = vec![transfer_envelope("nobody", "bob", 10, 0)];
+    unfunded.header.transaction_root = ConsensusEngine::calculate_merkle_root(&unfunded.transactions);
+    assert!(matches!(
+        importer.import_block(resign(unfunded, "producer-b")).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::InvalidTransaction { index: 0, .. })
+    ));
+
+    // The untouched block still imports afterwards
+    assert_eq!(importer.import_block(block).await.unwrap(), BlockImportResult::Imported);
+}
+
+#[tokio::test]
+async fn test_import_rejects_transactions_with_invalid_signatures() {
+    let producer = single_validator_engine("producer-sig").await;
+    let importer = importer_for("producer-sig").await;
+
+    producer.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(100_000_000), 0)).await.unwrap();
+    producer.add_transaction(transfer_envelope("alice", "bob", 50_000, 1)).await.unwrap();
+    let block = producer.produce_block().await.unwrap().unwrap();
+
+    // Rewrite a block's transfer, keeping its hash and the block consistent with the change
+    let tampered = |tamper: &dyn Fn(&mut EnergyTransactionEnvelope)| {
+        let mut tampered = block.clone();
+        tamper(&mut tampered.transactions[1]);
+        tampered.transactions[1].hash = tampered.transactions[1].compute_hash().unwrap();
+        tampered.header.transaction_root = ConsensusEngine::calculate_merkle_root(&tampered.transactions);
+        resign(tampered, "producer-sig")
+    };
+    let rejected = |reason: SignatureRejection| BlockImportResult::Rejected(BlockRejection::InvalidTransaction {
+        index: 1,
+        reason: TransactionRejection::Signature(reason),
+    });
+
+    // The producer redirects the transfer after alice signed it
+    let redirected = tampered(&|tx| {
+        if let EnergyTransaction::Transfer { to, .. } = &mut tx.transaction {
+            *to = account("producer-sig");
+        }
+    });
+    assert_eq!(importer.import_block(redirected).await.unwrap(), rejected(SignatureRejection::InvalidSignature));
+
+    let flipped = tampered(&|tx| tx.signature[0] ^= 1);
+    assert_eq!(importer.import_block(flipped).await.unwrap(), rejected(SignatureRejection::InvalidSignature));
+
+    let mallory = GridTokenXKeyPair::from_node_key("mallory").unwrap();
+    let resigned = tampered(&|tx| {
+        let signing_hash = tx.signing_hash().unwrap();
+        tx.public_key = mallory.export_public_key_bytes().to_vec();
+        tx.signature = mallory.sign(&signing_hash).to_bytes().to_vec();
+    });
+    assert_eq!(importer.import_block(resigned).await.unwrap(), rejected(SignatureRejection::SignerMismatch {
+        sender: account("alice"),
+        signer: account("mallory"),
+    }));
+
+    let stripped = tampered(&|tx| tx.signature.clear());
+    assert_eq!(importer.import_block(stripped).await.unwrap(), rejected(SignatureRejection::Unsigned));
+
+    // A producer does not include unsigned transactions either
+    let mut unsigned = transfer_envelope("alice", "carol", 1_000, 2);
+    unsigned.signature.clear();
+    unsigned.hash = unsigned.compute_hash().unwrap();
+    producer.add_transaction(unsigned).await.unwrap();
+    assert!(producer.produce_block().await.unwrap().is_none());
+
+    assert_eq!(importer.import_block(block).await.unwrap(), BlockImportResult::Imported);
+}
+
+#[tokio::test]
+async fn test_blocks_carry_receipts_with_events() {
+    let producer = single_validator_engine("producer-c").await;
+    let importer = importer_for("producer-c").await;
+
+    let mint = mint_envelope("alice", EnergyAmount::from_wh(100_000_000), 0);
+    let transfer = transfer_envelope("alice", "bob", 50_000, 1);
+    // Alice can still pay the fee but not the amount, so the transfer is included as failed
+    let overdraft = transfer_envelope("alice", "carol", 1_000_000, 2);
+    for tx in [&mint, &transfer, &overdraft] {
+        producer.add_transaction(tx.clone()).await.unwrap();
+    }
+    let block = producer.produce_block().await.unwrap().unwrap();
+    assert_eq!(block.receipts.len(), 3);
+    assert_eq!(block.header.receipts_root, ConsensusEngine::calculate_receipts_root(&block.receipts));
+
+    let minted = producer.get_transaction_receipt(&mint.hash).await.unwrap();
+    assert_eq!(minted.status, ReceiptStatus::Success);
+    assert_eq!(minted.fee_charged, 0);
+    assert_eq!(minted.events, vec![TransactionEvent::TokensMinted {
+        account: account("alice"),
+        energy_type: EnergySource::Solar,
+        amount: 100_000,
+    }]);
+
+    let transferred = producer.get_transaction_receipt(&transfer.hash).await.unwrap();
+    assert_eq!(transferred.status, ReceiptStatus::Success);
+    assert_eq!(transferred.gas_used, 21_000);
+    assert_eq!(transferred.fee_charged, 21_000);
+    assert!(matches!(&transferred.events[..], [TransactionEvent::TokensTransferred { amount: 50_000, .. }]));
+
+    let failed = producer.get_transaction_receipt(&overdraft.hash).await.unwrap();
+    assert_eq!(failed.status, ReceiptStatus::Failed);
+    assert_eq!(failed.fee_charged, 21_000);
+    assert!(failed.events.is_empty());
+    assert!(failed.error.is_some());
+
+    // Importers re-derive the same receipts and serve them by transaction hash
+    assert_eq!(importer.import_block(block.clone()).await.unwrap(), BlockImportResult::Imported);
+    assert_eq!(importer.get_transaction_receipt(&overdraft.hash).await, Some(failed));
+    assert_eq!(importer.get_transaction_receipt(&[0u8; 32]).await, None);
+}
+
+#[tokio::test]
+async fn test_fractional_kwh_amounts_are_rejected_not_rounded() {
+    let producer = single_validator_engine("producer-fraction").await;
+
+    let fractional = mint_envelope("alice", EnergyAmount::from_wh(1_999), 0);
+    let whole = mint_envelope("alice", EnergyAmount::from_wh(2_000), 1);
+    for tx in [&fractional, &whole] {
+        producer.add_transaction(tx.clone()).await.unwrap();
+    }
+    producer.produce_block().await.unwrap().unwrap();
+
+    let rejected = producer.get_transaction_receipt(&fractional.hash).await.unwrap();
+    assert_eq!(rejected.status, ReceiptStatus::Failed);
+    assert_eq!(rejected.error.as_deref(), Some("Invalid input: 1.999 kWh is not a whole number of tokens"));
+    let minted = producer.get_transaction_receipt(&whole.hash).await.unwrap();
+    assert_eq!(minted.events, vec![TransactionEvent::TokensMinted {
+        account: account("alice"),
+        energy_type: EnergySource::Solar,
+        amount: 2,
+    }]);
+    assert_eq!(producer.get_account_balance(&account("alice")).await.unwrap().total_balance, 2);
+}
+
+#[tokio::test]
+async fn test_account_proofs_verify_against_block_state_root() {
+    let producer = single_validator_engine("producer-d").await;
+    producer.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(100_000_000), 0)).await.unwrap();
+    let first = producer.produce_block().await.unwrap().unwrap();
+    producer.add_transaction(transfer_envelope("alice", "bob", 30_000, 1)).await.unwrap();
+    let second = producer.produce_block().await.unwrap().unwrap();
+
+    let at_first = producer.get_account_proof(&account("alice"), 1).await.unwrap();
+    assert_eq!(at_first.account_state.as_ref().unwrap().total_balance, 100_000);
+    assert!(verify_account_proof(&first.header.state_root, &at_first));
+    assert!(!verify_account_proof(&second.header.state_root, &at_first));
+
+    let at_second = producer.get_account_proof(&account("alice"), 2).await.unwrap();
+    assert_eq!(at_second.account_state.as_ref().unwrap().total_balance, 100_000 - 21_000 - 30_000);
+    assert!(verify_account_proof(&second.header.state_root, &at_second));
+
+    // Bob did not exist at height 1, which is provable as well
+    let absent = producer.get_account_proof(&account("bob"), 1).await.unwrap();
+    assert!(absent.account_state.is_none());
+    assert!(verify_account_proof(&first.header.state_root, &absent));
+
+    // Per-source and staked balances are covered, not just the total
+    let mut forged = at_second.clone();
+    forged.account_state.as_mut().unwrap().staked_balance += 1;
+    assert!(!verify_account_proof(&second.header.state_root, &forged));
+
+    assert!(producer.get_account_proof(&account("alice"), 3).await.is_none());
+}
+
+#[tokio::test]
+async fn test_transaction_inclusion_proofs() {
+    let producer = single_validator_engine("producer-e").await;
+    let transactions = vec![
+        mint_envelope("alice", EnergyAmount::from_wh(100_000_000), 0),
+        transfer_envelope("alice", "bob", 1_000, 1),
+        transfer_envelope("alice", "carol", 2_000, 2),
+        transfer_envelope("alice", "dave", 3_000, 3),
+        transfer_envelope("alice", "erin", 4_000, 4),
+    ];
+    for tx in &transactions {
+        producer.add_transaction(tx.clone()).await.unwrap();
+    }
+    let block = producer.produce_block().await.unwrap().unwrap();
+    assert_eq!(block.transactions.len(), 5);
+    let root = &block.header.transaction_root;
+
+    for (index, tx) in transactions.iter().enumerate() {
+        let proof = producer.get_transaction_proof(&tx.hash).await.unwrap();
+        assert_eq!(proof.block_number, 1);
+        assert_eq!(proof.block_hash, block.header.hash);
+        assert_eq!(proof.index as usize, index);
+        assert!(verify_transaction_proof(root, &tx.hash, &proof), "proof for transaction {}", index);
+
+        // A proof only works for its own transaction and root
+        let other = &transactions[(index + 1) % transactions.len()];
+        assert!(!verify_transaction_proof(root, &other.hash, &proof));
+        assert!(!verify_transaction_proof(&"00".repeat(32), &tx.hash, &proof));
+    }
+
+    let mut truncated = producer.get_transaction_proof(&transactions[0].hash).await.unwrap();
+    truncated.siblings.pop();
+    assert!(!verify_transaction_proof(root, &transactions[0].hash, &truncated));
+
+    assert!(producer.get_transaction_proof(&[7u8; 32]).await.is_none());
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::{ConsensusEngine, EnergyBlock};
+use thai_energy_trading_blockchain::blockchain::encoding::{self, EncodingError, ENCODING_VERSION};
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    ValidatorSignature,
+};
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use thai_energy_trading_blockchain::*;
+
+/// Account of the keypair derived from `key`
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+#[test]
+fn test_encoding_layout_is_fixed() {
+    // Version byte, big-endian fixed-width integers and length-prefixed strings
+    assert_eq!(encoding::encode(&(1u32, "ab")).unwrap(), vec![ENCODING_VERSION, 0, 0, 0, 1, 0, 0, 0, 2, b'a', b'b']);
+    assert_eq!(encoding::encode(&Some(true)).unwrap(), vec![ENCODING_VERSION, 1, 1]);
+
+    // Floats have no canonical encoding; fixed-point values are plain integers
+    assert_eq!(encoding::encode(&1.5f64), Err(EncodingError::Float));
+    assert_eq!(encoding::hash(&(1u32, 0.5f32)), Err(EncodingError::Float));
+    let mut expected = vec![ENCODING_VERSION];
+    expected.extend_from_slice(&1_500_000i64.to_be_bytes());
+    assert_eq!(encoding::encode(&FixedPoint::from_f64(1.5).unwrap()).unwrap(), expected);
+}
+
+#[test]
+fn test_maps_encode_independently_of_insertion_order() {
+    let ascending: HashMap<String, u64> = (0..64).map(|i| (format!("account-{}", i), i)).collect();
+    let descending: HashMap<String, u64> = (0..64).rev().map(|i| (format!("account-{}", i), i)).collect();
+    assert_eq!(encoding::encode(&ascending).unwrap(), encoding::encode(&descending).unwrap());
+    assert_eq!(encoding::decode::<HashMap<String, u64>>(&encoding::encode(&ascending).unwrap()).unwrap(), ascending);
+}
+
+#[test]
+fn test_decoding_rejects_other_versions_and_trailing_bytes() {
+    let mut encoded = encoding::encode(&7u64).unwrap();
+    encoded.push(0);
+    assert_eq!(encoding::decode::<u64>(&encoded), Err(EncodingError::TrailingBytes(1)));
+
+    encoded.pop();
+    encoded[0] = ENCODING_VERSION + 1;
+    assert_eq!(encoding::decode::<u64>(&encoded), Err(EncodingError::UnsupportedVersion(ENCODING_VERSION + 1)));
+    assert_eq!(encoding::decode::<u64>(&[ENCODING_VERSION, 0, 0]), Err(EncodingError::UnexpectedEnd));
+}
+
+#[test]
+fn test_transactions_hash_and_sign_their_canonical_encoding() {
+    let envelope = mint_envelope("encoding-alice", EnergyAmount::from_wh(100_000), 0);
+    assert_eq!(envelope.hash, envelope.compute_hash().unwrap());
+    assert!(envelope.has_valid_hash());
+
+    let decoded: EnergyTransactionEnvelope = encoding::decode(&encoding::encode(&envelope).unwrap()).unwrap();
+    assert_eq!(decoded, envelope);
+    assert_eq!(decoded.signing_hash(), envelope.signing_hash());
+    assert!(decoded.check_signature().is_ok());
+    assert_eq!(envelope.encoded_size(), encoding::encode(&envelope).unwrap().len());
+}
+
+#[test]
+fn test_transaction_hash_covers_the_signed_envelope() {
+    let keypair = GridTokenXKeyPair::from_node_key("encoding-alice").unwrap();
+    let first = mint_envelope("encoding-alice", EnergyAmount::from_wh(100_000), 0);
+
+    // The same transaction body under another nonce is a different transaction
+    let mut second = first.clone();
+    second.nonce = 1;
+    second.sign(&keypair);
+    assert_eq!(first.transaction, second.transaction);
+    assert_ne!(first.hash, second.hash);
+
+    // A hash that does not match the contents is detected
+    let mut forged = first.clone();
+    forged.signature[0] ^= 1;
+    assert!(!forged.has_valid_hash());
+    forged.hash = forged.compute_hash().unwrap();
+    assert_ne!(forged.hash, first.hash);
+}
+
+#[tokio::test]
+async fn test_blocks_round_trip_and_keep_their_hash() {
+    let config = BlockchainConfig {
+        node_key: "encoding-producer".to_string(),
+        validator: true,
+        ..BlockchainConfig::default()
+    };
+    let spec = ChainSpec {
+        authorities: vec![GenesisAuthority::development(&config.node_key).unwrap()],
+        ..ChainSpec::development(&config)
+    };
+    let producer = ConsensusEngine::with_chain_spec(&config, &spec).await.unwrap();
+    producer.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(100_000), 0)).await.unwrap();
+    let block = producer.produce_block().await.unwrap().unwrap();
+
+    let encoded = encoding::encode(&block).unwrap();
+    let decoded: EnergyBlock = encoding::decode(&encoded).unwrap();
+    assert_eq!(encoding::encode(&decoded).unwrap(), encoded);
+    assert_eq!(decoded.transactions, block.transactions);
+    assert_eq!(ConsensusEngine::calculate_header_hash(&decoded.header), block.header.hash);
+}
@@ -0,0 +1,409 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use thai_energy_trading_blockchain::application::enhanced_trading::EnhancedTradingService;
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::{
+    BlockImportResult, BlockRejection, ConsensusEngine, EnergyBlock,
+};
+use thai_energy_trading_blockchain::blockchain::finality::{Precommit, PrecommitRejection};
+use thai_energy_trading_blockchain::blockchain::network::NetworkLayer;
+use thai_energy_trading_blockchain::blockchain::node::BlockchainNode;
+use thai_energy_trading_blockchain::blockchain::state_transition::ReceiptStatus;
+use thai_energy_trading_blockchain::blockchain::storage::BlockchainStorage;
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    self, EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    GridValidation, ValidatorSignature,
+};
+use thai_energy_trading_blockchain::blockchain::BlockchainEngine;
+use thai_energy_trading_blockchain::config::{BlockchainConfig, DatabaseConfig, GridConfig};
+use thai_energy_trading_blockchain::infrastructure::database::DatabaseManager;
+use thai_energy_trading_blockchain::infrastructure::grid::GridManager;
+use thai_energy_trading_blockchain::runtime::continuous_double_auction::SettlementStatus;
+use thai_energy_trading_blockchain::*;
+use uuid::Uuid;
+
+const VALIDATOR_KEYS: [&str; 3] = ["finality-a", "finality-b", "finality-c"];
+
+fn validator_config(node_key: &str, authority_threshold: u32) -> BlockchainConfig {
+    BlockchainConfig {
+        node_key: node_key.to_string(),
+        validator: true,
+        authority_threshold,
+        ..BlockchainConfig::default()
+    }
+}
+
+/// Account of the keypair derived from `key`
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+/// Chain with the nodes with `keys` as authorities, scheduled in the given order
+fn authority_spec(config: &BlockchainConfig, keys: &[&str]) -> ChainSpec {
+    ChainSpec {
+        authorities: keys.iter().map(|key| GenesisAuthority::development(key).unwrap()).collect(),
+        ..ChainSpec::development(config)
+    }
+}
+
+/// Point `config` at a spec file in `dir` for the chain with the nodes with `keys` as authorities
+fn on_authority_chain(config: BlockchainConfig, keys: &[&str], dir: &tempfile::TempDir) -> BlockchainConfig {
+    let path = dir.path().join("chain-spec.json");
+    std::fs::write(&path, authority_spec(&config, keys).to_json().unwrap()).unwrap();
+    BlockchainConfig { chain_spec: path.to_string_lossy().into_owned(), ..config }
+}
+
+/// Three validators on one chain that need two precommits to finalize a block
+async fn validator_set() -> Vec<ConsensusEngine> {
+    let spec = authority_spec(&validator_config(VALIDATOR_KEYS[0], 2), &VALIDATOR_KEYS);
+    let mut engines = Vec::new();
+    for key in VALIDATOR_KEYS {
+        engines.push(ConsensusEngine::with_chain_spec(&validator_config(key, 2), &spec).await.unwrap());
+    }
+    engines
+}
+
+/// Validator with `key` among the three of `validator_set`, storing its chain in `dir`
+async fn persistent_validator(key: &str, dir: &tempfile::TempDir) -> ConsensusEngine {
+    let config = BlockchainConfig {
+        data_dir: Some(dir.path().to_string_lossy().into_owned()),
+        ..validator_config(key, 2)
+    };
+    let config = on_authority_chain(config, &VALIDATOR_KEYS, dir);
+    let storage = Arc::new(BlockchainStorage::new(&config).await.unwrap());
+    ConsensusEngine::with_storage(&config, storage).await.unwrap()
+}
+
+/// Two competing blocks at height 1 by the first validator, ordered by hash, so the first
+/// wins a tie between equally signed branches
+async fn competing_blocks() -> [EnergyBlock; 2] {
+    let spec = authority_spec(&validator_config(VALIDATOR_KEYS[0], 2), &VALIDATOR_KEYS);
+    let mut forks = Vec::new();
+    for recipient in ["carol", "bob"] {
+        let producer = ConsensusEngine::with_chain_spec(&validator_config(VALIDATOR_KEYS[0], 2), &spec).await.unwrap();
+        producer.add_transaction(mint_envelope(recipient, EnergyAmount::from_wh(300_000), 0)).await.unwrap();
+        forks.push(producer.produce_block().await.unwrap().unwrap());
+    }
+    forks.sort_by(|first, second| first.header.hash.cmp(&second.header.hash));
+    let second = forks.pop().unwrap();
+    [forks.pop().unwrap(), second]
+}
+
+/// Produce block 1 on the first validator
+async fn produce_first_block(producer: &ConsensusEngine) -> (EnergyBlock, EnergyTransactionEnvelope) {
+    let mint = mint_envelope("alice", EnergyAmount::from_wh(1000_000), 0);
+    producer.add_transaction(mint.clone()).await.unwrap();
+    (producer.produce_block().await.unwrap().unwrap(), mint)
+}
+
+/// Order of `amount` solar energy at 4.50 THB/kWh by the account of `key`
+fn order(key: &str, order_type: OrderType, amount: EnergyAmount) -> EnergyOrder {
+    EnergyOrder {
+        account_id: account(key),
+        order_type,
+        energy_amount: amount,
+        price_per_unit: TokenPrice::from_satang(450),
+        ..utils::testing::create_test_energy_order()
+    }
+}
+
+#[tokio::test]
+async fn test_block_finalizes_once_threshold_precommits_collected() {
+    let engines = validator_set().await;
+    let (producer, voter) = (&engines[0], &engines[1]);
+    let mut producer_precommits = producer.subscribe_precommits();
+    let mut voter_precommits = voter.subscribe_precommits();
+
+    // The producer's own precommit alone does not reach the threshold
+    let (block, mint) = produce_first_block(producer).await;
+    let producer_precommit = producer_precommits.try_recv().unwrap();
+    assert_eq!(producer_precommit.block_hash, block.header.hash);
+    assert_eq!(producer.finalized_height(), 0);
+    assert!(!producer.is_transaction_finalized(&mint.hash).await);
+
+    // A second authority imports the block and precommits to it
+    assert_eq!(voter.import_block(block.clone()).await.unwrap(), BlockImportResult::Imported);
+    let voter_precommit = voter_precommits.try_recv().unwrap();
+    assert_eq!(voter_precommit.validator, voter.node_account_id());
+    assert_eq!(voter.finalized_height(), 0);
+
+    producer.import_precommit(voter_precommit).await.unwrap();
+    assert_eq!(producer.finalized_height(), 1);
+    assert_eq!(producer.finalized_hash().await, block.header.hash);
+    assert!(producer.is_transaction_finalized(&mint.hash).await);
+
+    voter.import_precommit(producer_precommit).await.unwrap();
+    assert_eq!(voter.finalized_height(), 1);
+}
+
+#[tokio::test]
+async fn test_precommits_arriving_before_their_block_are_buffered() {
+    let engines = validator_set().await;
+    let (producer, late) = (&engines[0], &engines[2]);
+    let mut producer_precommits = producer.subscribe_precommits();
+
+    let (block, _) = produce_first_block(producer).await;
+    let precommit = producer_precommits.try_recv().unwrap();
+    late.import_precommit(precommit).await.unwrap();
+    assert_eq!(late.get_precommits(1, &block.header.hash).await.len(), 1);
+    assert_eq!(late.finalized_height(), 0);
+
+    // Importing the block adds the late node's own precommit, reaching the threshold
+    let wait = late.wait_for_finality(1);
+    let import = late.import_block(block.clone());
+    let (finalized, imported) = tokio::join!(
+        tokio::time::timeout(Duration::from_secs(5), wait),
+        import,
+    );
+    assert_eq!(imported.unwrap(), BlockImportResult::Imported);
+    finalized.expect("block not finalized in time").unwrap();
+    assert_eq!(late.finalized_height(), 1);
+    assert!(late.get_precommits(1, &block.header.hash).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_invalid_precommits_are_rejected() {
+    let engines = validator_set().await;
+    let (producer, voter) = (&engines[0], &engines[1]);
+    let (block, _) = produce_first_block(producer).await;
+    let voter_keypair = GridTokenXKeyPair::from_node_key(VALIDATOR_KEYS[1]).unwrap();
+
+    let stranger = GridTokenXKeyPair::from_node_key("finality-stranger").unwrap();
+    assert_eq!(
+        producer.import_precommit(Precommit::sign(1, block.header.hash.clone(), &stranger)).await,
+        Err(PrecommitRejection::UnknownAuthority(stranger.account_id().to_string()))
+    );
+
+    let mut forged = Precommit::sign(1, block.header.hash.clone(), &voter_keypair);
+    forged.block_number = 2;
+    assert_eq!(producer.import_precommit(forged).await, Err(PrecommitRejection::InvalidSignature));
+
+    assert_eq!(
+        producer.import_precommit(Precommit::sign(1_000, block.header.hash.clone(), &voter_keypair)).await,
+        Err(PrecommitRejection::TooFarAhead { block_number: 1_000, head: 1 })
+    );
+
+    // Voting for a competing block at the same height does not count twice
+    producer.import_precommit(Precommit::sign(1, "ab".repeat(32), &voter_keypair)).await.unwrap();
+    assert_eq!(
+        producer.import_precommit(Precommit::sign(1, block.header.hash.clone(), &voter_keypair)).await,
+        Err(PrecommitRejection::Equivocation {
+            validator: voter.node_account_id(),
+            block_number: 1,
+            existing: "ab".repeat(32),
+        })
+    );
+    assert_eq!(producer.finalized_height(), 0);
+
+    let third = GridTokenXKeyPair::from_node_key(VALIDATOR_KEYS[2]).unwrap();
+    producer.import_precommit(Precommit::sign(1, block.header.hash.clone(), &third)).await.unwrap();
+    assert_eq!(producer.finalized_height(), 1);
+    assert_eq!(
+        producer.import_precommit(Precommit::sign(1, block.header.hash.clone(), &voter_keypair)).await,
+        Err(PrecommitRejection::AlreadyFinalized { block_number: 1, finalized: 1 })
+    );
+}
+
+#[tokio::test]
+async fn test_validators_finalize_over_gossip() {
+    let network_config = |node_key: &str, bootnodes: Vec<String>| BlockchainConfig {
+        network: "finality-tests".to_string(),
+        p2p_port: 0,
+        bootnodes,
+        block_time: 1,
+        ..validator_config(node_key, 2)
+    };
+    let keys = ["finality-gossip-a", "finality-gossip-b"];
+    let dir = tempfile::tempdir().unwrap();
+
+    let producer = BlockchainEngine::new(&on_authority_chain(network_config(keys[0], vec![]), &keys, &dir)).await.unwrap();
+    producer.start().await.unwrap();
+    let address = wait_for(|| async {
+        let network = producer.network();
+        let address = network.listen_addresses().await.into_iter()
+            .find(|address| address.to_string().starts_with("/ip4/127.0.0.1/"));
+        address.map(|address| format!("{}/p2p/{}", address, network.local_peer_id()))
+    }).await;
+
+    let voter_config = on_authority_chain(network_config(keys[1], vec![address]), &keys, &dir);
+    let voter = BlockchainEngine::with_genesis(&voter_config, producer.consensus().genesis_block().await).await.unwrap();
+    voter.start().await.unwrap();
+    wait_for_gossip_peer(&producer.network()).await;
+    wait_for_gossip_peer(&voter.network()).await;
+
+    producer.consensus().add_transaction(mint_envelope("alice", EnergyAmount::from_wh(1000_000), 0)).await.unwrap();
+    wait_for(|| async { (producer.consensus().finalized_height() == 1).then_some(()) }).await;
+    wait_for(|| async { (voter.consensus().finalized_height() == 1).then_some(()) }).await;
+    assert_eq!(producer.consensus().finalized_hash().await, voter.consensus().finalized_hash().await);
+
+    voter.stop().await.unwrap();
+    producer.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_trades_settle_once_recording_transaction_is_final() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = validator_config("finality-settlement", 1);
+    // The buyer pays the fee in tokens and the price in satang
+    let spec = ChainSpec {
+        balances: BTreeMap::from([(account("finality-buyer"), HashMap::from([(EnergySource::Wind, 100_000_000)]))]),
+        currency: BTreeMap::from([(account("finality-buyer"), 100_000)]),
+        ..authority_spec(&config, &["finality-settlement"])
+    };
+    let path = dir.path().join("chain-spec.json");
+    std::fs::write(&path, spec.to_json().unwrap()).unwrap();
+    let config = BlockchainConfig { chain_spec: path.to_string_lossy().into_owned(), ..config };
+    let node = Arc::new(BlockchainNode::new(&config).await.unwrap());
+    let consensus = node.engine().consensus();
+
+    let trading = EnhancedTradingService::new(
+        node.clone(),
+        Arc::new(DatabaseManager::new(&DatabaseConfig::default(), true).await.unwrap()),
+        Arc::new(GridManager::new(&GridConfig::default()).await.unwrap()),
+    ).await.unwrap();
+
+    // A single authority with threshold one finalizes its own blocks
+    let amount = EnergyAmount::from_wh(10_000);
+    consensus.add_transaction(mint_envelope("finality-seller", amount, 0)).await.unwrap();
+    consensus.produce_block().await.unwrap().unwrap();
+
+    trading.place_order(order("finality-seller", OrderType::Sell, amount)).await.unwrap();
+    let trade = trading.place_order(order("finality-buyer", OrderType::Buy, amount)).await.unwrap().executions.remove(0);
+    let trade_id = Uuid::parse_str(&trade.trade_id).unwrap();
+    assert_eq!(trading.settlement_status(&trade_id).await, Some(SettlementStatus::Pending));
+
+    // Only a transaction executing the trade records it
+    assert!(trading.submit_trade(mint_envelope("finality-seller", amount, 1)).await.is_err());
+
+    let seller_signature = transactions::sign_trade(&trade, &GridTokenXKeyPair::from_node_key("finality-seller").unwrap());
+    let mut record = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ExecuteTrade {
+            trade,
+            buyer_signature: vec![],
+            seller_signature,
+            grid_validation: GridValidation {
+                validator: "grid-operator".to_string(),
+                capacity_check: true,
+                congestion_level: CongestionLevel::Low,
+                transmission_cost: 0,
+                timestamp: SystemTime::UNIX_EPOCH,
+            },
+        },
+        0,
+    );
+    record.sign(&GridTokenXKeyPair::from_node_key("finality-buyer").unwrap());
+    trading.submit_trade(record.clone()).await.unwrap();
+    trading.process_settlements().await.unwrap();
+    assert_eq!(trading.settlement_status(&trade_id).await, Some(SettlementStatus::Pending));
+
+    let block = consensus.produce_block().await.unwrap().unwrap();
+    assert!(block.transactions.iter().any(|tx| tx.hash == record.hash));
+    assert_eq!(node.engine().get_transaction_receipt(&record.hash).await.unwrap().status, ReceiptStatus::Success);
+    assert_eq!(consensus.finalized_height(), 2);
+    trading.process_settlements().await.unwrap();
+    assert_eq!(trading.settlement_status(&trade_id).await, Some(SettlementStatus::Settled));
+    assert_eq!(trading.settlement_status(&Uuid::new_v4()).await, None);
+}
+
+#[tokio::test]
+async fn test_finalized_head_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let [competing, finalized] = competing_blocks().await;
+    let node = persistent_validator(VALIDATOR_KEYS[1], &dir).await;
+    assert_eq!(node.import_block(finalized.clone()).await.unwrap(), BlockImportResult::Imported);
+    let producer = GridTokenXKeyPair::from_node_key(VALIDATOR_KEYS[0]).unwrap();
+    node.import_precommit(Precommit::sign(1, finalized.header.hash.clone(), &producer)).await.unwrap();
+    assert_eq!(node.finalized_height(), 1);
+    drop(node);
+
+    let restarted = persistent_validator(VALIDATOR_KEYS[1], &dir).await;
+    assert_eq!(restarted.finalized_height(), 1);
+    assert_eq!(restarted.finalized_hash().await, finalized.header.hash);
+    assert_eq!(
+        restarted.import_block(competing).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::ConflictsWithFinalized { number: 1, finalized: 1 })
+    );
+}
+
+#[tokio::test]
+async fn test_restarted_validator_does_not_precommit_to_a_conflicting_block() {
+    let dir = tempfile::tempdir().unwrap();
+    let [preferred, voted] = competing_blocks().await;
+    let node = persistent_validator(VALIDATOR_KEYS[1], &dir).await;
+    let mut precommits = node.subscribe_precommits();
+    assert_eq!(node.import_block(voted.clone()).await.unwrap(), BlockImportResult::Imported);
+    assert_eq!(precommits.try_recv().unwrap().block_hash, voted.header.hash);
+    drop(node);
+
+    let restarted = persistent_validator(VALIDATOR_KEYS[1], &dir).await;
+    let mut precommits = restarted.subscribe_precommits();
+    assert_eq!(restarted.get_precommits(1, &voted.header.hash).await.len(), 1);
+
+    // Another authority's vote ties the branches, so the chain switches to the preferred
+    // block, but the node's earlier vote for the other one stands
+    assert_eq!(restarted.import_block(preferred.clone()).await.unwrap(), BlockImportResult::Imported);
+    let third = GridTokenXKeyPair::from_node_key(VALIDATOR_KEYS[2]).unwrap();
+    restarted.import_precommit(Precommit::sign(1, preferred.header.hash.clone(), &third)).await.unwrap();
+    assert_eq!(restarted.get_blockchain_state().await.latest_block_hash, preferred.header.hash);
+    assert!(precommits.try_recv().is_err());
+    assert!(restarted.get_precommits(1, &preferred.header.hash).await.iter()
+        .all(|precommit| precommit.validator != restarted.node_account_id()));
+    assert_eq!(restarted.finalized_height(), 0);
+}
+
+/// Poll until `check` yields a value, failing the test after ten seconds
+async fn wait_for<T, F, Fut>(mut check: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Option<T>>,
+{
+    for _ in 0..100 {
+        if let Some(value) = check().await {
+            return value;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("condition not reached in time");
+}
+
+/// Wait until the network has a peer subscribed to every gossip topic
+async fn wait_for_gossip_peer(network: &NetworkLayer) {
+    wait_for(|| async {
+        network.peers().await.into_iter().find(|peer| peer.topics.len() == 3).map(|_| ())
+    }).await;
+}
@@ -0,0 +1,297 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::{
+    BlockImportResult, BlockRejection, ConsensusEngine, EnergyBlock,
+};
+use thai_energy_trading_blockchain::blockchain::finality::Precommit;
+use thai_energy_trading_blockchain::blockchain::liveness::ValidatorLiveness;
+use thai_energy_trading_blockchain::blockchain::slashing::MisbehaviorEvidence;
+use thai_energy_trading_blockchain::blockchain::storage::BlockchainStorage;
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    ValidatorSignature,
+};
+use thai_energy_trading_blockchain::blockchain::BlockchainEngine;
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use thai_energy_trading_blockchain::*;
+
+const VALIDATOR_KEYS: [&str; 2] = ["fork-a", "fork-b"];
+
+fn validator_config(node_key: &str, authority_threshold: u32) -> BlockchainConfig {
+    BlockchainConfig {
+        node_key: node_key.to_string(),
+        validator: true,
+        authority_threshold,
+        ..BlockchainConfig::default()
+    }
+}
+
+/// Node that follows the chain without producing blocks or precommits
+fn observer_config(authority_threshold: u32) -> BlockchainConfig {
+    BlockchainConfig {
+        validator: false,
+        ..validator_config("fork-observer", authority_threshold)
+    }
+}
+
+/// Account of the keypair derived from `key`
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+/// Evidence report, signed by `reporter`, that pays no fee
+fn evidence_envelope(reporter: &str, evidence: MisbehaviorEvidence) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportMisbehavior { reporter: account(reporter), evidence },
+        0,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(reporter).unwrap());
+    envelope
+}
+
+/// Chain with the nodes with `keys` as authorities, scheduled in the given order
+fn authority_spec(config: &BlockchainConfig, keys: &[&str]) -> ChainSpec {
+    ChainSpec {
+        authorities: keys.iter().map(|key| GenesisAuthority::development(key).unwrap()).collect(),
+        ..ChainSpec::development(config)
+    }
+}
+
+/// Two competing blocks at height 1, both produced by the first validator
+///
+/// Returns the blocks ordered by hash, each with the transaction only it includes: the
+/// block with the lower hash wins the tie between equally signed branches.
+async fn competing_blocks(spec: &ChainSpec) -> [(EnergyBlock, EnergyTransactionEnvelope); 2] {
+    let mut forks = Vec::new();
+    for recipient in ["carol", "bob"] {
+        let producer = ConsensusEngine::with_chain_spec(&validator_config(VALIDATOR_KEYS[0], 2), spec).await.unwrap();
+        let only_here = mint_envelope(recipient, EnergyAmount::from_wh(300_000), 0);
+        producer.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(1000_000), 0)).await.unwrap();
+        producer.add_transaction(only_here.clone()).await.unwrap();
+        forks.push((producer.produce_block().await.unwrap().unwrap(), only_here));
+    }
+    forks.sort_by(|(first, _), (second, _)| first.header.hash.cmp(&second.header.hash));
+    let second = forks.pop().unwrap();
+    [forks.pop().unwrap(), second]
+}
+
+async fn observer(authority_threshold: u32) -> (ConsensusEngine, [(EnergyBlock, EnergyTransactionEnvelope); 2]) {
+    let spec = authority_spec(&observer_config(authority_threshold), &VALIDATOR_KEYS);
+    let observer = ConsensusEngine::with_chain_spec(&observer_config(authority_threshold), &spec).await.unwrap();
+    let forks = competing_blocks(&spec).await;
+    (observer, forks)
+}
+
+#[tokio::test]
+async fn test_competing_block_is_kept_on_a_side_branch() {
+    let (observer, [(canonical, _), (competing, _)]) = observer(2).await;
+
+    // Import the losing block first so the tie break has to switch the head
+    assert_eq!(observer.import_block(competing.clone()).await.unwrap(), BlockImportResult::Imported);
+    assert_eq!(observer.import_block(canonical.clone()).await.unwrap(), BlockImportResult::Imported);
+
+    let state = observer.get_blockchain_state().await;
+    assert_eq!(state.block_height, 1);
+    assert_eq!(state.latest_block_hash, canonical.header.hash);
+    assert_eq!(state.block_tree.side_block_count(), 1);
+    assert!(state.block_tree.contains(&competing.header.hash));
+    assert!(observer.has_block(&canonical.header.hash).await);
+    assert!(observer.has_block(&competing.header.hash).await);
+    assert_eq!(observer.get_blocks_by_hash(&[competing.header.hash.clone()]).await, vec![competing.clone()]);
+
+    assert_eq!(
+        observer.import_block(competing.clone()).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::AlreadyImported(competing.header.hash.clone()))
+    );
+}
+
+#[tokio::test]
+async fn test_precommit_moves_fork_choice_to_the_heavier_branch() {
+    let (observer, [(canonical, canonical_only), (competing, competing_only)]) = observer(2).await;
+    let mut reorgs = observer.subscribe_reorgs();
+    observer.import_block(canonical.clone()).await.unwrap();
+    observer.import_block(competing.clone()).await.unwrap();
+    assert_eq!(observer.get_blockchain_state().await.latest_block_hash, canonical.header.hash);
+    assert!(reorgs.try_recv().is_err());
+
+    // A second authority vouching for the competing block outweighs the canonical one
+    let voter = GridTokenXKeyPair::from_node_key(VALIDATOR_KEYS[1]).unwrap();
+    observer.import_precommit(Precommit::sign(1, competing.header.hash.clone(), &voter)).await.unwrap();
+
+    let reorg = reorgs.try_recv().unwrap();
+    assert_eq!(reorg.common_ancestor, 0);
+    assert_eq!(reorg.retracted, vec![canonical.header.hash.clone()]);
+    assert_eq!(reorg.enacted, vec![competing.header.hash.clone()]);
+    assert!(reorg.orphaned_transactions.contains(&canonical_only));
+
+    let state = observer.get_blockchain_state().await;
+    assert_eq!(state.latest_block_hash, competing.header.hash);
+    assert!(state.block_tree.contains(&canonical.header.hash));
+    assert!(state.pending_transactions.contains(&canonical_only.hash));
+    assert!(observer.get_transaction_receipt(&canonical_only.hash).await.is_none());
+    assert!(observer.get_transaction_receipt(&competing_only.hash).await.is_some());
+    assert!(observer.get_account_balance(&recipient(&canonical_only)).await.is_none());
+    assert!(observer.get_account_balance(&recipient(&competing_only)).await.is_some());
+}
+
+#[tokio::test]
+async fn test_longer_branch_replaces_the_stored_chain() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = BlockchainConfig {
+        network: "fork-choice-tests".to_string(),
+        p2p_port: 0,
+        data_dir: Some(dir.path().to_string_lossy().into_owned()),
+        ..observer_config(2)
+    };
+    let spec = authority_spec(&config, &VALIDATOR_KEYS);
+    let path = dir.path().join("chain-spec.json");
+    std::fs::write(&path, spec.to_json().unwrap()).unwrap();
+    let config = BlockchainConfig { chain_spec: path.to_string_lossy().into_owned(), ..config };
+    let observer = BlockchainEngine::new(&config).await.unwrap();
+    observer.start().await.unwrap();
+    let genesis = observer.consensus().genesis_block().await;
+    let [(canonical, canonical_only), (competing, _)] = competing_blocks(&spec).await;
+
+    // The second validator builds block 2 on top of the competing block
+    let builder = ConsensusEngine::with_chain_spec(&validator_config(VALIDATOR_KEYS[1], 2), &spec).await.unwrap();
+    assert_eq!(builder.import_block(competing.clone()).await.unwrap(), BlockImportResult::Imported);
+    builder.add_transaction(mint_envelope("dave", EnergyAmount::from_wh(50_000), 0)).await.unwrap();
+    let extension = builder.produce_block().await.unwrap().unwrap();
+
+    let mut reorgs = observer.consensus().subscribe_reorgs();
+    for block in [&canonical, &competing, &extension] {
+        assert_eq!(observer.import_block(block.clone()).await.unwrap(), BlockImportResult::Imported);
+    }
+    let reorg = reorgs.recv().await.unwrap();
+    assert_eq!(reorg.enacted, vec![competing.header.hash.clone(), extension.header.hash.clone()]);
+
+    let status = observer.consensus().chain_status().await;
+    assert_eq!(status.best_number, 2);
+    assert_eq!(status.best_hash, extension.header.hash);
+    wait_for_pool(&observer, &canonical_only).await;
+
+    observer.stop().await.unwrap();
+    drop(observer);
+
+    let storage = Arc::new(BlockchainStorage::new(&config).await.unwrap());
+    let reopened = ConsensusEngine::with_storage_and_genesis(&config, storage, genesis).await.unwrap();
+    let state = reopened.get_blockchain_state().await;
+    assert_eq!(state.block_height, 2);
+    assert_eq!(state.latest_block_hash, extension.header.hash);
+    assert_eq!(state.blocks[1].header.hash, competing.header.hash);
+}
+
+#[tokio::test]
+async fn test_finalized_block_is_never_reverted() {
+    let (observer, [(canonical, _), (competing, _)]) = observer(1).await;
+    observer.import_block(canonical.clone()).await.unwrap();
+
+    let voter = GridTokenXKeyPair::from_node_key(VALIDATOR_KEYS[1]).unwrap();
+    observer.import_precommit(Precommit::sign(1, canonical.header.hash.clone(), &voter)).await.unwrap();
+    assert_eq!(observer.finalized_height(), 1);
+
+    assert_eq!(
+        observer.import_block(competing).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::ConflictsWithFinalized { number: 1, finalized: 1 })
+    );
+    assert_eq!(observer.get_blockchain_state().await.latest_block_hash, canonical.header.hash);
+}
+
+#[tokio::test]
+async fn test_reorg_undoes_slashes_and_slots_of_the_retracted_branch() {
+    let (observer, [(canonical, _), (competing, _)]) = observer(2).await;
+    let offender = account(VALIDATOR_KEYS[0]);
+    let genesis_authority = observer.get_authority(&offender).await.unwrap();
+    let spec = authority_spec(&observer_config(2), &VALIDATOR_KEYS);
+
+    // On the canonical branch the second validator slashes the first for signing both blocks
+    let slasher = ConsensusEngine::with_chain_spec(&validator_config(VALIDATOR_KEYS[1], 2), &spec).await.unwrap();
+    slasher.import_block(canonical.clone()).await.unwrap();
+    slasher.add_transaction(evidence_envelope("reporter", MisbehaviorEvidence::double_sign(&canonical, &competing))).await.unwrap();
+    let slashing = slasher.produce_block().await.unwrap().unwrap();
+
+    // On the competing branch it just builds on the competing block
+    let builder = ConsensusEngine::with_chain_spec(&validator_config(VALIDATOR_KEYS[1], 2), &spec).await.unwrap();
+    builder.import_block(competing.clone()).await.unwrap();
+    builder.add_transaction(mint_envelope("dave", EnergyAmount::from_wh(50_000), 0)).await.unwrap();
+    let extension = builder.produce_block().await.unwrap().unwrap();
+
+    for block in [&canonical, &competing, &slashing] {
+        assert_eq!(observer.import_block(block.clone()).await.unwrap(), BlockImportResult::Imported);
+    }
+    assert_eq!(observer.get_blockchain_state().await.latest_block_hash, slashing.header.hash);
+    assert!(!observer.get_authority(&offender).await.unwrap().is_active);
+    assert!(!observer.get_validator_schedule().await.active_validators.contains(&offender));
+
+    // A precommit makes the competing branch heavier; its chain never slashed anyone
+    assert_eq!(observer.import_block(extension.clone()).await.unwrap(), BlockImportResult::Imported);
+    let voter = GridTokenXKeyPair::from_node_key(VALIDATOR_KEYS[1]).unwrap();
+    observer.import_precommit(Precommit::sign(1, competing.header.hash.clone(), &voter)).await.unwrap();
+    assert_eq!(observer.get_blockchain_state().await.latest_block_hash, extension.header.hash);
+    assert_eq!(observer.get_authority(&offender).await.unwrap(), genesis_authority);
+    assert_eq!(
+        observer.get_validator_schedule().await.active_validators,
+        VALIDATOR_KEYS.iter().map(|key| account(key)).collect::<Vec<_>>()
+    );
+    // Only the extension counts towards the second validator's slots, not the slashing block
+    assert_eq!(observer.get_validator_liveness().await[&account(VALIDATOR_KEYS[1])], ValidatorLiveness {
+        produced_slots: 1,
+        missed_slots: 0,
+        last_produced: Some(2),
+    });
+}
+
+/// Account a mint transaction credits
+fn recipient(mint: &EnergyTransactionEnvelope) -> AccountId {
+    match &mint.transaction {
+        EnergyTransaction::ReportProduction { producer, .. } => producer.clone(),
+        _ => unreachable!("not a mint"),
+    }
+}
+
+/// Wait until a reorg has returned a transaction to the pool
+async fn wait_for_pool(engine: &BlockchainEngine, transaction: &EnergyTransactionEnvelope) {
+    for _ in 0..100 {
+        if engine.pending_transactions().await.contains(transaction) {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("orphaned transaction not returned to the pool");
+}
@@ -93,9 +93,9 @@ async fn test_governance_operations() {
             no_votes: 0,
             abstain_votes: 0,
             total_voting_power: 0,
-            participation_rate: 0.0,
+            participation_rate: FixedPoint::ZERO,
             total_eligible: 0,
-            turnout_percentage: 0.0,
+            turnout_percentage: FixedPoint::ZERO,
         },
     };
     
@@ -0,0 +1,138 @@
+use std::time::{Duration, SystemTime};
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::{BlockImportResult, ConsensusEngine, STARTING_REPUTATION};
+use thai_energy_trading_blockchain::blockchain::liveness::{ValidatorLiveness, MISSED_SLOT_PENALTY};
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    ValidatorSignature,
+};
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use thai_energy_trading_blockchain::*;
+
+const VALIDATOR_KEYS: [&str; 3] = ["liveness-a", "liveness-b", "liveness-c"];
+
+/// Validator whose slots open right after the parent block and pass on after a second
+fn validator_config(node_key: &str) -> BlockchainConfig {
+    BlockchainConfig {
+        node_key: node_key.to_string(),
+        validator: true,
+        block_time: 0,
+        slot_timeout: 1,
+        ..BlockchainConfig::default()
+    }
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+/// Chain with the `VALIDATOR_KEYS` nodes as authorities, scheduled in that order
+fn spec() -> ChainSpec {
+    ChainSpec {
+        authorities: VALIDATOR_KEYS.iter().map(|key| GenesisAuthority::development(key).unwrap()).collect(),
+        ..ChainSpec::development(&validator_config(VALIDATOR_KEYS[0]))
+    }
+}
+
+async fn validator(config: &BlockchainConfig) -> ConsensusEngine {
+    ConsensusEngine::with_chain_spec(config, &spec()).await.unwrap()
+}
+
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+#[tokio::test]
+async fn test_next_validator_takes_over_a_missed_slot() {
+    let first = validator(&validator_config(VALIDATOR_KEYS[0])).await;
+    let third = validator(&validator_config(VALIDATOR_KEYS[2])).await;
+
+    first.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(100_000), 0)).await.unwrap();
+    let opening = first.produce_block().await.unwrap().unwrap();
+    assert_eq!(third.import_block(opening.clone()).await.unwrap(), BlockImportResult::Imported);
+
+    // The second validator stays offline; until its slot times out the third has to wait
+    third.add_transaction(mint_envelope("bob", EnergyAmount::from_wh(100_000), 0)).await.unwrap();
+    let mut takeover = None;
+    for _ in 0..30 {
+        takeover = third.produce_block().await.unwrap();
+        if takeover.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let takeover = takeover.expect("third validator never took over the missed slot");
+    assert!(takeover.header.timestamp > opening.header.timestamp);
+    assert_eq!(takeover.header.validator, account(VALIDATOR_KEYS[2]));
+
+    // Every node counts the missed slot against the second validator
+    assert_eq!(first.import_block(takeover).await.unwrap(), BlockImportResult::Imported);
+    for node in [&first, &third] {
+        let liveness = node.get_validator_liveness().await;
+        assert_eq!(liveness[&account(VALIDATOR_KEYS[0])], ValidatorLiveness {
+            produced_slots: 1,
+            missed_slots: 0,
+            last_produced: Some(1),
+        });
+        assert_eq!(liveness[&account(VALIDATOR_KEYS[1])].missed_slots, 1);
+        assert_eq!(liveness[&account(VALIDATOR_KEYS[1])].availability(), 0.0);
+        assert_eq!(liveness[&account(VALIDATOR_KEYS[2])].last_produced, Some(2));
+
+        let missed = node.get_authority(&account(VALIDATOR_KEYS[1])).await.unwrap();
+        assert_eq!(missed.reputation, STARTING_REPUTATION.saturating_sub(MISSED_SLOT_PENALTY));
+        assert_eq!(node.get_authority(&account(VALIDATOR_KEYS[2])).await.unwrap().reputation, STARTING_REPUTATION);
+    }
+
+    // The rotation continues after the validator that filled the slot
+    let schedule = first.get_validator_schedule().await;
+    assert_eq!(schedule.get_current_validator(), Some(&account(VALIDATOR_KEYS[0])));
+    assert_eq!(schedule.round, 1);
+}
+
+#[tokio::test]
+async fn test_empty_blocks_are_produced_when_enabled() {
+    let config = BlockchainConfig {
+        produce_empty_blocks: true,
+        ..validator_config(VALIDATOR_KEYS[0])
+    };
+    let producer = validator(&config).await;
+    let importer = validator(&validator_config(VALIDATOR_KEYS[1])).await;
+
+    let block = producer.produce_block().await.unwrap().unwrap();
+    assert!(block.transactions.is_empty());
+    assert_eq!(importer.import_block(block).await.unwrap(), BlockImportResult::Imported);
+    assert_eq!(importer.get_validator_liveness().await[&account(VALIDATOR_KEYS[0])].produced_slots, 1);
+
+    // Without empty blocks an idle validator skips its slot
+    assert!(importer.produce_block().await.unwrap().is_none());
+}
@@ -0,0 +1,282 @@
+use std::time::{Duration, SystemTime};
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::{BlockchainState, ConsensusEngine};
+use thai_energy_trading_blockchain::blockchain::network::{NetworkEvent, NetworkLayer};
+use thai_energy_trading_blockchain::blockchain::sync::{SyncRequest, SyncResponse, SyncState};
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    ValidatorSignature,
+};
+use thai_energy_trading_blockchain::blockchain::BlockchainEngine;
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use thai_energy_trading_blockchain::*;
+use tokio::sync::broadcast;
+
+/// Config for a node listening on an OS-assigned local port
+fn network_config(node_key: &str, validator: bool, bootnodes: Vec<String>) -> BlockchainConfig {
+    BlockchainConfig {
+        network: "network-tests".to_string(),
+        node_key: node_key.to_string(),
+        validator,
+        p2p_port: 0,
+        bootnodes,
+        block_time: 1,
+        authority_threshold: 1,
+        ..BlockchainConfig::default()
+    }
+}
+
+/// Loopback address of a started network layer, including its peer ID
+async fn bootnode_address(network: &NetworkLayer) -> String {
+    let address = wait_for(|| async {
+        network.listen_addresses().await.into_iter().find(|address| address.to_string().starts_with("/ip4/127.0.0.1/"))
+    }).await;
+    format!("{}/p2p/{}", address, network.local_peer_id())
+}
+
+/// Poll until `check` yields a value, failing the test after ten seconds
+async fn wait_for<T, F, Fut>(mut check: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Option<T>>,
+{
+    for _ in 0..100 {
+        if let Some(value) = check().await {
+            return value;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("condition not reached in time");
+}
+
+/// Wait for the first network event `pick` accepts, failing the test after ten seconds
+async fn next_event<T>(events: &mut broadcast::Receiver<NetworkEvent>, pick: impl Fn(NetworkEvent) -> Option<T>) -> T {
+    let wait = async {
+        loop {
+            if let Some(value) = pick(events.recv().await.unwrap()) {
+                return value;
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), wait).await.expect("event not received in time")
+}
+
+/// Wait until the network has a peer subscribed to every gossip topic
+async fn wait_for_gossip_peer(network: &NetworkLayer) {
+    wait_for(|| async {
+        network.peers().await.into_iter().find(|peer| peer.topics.len() == 3).map(|_| ())
+    }).await;
+}
+
+/// Wait until the engine's chain reaches `height`
+async fn wait_for_height(engine: &BlockchainEngine, height: u32) -> BlockchainState {
+    wait_for(|| async {
+        let state = engine.consensus().get_blockchain_state().await;
+        (state.block_height == height).then_some(state)
+    }).await
+}
+
+/// Account of the keypair derived from `key`
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+/// Transfer between the accounts of `from` and `to`, signed by `from`
+fn transfer_envelope(from: &str, to: &str, amount: Balance, nonce: u64) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::Transfer {
+            from: account(from),
+            to: account(to),
+            amount,
+            energy_type: EnergySource::Solar,
+            grid_location: utils::testing::create_test_grid_location(),
+        },
+        nonce,
+    );
+    envelope.gas_price = 1;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(from).unwrap());
+    envelope
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+/// Chain whose only authority is the node with `producer_key`
+fn producer_spec(producer_key: &str) -> ChainSpec {
+    ChainSpec {
+        authorities: vec![GenesisAuthority::development(producer_key).unwrap()],
+        ..ChainSpec::development(&network_config(producer_key, true, vec![]))
+    }
+}
+
+/// Point `config` at a spec file in `dir` for the chain of the producer with `producer_key`
+fn on_chain_of(config: BlockchainConfig, producer_key: &str, dir: &tempfile::TempDir) -> BlockchainConfig {
+    let path = dir.path().join(format!("{}.json", producer_key));
+    std::fs::write(&path, producer_spec(producer_key).to_json().unwrap()).unwrap();
+    BlockchainConfig { chain_spec: path.to_string_lossy().into_owned(), ..config }
+}
+
+#[tokio::test]
+async fn test_peer_id_is_derived_from_node_key() {
+    let first = NetworkLayer::new(&network_config("peer-identity", false, vec![])).await.unwrap();
+    let again = NetworkLayer::new(&network_config("peer-identity", false, vec![])).await.unwrap();
+    let other = NetworkLayer::new(&network_config("other-identity", false, vec![])).await.unwrap();
+
+    assert_eq!(first.local_peer_id(), again.local_peer_id());
+    assert_ne!(first.local_peer_id(), other.local_peer_id());
+}
+
+#[tokio::test]
+async fn test_nodes_connect_through_bootnode_and_gossip() {
+    let bootnode = NetworkLayer::new(&network_config("gossip-bootnode", false, vec![])).await.unwrap();
+    bootnode.start().await.unwrap();
+    let address = bootnode_address(&bootnode).await;
+
+    let joiner = NetworkLayer::new(&network_config("gossip-joiner", false, vec![address])).await.unwrap();
+    let mut joiner_events = joiner.subscribe();
+    let mut bootnode_events = bootnode.subscribe();
+    joiner.start().await.unwrap();
+
+    wait_for_gossip_peer(&bootnode).await;
+    wait_for_gossip_peer(&joiner).await;
+    let peers = joiner.peers().await;
+    assert_eq!(peers.len(), 1);
+    assert_eq!(peers[0].peer_id, bootnode.local_peer_id());
+
+    let producer = ConsensusEngine::with_chain_spec(&network_config("gossip-producer", true, vec![]), &producer_spec("gossip-producer")).await.unwrap();
+    producer.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(1000_000), 0)).await.unwrap();
+    let block = producer.produce_block().await.unwrap().unwrap();
+
+    bootnode.broadcast_block(&block).await.unwrap();
+    let received = next_event(&mut joiner_events, |event| match event {
+        NetworkEvent::BlockReceived { source, block } => Some((source, *block)),
+        _ => None,
+    }).await;
+    assert_eq!(received, (bootnode.local_peer_id(), block.clone()));
+
+    let transaction = mint_envelope("bob", EnergyAmount::from_wh(500_000), 0);
+    joiner.broadcast_transaction(&transaction).await.unwrap();
+    let received = next_event(&mut bootnode_events, |event| match event {
+        NetworkEvent::TransactionReceived { transaction, .. } => Some(transaction),
+        _ => None,
+    }).await;
+    assert_eq!(*received, transaction);
+
+    joiner.stop().await.unwrap();
+    bootnode.stop().await.unwrap();
+    assert!(bootnode.broadcast_block(&block).await.is_err());
+}
+
+#[tokio::test]
+async fn test_engines_converge_on_gossiped_chain() {
+    let dir = tempfile::tempdir().unwrap();
+    let producer = BlockchainEngine::new(&on_chain_of(network_config("converge-producer", true, vec![]), "converge-producer", &dir)).await.unwrap();
+    producer.start().await.unwrap();
+    let address = bootnode_address(&producer.network()).await;
+
+    // The follower loads the producer's chain spec so both extend the same chain
+    let follower_config = on_chain_of(network_config("converge-follower", false, vec![address]), "converge-producer", &dir);
+    let follower = BlockchainEngine::new(&follower_config).await.unwrap();
+    follower.start().await.unwrap();
+    wait_for_gossip_peer(&follower.network()).await;
+    wait_for_gossip_peer(&producer.network()).await;
+
+    // A block produced on the validator reaches the follower
+    producer.consensus().add_transaction(mint_envelope("alice", EnergyAmount::from_wh(100_000_000), 0)).await.unwrap();
+    wait_for_height(&follower, 1).await;
+
+    // A transaction submitted on the follower is included by the validator and imported back
+    let mut transaction = transfer_envelope("alice", "bob", 30_000, 1);
+    transaction.gas_limit = 21_000;
+    transaction.sign(&GridTokenXKeyPair::from_node_key("alice").unwrap());
+    follower.submit_transaction(transaction.clone()).await.unwrap();
+    let state = wait_for_height(&follower, 2).await;
+
+    let producer_state = producer.consensus().get_blockchain_state().await;
+    assert_eq!(state.latest_block_hash, producer_state.latest_block_hash);
+    assert_eq!(state.balances, producer_state.balances);
+    assert_eq!(state.balances[&account("bob")].total_balance, 30_000);
+    assert!(follower.get_transaction_receipt(&transaction.hash).await.is_some());
+
+    follower.stop().await.unwrap();
+    producer.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_late_joiner_syncs_missing_blocks() {
+    let dir = tempfile::tempdir().unwrap();
+    let producer = BlockchainEngine::new(&on_chain_of(network_config("sync-producer", true, vec![]), "sync-producer", &dir)).await.unwrap();
+    producer.start().await.unwrap();
+    for (height, account) in ["alice", "bob", "carol"].into_iter().enumerate() {
+        producer.consensus().add_transaction(mint_envelope(account, EnergyAmount::from_wh(1000_000), 0)).await.unwrap();
+        wait_for_height(&producer, height as u32 + 1).await;
+    }
+    let address = bootnode_address(&producer.network()).await;
+
+    // The follower missed every gossiped block and has to download them
+    let follower_config = on_chain_of(network_config("sync-follower", false, vec![address]), "sync-producer", &dir);
+    let follower = BlockchainEngine::new(&follower_config).await.unwrap();
+    assert_eq!(follower.node_manager().sync_status().await.state, SyncState::Idle);
+    follower.start().await.unwrap();
+
+    let state = wait_for_height(&follower, 3).await;
+    assert_eq!(state.latest_block_hash, producer.consensus().get_blockchain_state().await.latest_block_hash);
+    assert_eq!(state.balances[&account("carol")].total_balance, 1000);
+
+    let status = wait_for(|| async {
+        let status = follower.node_manager().sync_status().await;
+        (status.state == SyncState::Synced).then_some(status)
+    }).await;
+    assert_eq!(status.current_height, 3);
+    assert_eq!(status.target_height, 3);
+    assert_eq!(status.blocks_imported, 3);
+    assert!(status.last_error.is_none());
+
+    // Peers serve headers by range and bodies by hash
+    let producer_peer = producer.network().local_peer_id();
+    let headers = match follower.network().request(&producer_peer, SyncRequest::Headers { from: 2, count: 10 }).await.unwrap() {
+        SyncResponse::Headers(headers) => headers,
+        other => panic!("unexpected response {:?}", other),
+    };
+    assert_eq!(headers.iter().map(|header| header.number).collect::<Vec<_>>(), vec![2, 3]);
+    let blocks = match follower.network().request(&producer_peer, SyncRequest::Blocks { hashes: vec![headers[1].hash.clone()] }).await.unwrap() {
+        SyncResponse::Blocks(blocks) => blocks,
+        other => panic!("unexpected response {:?}", other),
+    };
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].header, headers[1]);
+
+    follower.stop().await.unwrap();
+    producer.stop().await.unwrap();
+}
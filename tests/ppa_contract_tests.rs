@@ -0,0 +1,577 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::contract_abi::{self, AbiType, AbiValue};
+use thai_energy_trading_blockchain::blockchain::ppa_contract::{
+    ppa_abi, MeterReadings, Payment, PeriodSettlement, PowerPurchaseAgreement, PpaError, PpaStatus, PpaTerms,
+};
+use thai_energy_trading_blockchain::blockchain::smart_contracts::SmartContractVM;
+use thai_energy_trading_blockchain::blockchain::state_transition::{
+    apply_transaction, BlockContext, ReceiptStatus, TransactionEvent, TransactionReceipt,
+};
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    ConsumerType, EnergyBalanceState, EnergyConsumptionRecord, EnergyProductionRecord,
+    EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope, MeteredProduction,
+    ValidatorSignature,
+};
+use thai_energy_trading_blockchain::blockchain::BlockchainEngine;
+use thai_energy_trading_blockchain::config::{BlockchainConfig, SmartContractConfig};
+use thai_energy_trading_blockchain::*;
+
+/// Gas given to every PPA call
+const GAS: u64 = 1_000_000;
+
+/// Three 100 second periods of 10 kWh at 4 THB/kWh, starting at 2000, with a 1 THB/kWh penalty
+fn terms(seller: &str) -> PpaTerms {
+    PpaTerms {
+        seller: seller.to_string(),
+        price: TokenPrice::from_satang(400),
+        volume: EnergyAmount::from_kwh(10).unwrap(),
+        start: 2_000,
+        period_length: 100,
+        periods: 3,
+        shortfall_penalty: TokenPrice::from_satang(100),
+        seller_bond: 1_000,
+    }
+}
+
+/// Production and the tokens minted for it per account, kept as account state keeps them
+#[derive(Default)]
+struct Meters {
+    produced: HashMap<String, EnergyBalanceState>,
+}
+
+impl Meters {
+    /// Record production of `account` as `(block timestamp, kWh)` reports
+    fn report(&mut self, account: &str, reports: &[(u64, u64)]) {
+        let state = self.produced.entry(account.to_string()).or_insert_with(EnergyBalanceState::new);
+        for &(reported_at, kwh) in reports {
+            state.receive(EnergySource::Solar, kwh as Balance);
+            state.metered_production.push(MeteredProduction {
+                reported_at,
+                amount: EnergyAmount::from_kwh(kwh).unwrap(),
+                claimed: EnergyAmount::ZERO,
+            });
+        }
+    }
+}
+
+impl MeterReadings for Meters {
+    fn claim_production(&mut self, account: &AccountId, from: u64, to: u64, limit: EnergyAmount) -> EnergyAmount {
+        self.produced.get_mut(account).map_or(EnergyAmount::ZERO, |state| state.claim_production(from, to, limit))
+    }
+
+    fn available_energy(&self, account: &AccountId) -> EnergyAmount {
+        let tokens = self.produced.get(account).map_or(0, EnergyBalanceState::get_total_available_balance);
+        EnergyAmount::from_kwh(tokens as u64).unwrap()
+    }
+}
+
+fn at(seconds: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+fn block(timestamp: u64) -> BlockContext {
+    let trusted = ChainSpec::development(&BlockchainConfig::default()).trusted_parties().unwrap();
+    BlockContext { number: 1, timestamp, validator: "validator".to_string(), trusted: Arc::new(trusted) }
+}
+
+/// Account of the keypair derived from `key`
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+/// Fee-free verified production report of `kwh` at `timestamp`, attested by the development
+/// oracle and signed by `producer`
+fn production_envelope(producer: &str, kwh: u64, timestamp: u64, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount: EnergyAmount::from_kwh(kwh).unwrap(),
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: at(timestamp),
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+/// Fee-free verified consumption report of `kwh` at `timestamp`, signed by `consumer`
+fn consumption_envelope(consumer: &str, kwh: u64, timestamp: u64, nonce: u64) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportConsumption {
+            consumer: account(consumer),
+            consumption_record: EnergyConsumptionRecord {
+                amount: EnergyAmount::from_kwh(kwh).unwrap(),
+                location: utils::testing::create_test_grid_location(),
+                timestamp: at(timestamp),
+                verified: true,
+                consumer_type: ConsumerType::Commercial,
+                appliance_breakdown: HashMap::new(),
+            },
+            meter_signature: vec![],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(consumer).unwrap());
+    envelope
+}
+
+/// Fee-free deployment of a PPA offered and signed by `buyer`
+fn deploy_envelope(buyer: &str, terms: &PpaTerms, nonce: u64) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(terms.deployment(&account(buyer), GAS), nonce);
+    envelope.gas_price = 0;
+    envelope.gas_limit = 2 * GAS;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(buyer).unwrap());
+    envelope
+}
+
+/// Fee-free call of a PPA function, signed by `caller`
+fn call_envelope(caller: &str, contract: &str, method: &str, nonce: u64) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ExecuteContract {
+            caller: account(caller),
+            contract_address: contract.to_string(),
+            method: method.to_string(),
+            args: vec![],
+            gas_limit: GAS,
+            signature: vec![],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.gas_limit = 2 * GAS;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(caller).unwrap());
+    envelope
+}
+
+/// Apply a transaction in a block at `timestamp`, which must be includable
+fn apply(balances: &mut HashMap<AccountId, EnergyBalanceState>, vm: &SmartContractVM, envelope: EnergyTransactionEnvelope, timestamp: u64) -> TransactionReceipt {
+    apply_transaction(balances, &envelope, &block(timestamp), vm).unwrap()
+}
+
+/// Give `account` a currency balance of `satang`
+fn fund(balances: &mut HashMap<AccountId, EnergyBalanceState>, account: &str, satang: Balance) {
+    balances.entry(account.to_string()).or_insert_with(EnergyBalanceState::new).currency_balance = satang;
+}
+
+/// Satang `account` holds
+fn currency(balances: &HashMap<AccountId, EnergyBalanceState>, account: &str) -> Balance {
+    balances.get(account).map_or(0, |state| state.currency_balance)
+}
+
+/// Settlement events carried by a receipt, decoded from their ABI-encoded data
+fn settled_periods(receipt: &TransactionReceipt) -> Vec<Vec<AbiValue>> {
+    let types = [AbiType::U32, AbiType::U64, AbiType::U64, AbiType::U128, AbiType::U128];
+    receipt.events.iter()
+        .filter_map(|event| match event {
+            TransactionEvent::ContractEventEmitted { topic, data, .. } if topic == "period_settled" => {
+                Some(contract_abi::decode(&types, data).unwrap())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_periods_settle_against_metered_delivery() {
+    let mut meters = Meters::default();
+    meters.report("seller", &[(2_010, 12), (2_120, 6), (2_250, 10), (2_300, 50)]);
+    let contract = "ppa".to_string();
+
+    let (mut agreement, lock) = PowerPurchaseAgreement::offer(&"buyer".to_string(), &contract, terms("seller")).unwrap();
+    assert_eq!(lock, Payment { from: "buyer".to_string(), to: contract.clone(), amount: 12_000 });
+    agreement.accept(&"seller".to_string(), &contract, 1_999).unwrap();
+
+    // Only periods that have ended are settled
+    assert_eq!(agreement.settle(&contract, 2_099, &mut meters), Err(PpaError::NothingToSettle));
+    let (first, payments) = agreement.settle(&contract, 2_150, &mut meters).unwrap();
+    assert_eq!(first, vec![PeriodSettlement {
+        period: 0,
+        delivered: EnergyAmount::from_kwh(10).unwrap(),
+        shortfall: EnergyAmount::ZERO,
+        payment: 4_000,
+        penalty: 0,
+    }]);
+    assert_eq!(payments, vec![Payment { from: contract.clone(), to: "seller".to_string(), amount: 4_000 }]);
+
+    let (rest, payments) = agreement.settle(&contract, 2_300, &mut meters).unwrap();
+    // Period 1: the seller produced 4 kWh short of the volume and pays the penalty for it
+    assert_eq!((rest[0].delivered, rest[0].shortfall), (EnergyAmount::from_kwh(6).unwrap(), EnergyAmount::from_kwh(4).unwrap()));
+    assert_eq!((rest[0].payment, rest[0].penalty), (2_400, 400));
+    // Period 2: production reported once the period ended is not counted toward it
+    assert_eq!((rest[1].delivered, rest[1].shortfall), (EnergyAmount::from_kwh(10).unwrap(), EnergyAmount::ZERO));
+    assert_eq!((rest[1].payment, rest[1].penalty), (4_000, 0));
+
+    // The last settlement releases what is left of the collateral and the bond
+    assert_eq!(payments[payments.len() - 2..], [
+        Payment { from: contract.clone(), to: "buyer".to_string(), amount: 12_000 - 4_000 - 2_400 - 4_000 },
+        Payment { from: contract.clone(), to: "seller".to_string(), amount: 1_000 - 400 },
+    ]);
+    assert_eq!(agreement.status, PpaStatus::Completed);
+    assert_eq!((agreement.collateral, agreement.bond), (0, 0));
+    assert_eq!(agreement.settle(&contract, 9_999, &mut meters), Err(PpaError::NotActive(PpaStatus::Completed)));
+}
+
+#[test]
+fn test_penalties_are_capped_by_the_bond() {
+    let contract = "ppa".to_string();
+    let (mut agreement, _) = PowerPurchaseAgreement::offer(&"buyer".to_string(), &contract, terms("seller")).unwrap();
+    agreement.accept(&"seller".to_string(), &contract, 0).unwrap();
+
+    // Nothing produced: each period's 10 kWh shortfall costs 1,000 of the 1,000 bond
+    let (settlements, _) = agreement.settle(&contract, 2_200, &mut Meters::default()).unwrap();
+    assert_eq!(settlements.iter().map(|settlement| settlement.penalty).collect::<Vec<_>>(), vec![1_000, 0]);
+    assert_eq!(settlements.iter().map(|settlement| settlement.payment).collect::<Vec<_>>(), vec![0, 0]);
+    assert_eq!(agreement.bond, 0);
+}
+
+#[test]
+fn test_offers_are_accepted_by_the_seller_before_delivery() {
+    let contract = "ppa".to_string();
+    let buyer = "buyer".to_string();
+    let seller = "seller".to_string();
+    let (mut agreement, _) = PowerPurchaseAgreement::offer(&buyer, &contract, terms("seller")).unwrap();
+
+    assert_eq!(agreement.accept(&buyer, &contract, 0), Err(PpaError::NotAllowed { role: "seller", action: "accept" }));
+    assert_eq!(agreement.accept(&seller, &contract, 2_000), Err(PpaError::DeliveryStarted { start: 2_000 }));
+    assert_eq!(agreement.cancel(&seller, &contract), Err(PpaError::NotAllowed { role: "buyer", action: "cancel" }));
+    assert_eq!(agreement.settle(&contract, 9_999, &mut Meters::default()), Err(PpaError::NotActive(PpaStatus::Offered)));
+
+    // An offer nobody accepted can be withdrawn, returning the collateral
+    let mut withdrawn = agreement.clone();
+    assert_eq!(withdrawn.cancel(&buyer, &contract), Ok(Payment { from: contract.clone(), to: buyer.clone(), amount: 12_000 }));
+    assert_eq!(withdrawn.status, PpaStatus::Cancelled);
+    assert_eq!(withdrawn.accept(&seller, &contract, 0), Err(PpaError::NotOffered(PpaStatus::Cancelled)));
+
+    assert_eq!(agreement.accept(&seller, &contract, 1_000), Ok(Payment { from: seller.clone(), to: contract.clone(), amount: 1_000 }));
+    assert_eq!(agreement.cancel(&buyer, &contract), Err(PpaError::NotOffered(PpaStatus::Active)));
+
+    // Terms that cannot be settled are refused
+    let invalid = [
+        PpaTerms { seller: "buyer".to_string(), ..terms("seller") },
+        PpaTerms { periods: 0, ..terms("seller") },
+        PpaTerms { volume: EnergyAmount::ZERO, ..terms("seller") },
+        PpaTerms { volume: EnergyAmount::from_wh(10_500), ..terms("seller") },
+        PpaTerms { start: u64::MAX, ..terms("seller") },
+    ];
+    for terms in invalid {
+        assert!(matches!(PowerPurchaseAgreement::offer(&buyer, &contract, terms), Err(PpaError::InvalidTerms(_))));
+    }
+    assert_eq!(PpaTerms::from_args(&terms("seller").to_args()), Ok(terms("seller")));
+}
+
+#[test]
+fn test_agreements_settle_on_chain_from_reported_records() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let mut balances = HashMap::new();
+    let contract = SmartContractVM::contract_address(&account("buyer"), 1);
+
+    // Deployment locks the collateral in the contract account
+    fund(&mut balances, &account("buyer"), 20_000);
+    fund(&mut balances, &account("seller"), 5_000);
+    apply(&mut balances, &vm, production_envelope("buyer", 100, 100, 0), 1_000);
+    apply(&mut balances, &vm, production_envelope("seller", 100, 100, 0), 1_000);
+    let deployed = apply(&mut balances, &vm, deploy_envelope("buyer", &terms(&account("seller")), 1), 1_000);
+    assert_eq!(deployed.status, ReceiptStatus::Success, "{:?}", deployed.error);
+    assert!(deployed.events.contains(&TransactionEvent::PaymentMade {
+        from: account("buyer"),
+        to: contract.clone(),
+        amount: 12_000,
+    }));
+    assert!(deployed.gas_used > 200_000);
+    assert_eq!(currency(&balances, &account("buyer")), 8_000);
+
+    let wrong_party = apply(&mut balances, &vm, call_envelope("buyer", &contract, "accept", 2), 1_500);
+    assert_eq!(wrong_party.error.as_deref(), Some("contract reverted: only the seller may accept the agreement"));
+    let accepted = apply(&mut balances, &vm, call_envelope("seller", &contract, "accept", 1), 1_500);
+    assert_eq!(accepted.status, ReceiptStatus::Success, "{:?}", accepted.error);
+    let too_early = apply(&mut balances, &vm, call_envelope("seller", &contract, "settle", 2), 2_050);
+    assert_eq!(too_early.error.as_deref(), Some("contract reverted: no unsettled delivery period has ended yet"));
+
+    // Metering records reported during delivery; the buyer reports taking only 7 kWh in
+    // the last period but pays for all 10 kWh delivered
+    apply(&mut balances, &vm, production_envelope("seller", 12, 2_010, 3), 2_010);
+    apply(&mut balances, &vm, consumption_envelope("buyer", 10, 2_050, 3), 2_050);
+    apply(&mut balances, &vm, production_envelope("seller", 6, 2_120, 4), 2_120);
+    apply(&mut balances, &vm, consumption_envelope("buyer", 10, 2_150, 4), 2_150);
+    apply(&mut balances, &vm, production_envelope("seller", 10, 2_250, 5), 2_250);
+    apply(&mut balances, &vm, consumption_envelope("buyer", 7, 2_260, 5), 2_260);
+
+    let seller_before = currency(&balances, &account("seller"));
+    let buyer_before = currency(&balances, &account("buyer"));
+    let settled = apply(&mut balances, &vm, call_envelope("bob", &contract, "settle", 0), 2_300);
+    assert_eq!(settled.status, ReceiptStatus::Success, "{:?}", settled.error);
+    let periods = settled_periods(&settled);
+    assert_eq!(periods.len(), 3);
+    assert_eq!(periods[1], vec![AbiValue::U32(1), AbiValue::U64(6_000), AbiValue::U64(4_000), AbiValue::U128(2_400), AbiValue::U128(400)]);
+    assert!(settled.events.iter().any(|event| matches!(event,
+        TransactionEvent::ContractEventEmitted { topic, .. } if topic == "completed")));
+
+    assert_eq!(periods[2], vec![AbiValue::U32(2), AbiValue::U64(10_000), AbiValue::U64(0), AbiValue::U128(4_000), AbiValue::U128(0)]);
+
+    // Seller: 4,000 + 2,400 + 4,000 paid, 400 penalty kept out of its 1,000 bond
+    assert_eq!(currency(&balances, &account("seller")) - seller_before, 10_400 + 600);
+    // Buyer: 400 penalty and 1,600 unused collateral back
+    assert_eq!(currency(&balances, &account("buyer")) - buyer_before, 2_000);
+    assert_eq!(currency(&balances, &contract), 0);
+
+    let agreement = PowerPurchaseAgreement::from_contract(balances[&contract].contract.as_ref().unwrap()).unwrap();
+    assert_eq!(agreement.status, PpaStatus::Completed);
+    assert_eq!(agreement.settled_periods, 3);
+}
+
+#[test]
+fn test_production_counts_toward_one_agreement() {
+    let mut meters = Meters::default();
+    meters.report("seller", &[(2_010, 6), (2_050, 6)]);
+    let contract = "ppa".to_string();
+    let mut agreement = || {
+        let (mut agreement, _) = PowerPurchaseAgreement::offer(&"buyer".to_string(), &contract, terms("seller")).unwrap();
+        agreement.accept(&"seller".to_string(), &contract, 0).unwrap();
+        agreement
+    };
+    let (mut first, mut second) = (agreement(), agreement());
+
+    // The agreement settled first takes the contracted 10 kWh, leaving 2 kWh for the other
+    let (settled, _) = first.settle(&contract, 2_100, &mut meters).unwrap();
+    assert_eq!((settled[0].delivered, settled[0].shortfall), (EnergyAmount::from_kwh(10).unwrap(), EnergyAmount::ZERO));
+    let (settled, _) = second.settle(&contract, 2_100, &mut meters).unwrap();
+    assert_eq!((settled[0].delivered, settled[0].shortfall), (EnergyAmount::from_kwh(2).unwrap(), EnergyAmount::from_kwh(8).unwrap()));
+    assert_eq!((settled[0].payment, settled[0].penalty), (800, 800));
+}
+
+#[test]
+fn test_settled_energy_moves_to_the_buyer() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let mut balances = HashMap::new();
+    let contracts = accepted_agreements(&mut balances, &vm, 1);
+    let tokens = |balances: &HashMap<AccountId, EnergyBalanceState>, key: &str| balances[&account(key)].total_balance;
+
+    apply(&mut balances, &vm, production_envelope("seller", 10, 2_050, 2), 2_050);
+    let (seller_before, buyer_before) = (tokens(&balances, "seller"), tokens(&balances, "buyer"));
+    let settled = apply(&mut balances, &vm, call_envelope("bob", &contracts[0], "settle", 0), 2_100);
+    assert_eq!(settled.status, ReceiptStatus::Success, "{:?}", settled.error);
+    assert!(settled.events.contains(&TransactionEvent::TokensTransferred {
+        from: account("seller"),
+        to: account("buyer"),
+        energy_type: EnergySource::Solar,
+        amount: 10,
+    }));
+    assert_eq!(tokens(&balances, "seller"), seller_before - 10);
+    assert_eq!(tokens(&balances, "buyer"), buyer_before + 10);
+
+    // The seller cannot spend the energy the buyer paid for
+    let mut resale = EnergyTransactionEnvelope::new(
+        EnergyTransaction::Transfer {
+            from: account("seller"),
+            to: account("carol"),
+            amount: seller_before,
+            energy_type: EnergySource::Solar,
+            grid_location: utils::testing::create_test_grid_location(),
+        },
+        3,
+    );
+    resale.gas_price = 0;
+    resale.sign(&GridTokenXKeyPair::from_node_key("seller").unwrap());
+    let resale = apply(&mut balances, &vm, resale, 2_110);
+    assert_eq!(resale.status, ReceiptStatus::Failed);
+    assert_eq!(tokens(&balances, "seller"), seller_before - 10);
+}
+
+#[test]
+fn test_energy_the_seller_no_longer_holds_is_not_delivered() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let mut balances = HashMap::new();
+    let contracts = accepted_agreements(&mut balances, &vm, 1);
+
+    // The seller sells all its tokens elsewhere before the period is settled
+    apply(&mut balances, &vm, production_envelope("seller", 10, 2_050, 2), 2_050);
+    let mut sale = EnergyTransactionEnvelope::new(
+        EnergyTransaction::Transfer {
+            from: account("seller"),
+            to: account("carol"),
+            amount: balances[&account("seller")].total_balance,
+            energy_type: EnergySource::Solar,
+            grid_location: utils::testing::create_test_grid_location(),
+        },
+        3,
+    );
+    sale.gas_price = 0;
+    sale.sign(&GridTokenXKeyPair::from_node_key("seller").unwrap());
+    assert_eq!(apply(&mut balances, &vm, sale, 2_060).status, ReceiptStatus::Success);
+
+    let settled = apply(&mut balances, &vm, call_envelope("bob", &contracts[0], "settle", 0), 2_100);
+    assert_eq!(settled_periods(&settled), vec![
+        vec![AbiValue::U32(0), AbiValue::U64(0), AbiValue::U64(10_000), AbiValue::U128(0), AbiValue::U128(1_000)],
+    ]);
+}
+
+/// Deploy `count` single-period agreements from "buyer" to "seller" and have the seller
+/// accept them, returning their addresses
+fn accepted_agreements(balances: &mut HashMap<AccountId, EnergyBalanceState>, vm: &SmartContractVM, count: u64) -> Vec<AccountId> {
+    fund(balances, &account("buyer"), 20_000);
+    fund(balances, &account("seller"), 5_000);
+    apply(balances, vm, production_envelope("buyer", 100, 100, 0), 1_000);
+    apply(balances, vm, production_envelope("seller", 100, 100, 0), 1_000);
+    let terms = PpaTerms { periods: 1, ..terms(&account("seller")) };
+    (1..=count)
+        .map(|nonce| {
+            let deployed = apply(balances, vm, deploy_envelope("buyer", &terms, nonce), 1_000);
+            assert_eq!(deployed.status, ReceiptStatus::Success, "{:?}", deployed.error);
+            let contract = SmartContractVM::contract_address(&account("buyer"), nonce);
+            let accepted = apply(balances, vm, call_envelope("seller", &contract, "accept", nonce), 1_500);
+            assert_eq!(accepted.status, ReceiptStatus::Success, "{:?}", accepted.error);
+            contract
+        })
+        .collect()
+}
+
+#[test]
+fn test_production_settles_one_agreement_in_the_period_it_was_reported() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let mut balances = HashMap::new();
+    let contracts = accepted_agreements(&mut balances, &vm, 2);
+
+    apply(&mut balances, &vm, production_envelope("seller", 10, 2_050, 3), 2_050);
+    // Reported after the period ended, with a timestamp inside it
+    apply(&mut balances, &vm, production_envelope("seller", 10, 2_060, 4), 2_100);
+
+    let first = apply(&mut balances, &vm, call_envelope("bob", &contracts[0], "settle", 0), 2_100);
+    assert_eq!(settled_periods(&first), vec![
+        vec![AbiValue::U32(0), AbiValue::U64(10_000), AbiValue::U64(0), AbiValue::U128(4_000), AbiValue::U128(0)],
+    ]);
+    // The same production cannot be sold again to the second buyer
+    let second = apply(&mut balances, &vm, call_envelope("bob", &contracts[1], "settle", 1), 2_100);
+    assert_eq!(settled_periods(&second), vec![
+        vec![AbiValue::U32(0), AbiValue::U64(0), AbiValue::U64(10_000), AbiValue::U128(0), AbiValue::U128(1_000)],
+    ]);
+}
+
+#[test]
+fn test_constructor_runs_only_on_deployment() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let mut balances = HashMap::new();
+    let contract = accepted_agreements(&mut balances, &vm, 1).remove(0);
+    let before = balances[&contract].contract.clone();
+    let buyer_before = currency(&balances, &account("buyer"));
+
+    // Re-running the constructor would replace the accepted terms and lock more collateral
+    let mut redeploy = call_envelope("buyer", &contract, "deploy", 2);
+    if let EnergyTransaction::ExecuteContract { args, .. } = &mut redeploy.transaction {
+        *args = PpaTerms { price: TokenPrice::from_satang(1), ..terms(&account("seller")) }.to_args();
+    }
+    redeploy.sign(&GridTokenXKeyPair::from_node_key("buyer").unwrap());
+    let redeployed = apply(&mut balances, &vm, redeploy, 1_600);
+    assert_eq!(redeployed.error.as_deref(), Some("The deploy constructor runs only when the contract is deployed"));
+    assert_eq!(balances[&contract].contract, before);
+    assert_eq!(currency(&balances, &account("buyer")), buyer_before);
+}
+
+#[test]
+fn test_failed_ppa_calls_move_no_tokens() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let mut balances = HashMap::new();
+
+    // The buyer cannot cover the 12,000 collateral, whatever energy tokens it holds
+    fund(&mut balances, &account("buyer"), 5_000);
+    apply(&mut balances, &vm, production_envelope("buyer", 20_000, 100, 0), 1_000);
+    let unfunded = apply(&mut balances, &vm, deploy_envelope("buyer", &terms(&account("seller")), 1), 1_000);
+    assert_eq!(unfunded.status, ReceiptStatus::Failed);
+    assert_eq!(unfunded.error.as_deref(), Some("Trading error: Insufficient currency balance for payment"));
+    assert!(balances.get(&SmartContractVM::contract_address(&account("buyer"), 1)).is_none());
+    assert_eq!(currency(&balances, &account("buyer")), 5_000);
+
+    // Running out of gas fails the constructor before anything is locked
+    let mut starved = deploy_envelope("buyer", &PpaTerms { volume: EnergyAmount::from_kwh(1).unwrap(), ..terms(&account("seller")) }, 2);
+    if let EnergyTransaction::DeployContract { gas_limit, .. } = &mut starved.transaction {
+        *gas_limit = 1_000;
+    }
+    starved.sign(&GridTokenXKeyPair::from_node_key("buyer").unwrap());
+    let starved = apply(&mut balances, &vm, starved, 1_000);
+    assert_eq!(starved.error.as_deref(), Some("contract ran out of its 1000 gas"));
+    assert_eq!(currency(&balances, &account("buyer")), 5_000);
+}
+
+#[tokio::test]
+async fn test_engine_deploys_and_reports_agreements() {
+    let buyer = GridTokenXKeyPair::from_node_key("ppa-buyer").unwrap();
+    let buyer_id = buyer.account_id().to_string();
+
+    // A chain with this node as its only authority and a buyer holding 200 THB
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = BlockchainConfig {
+        network: "ppa-tests".to_string(),
+        node_key: "ppa-engine".to_string(),
+        validator: true,
+        p2p_port: 0,
+        authority_threshold: 1,
+        ..BlockchainConfig::default()
+    };
+    let spec = ChainSpec {
+        authorities: vec![GenesisAuthority::development(&config.node_key).unwrap()],
+        currency: BTreeMap::from([(buyer_id.clone(), 20_000)]),
+        ..ChainSpec::development(&config)
+    };
+    let path = dir.path().join("chain-spec.json");
+    std::fs::write(&path, spec.to_json().unwrap()).unwrap();
+    config.chain_spec = path.to_string_lossy().into_owned();
+    let engine = BlockchainEngine::new(&config).await.unwrap();
+    let offer = |terms: &PpaTerms, nonce: u64| {
+        let mut envelope = EnergyTransactionEnvelope::new(terms.deployment(&buyer_id, GAS), nonce);
+        envelope.gas_price = 1;
+        envelope.gas_limit = 2 * GAS;
+        envelope.sign(&buyer);
+        envelope
+    };
+    let terms = PpaTerms { start: u64::MAX / 2, ..terms("seller") };
+    engine.consensus().add_transaction(production_envelope("ppa-buyer", 20_000_000, 100, 0)).await.unwrap();
+    engine.consensus().produce_block().await.unwrap().unwrap();
+
+    // Terms are checked before the deployment is admitted
+    let own_seller = engine.deploy_contract(offer(&PpaTerms { seller: buyer_id.clone(), ..terms.clone() }, 1)).await.unwrap_err();
+    assert!(own_seller.to_string().contains("buyer and seller must differ"), "{}", own_seller);
+    let mut foreign_abi = offer(&terms, 1);
+    if let EnergyTransaction::DeployContract { abi, .. } = &mut foreign_abi.transaction {
+        abi.functions.pop();
+    }
+    foreign_abi.sign(&buyer);
+    assert!(engine.deploy_contract(foreign_abi).await.is_err());
+
+    let contract = engine.deploy_contract(offer(&terms, 1)).await.unwrap();
+    let block = engine.consensus().produce_block().await.unwrap().unwrap();
+    assert_eq!(block.receipts[0].status, ReceiptStatus::Success, "{:?}", block.receipts[0].error);
+
+    let agreement = engine.get_power_purchase_agreement(&contract).await.unwrap();
+    assert_eq!(agreement.buyer, buyer_id);
+    assert_eq!(agreement.status, PpaStatus::Offered);
+    assert_eq!(agreement.collateral, 12_000);
+    assert_eq!(engine.get_contract(&contract).await.unwrap().abi, ppa_abi());
+    assert_eq!(engine.consensus().get_account_balance(&contract).await.unwrap().currency_balance, 12_000);
+
+    // PPA functions change state, so they cannot be called read-only
+    let read_only = engine.execute_contract_function(&contract, &buyer_id, "settle", &[], GAS).await.unwrap();
+    assert!(!read_only.success);
+    assert_eq!(read_only.return_values, Vec::<AbiValue>::new());
+}
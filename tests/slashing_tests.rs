@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::{BlockImportResult, ConsensusEngine, EnergyBlock, STARTING_REPUTATION};
+use thai_energy_trading_blockchain::blockchain::slashing::{
+    EvidenceRejection, MisbehaviorEvidence, DOUBLE_SIGN_PENALTY, OUT_OF_TURN_PENALTY,
+};
+use thai_energy_trading_blockchain::blockchain::state_transition::TransactionEvent;
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    ValidatorSignature,
+};
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use thai_energy_trading_blockchain::*;
+
+const VALIDATOR_KEYS: [&str; 3] = ["slashing-a", "slashing-b", "slashing-c"];
+
+fn validator_config(node_key: &str) -> BlockchainConfig {
+    BlockchainConfig {
+        node_key: node_key.to_string(),
+        validator: true,
+        ..BlockchainConfig::default()
+    }
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+/// Evidence report, signed by `reporter`, that pays no fee
+fn evidence_envelope(reporter: &str, evidence: MisbehaviorEvidence) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportMisbehavior { reporter: account(reporter), evidence },
+        0,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(reporter).unwrap());
+    envelope
+}
+
+/// Chain with the nodes with `keys` as authorities, scheduled in the given order
+fn authority_spec(keys: &[&str]) -> ChainSpec {
+    ChainSpec {
+        authorities: keys.iter().map(|key| GenesisAuthority::development(key).unwrap()).collect(),
+        ..ChainSpec::development(&validator_config(keys[0]))
+    }
+}
+
+/// Validator on the chain with every validator of `VALIDATOR_KEYS` as an authority
+async fn validator(key: &str) -> ConsensusEngine {
+    ConsensusEngine::with_chain_spec(&validator_config(key), &authority_spec(&VALIDATOR_KEYS)).await.unwrap()
+}
+
+/// Produce a block minting tokens to `recipient`
+async fn produce(producer: &ConsensusEngine, recipient: &str) -> EnergyBlock {
+    producer.add_transaction(mint_envelope(recipient, EnergyAmount::from_wh(100_000), 0)).await.unwrap();
+    producer.produce_block().await.unwrap().unwrap()
+}
+
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+/// Sign a block with transactions the producer would never include, by hand
+fn forged_block(key: &str, parent: &EnergyBlock, transactions: Vec<EnergyTransactionEnvelope>) -> EnergyBlock {
+    let keypair = GridTokenXKeyPair::from_node_key(key).unwrap();
+    let mut block = parent.clone();
+    block.header.number = parent.header.number + 1;
+    block.header.parent_hash = parent.header.hash.clone();
+    block.header.transaction_root = ConsensusEngine::calculate_merkle_root(&transactions);
+    block.header.validator = keypair.account_id().to_string();
+    block.header.hash = ConsensusEngine::calculate_block_hash(&block);
+    block.transactions = transactions;
+    block.receipts = vec![];
+    block.validator_signature.validator = block.header.validator.clone();
+    block.validator_signature.signature = keypair.sign(&hex::decode(&block.header.hash).unwrap()).to_bytes().to_vec();
+    block
+}
+
+#[tokio::test]
+async fn test_double_signing_validator_is_slashed() {
+    let first = validator(VALIDATOR_KEYS[0]).await;
+    let second = validator(VALIDATOR_KEYS[0]).await;
+    let reporter = validator(VALIDATOR_KEYS[1]).await;
+    let observer = validator(VALIDATOR_KEYS[2]).await;
+
+    // The first validator signs two different blocks for height 1
+    let block = produce(&first, "alice").await;
+    let conflicting = produce(&second, "bob").await;
+    assert_eq!(reporter.import_block(block.clone()).await.unwrap(), BlockImportResult::Imported);
+
+    let report = evidence_envelope("reporter", MisbehaviorEvidence::double_sign(&block, &conflicting));
+    reporter.add_transaction(report.clone()).await.unwrap();
+    let slashing_block = reporter.produce_block().await.unwrap().unwrap();
+    let receipt = reporter.get_transaction_receipt(&report.hash).await.unwrap();
+    assert_eq!(receipt.events, vec![TransactionEvent::MisbehaviorReported {
+        reporter: account("reporter"),
+        offender: account(VALIDATOR_KEYS[0]),
+    }]);
+
+    // Every node applying the block slashes the offender
+    for engine in [&reporter, &observer] {
+        if engine.get_blockchain_state().await.block_height < 2 {
+            engine.import_block(block.clone()).await.unwrap();
+            assert_eq!(engine.import_block(slashing_block.clone()).await.unwrap(), BlockImportResult::Imported);
+        }
+        let offender = engine.get_authority(&account(VALIDATOR_KEYS[0])).await.unwrap();
+        assert!(!offender.is_active);
+        assert_eq!(offender.reputation, STARTING_REPUTATION.saturating_sub(DOUBLE_SIGN_PENALTY));
+
+        let schedule = engine.get_validator_schedule().await;
+        assert_eq!(schedule.active_validators, vec![account(VALIDATOR_KEYS[1]), account(VALIDATOR_KEYS[2])]);
+        assert_eq!(schedule.get_current_validator(), Some(&account(VALIDATOR_KEYS[2])));
+    }
+
+    // The same evidence cannot slash twice
+    let repeated = evidence_envelope("another-reporter", MisbehaviorEvidence::double_sign(&conflicting, &block));
+    observer.add_transaction(repeated).await.unwrap();
+    assert_eq!(observer.produce_block().await.unwrap(), None);
+    assert!(observer.get_blockchain_state().await.pending_transactions.is_empty());
+}
+
+#[tokio::test]
+async fn test_out_of_turn_block_is_slashed() {
+    let scheduled = validator(VALIDATOR_KEYS[0]).await;
+    let reporter = validator(VALIDATOR_KEYS[1]).await;
+    let genesis = scheduled.genesis_block().await;
+
+    // The third validator signs a block for the first slot, which belongs to the first validator
+    let out_of_turn = forged_block(VALIDATOR_KEYS[2], &genesis, vec![mint_envelope("mallory", EnergyAmount::from_wh(100_000), 0)]);
+    let block = produce(&scheduled, "alice").await;
+    assert!(matches!(reporter.import_block(out_of_turn.clone()).await.unwrap(), BlockImportResult::Rejected(_)));
+    assert_eq!(reporter.import_block(block.clone()).await.unwrap(), BlockImportResult::Imported);
+
+    // A block made in its producer's own slot is no evidence
+    reporter.add_transaction(evidence_envelope("honest-mistake", MisbehaviorEvidence::out_of_turn(&block))).await.unwrap();
+    reporter.add_transaction(evidence_envelope("reporter", MisbehaviorEvidence::out_of_turn(&out_of_turn))).await.unwrap();
+    let slashing_block = reporter.produce_block().await.unwrap().unwrap();
+    assert_eq!(slashing_block.transactions.len(), 1);
+
+    let offender = reporter.get_authority(&account(VALIDATOR_KEYS[2])).await.unwrap();
+    assert!(!offender.is_active);
+    assert_eq!(offender.reputation, STARTING_REPUTATION.saturating_sub(OUT_OF_TURN_PENALTY));
+    let schedule = reporter.get_validator_schedule().await;
+    assert_eq!(schedule.active_validators, vec![account(VALIDATOR_KEYS[0]), account(VALIDATOR_KEYS[1])]);
+    assert_eq!(schedule.get_current_validator(), Some(&account(VALIDATOR_KEYS[0])));
+
+    // The first validator accepts the block and follows the shortened rotation
+    assert_eq!(scheduled.import_block(slashing_block).await.unwrap(), BlockImportResult::Imported);
+    assert!(!scheduled.get_authority(&account(VALIDATOR_KEYS[2])).await.unwrap().is_active);
+    assert_eq!(produce(&scheduled, "carol").await.header.number, 3);
+}
+
+#[tokio::test]
+async fn test_evidence_is_checked_against_signatures() {
+    let engine = validator(VALIDATOR_KEYS[0]).await;
+    let genesis = engine.genesis_block().await;
+    let offender = account(VALIDATOR_KEYS[0]);
+    let mut authorities = HashMap::new();
+    for key in VALIDATOR_KEYS {
+        authorities.insert(account(key), engine.get_authority(&account(key)).await.unwrap());
+    }
+    let chain = [genesis.header.clone()];
+    let ancestor = |number: u32| chain.get(number as usize);
+
+    let block = produce(&engine, "alice").await;
+    let conflicting = forged_block(VALIDATOR_KEYS[0], &genesis, vec![mint_envelope("bob", EnergyAmount::from_wh(5_000), 0)]);
+    assert_eq!(MisbehaviorEvidence::double_sign(&block, &conflicting).verify(2, &authorities, ancestor), Ok(&offender));
+    assert_eq!(
+        MisbehaviorEvidence::double_sign(&block, &block).verify(2, &authorities, ancestor),
+        Err(EvidenceRejection::NotConflicting)
+    );
+
+    // Someone else's signature on the second block proves nothing against the producer
+    let mut impostor = forged_block(VALIDATOR_KEYS[1], &genesis, vec![]);
+    impostor.header.validator = offender.clone();
+    impostor.header.hash = ConsensusEngine::calculate_block_hash(&impostor);
+    assert_eq!(
+        MisbehaviorEvidence::double_sign(&block, &impostor).verify(2, &authorities, ancestor),
+        Err(EvidenceRejection::InvalidSignature)
+    );
+    let mut tampered = conflicting.clone();
+    tampered.header.timestamp += 1;
+    assert_eq!(
+        MisbehaviorEvidence::double_sign(&block, &tampered).verify(2, &authorities, ancestor),
+        Err(EvidenceRejection::InvalidBlockHash)
+    );
+
+    // A block including the same transaction twice can never be valid
+    let mint = mint_envelope("carol", EnergyAmount::from_wh(5_000), 0);
+    let invalid = forged_block(VALIDATOR_KEYS[0], &genesis, vec![mint.clone(), mint]);
+    assert_eq!(MisbehaviorEvidence::invalid_block(&invalid).verify(2, &authorities, ancestor), Ok(&offender));
+    assert_eq!(
+        MisbehaviorEvidence::invalid_block(&block).verify(2, &authorities, ancestor),
+        Err(EvidenceRejection::NoFault)
+    );
+    let MisbehaviorEvidence::InvalidBlock { block: header, .. } = MisbehaviorEvidence::invalid_block(&invalid) else {
+        unreachable!()
+    };
+    assert_eq!(
+        MisbehaviorEvidence::InvalidBlock { block: header, transactions: vec![] }.verify(2, &authorities, ancestor),
+        Err(EvidenceRejection::BodyMismatch)
+    );
+
+    authorities.get_mut(&offender).unwrap().is_active = false;
+    assert_eq!(
+        MisbehaviorEvidence::double_sign(&block, &conflicting).verify(2, &authorities, ancestor),
+        Err(EvidenceRejection::AlreadySlashed(offender.clone()))
+    );
+    authorities.remove(&offender);
+    assert_eq!(
+        MisbehaviorEvidence::double_sign(&block, &conflicting).verify(2, &authorities, ancestor),
+        Err(EvidenceRejection::UnknownAuthority(offender))
+    );
+}
@@ -0,0 +1,621 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::{BlockImportResult, ConsensusEngine};
+use thai_energy_trading_blockchain::blockchain::contract_abi::{self, AbiError, AbiType, AbiValue};
+use thai_energy_trading_blockchain::blockchain::contract_runtime::{
+    ContractCall, ExecutionContext, GasSchedule, DEPLOY_ENTRY_POINT,
+};
+use thai_energy_trading_blockchain::blockchain::smart_contracts::{
+    ContractABI, ContractEvent, ContractExecutionResult, ContractFunction, ContractState, SmartContractVM,
+};
+use thai_energy_trading_blockchain::blockchain::state_transition::{ReceiptStatus, TransactionEvent};
+use thai_energy_trading_blockchain::blockchain::state_trie::verify_account_proof;
+use thai_energy_trading_blockchain::blockchain::storage::BlockchainStorage;
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    ValidatorSignature,
+};
+use thai_energy_trading_blockchain::blockchain::BlockchainEngine;
+use thai_energy_trading_blockchain::config::{BlockchainConfig, SmartContractConfig};
+use thai_energy_trading_blockchain::*;
+
+/// Gas given to constructors and calls that are not about running out of it
+const GAS: u64 = 1_000_000;
+
+/// Counter kept under the `count` storage key, initialised from the constructor arguments
+const COUNTER: &str = r#"
+(module
+  (import "env" "input" (func $input (param i32 i32) (result i32)))
+  (import "env" "storage_read" (func $storage_read (param i32 i32 i32 i32) (result i32)))
+  (import "env" "storage_write" (func $storage_write (param i32 i32 i32 i32)))
+  (import "env" "emit_event" (func $emit_event (param i32 i32 i32 i32)))
+  (import "env" "return_value" (func $return_value (param i32 i32)))
+  (import "env" "revert" (func $revert (param i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "count")
+  (data (i32.const 16) "incremented")
+  (data (i32.const 48) "not today")
+  (func (export "deploy")
+    (drop (call $input (i32.const 32) (i32.const 8)))
+    (call $storage_write (i32.const 0) (i32.const 5) (i32.const 32) (i32.const 8)))
+  (func (export "increment")
+    (drop (call $storage_read (i32.const 0) (i32.const 5) (i32.const 32) (i32.const 8)))
+    (i64.store (i32.const 32) (i64.add (i64.load (i32.const 32)) (i64.const 1)))
+    (call $storage_write (i32.const 0) (i32.const 5) (i32.const 32) (i32.const 8))
+    (call $emit_event (i32.const 16) (i32.const 11) (i32.const 32) (i32.const 8))
+    (call $return_value (i32.const 32) (i32.const 8)))
+  (func (export "add")
+    (drop (call $input (i32.const 40) (i32.const 8)))
+    (drop (call $storage_read (i32.const 0) (i32.const 5) (i32.const 32) (i32.const 8)))
+    (i64.store (i32.const 32) (i64.add (i64.load (i32.const 32)) (i64.load (i32.const 40))))
+    (call $storage_write (i32.const 0) (i32.const 5) (i32.const 32) (i32.const 8))
+    (call $return_value (i32.const 32) (i32.const 8)))
+  (func (export "count_as_u32")
+    (drop (call $storage_read (i32.const 0) (i32.const 5) (i32.const 32) (i32.const 8)))
+    (call $return_value (i32.const 32) (i32.const 8)))
+  (func (export "increment_then_revert")
+    (call $storage_write (i32.const 0) (i32.const 5) (i32.const 48) (i32.const 8))
+    (call $revert (i32.const 48) (i32.const 9)))
+  (func (export "fill_storage")
+    (call $storage_write (i32.const 0) (i32.const 5) (i32.const 0) (i32.const 4096))))
+"#;
+
+/// Returns the caller's balance followed by the chain head number
+const CONTEXT: &str = r#"
+(module
+  (import "env" "caller" (func $caller (param i32 i32) (result i32)))
+  (import "env" "balance" (func $balance (param i32 i32 i32)))
+  (import "env" "block_number" (func $block_number (result i64)))
+  (import "env" "return_value" (func $return_value (param i32 i32)))
+  (memory (export "memory") 1)
+  (func (export "caller_balance")
+    (call $balance (i32.const 0) (call $caller (i32.const 0) (i32.const 64)) (i32.const 64))
+    (i64.store (i32.const 80) (call $block_number))
+    (call $return_value (i32.const 64) (i32.const 24))))
+"#;
+
+/// Loops and storage writes whose cost depends on how much work they do
+const METERED: &str = r#"
+(module
+  (import "env" "storage_write" (func $storage_write (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (func $count (param $n i32)
+    (loop $again
+      (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+      (br_if $again (local.get $n))))
+  (func (export "count_to_ten") (call $count (i32.const 10)))
+  (func (export "count_to_hundred") (call $count (i32.const 100)))
+  (func (export "write_short")
+    (call $storage_write (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 8)))
+  (func (export "write_long")
+    (call $storage_write (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 512)))
+  (func (export "write_then_spin")
+    (call $storage_write (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 8))
+    (loop $forever (br $forever))))
+"#;
+
+fn wasm(source: &str) -> Vec<u8> {
+    wat::parse_str(source).unwrap()
+}
+
+fn function(name: &str, inputs: &[AbiType], outputs: &[AbiType]) -> ContractFunction {
+    ContractFunction { name: name.to_string(), inputs: inputs.to_vec(), outputs: outputs.to_vec() }
+}
+
+/// ABI of functions that take no arguments and return nothing
+fn abi(functions: &[&str]) -> ContractABI {
+    ContractABI { functions: functions.iter().map(|name| function(name, &[], &[])).collect() }
+}
+
+fn counter_abi() -> ContractABI {
+    ContractABI {
+        functions: vec![
+            function("increment", &[], &[AbiType::U64]),
+            function("add", &[AbiType::U64], &[AbiType::U64]),
+            // Declares a narrower type than the code returns
+            function("count_as_u32", &[], &[AbiType::U32]),
+            function("increment_then_revert", &[], &[]),
+            function("fill_storage", &[], &[]),
+        ],
+    }
+}
+
+fn metered_abi() -> ContractABI {
+    abi(&["count_to_ten", "count_to_hundred", "write_short", "write_long", "write_then_spin"])
+}
+
+/// Address of alice's first contract
+fn alice_contract() -> AccountId {
+    SmartContractVM::contract_address(&account("alice"), 0)
+}
+
+/// Run the constructor of `code` as alice's first contract
+fn deploy_as_alice(vm: &SmartContractVM, code: Vec<u8>, abi: ContractABI, args: &[u8], context: &ExecutionContext) -> (ContractExecutionResult, Option<ContractState>) {
+    let address = alice_contract();
+    let constructor = ContractCall {
+        address: &address,
+        caller: &account("alice"),
+        entry_point: DEPLOY_ENTRY_POINT,
+        input: args,
+        gas_limit: GAS,
+    };
+    vm.deploy_contract(code, abi, constructor, context)
+}
+
+/// Deploy `code` as alice's first contract, panicking if the VM rejects it
+fn deploy(vm: &SmartContractVM, code: &str, abi: ContractABI, args: &[u8], context: &ExecutionContext) -> ContractState {
+    let (result, contract) = deploy_as_alice(vm, wasm(code), abi, args, context);
+    contract.unwrap_or_else(|| panic!("{:?}", result.error))
+}
+
+/// Chain whose only authority is the node with `node_key`
+fn single_authority_spec(node_key: &str) -> ChainSpec {
+    ChainSpec {
+        authorities: vec![GenesisAuthority::development(node_key).unwrap()],
+        ..ChainSpec::development(&BlockchainConfig { authority_threshold: 1, ..BlockchainConfig::default() })
+    }
+}
+
+/// Write `spec` to `dir` and configure the node to load it
+fn with_spec_file(config: &BlockchainConfig, spec: &ChainSpec, dir: &Path) -> BlockchainConfig {
+    let path = dir.join("chain-spec.json");
+    std::fs::write(&path, spec.to_json().unwrap()).unwrap();
+    BlockchainConfig { chain_spec: path.to_string_lossy().into_owned(), ..config.clone() }
+}
+
+/// Create a consensus engine where the local node is the only scheduled validator
+async fn single_validator_engine(config: &BlockchainConfig) -> ConsensusEngine {
+    let spec = single_authority_spec(&config.node_key);
+    match &config.data_dir {
+        Some(data_dir) => {
+            let config = with_spec_file(config, &spec, Path::new(data_dir));
+            ConsensusEngine::with_storage(&config, Arc::new(BlockchainStorage::new(&config).await.unwrap())).await.unwrap()
+        }
+        None => ConsensusEngine::with_chain_spec(config, &spec).await.unwrap(),
+    }
+}
+
+fn validator_config(node_key: &str) -> BlockchainConfig {
+    BlockchainConfig {
+        node_key: node_key.to_string(),
+        validator: true,
+        ..BlockchainConfig::default()
+    }
+}
+
+/// Create a non-validating node on the chain of the producer with `producer_key`
+async fn importer_for(producer_key: &str) -> ConsensusEngine {
+    let config = BlockchainConfig { validator: false, ..BlockchainConfig::default() };
+    ConsensusEngine::with_chain_spec(&config, &single_authority_spec(producer_key)).await.unwrap()
+}
+
+/// Account of the keypair derived from `key`
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+/// Fee-free deployment of the counter contract starting at `initial`, signed by `deployer`
+fn deploy_counter_envelope(deployer: &str, initial: u64, nonce: u64) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::DeployContract {
+            deployer: account(deployer),
+            contract_code: wasm(COUNTER),
+            abi: counter_abi(),
+            constructor_args: initial.to_le_bytes().to_vec(),
+            gas_limit: GAS,
+            signature: vec![],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.gas_limit = 2 * GAS;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(deployer).unwrap());
+    envelope
+}
+
+/// Call of `method`, signed by `caller`, that may run contract code for up to `gas_limit` gas
+fn call_envelope(caller: &str, contract: &str, method: &str, gas_limit: u64, nonce: u64) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ExecuteContract {
+            caller: account(caller),
+            contract_address: contract.to_string(),
+            method: method.to_string(),
+            args: vec![],
+            gas_limit,
+            signature: vec![],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.gas_limit = 2 * GAS;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(caller).unwrap());
+    envelope
+}
+
+/// Call `function` of alice's first contract with encoded arguments
+fn call_with(vm: &SmartContractVM, contract: &mut ContractState, caller: &str, function: &str, input: &[u8], gas_limit: u64, context: &ExecutionContext) -> ContractExecutionResult {
+    let address = alice_contract();
+    let call = ContractCall { address: &address, caller: &caller.to_string(), entry_point: function, input, gas_limit };
+    vm.execute_contract(contract, call, context)
+}
+
+/// Call `function` of alice's first contract without arguments
+fn call(vm: &SmartContractVM, contract: &mut ContractState, caller: &str, function: &str, gas_limit: u64, context: &ExecutionContext) -> ContractExecutionResult {
+    call_with(vm, contract, caller, function, &[], gas_limit, context)
+}
+
+#[test]
+fn test_deployed_code_is_executed_against_contract_storage() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let context = ExecutionContext::default();
+    let mut contract = deploy(&vm, COUNTER, counter_abi(), &41u64.to_le_bytes(), &context);
+    let address = alice_contract();
+
+    let result = call(&vm, &mut contract, "bob", "increment", 100_000, &context);
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.output, 42u64.to_le_bytes());
+    assert_eq!(result.return_values, vec![AbiValue::U64(42)]);
+    assert_eq!(result.events, vec![ContractEvent {
+        contract: address.clone(),
+        topic: "incremented".to_string(),
+        data: 42u64.to_le_bytes().to_vec(),
+    }]);
+    assert_eq!(contract.storage["count"], 42u64.to_le_bytes());
+
+    let missing = call(&vm, &mut contract, "bob", "decrement", 100_000, &context);
+    assert!(!missing.success);
+    assert_eq!(missing.error.as_deref(), Some("Function decrement not found"));
+
+    // The constructor cannot be run again to reset the counter, even if the ABI lists it
+    contract.abi.functions.push(function(DEPLOY_ENTRY_POINT, &[AbiType::U64], &[]));
+    let reset = call_with(&vm, &mut contract, "bob", DEPLOY_ENTRY_POINT, &0u64.to_le_bytes(), 100_000, &context);
+    assert_eq!(reset.error.as_deref(), Some("The deploy constructor runs only when the contract is deployed"));
+    assert_eq!(contract.storage["count"], 42u64.to_le_bytes());
+}
+
+#[test]
+fn test_failed_calls_leave_storage_untouched() {
+    let vm = SmartContractVM::new(&SmartContractConfig { max_storage_size: 1024, ..SmartContractConfig::default() });
+    let context = ExecutionContext::default();
+    let mut contract = deploy(&vm, COUNTER, counter_abi(), &7u64.to_le_bytes(), &context);
+
+    let reverted = call(&vm, &mut contract, "bob", "increment_then_revert", 100_000, &context);
+    assert!(!reverted.success);
+    assert_eq!(reverted.error.as_deref(), Some("contract reverted: not today"));
+
+    let oversized = call(&vm, &mut contract, "bob", "fill_storage", GAS, &context);
+    assert!(!oversized.success);
+    assert!(oversized.error.unwrap().contains("exceed the 1024 byte limit"));
+
+    assert_eq!(contract.storage["count"], 7u64.to_le_bytes());
+}
+
+#[test]
+fn test_contracts_read_the_caller_and_chain_context() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let context = ExecutionContext {
+        block_number: 12,
+        timestamp: 1_700_000_000,
+        balances: &HashMap::from([("alice".to_string(), 5_000)]),
+    };
+    let abi = ContractABI { functions: vec![function("caller_balance", &[], &[AbiType::U128, AbiType::U64])] };
+    let mut contract = deploy(&vm, CONTEXT, abi, &[], &context);
+
+    let result = call(&vm, &mut contract, "alice", "caller_balance", 100_000, &context);
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.output[..16], 5_000u128.to_le_bytes());
+    assert_eq!(result.output[16..], 12u64.to_le_bytes());
+    assert_eq!(result.return_values, vec![AbiValue::U128(5_000), AbiValue::U64(12)]);
+
+    // Every balance lookup is charged
+    let schedule = GasSchedule::default();
+    let pricier = SmartContractConfig {
+        gas_schedule: GasSchedule { balance_read: schedule.balance_read + 1_000, ..schedule },
+        ..SmartContractConfig::default()
+    };
+    let repriced = call(&SmartContractVM::new(&pricier), &mut contract, "alice", "caller_balance", 100_000, &context);
+    assert!(repriced.success, "{:?}", repriced.error);
+    assert_eq!(repriced.gas_used - result.gas_used, 1_000);
+}
+
+#[test]
+fn test_deployment_rejects_code_the_runtime_cannot_run() {
+    let config = SmartContractConfig { max_contract_size: 1024, ..SmartContractConfig::default() };
+    let vm = SmartContractVM::new(&config);
+    let context = ExecutionContext::default();
+    let deploy = |code: Vec<u8>, abi: ContractABI| deploy_as_alice(&vm, code, abi, &[], &context).1;
+
+    assert!(deploy(b"not wasm".to_vec(), abi(&[])).is_none());
+    assert!(deploy(vec![0; 2048], abi(&[])).is_none());
+    // Every ABI function has to be exported
+    assert!(deploy(wasm(CONTEXT), abi(&["caller_balance", "transfer"])).is_none());
+    // Only the chain's host functions can be imported
+    assert!(deploy(wasm(r#"(module (import "wasi" "fd_write" (func (param i32))) (memory (export "memory") 1))"#), abi(&[])).is_none());
+    // Floating point would make results differ between nodes
+    assert!(deploy(wasm(r#"(module (memory (export "memory") 1) (func (export "f") (drop (f64.const 1.5))))"#), abi(&["f"])).is_none());
+
+    let disabled = SmartContractVM::new(&SmartContractConfig { enable_wasm: false, ..config });
+    assert!(disabled.check_code(&wasm(CONTEXT), &abi(&["caller_balance"])).is_err());
+}
+
+#[test]
+fn test_gas_is_charged_for_the_work_done() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let context = ExecutionContext::default();
+    let mut contract = deploy(&vm, METERED, metered_abi(), &[], &context);
+    let mut run = |function: &str| call(&vm, &mut contract, "bob", function, GAS, &context);
+
+    let ten = run("count_to_ten");
+    let hundred = run("count_to_hundred");
+    assert!(ten.success && hundred.success);
+    assert!(hundred.gas_used > ten.gas_used);
+    // Identical calls cost the same on every run
+    assert_eq!(run("count_to_hundred").gas_used, hundred.gas_used);
+
+    // Storage writes are priced per byte stored, on top of copying the bytes out of memory
+    let schedule = GasSchedule::default();
+    let short = run("write_short");
+    let long = run("write_long");
+    assert!(short.success && long.success);
+    assert_eq!(long.gas_used - short.gas_used, (512 - 8) * (schedule.storage_write_per_byte + schedule.per_byte));
+    assert!(short.gas_used > schedule.host_call + schedule.storage_write);
+}
+
+#[test]
+fn test_running_out_of_gas_reverts_storage() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let context = ExecutionContext::default();
+    let mut contract = deploy(&vm, METERED, metered_abi(), &[], &context);
+
+    let result = call(&vm, &mut contract, "bob", "write_then_spin", 50_000, &context);
+    assert!(!result.success);
+    assert_eq!(result.gas_used, 50_000);
+    assert_eq!(result.error.as_deref(), Some("contract ran out of its 50000 gas"));
+    assert!(contract.storage.is_empty());
+
+    // Host functions are refused once the remaining gas cannot pay for them
+    let starved = call(&vm, &mut contract, "bob", "write_long", 1_000, &context);
+    assert!(!starved.success);
+    assert_eq!(starved.gas_used, 1_000);
+    assert!(contract.storage.is_empty());
+}
+
+#[test]
+fn test_abi_values_round_trip_in_declared_layout() {
+    let types = [AbiType::Bool, AbiType::U64, AbiType::I64, AbiType::String, AbiType::Address];
+    let values = vec![
+        AbiValue::Bool(true),
+        AbiValue::U64(42),
+        AbiValue::I64(-1),
+        AbiValue::String("kWh".to_string()),
+        AbiValue::Address("alice".to_string()),
+    ];
+    let encoded = contract_abi::encode(&types, &values).unwrap();
+    assert_eq!(encoded[..9], [1, 42, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(encoded[17..24], [3, 0, 0, 0, b'k', b'W', b'h']);
+    assert_eq!(contract_abi::decode(&types, &encoded).unwrap(), values);
+
+    assert_eq!(
+        contract_abi::encode(&types[..1], &[AbiValue::U32(1)]),
+        Err(AbiError::TypeMismatch { index: 0, expected: AbiType::Bool, found: AbiType::U32 }),
+    );
+    assert_eq!(contract_abi::encode(&types, &values[..2]), Err(AbiError::ArityMismatch { expected: 5, found: 2 }));
+    assert_eq!(contract_abi::decode(&types, &encoded[..20]), Err(AbiError::UnexpectedEnd { index: 3 }));
+    assert_eq!(contract_abi::decode(&[AbiType::Bool], &[2]), Err(AbiError::InvalidValue { index: 0, expected: AbiType::Bool }));
+    assert_eq!(contract_abi::decode(&[AbiType::U32], &[0; 6]), Err(AbiError::TrailingBytes(2)));
+}
+
+#[test]
+fn test_calls_are_checked_against_the_function_signature() {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    let context = ExecutionContext::default();
+    let mut contract = deploy(&vm, COUNTER, counter_abi(), &40u64.to_le_bytes(), &context);
+
+    let added = call_with(&vm, &mut contract, "bob", "add", &2u64.to_le_bytes(), GAS, &context);
+    assert!(added.success, "{:?}", added.error);
+    assert_eq!(added.return_values, vec![AbiValue::U64(42)]);
+
+    // Arguments that do not decode as the inputs are refused before any code runs
+    let short = call_with(&vm, &mut contract, "bob", "add", &2u32.to_le_bytes(), GAS, &context);
+    assert_eq!(short.error.as_deref(), Some("Invalid arguments for add: value 0 is cut off"));
+    assert_eq!(short.gas_used, 0);
+    let extra = call_with(&vm, &mut contract, "bob", "increment", &[1], GAS, &context);
+    assert_eq!(extra.error.as_deref(), Some("Invalid arguments for increment: 1 bytes left after the last value"));
+
+    // Output that does not match the declared outputs fails the call
+    let mismatched = call(&vm, &mut contract, "bob", "count_as_u32", GAS, &context);
+    assert!(!mismatched.success);
+    assert_eq!(mismatched.error.as_deref(), Some("Invalid return value from count_as_u32: 4 bytes left after the last value"));
+    assert!(mismatched.gas_used > 0);
+    assert_eq!(contract.storage["count"], 42u64.to_le_bytes());
+}
+
+#[tokio::test]
+async fn test_engine_calls_take_and_return_typed_values() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = BlockchainConfig { network: "contract-tests".to_string(), p2p_port: 0, ..validator_config("contract-engine") };
+    let engine = BlockchainEngine::new(&with_spec_file(&config, &single_authority_spec(&config.node_key), dir.path())).await.unwrap();
+    let keypair = GridTokenXKeyPair::from_node_key("contract-deployer").unwrap();
+    let deployer = keypair.account_id().to_string();
+    let deployment = |initial: u64, nonce: u64| {
+        let mut envelope = deploy_counter_envelope("contract-deployer", initial, nonce);
+        envelope.gas_price = 1;
+        envelope.sign(&keypair);
+        envelope
+    };
+    engine.consensus().add_transaction(mint_envelope("contract-deployer", EnergyAmount::from_wh(10_000_000_000), 0)).await.unwrap();
+    engine.consensus().produce_block().await.unwrap().unwrap();
+
+    // Each deployment of the same code by the same account gets its own address
+    let first = engine.deploy_contract(deployment(40, 1)).await.unwrap();
+    let second = engine.deploy_contract(deployment(0, 2)).await.unwrap();
+    assert_eq!(first, SmartContractVM::contract_address(&deployer, 1));
+    assert_eq!(second, SmartContractVM::contract_address(&deployer, 2));
+    assert_ne!(first, second);
+    engine.consensus().produce_block().await.unwrap().unwrap();
+    assert!(engine.get_contract(&first).await.is_some() && engine.get_contract(&second).await.is_some());
+
+    // An address that already holds a contract cannot be deployed to again
+    let collision = engine.deploy_contract(deployment(0, 1)).await.unwrap_err();
+    assert!(collision.to_string().contains(&format!("Contract already deployed at {}", first)), "{}", collision);
+
+    let bob = "bob".to_string();
+    let result = engine.execute_contract_function(&first, &bob, "add", &[AbiValue::U64(2)], GAS).await.unwrap();
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.return_values, vec![AbiValue::U64(42)]);
+    // Calls against the head are read-only
+    assert_eq!(engine.get_contract(&first).await.unwrap().storage["count"], 40u64.to_le_bytes());
+
+    let wrong_type = engine.execute_contract_function(&first, &bob, "add", &[AbiValue::U32(2)], GAS).await.unwrap_err();
+    assert!(wrong_type.to_string().contains("Invalid arguments for add: value 0 should be U64, got U32"), "{}", wrong_type);
+    let wrong_arity = engine.execute_contract_function(&first, &bob, "add", &[], GAS).await.unwrap_err();
+    assert!(wrong_arity.to_string().contains("expected 1 values, got 0"), "{}", wrong_arity);
+    let missing = engine.execute_contract_function(&first, &bob, "decrement", &[], GAS).await.unwrap_err();
+    assert!(missing.to_string().contains("Function decrement not found"), "{}", missing);
+}
+
+#[tokio::test]
+async fn test_contract_storage_is_committed_to_the_state_root() {
+    let producer = single_validator_engine(&validator_config("contract-producer")).await;
+    let importer = importer_for("contract-producer").await;
+    let address = alice_contract();
+
+    producer.add_transaction(deploy_counter_envelope("alice", 41, 0)).await.unwrap();
+    let deployed = producer.produce_block().await.unwrap().unwrap();
+    assert_eq!(deployed.receipts[0].status, ReceiptStatus::Success, "{:?}", deployed.receipts[0].error);
+    assert!(deployed.receipts[0].events.contains(&TransactionEvent::ContractDeployed {
+        deployer: account("alice"),
+        contract: address.clone(),
+    }));
+    assert_eq!(producer.get_contract(&address).await.unwrap().storage["count"], 41u64.to_le_bytes());
+
+    producer.add_transaction(call_envelope("bob", &address, "increment", GAS, 0)).await.unwrap();
+    let called = producer.produce_block().await.unwrap().unwrap();
+    assert_eq!(called.receipts[0].events, vec![TransactionEvent::ContractEventEmitted {
+        contract: address.clone(),
+        topic: "incremented".to_string(),
+        data: 42u64.to_le_bytes().to_vec(),
+    }]);
+    assert_ne!(called.header.state_root, deployed.header.state_root);
+
+    // Contract storage is part of the account state proven against the state root
+    let proof = producer.get_account_proof(&address, 2).await.unwrap();
+    assert_eq!(proof.account_state.as_ref().unwrap().contract.as_ref().unwrap().storage["count"], 42u64.to_le_bytes());
+    assert!(verify_account_proof(&called.header.state_root, &proof));
+    let mut forged = proof.clone();
+    forged.account_state.as_mut().unwrap().contract.as_mut().unwrap().storage.insert("count".to_string(), 1u64.to_le_bytes().to_vec());
+    assert!(!verify_account_proof(&called.header.state_root, &forged));
+
+    // Importers re-execute the contract code and arrive at the same state root
+    assert_eq!(importer.import_block(deployed).await.unwrap(), BlockImportResult::Imported);
+    assert_eq!(importer.import_block(called).await.unwrap(), BlockImportResult::Imported);
+    assert_eq!(importer.get_contract(&address).await, producer.get_contract(&address).await);
+}
+
+#[tokio::test]
+async fn test_failed_contract_transactions_revert_storage_and_charge_gas_used() {
+    let producer = single_validator_engine(&validator_config("contract-failures")).await;
+    let address = SmartContractVM::contract_address(&account("alice"), 1);
+    producer.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(1_000_000_000), 0)).await.unwrap();
+    producer.add_transaction(deploy_counter_envelope("alice", 7, 1)).await.unwrap();
+    producer.produce_block().await.unwrap().unwrap();
+    let funded = producer.get_account_balance(&account("alice")).await.unwrap().total_balance;
+
+    let mut reverted = call_envelope("alice", &address, "increment_then_revert", 100_000, 2);
+    let mut starved = call_envelope("alice", &address, "fill_storage", 1_000, 3);
+    let alice = GridTokenXKeyPair::from_node_key("alice").unwrap();
+    reverted.gas_price = 1;
+    reverted.sign(&alice);
+    starved.gas_price = 1;
+    starved.sign(&alice);
+    producer.add_transaction(reverted).await.unwrap();
+    producer.add_transaction(starved).await.unwrap();
+    producer.add_transaction(deploy_counter_envelope("alice", 0, 4)).await.unwrap();
+    let block = producer.produce_block().await.unwrap().unwrap();
+
+    let reverted = &block.receipts[0];
+    assert_eq!(reverted.status, ReceiptStatus::Failed);
+    assert_eq!(reverted.error.as_deref(), Some("contract reverted: not today"));
+    assert!(reverted.gas_used > 80_000 && reverted.gas_used < 180_000);
+    assert_eq!(reverted.fee_charged, reverted.gas_used as Balance);
+
+    let starved = &block.receipts[1];
+    assert_eq!(starved.error.as_deref(), Some("contract ran out of its 1000 gas"));
+    assert_eq!(starved.gas_used, 81_000);
+
+    // A second deployment of the same code gets an address of its own
+    let redeployed = &block.receipts[2];
+    assert_eq!(redeployed.status, ReceiptStatus::Success, "{:?}", redeployed.error);
+    let second = SmartContractVM::contract_address(&account("alice"), 4);
+    assert_ne!(second, address);
+    assert_eq!(producer.get_contract(&second).await.unwrap().storage["count"], 0u64.to_le_bytes());
+
+    assert_eq!(producer.get_contract(&address).await.unwrap().storage["count"], 7u64.to_le_bytes());
+    let alice = producer.get_account_balance(&account("alice")).await.unwrap();
+    assert_eq!(alice.nonce, 5);
+    assert_eq!(alice.total_balance, funded - reverted.fee_charged - starved.fee_charged);
+}
+
+#[tokio::test]
+async fn test_contract_state_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = BlockchainConfig {
+        data_dir: Some(dir.path().to_string_lossy().into_owned()),
+        snapshot_interval: 2,
+        ..validator_config("contract-storage")
+    };
+    let address = alice_contract();
+
+    let engine = single_validator_engine(&config).await;
+    engine.add_transaction(deploy_counter_envelope("alice", 41, 0)).await.unwrap();
+    engine.produce_block().await.unwrap().unwrap();
+    for nonce in 0..2 {
+        engine.add_transaction(call_envelope("bob", &address, "increment", GAS, nonce)).await.unwrap();
+        engine.produce_block().await.unwrap().unwrap();
+    }
+    let before = engine.get_contract(&address).await.unwrap();
+    assert_eq!(before.storage["count"], 43u64.to_le_bytes());
+    drop(engine);
+
+    // Block #2 is restored from a snapshot and block #3 is replayed on top of it
+    let restarted = single_validator_engine(&config).await;
+    assert_eq!(restarted.get_blockchain_state().await.block_height, 3);
+    assert_eq!(restarted.get_contract(&address).await, Some(before));
+}
@@ -0,0 +1,235 @@
+use chrono::DateTime;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+use thai_energy_trading_blockchain::blockchain::chain_spec::{
+    ChainSpec, DEVELOPMENT_CARBON_CREDIT_ISSUER, DEVELOPMENT_ORACLE,
+};
+use thai_energy_trading_blockchain::blockchain::smart_contracts::SmartContractVM;
+use thai_energy_trading_blockchain::blockchain::state_transition::{
+    apply_transaction, BlockContext, ReceiptStatus, TransactionEvent, TransactionReceipt,
+};
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    self, CarbonVerification, EnergyBalanceState, EnergyProductionRecord, EnergyQualityMetrics,
+    EnergyTransaction, EnergyTransactionEnvelope, GridValidation, ValidatorSignature,
+};
+use thai_energy_trading_blockchain::config::{BlockchainConfig, SmartContractConfig};
+use thai_energy_trading_blockchain::*;
+
+/// Block on the development chain, which trusts its development oracle and carbon credit issuer
+fn block() -> BlockContext {
+    let trusted = ChainSpec::development(&BlockchainConfig::default()).trusted_parties().unwrap();
+    BlockContext { number: 1, timestamp: 1_700_000_000, validator: "validator".to_string(), trusted: Arc::new(trusted) }
+}
+
+/// Account of the keypair derived from `key`
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+/// Sign `transaction` as `key` without paying a fee
+fn envelope(key: &str, transaction: EnergyTransaction, nonce: u64) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(transaction, nonce);
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(key).unwrap());
+    envelope
+}
+
+/// Apply a transaction that must be includable
+fn apply(balances: &mut HashMap<AccountId, EnergyBalanceState>, envelope: EnergyTransactionEnvelope) -> TransactionReceipt {
+    let vm = SmartContractVM::new(&SmartContractConfig::default());
+    apply_transaction(balances, &envelope, &block(), &vm).unwrap()
+}
+
+/// Error of a receipt that must have failed
+fn failure(receipt: TransactionReceipt) -> String {
+    assert_eq!(receipt.status, ReceiptStatus::Failed);
+    receipt.error.unwrap()
+}
+
+/// Verified solar production of `kwh` by `producer`
+fn production_record(producer: &str, kwh: u64) -> EnergyProductionRecord {
+    EnergyProductionRecord {
+        amount: EnergyAmount::from_kwh(kwh).unwrap(),
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    }
+}
+
+/// Report of `record` by `producer`, carrying `attestations`
+fn report(producer: &str, record: EnergyProductionRecord, attestations: Vec<ValidatorSignature>, nonce: u64) -> EnergyTransactionEnvelope {
+    let transaction = EnergyTransaction::ReportProduction {
+        producer: account(producer),
+        production_record: record,
+        validator_signatures: attestations,
+    };
+    envelope(producer, transaction, nonce)
+}
+
+/// Attestation of `record` for `producer` by the oracle with `oracle_key`
+fn attestation(oracle_key: &str, producer: &str, record: &EnergyProductionRecord) -> ValidatorSignature {
+    ValidatorSignature::attest(&GridTokenXKeyPair::from_node_key(oracle_key).unwrap(), &account(producer), record)
+}
+
+/// Sale of `kwh` of solar energy from "seller" to "buyer" at 4.50 THB/kWh
+fn trade(kwh: u64) -> EnergyTrade {
+    let energy_amount = EnergyAmount::from_kwh(kwh).unwrap();
+    let price = TokenPrice::from_satang(450);
+    EnergyTrade {
+        trade_id: format!("trade-{}", kwh),
+        energy_amount,
+        price_per_unit: price,
+        buyer_id: account("buyer"),
+        seller_id: account("seller"),
+        timestamp: 1_700_000_000,
+        status: TradeStatus::Pending,
+        grid_location: utils::testing::create_test_grid_location(),
+        id: format!("trade-{}", kwh),
+        buy_order_id: "buy".to_string(),
+        sell_order_id: "sell".to_string(),
+        price_per_kwh: price,
+        total_price: price.total(energy_amount),
+        grid_fee: 0,
+        energy_source: EnergySource::Solar,
+        carbon_offset: CarbonOffset {
+            offset_credits: FixedPoint::ZERO,
+            verified: false,
+            certification_body: String::new(),
+            timestamp: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        },
+    }
+}
+
+/// Buyer's submission of `trade` with `seller_signature` as the seller's consent
+fn trade_envelope(trade: EnergyTrade, seller_signature: Vec<u8>, nonce: u64) -> EnergyTransactionEnvelope {
+    let transaction = EnergyTransaction::ExecuteTrade {
+        trade,
+        buyer_signature: vec![],
+        seller_signature,
+        grid_validation: GridValidation {
+            validator: "grid-operator".to_string(),
+            capacity_check: true,
+            congestion_level: CongestionLevel::Low,
+            transmission_cost: 0,
+            timestamp: SystemTime::UNIX_EPOCH,
+        },
+    };
+    envelope("buyer", transaction, nonce)
+}
+
+/// Balances where the seller holds 10 solar tokens and the buyer 100 THB
+fn market() -> HashMap<AccountId, EnergyBalanceState> {
+    let mut balances = HashMap::new();
+    let record = production_record("seller", 10);
+    let attested = attestation(DEVELOPMENT_ORACLE, "seller", &record);
+    assert_eq!(apply(&mut balances, report("seller", record, vec![attested], 0)).status, ReceiptStatus::Success);
+    balances.entry(account("buyer")).or_insert_with(EnergyBalanceState::new).currency_balance = 10_000;
+    balances
+}
+
+/// Issue of `credits` carbon credits to "recipient" by `issuer`
+fn carbon_credit(issuer: &str, credits: u32) -> EnergyTransactionEnvelope {
+    let transaction = EnergyTransaction::CarbonCredit {
+        issuer: account(issuer),
+        recipient: account("recipient"),
+        credits,
+        energy_source: EnergySource::Solar,
+        verification: CarbonVerification {
+            verifier: account(issuer),
+            methodology: "ACM0002".to_string(),
+            co2_reduced: FixedPoint::from_int(1),
+            verification_standard: "T-VER".to_string(),
+            timestamp: SystemTime::UNIX_EPOCH,
+        },
+        signature: vec![],
+    };
+    envelope(issuer, transaction, 0)
+}
+
+#[test]
+fn test_trade_settles_only_with_the_sellers_consent_to_its_exact_terms() {
+    let mut balances = market();
+    let seller = GridTokenXKeyPair::from_node_key("seller").unwrap();
+
+    let unsigned = failure(apply(&mut balances, trade_envelope(trade(4), vec![], 0)));
+    assert!(unsigned.contains("Seller has not agreed"), "{}", unsigned);
+
+    let mallory = GridTokenXKeyPair::from_node_key("mallory").unwrap();
+    let forged = failure(apply(&mut balances, trade_envelope(trade(4), transactions::sign_trade(&trade(4), &mallory), 1)));
+    assert!(forged.contains("Seller has not agreed"), "{}", forged);
+
+    // The seller agreed to sell 2 kWh, not 4
+    let altered = failure(apply(&mut balances, trade_envelope(trade(4), transactions::sign_trade(&trade(2), &seller), 2)));
+    assert!(altered.contains("Seller has not agreed"), "{}", altered);
+
+    let mut underpriced = trade(4);
+    underpriced.total_price = 1;
+    let consent = transactions::sign_trade(&underpriced, &seller);
+    let mismatch = failure(apply(&mut balances, trade_envelope(underpriced, consent, 3)));
+    assert!(mismatch.contains("does not match"), "{}", mismatch);
+
+    // Nothing moved until the seller's consent covers the real price
+    assert_eq!(balances[&account("seller")].energy_balances[&EnergySource::Solar], 10);
+    assert_eq!(balances[&account("buyer")].currency_balance, 10_000);
+
+    let settled = apply(&mut balances, trade_envelope(trade(4), transactions::sign_trade(&trade(4), &seller), 4));
+    assert_eq!(settled.status, ReceiptStatus::Success, "{:?}", settled.error);
+    assert_eq!(settled.events, vec![TransactionEvent::TradeSettled {
+        trade_id: "trade-4".to_string(),
+        buyer: account("buyer"),
+        seller: account("seller"),
+        energy_amount: 4,
+        total_price: 1_800,
+    }]);
+    assert_eq!(balances[&account("seller")].energy_balances[&EnergySource::Solar], 6);
+    assert_eq!(balances[&account("seller")].currency_balance, 1_800);
+    assert_eq!(balances[&account("buyer")].energy_balances[&EnergySource::Solar], 4);
+    assert_eq!(balances[&account("buyer")].currency_balance, 8_200);
+}
+
+#[test]
+fn test_production_mints_only_with_a_registered_oracle_attestation() {
+    let mut balances = HashMap::new();
+    let record = production_record("alice", 5);
+
+    let unattested = failure(apply(&mut balances, report("alice", record.clone(), vec![], 0)));
+    assert!(unattested.contains("not attested"), "{}", unattested);
+
+    let rogue = attestation("rogue-oracle", "alice", &record);
+    assert!(failure(apply(&mut balances, report("alice", record.clone(), vec![rogue], 1))).contains("not attested"));
+
+    // An attestation is bound to the producer and the record it was made for
+    let for_bob = attestation(DEVELOPMENT_ORACLE, "bob", &record);
+    assert!(failure(apply(&mut balances, report("alice", record.clone(), vec![for_bob], 2))).contains("not attested"));
+    let smaller = attestation(DEVELOPMENT_ORACLE, "alice", &production_record("alice", 1));
+    assert!(failure(apply(&mut balances, report("alice", record.clone(), vec![smaller], 3))).contains("not attested"));
+
+    let attested = attestation(DEVELOPMENT_ORACLE, "alice", &record);
+    let minted = apply(&mut balances, report("alice", record, vec![attested], 4));
+    assert_eq!(minted.status, ReceiptStatus::Success, "{:?}", minted.error);
+    assert_eq!(balances[&account("alice")].energy_balances[&EnergySource::Solar], 5);
+}
+
+#[test]
+fn test_only_registered_issuers_issue_carbon_credits() {
+    let mut balances = HashMap::new();
+
+    let unregistered = failure(apply(&mut balances, carbon_credit("greenwasher", 100)));
+    assert!(unregistered.contains("not a registered carbon credit issuer"), "{}", unregistered);
+    assert!(!balances.contains_key(&account("recipient")));
+
+    let issued = apply(&mut balances, carbon_credit(DEVELOPMENT_CARBON_CREDIT_ISSUER, 100));
+    assert_eq!(issued.status, ReceiptStatus::Success, "{:?}", issued.error);
+    assert_eq!(balances[&account("recipient")].carbon_credits, 100);
+}
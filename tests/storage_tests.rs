@@ -0,0 +1,217 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::SystemTime;
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::ConsensusEngine;
+use thai_energy_trading_blockchain::blockchain::storage::BlockchainStorage;
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    ValidatorSignature,
+};
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use thai_energy_trading_blockchain::*;
+
+/// Node storing its chain in `data_dir`, on a chain spec there naming it the sole authority
+fn persistent_config(data_dir: &std::path::Path) -> BlockchainConfig {
+    let config = BlockchainConfig {
+        node_key: "storage-validator".to_string(),
+        validator: true,
+        authority_threshold: 1,
+        data_dir: Some(data_dir.to_string_lossy().into_owned()),
+        ..BlockchainConfig::default()
+    };
+    let spec = ChainSpec {
+        authorities: vec![GenesisAuthority::development(&config.node_key).unwrap()],
+        ..ChainSpec::development(&config)
+    };
+    let path = data_dir.join("chain-spec.json");
+    fs::write(&path, spec.to_json().unwrap()).unwrap();
+    BlockchainConfig { chain_spec: path.to_string_lossy().into_owned(), ..config }
+}
+
+/// Open the store and a consensus engine on top of it
+async fn open_engine(config: &BlockchainConfig) -> (Arc<BlockchainStorage>, ConsensusEngine) {
+    let storage = Arc::new(BlockchainStorage::new(config).await.unwrap());
+    let engine = ConsensusEngine::with_storage(config, storage.clone()).await.unwrap();
+    (storage, engine)
+}
+
+/// Account of the keypair derived from `key`
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Wind,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(800_000),
+        weather_conditions: None,
+        equipment_id: format!("turbine-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+#[tokio::test]
+async fn test_chain_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = persistent_config(dir.path());
+
+    let (_, engine) = open_engine(&config).await;
+    let genesis = engine.genesis_block().await;
+    let mint = mint_envelope("alice", EnergyAmount::from_wh(500_000), 0);
+    engine.add_transaction(mint.clone()).await.unwrap();
+    let first = engine.produce_block().await.unwrap().unwrap();
+    engine.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(250_000), 1)).await.unwrap();
+    let second = engine.produce_block().await.unwrap().unwrap();
+    let before = engine.get_blockchain_state().await;
+    drop(engine);
+
+    let (storage, restarted) = open_engine(&config).await;
+    let after = restarted.get_blockchain_state().await;
+    assert_eq!(restarted.genesis_block().await, genesis);
+    assert_eq!(after.block_height, 2);
+    assert_eq!(after.latest_block_hash, second.header.hash);
+    assert_eq!(after.balances, before.balances);
+    assert_eq!(after.balances[&account("alice")].total_balance, 750);
+    assert!(restarted.get_transaction_receipt(&mint.hash).await.is_some());
+
+    assert_eq!(storage.block_count().await, 3);
+    assert_eq!(storage.get_block_by_height(1).await.unwrap(), Some(first.clone()));
+    assert_eq!(storage.get_block_by_hash(&second.header.hash).await.unwrap(), Some(second));
+    assert_eq!(storage.get_block_by_transaction(&mint.hash).await.unwrap(), Some(first));
+    assert_eq!(storage.get_block_by_height(3).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_recovery_discards_torn_record() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = persistent_config(dir.path());
+
+    let (_, engine) = open_engine(&config).await;
+    engine.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(500_000), 0)).await.unwrap();
+    let block = engine.produce_block().await.unwrap().unwrap();
+    drop(engine);
+
+    // Simulate a crash half-way through writing the next record
+    let mut log = OpenOptions::new().append(true).open(dir.path().join("blocks.log")).unwrap();
+    log.write_all(&[0, 0, 1, 0, 42, 42, 42]).unwrap();
+    drop(log);
+
+    let (storage, restarted) = open_engine(&config).await;
+    assert_eq!(restarted.get_blockchain_state().await.latest_block_hash, block.header.hash);
+    assert_eq!(storage.block_count().await, 2);
+
+    // The store keeps accepting blocks after the torn tail is dropped
+    restarted.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(100_000), 1)).await.unwrap();
+    restarted.produce_block().await.unwrap().unwrap();
+    drop(restarted);
+    drop(storage);
+    let (_, reopened) = open_engine(&config).await;
+    assert_eq!(reopened.get_blockchain_state().await.block_height, 2);
+}
+
+#[tokio::test]
+async fn test_recovery_completes_block_from_wal() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = persistent_config(dir.path());
+    let log_path = dir.path().join("blocks.log");
+
+    let (_, engine) = open_engine(&config).await;
+    let genesis_len = fs::metadata(&log_path).unwrap().len() as usize;
+    engine.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(500_000), 0)).await.unwrap();
+    let block = engine.produce_block().await.unwrap().unwrap();
+    drop(engine);
+
+    // Simulate a crash after the WAL was written but before the log append
+    let contents = fs::read(&log_path).unwrap();
+    fs::write(dir.path().join("blocks.wal"), &contents[genesis_len..]).unwrap();
+    fs::write(&log_path, &contents[..genesis_len]).unwrap();
+
+    let (storage, restarted) = open_engine(&config).await;
+    assert_eq!(restarted.get_blockchain_state().await.latest_block_hash, block.header.hash);
+    assert_eq!(storage.get_block_by_height(1).await.unwrap(), Some(block));
+    assert_eq!(fs::metadata(dir.path().join("blocks.wal")).unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_storage_without_data_dir_is_in_memory() {
+    let storage = BlockchainStorage::new(&BlockchainConfig::default()).await.unwrap();
+    assert!(!storage.is_persistent().await);
+    assert_eq!(storage.block_count().await, 0);
+    assert!(storage.load_blocks().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_restart_from_snapshot() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = BlockchainConfig { snapshot_interval: 2, ..persistent_config(dir.path()) };
+
+    let (storage, engine) = open_engine(&config).await;
+    for (nonce, amount) in [(0, 100_000), (1, 200_000), (2, 300_000)] {
+        engine.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(amount), nonce)).await.unwrap();
+        engine.produce_block().await.unwrap().unwrap();
+    }
+    let before = engine.get_blockchain_state().await;
+
+    let snapshot = storage.latest_snapshot().await.unwrap().expect("snapshot at block #2");
+    assert_eq!(snapshot.height, 2);
+    assert_eq!(snapshot.block_hash, before.blocks[2].header.hash);
+    assert_eq!(snapshot.token_supply, 300);
+    drop(engine);
+    drop(storage);
+
+    let (_, restarted) = open_engine(&config).await;
+    let after = restarted.get_blockchain_state().await;
+    assert_eq!(after.block_height, 3);
+    assert_eq!(after.latest_block_hash, before.latest_block_hash);
+    assert_eq!(after.balances, before.balances);
+    assert_eq!(after.blocks, before.blocks);
+    assert_eq!(restarted.create_snapshot().await.token_supply, 600);
+}
+
+#[tokio::test]
+async fn test_snapshot_not_matching_state_root_is_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = BlockchainConfig { snapshot_interval: 1, ..persistent_config(dir.path()) };
+
+    let (storage, engine) = open_engine(&config).await;
+    engine.add_transaction(mint_envelope("alice", EnergyAmount::from_wh(100_000), 0)).await.unwrap();
+    engine.produce_block().await.unwrap().unwrap();
+    let before = engine.get_blockchain_state().await;
+
+    // Overwrite the snapshot with inflated balances
+    let mut forged = storage.latest_snapshot().await.unwrap().unwrap();
+    forged.balances.get_mut(&account("alice")).unwrap().total_balance = 1_000_000;
+    storage.save_snapshot(&forged).await.unwrap();
+    drop(engine);
+    drop(storage);
+
+    // The node falls back to replaying every block
+    let (_, restarted) = open_engine(&config).await;
+    let after = restarted.get_blockchain_state().await;
+    assert_eq!(after.balances, before.balances);
+    assert_eq!(after.balances[&account("alice")].total_balance, 100);
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::ConsensusEngine;
+use thai_energy_trading_blockchain::blockchain::transaction_pool::{
+    AdmissionRejection, PoolRejection, TransactionPool, MAX_FUTURE_NONCES,
+};
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    SignatureRejection, ValidatorSignature,
+};
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use thai_energy_trading_blockchain::*;
+
+/// Pool holding at most `size` transactions for an hour
+fn pool(size: usize) -> TransactionPool {
+    TransactionPool::new(&BlockchainConfig {
+        transaction_pool_size: size,
+        transaction_lifetime: 3600,
+        ..BlockchainConfig::default()
+    })
+}
+
+fn transfer_envelope(from: &str, to: &str, amount: Balance, nonce: u64, gas_price: Balance) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::Transfer {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+            energy_type: EnergySource::Solar,
+            grid_location: utils::testing::create_test_grid_location(),
+        },
+        nonce,
+    );
+    envelope.gas_price = gas_price;
+    envelope.hash = envelope.compute_hash().unwrap();
+    envelope
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &GridTokenXKeyPair::from_node_key(producer).unwrap().account_id().to_string(), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: GridTokenXKeyPair::from_node_key(producer).unwrap().account_id().to_string(),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+fn hashes(transactions: &[EnergyTransactionEnvelope]) -> Vec<[u8; 32]> {
+    transactions.iter().map(|tx| tx.hash).collect()
+}
+
+#[test]
+fn test_ready_transactions_follow_priority_and_nonces() {
+    let mut pool = pool(10);
+    let alice_first = transfer_envelope("alice", "bob", 10, 0, 1);
+    let alice_second = transfer_envelope("alice", "bob", 20, 1, 50);
+    let carol = transfer_envelope("carol", "bob", 30, 0, 5);
+    let dave_gapped = transfer_envelope("dave", "bob", 40, 3, 100);
+    for tx in [&alice_second, &dave_gapped, &carol, &alice_first] {
+        pool.insert(tx.clone(), 0).unwrap();
+    }
+
+    // Carol outbids Alice's first transaction; Alice's second has to wait for her first
+    let nonces = HashMap::from([("dave".to_string(), 1)]);
+    let account_nonce = |sender: &AccountId| nonces.get(sender).copied().unwrap_or(0);
+    assert_eq!(hashes(&pool.ready(account_nonce)), hashes(&[carol, alice_first, alice_second]));
+    assert_eq!(hashes(&pool.future(account_nonce)), hashes(&[dave_gapped]));
+}
+
+#[test]
+fn test_replacement_must_raise_the_gas_price() {
+    let mut pool = pool(10);
+    let original = transfer_envelope("alice", "bob", 10, 0, 100);
+    pool.insert(original.clone(), 0).unwrap();
+    assert_eq!(pool.insert(original.clone(), 0), Err(PoolRejection::ReplacementUnderpriced { required: 110, found: 100 }));
+
+    let underpriced = transfer_envelope("alice", "carol", 10, 0, 109);
+    assert!(matches!(pool.insert(underpriced, 0), Err(PoolRejection::ReplacementUnderpriced { .. })));
+
+    let replacement = transfer_envelope("alice", "carol", 10, 0, 110);
+    pool.insert(replacement.clone(), 0).unwrap();
+    assert_eq!(pool.len(), 1);
+    assert!(!pool.contains(&original.hash));
+    assert_eq!(pool.get(&replacement.hash), Some(&replacement));
+}
+
+#[test]
+fn test_full_pool_evicts_the_lowest_priority_transaction() {
+    let mut pool = pool(2);
+    let cheap_first = transfer_envelope("alice", "bob", 10, 0, 1);
+    let cheap_second = transfer_envelope("alice", "bob", 20, 1, 1);
+    pool.insert(cheap_first.clone(), 0).unwrap();
+    pool.insert(cheap_second.clone(), 0).unwrap();
+
+    // Alice's later transaction goes so her earlier one stays ready
+    let outbidding = transfer_envelope("carol", "bob", 30, 0, 2);
+    pool.insert(outbidding.clone(), 0).unwrap();
+    assert!(pool.contains(&cheap_first.hash));
+    assert!(!pool.contains(&cheap_second.hash));
+    assert!(pool.contains(&outbidding.hash));
+
+    let lowball = transfer_envelope("dave", "bob", 40, 0, 1);
+    assert_eq!(pool.insert(lowball, 0), Err(PoolRejection::PoolFull));
+    assert_eq!(pool.len(), 2);
+}
+
+#[test]
+fn test_eviction_skips_the_senders_own_earlier_transaction() {
+    let mut pool = pool(2);
+    let cheap = transfer_envelope("alice", "bob", 10, 0, 1);
+    let other = transfer_envelope("carol", "bob", 20, 0, 5);
+    pool.insert(cheap.clone(), 0).unwrap();
+    pool.insert(other.clone(), 0).unwrap();
+
+    // Alice's first transaction is the cheapest but her second needs it, so Carol's goes
+    let follow_up = transfer_envelope("alice", "bob", 30, 1, 10);
+    pool.insert(follow_up.clone(), 0).unwrap();
+    assert!(pool.contains(&cheap.hash));
+    assert!(!pool.contains(&other.hash));
+    assert!(pool.contains(&follow_up.hash));
+
+    // With only her own earlier transactions left to evict, Alice cannot queue a third
+    let third = transfer_envelope("alice", "bob", 40, 2, 20);
+    assert_eq!(pool.insert(third, 0), Err(PoolRejection::PoolFull));
+}
+
+#[test]
+fn test_stale_and_included_transactions_leave_the_pool() {
+    let mut pool = pool(10);
+    let old = transfer_envelope("alice", "bob", 10, 0, 1);
+    pool.insert(old.clone(), 0).unwrap();
+    let first = transfer_envelope("carol", "bob", 20, 0, 1);
+    let second = transfer_envelope("carol", "bob", 30, 1, 1);
+    pool.insert(first.clone(), 3000).unwrap();
+    pool.insert(second.clone(), 3000).unwrap();
+
+    pool.prune_expired(3601);
+    assert!(!pool.contains(&old.hash));
+    assert_eq!(pool.len(), 2);
+
+    // Including Carol's first transaction leaves only her second
+    pool.remove_included(std::slice::from_ref(&first));
+    assert_eq!(hashes(&pool.transactions()), hashes(&[second]));
+}
+
+/// Validator producing blocks on its own, with the given block size limit
+async fn single_validator_engine(node_key: &str, block_size_limit: usize) -> ConsensusEngine {
+    let config = BlockchainConfig {
+        node_key: node_key.to_string(),
+        validator: true,
+        block_size_limit,
+        ..BlockchainConfig::default()
+    };
+    let spec = ChainSpec {
+        authorities: vec![GenesisAuthority::development(node_key).unwrap()],
+        ..ChainSpec::development(&config)
+    };
+    ConsensusEngine::with_chain_spec(&config, &spec).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_block_production_respects_the_block_size_limit() {
+    let (first, second) = (mint_envelope("alice", EnergyAmount::from_wh(100_000), 0), mint_envelope("bob", EnergyAmount::from_wh(100_000), 0));
+    let producer = single_validator_engine("pool-producer", first.encoded_size().max(second.encoded_size())).await;
+
+    // Only one of the two transactions fits; the other waits for the next block
+    producer.add_transaction(first).await.unwrap();
+    producer.add_transaction(second).await.unwrap();
+    let block = producer.produce_block().await.unwrap().unwrap();
+    assert_eq!(block.transactions.len(), 1);
+    assert_eq!(producer.pending_transactions().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_admission_checks_signature_nonce_and_balance() {
+    let producer = single_validator_engine("admission-producer", 1_048_576).await;
+    let alice = GridTokenXKeyPair::from_node_key("admission-alice").unwrap();
+    let mallory = GridTokenXKeyPair::from_node_key("admission-mallory").unwrap();
+    producer.add_transaction(mint_envelope("admission-alice", EnergyAmount::from_wh(100_000_000), 0)).await.unwrap();
+    producer.produce_block().await.unwrap().unwrap();
+
+    let signed = |amount: Balance, nonce: u64, keypair: &GridTokenXKeyPair| {
+        let mut envelope = transfer_envelope(alice.account_id(), "bob", amount, nonce, 1);
+        envelope.gas_limit = 21_000;
+        envelope.sign(keypair);
+        envelope
+    };
+
+    let unsigned = transfer_envelope(alice.account_id(), "bob", 10, 1, 1);
+    assert_eq!(producer.admit_transaction(unsigned).await, Err(AdmissionRejection::Signature(SignatureRejection::Unsigned)));
+    assert_eq!(
+        producer.admit_transaction(signed(10, 1, &mallory)).await,
+        Err(AdmissionRejection::Signature(SignatureRejection::SignerMismatch {
+            sender: alice.account_id().to_string(),
+            signer: mallory.account_id().to_string(),
+        }))
+    );
+
+    // The signature covers the gas terms, not just the transaction body
+    let mut repriced = signed(10, 1, &alice);
+    repriced.gas_price = 2;
+    assert_eq!(producer.admit_transaction(repriced).await, Err(AdmissionRejection::Signature(SignatureRejection::InvalidSignature)));
+
+    // The mint used nonce 0, and the fee counts towards what Alice has to cover
+    assert_eq!(producer.admit_transaction(signed(10, 0, &alice)).await, Err(AdmissionRejection::NonceTooLow { expected: 1, found: 0 }));
+    assert_eq!(
+        producer.admit_transaction(signed(80_000, 1, &alice)).await,
+        Err(AdmissionRejection::InsufficientBalance { required: 101_000, available: 100_000 })
+    );
+
+    let transfer = signed(10, 1, &alice);
+    producer.admit_transaction(transfer.clone()).await.unwrap();
+    assert_eq!(producer.admit_transaction(transfer).await, Err(AdmissionRejection::Pool(PoolRejection::ReplacementUnderpriced {
+        required: 2,
+        found: 1,
+    })));
+}
+
+#[tokio::test]
+async fn test_admission_bounds_what_a_sender_queues() {
+    let producer = single_validator_engine("queue-producer", 1_048_576).await;
+    let alice = GridTokenXKeyPair::from_node_key("queue-alice").unwrap();
+    producer.add_transaction(mint_envelope("queue-alice", EnergyAmount::from_wh(100_000_000), 0)).await.unwrap();
+    producer.produce_block().await.unwrap().unwrap();
+
+    let signed = |amount: Balance, nonce: u64, gas_price: Balance| {
+        let mut envelope = transfer_envelope(alice.account_id(), "bob", amount, nonce, gas_price);
+        envelope.gas_limit = 21_000;
+        envelope.sign(&alice);
+        envelope
+    };
+
+    // Each transaction alone is affordable, but not on top of what Alice already queued
+    producer.admit_transaction(signed(60_000, 1, 1)).await.unwrap();
+    assert_eq!(
+        producer.admit_transaction(signed(10_000, 2, 1)).await,
+        Err(AdmissionRejection::InsufficientBalance { required: 112_000, available: 100_000 })
+    );
+    assert_eq!(
+        producer.admit_transaction(signed(10_000, 5, 1)).await,
+        Err(AdmissionRejection::InsufficientBalance { required: 112_000, available: 100_000 })
+    );
+
+    // A replacement only has to be affordable instead of the transaction it replaces
+    producer.admit_transaction(signed(10, 1, 2)).await.unwrap();
+    producer.admit_transaction(signed(10_000, 2, 1)).await.unwrap();
+
+    let horizon = 1 + MAX_FUTURE_NONCES;
+    assert_eq!(
+        producer.admit_transaction(signed(10, horizon, 1)).await,
+        Err(AdmissionRejection::NonceTooHigh { expected: 1, found: horizon })
+    );
+    producer.admit_transaction(signed(10, horizon - 1, 1)).await.unwrap();
+    assert_eq!(producer.pending_transactions().await.len(), 3);
+}
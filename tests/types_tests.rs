@@ -6,8 +6,8 @@ use uuid::Uuid;
 fn test_energy_trade_creation() {
     let trade = EnergyTrade {
         trade_id: "test_trade_123".to_string(),
-        energy_amount: 100.0,
-        price_per_unit: 120_000_000_000_000_000_000u128, // 0.12 THB in wei
+        energy_amount: EnergyAmount::from_wh(100_000),
+        price_per_unit: TokenPrice::from_satang(450), // 4.50 THB/kWh
         buyer_id: "buyer_123".to_string(),
         seller_id: "seller_123".to_string(),
         timestamp: Utc::now().timestamp() as u64,
@@ -17,12 +17,12 @@ fn test_energy_trade_creation() {
         id: "test_trade_123".to_string(),
         buy_order_id: "buy_123".to_string(),
         sell_order_id: "sell_123".to_string(),
-        price_per_kwh: 120_000_000_000_000_000_000u128,
-        total_price: 12_000_000_000_000_000_000_000u128,
-        grid_fee: 500_000_000_000_000_000_000u128,
+        price_per_kwh: TokenPrice::from_satang(450),
+        total_price: 45_000,
+        grid_fee: 2_250,
         energy_source: EnergySource::Solar,
         carbon_offset: CarbonOffset {
-            offset_credits: 10.0,
+            offset_credits: FixedPoint::from_int(10),
             verified: true,
             certification_body: "carbon_issuer".to_string(),
             timestamp: Utc::now(),
@@ -30,7 +30,8 @@ fn test_energy_trade_creation() {
     };
     
     assert_eq!(trade.trade_id, "test_trade_123");
-    assert_eq!(trade.energy_amount, 100.0);
+    assert_eq!(trade.energy_amount, EnergyAmount::from_wh(100_000));
+    assert_eq!(trade.price_per_unit.total(trade.energy_amount), trade.total_price);
     assert_eq!(trade.status, TradeStatus::Completed);
     assert_eq!(trade.energy_source, EnergySource::Solar);
 }
@@ -40,8 +41,8 @@ fn test_energy_order_creation() {
     let order = EnergyOrder {
         id: Uuid::new_v4(),
         order_type: OrderType::Buy,
-        energy_amount: 150.0,
-        price_per_unit: 150_000_000_000_000_000_000u128,
+        energy_amount: EnergyAmount::from_wh(150_000),
+        price_per_unit: TokenPrice::from_satang(500),
         location: utils::testing::create_test_grid_location(),
         energy_source: Some(EnergySource::Wind),
         timestamp: Utc::now(),
@@ -51,7 +52,7 @@ fn test_energy_order_creation() {
     };
     
     assert_eq!(order.order_type, OrderType::Buy);
-    assert_eq!(order.energy_amount, 150.0);
+    assert_eq!(order.energy_amount, EnergyAmount::from_wh(150_000));
     assert_eq!(order.energy_source, Some(EnergySource::Wind));
     assert_eq!(order.status, OrderStatus::Pending);
 }
@@ -60,31 +61,31 @@ fn test_energy_order_creation() {
 fn test_grid_status_functionality() {
     let grid_status = GridStatus {
         region: "Bangkok".to_string(),
-        current_load: 5000.0,
-        max_capacity: 10000.0,
-        health: 0.95,
+        current_load: EnergyAmount::from_kwh(5000).unwrap(),
+        max_capacity: EnergyAmount::from_kwh(10000).unwrap(),
+        health: FixedPoint::from_millionths(950_000),
         last_updated: Utc::now(),
         location: utils::testing::create_test_grid_location(),
-        capacity: 10000.0,
+        capacity: EnergyAmount::from_kwh(10000).unwrap(),
         congestion_level: CongestionLevel::Low,
-        stability_score: 0.9,
-        outage_risk: 0.05,
+        stability_score: FixedPoint::from_millionths(900_000),
+        outage_risk: FixedPoint::from_millionths(50_000),
         updated_at: Utc::now(),
     };
     
     // Test can_handle_load method
-    assert!(grid_status.can_handle_load(2000.0)); // 5000 + 2000 = 7000 < 10000
-    assert!(grid_status.can_handle_load(5000.0)); // 5000 + 5000 = 10000 = 10000
-    assert!(!grid_status.can_handle_load(6000.0)); // 5000 + 6000 = 11000 > 10000
+    assert!(grid_status.can_handle_load(EnergyAmount::from_kwh(2000).unwrap())); // 5000 + 2000 = 7000 < 10000
+    assert!(grid_status.can_handle_load(EnergyAmount::from_kwh(5000).unwrap())); // 5000 + 5000 = 10000 = 10000
+    assert!(!grid_status.can_handle_load(EnergyAmount::from_kwh(6000).unwrap())); // 5000 + 6000 = 11000 > 10000
     
     assert_eq!(grid_status.congestion_level, CongestionLevel::Low);
-    assert_eq!(grid_status.health, 0.95);
+    assert_eq!(grid_status.health, FixedPoint::from_millionths(950_000));
 }
 
 #[test]
 fn test_energy_production_record() {
     let production_record = EnergyProductionRecord {
-        amount: 250.0,
+        amount: EnergyAmount::from_wh(250_000),
         energy_type: EnergySource::Solar,
         location: utils::testing::create_test_grid_location(),
         timestamp: Utc::now(),
@@ -101,7 +102,7 @@ fn test_energy_production_record() {
         energy_source: EnergySource::Solar, // Compatibility field
     };
     
-    assert_eq!(production_record.amount, 250.0);
+    assert_eq!(production_record.amount, EnergyAmount::from_wh(250_000));
     assert_eq!(production_record.energy_type, EnergySource::Solar);
     assert!(production_record.verified);
     assert_eq!(production_record.efficiency, 0.92);
@@ -124,16 +125,16 @@ fn test_governance_proposal() {
             no_votes: 2000,
             abstain_votes: 500,
             total_voting_power: 10000,
-            participation_rate: 0.75,
+            participation_rate: FixedPoint::from_millionths(750_000),
             total_eligible: 100,
-            turnout_percentage: 75.0,
+            turnout_percentage: FixedPoint::from_int(75),
         },
     };
     
     assert_eq!(proposal.proposal_type, ProposalType::GridUpgrade);
     assert_eq!(proposal.status, ProposalStatus::Active);
     assert_eq!(proposal.vote_results.yes_votes, 5000);
-    assert_eq!(proposal.vote_results.participation_rate, 0.75);
+    assert_eq!(proposal.vote_results.participation_rate, FixedPoint::from_millionths(750_000));
 }
 
 #[test]
@@ -143,35 +144,35 @@ fn test_voting_results() {
         no_votes: 2000,
         abstain_votes: 500,
         total_voting_power: 10000,
-        participation_rate: 0.80,
+        participation_rate: FixedPoint::from_millionths(800_000),
         total_eligible: 125,
-        turnout_percentage: 80.0,
+        turnout_percentage: FixedPoint::from_int(80),
     };
     
     assert_eq!(results.yes_votes, 7500);
     assert_eq!(results.no_votes, 2000);
     assert_eq!(results.abstain_votes, 500);
     assert_eq!(results.total_voting_power, 10000);
-    assert_eq!(results.participation_rate, 0.80);
+    assert_eq!(results.participation_rate, FixedPoint::from_millionths(800_000));
 }
 
 #[test]
 fn test_market_data() {
     let market_data = MarketData {
-        current_price: 5500.0,
-        volume_24h: 15000.0,
+        current_price: TokenPrice::from_satang(5500),
+        volume_24h: EnergyAmount::from_wh(15_000_000),
         price_change_24h: 2.5,
         trades_24h: 250,
-        high_24h: 6000.0,
-        low_24h: 5000.0,
+        high_24h: TokenPrice::from_satang(6000),
+        low_24h: TokenPrice::from_satang(5000),
         timestamp: Utc::now(),
         energy_source: EnergySource::Mixed,
         location: utils::testing::create_test_grid_location(),
         price_trend: PriceTrend::Rising,
     };
     
-    assert_eq!(market_data.current_price, 5500.0);
-    assert_eq!(market_data.volume_24h, 15000.0);
+    assert_eq!(market_data.current_price, TokenPrice::from_satang(5500));
+    assert_eq!(market_data.volume_24h, EnergyAmount::from_wh(15_000_000));
     assert_eq!(market_data.price_change_24h, 2.5);
     assert_eq!(market_data.trades_24h, 250);
     assert_eq!(market_data.energy_source, EnergySource::Mixed);
@@ -237,7 +238,7 @@ fn test_grid_location() {
     let location = GridLocation {
         province: "Chiang Mai".to_string(),
         district: "Muang".to_string(),
-        coordinates: GridCoordinates { lat: 18.7883, lng: 98.9853 },
+        coordinates: GridCoordinates { lat: FixedPoint::from_millionths(18_788_300), lng: FixedPoint::from_millionths(98_985_300) },
         region: "North".to_string(),
         substation: "CM-Central".to_string(),
         grid_code: "CM-001".to_string(),
@@ -247,20 +248,21 @@ fn test_grid_location() {
     assert_eq!(location.province, "Chiang Mai");
     assert_eq!(location.district, "Muang");
     assert_eq!(location.region, "North");
-    assert_eq!(location.coordinates.lat, 18.7883);
-    assert_eq!(location.coordinates.lng, 98.9853);
+    assert_eq!(location.coordinates.lat.as_f64(), 18.7883);
+    assert_eq!(location.coordinates.lng.as_f64(), 98.9853);
+    assert_eq!(location.coordinates, GridCoordinates::from_degrees(18.7883, 98.9853).unwrap());
 }
 
 #[test]
 fn test_carbon_offset() {
     let carbon_offset = CarbonOffset {
-        offset_credits: 25.5,
+        offset_credits: FixedPoint::from_millionths(25_500_000),
         verified: true,
         certification_body: "carbon_authority".to_string(),
         timestamp: Utc::now(),
     };
     
-    assert_eq!(carbon_offset.offset_credits, 25.5);
+    assert_eq!(carbon_offset.offset_credits.as_f64(), 25.5);
     assert!(carbon_offset.verified);
     assert_eq!(carbon_offset.certification_body, "carbon_authority");
 }
@@ -283,12 +285,47 @@ fn test_quality_metrics() {
 #[test]
 fn test_balance_and_amounts() {
     let balance: Balance = 1000000;
-    let energy_amount: EnergyAmount = 500.5;
-    let token_price: TokenPrice = 4750.25;
+    let energy_amount = EnergyAmount::from_kwh_f64(500.5).unwrap();
+    let token_price = TokenPrice::from_thb_f64(47.5025).unwrap();
     
     assert_eq!(balance, 1000000);
-    assert_eq!(energy_amount, 500.5);
-    assert_eq!(token_price, 4750.25);
+    assert_eq!(energy_amount.as_wh(), 500_500);
+    // Only whole kWh convert to tokens
+    assert_eq!(energy_amount.tokens(), None);
+    assert_eq!(EnergyAmount::from_wh(500_000).tokens(), Some(500));
+    assert_eq!(energy_amount.to_string(), "500.500 kWh");
+    // Prices are rounded to the nearest satang
+    assert_eq!(token_price.as_satang(), 4750);
+    assert_eq!(token_price.to_string(), "47.50 THB/kWh");
+    assert_eq!(EnergyAmount::from_kwh(500), Some(EnergyAmount::from_wh(500_000)));
+}
+
+#[test]
+fn test_fixed_point_arithmetic() {
+    // 1.001 kWh at 47.50 THB/kWh is 4754.75 satang, rounded down
+    let price = TokenPrice::from_satang(4750);
+    assert_eq!(price.total(EnergyAmount::from_wh(1_001)), 4754);
+    assert_eq!(TokenPrice::average(4750, EnergyAmount::from_wh(1_000)), Some(price));
+    assert_eq!(TokenPrice::average(4750, EnergyAmount::ZERO), None);
+    assert_eq!(price.midpoint(TokenPrice::from_satang(4751)), price);
+
+    let amount = EnergyAmount::from_wh(u64::MAX);
+    assert_eq!(amount.checked_add(EnergyAmount::from_wh(1)), None);
+    assert_eq!(amount.saturating_add(EnergyAmount::from_wh(1)), amount);
+    assert_eq!(EnergyAmount::ZERO.checked_sub(EnergyAmount::from_wh(1)), None);
+    assert_eq!(EnergyAmount::from_wh(10_000).scale_bps(9_500), EnergyAmount::from_wh(9_500));
+
+    // Amounts and prices serialize as their integer units
+    assert_eq!(serde_json::to_string(&EnergyAmount::from_wh(1_500)).unwrap(), "1500");
+    assert_eq!(serde_json::to_string(&price).unwrap(), "4750");
+}
+
+#[test]
+fn test_external_amounts_are_validated() {
+    assert!(EnergyAmount::from_kwh_f64(-1.0).is_err());
+    assert!(EnergyAmount::from_kwh_f64(f64::NAN).is_err());
+    assert!(TokenPrice::from_thb_f64(f64::INFINITY).is_err());
+    assert_eq!(EnergyAmount::from_kwh(u64::MAX), None);
 }
 
 #[test]
@@ -1,4 +1,5 @@
 use thai_energy_trading_blockchain::utils::*;
+use thai_energy_trading_blockchain::{EnergyAmount, TokenPrice};
 
 #[test]
 fn test_transaction_id_generation() {
@@ -71,14 +72,14 @@ fn test_validation_utilities() {
     assert!(!validation::is_valid_email("invalid_email"));
     
     // Test energy amount validation
-    assert!(validation::is_valid_energy_amount(100));
-    assert!(!validation::is_valid_energy_amount(0));
-    assert!(!validation::is_valid_energy_amount(2_000_000));
+    assert!(validation::is_valid_energy_amount(EnergyAmount::from_wh(100_000)));
+    assert!(!validation::is_valid_energy_amount(EnergyAmount::ZERO));
+    assert!(!validation::is_valid_energy_amount(EnergyAmount::from_kwh(2_000_000).unwrap()));
     
     // Test price validation
-    assert!(validation::is_valid_price(1000));
-    assert!(!validation::is_valid_price(0));
-    assert!(!validation::is_valid_price(200_000_000));
+    assert!(validation::is_valid_price(TokenPrice::from_satang(1000)));
+    assert!(!validation::is_valid_price(TokenPrice::ZERO));
+    assert!(!validation::is_valid_price(TokenPrice::from_satang(200_000_000)));
     
     // Test account ID validation
     assert!(validation::is_valid_account_id("valid_account"));
@@ -227,7 +228,7 @@ fn test_testing_utilities() {
     // Test test energy order creation
     let order = testing::create_test_energy_order();
     assert_eq!(order.order_type, thai_energy_trading_blockchain::OrderType::Buy);
-    assert_eq!(order.energy_amount, 100.0);
+    assert_eq!(order.energy_amount, EnergyAmount::from_wh(100_000));
 }
 
 #[test]
@@ -0,0 +1,290 @@
+use chrono::Utc;
+use std::time::SystemTime;
+use uuid::Uuid;
+use thai_energy_trading_blockchain::blockchain::chain_spec::{ChainSpec, GenesisAuthority, DEVELOPMENT_ORACLE};
+use thai_energy_trading_blockchain::blockchain::consensus::{
+    BlockImportResult, BlockRejection, ConsensusEngine, EnergyBlock,
+};
+use thai_energy_trading_blockchain::blockchain::state_transition::ReceiptStatus;
+use thai_energy_trading_blockchain::blockchain::transactions::{
+    EnergyProductionRecord, EnergyQualityMetrics, EnergyTransaction, EnergyTransactionEnvelope,
+    ValidatorSignature, VoteChoice,
+};
+use thai_energy_trading_blockchain::blockchain::validator_set::{ValidatorCandidate, ValidatorSetChange};
+use thai_energy_trading_blockchain::config::BlockchainConfig;
+use thai_energy_trading_blockchain::*;
+
+const VALIDATOR_KEYS: [&str; 2] = ["epoch-a", "epoch-b"];
+const CANDIDATE_KEY: &str = "epoch-c";
+
+/// Validator whose set changes every second block
+fn validator_config(node_key: &str, max_validators: u32) -> BlockchainConfig {
+    BlockchainConfig {
+        node_key: node_key.to_string(),
+        validator: true,
+        validator_rotation_interval: 2,
+        max_validators,
+        ..BlockchainConfig::default()
+    }
+}
+
+/// Verified solar production report, attested by the development oracle and signed by
+/// `producer`, that mints `amount` tokens without paying a fee
+fn mint_envelope(producer: &str, amount: EnergyAmount, nonce: u64) -> EnergyTransactionEnvelope {
+    let record = EnergyProductionRecord {
+        amount,
+        energy_type: EnergySource::Solar,
+        location: utils::testing::create_test_grid_location(),
+        timestamp: SystemTime::UNIX_EPOCH,
+        verified: true,
+        efficiency: FixedPoint::from_millionths(900_000),
+        weather_conditions: None,
+        equipment_id: format!("panel-{}", producer),
+        quality_metrics: EnergyQualityMetrics {
+            voltage: FixedPoint::from_int(230),
+            frequency: FixedPoint::from_int(50),
+            power_factor: FixedPoint::from_millionths(950_000),
+            harmonic_distortion: FixedPoint::from_millionths(20_000),
+        },
+    };
+    let oracle = GridTokenXKeyPair::from_node_key(DEVELOPMENT_ORACLE).unwrap();
+    let attestation = ValidatorSignature::attest(&oracle, &account(producer), &record);
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::ReportProduction {
+            producer: account(producer),
+            production_record: record,
+            validator_signatures: vec![attestation],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(producer).unwrap());
+    envelope
+}
+
+/// Chain founded by the validators of `VALIDATOR_KEYS`
+fn founding_spec(max_validators: u32) -> ChainSpec {
+    ChainSpec {
+        authorities: VALIDATOR_KEYS.iter().map(|key| GenesisAuthority::development(key).unwrap()).collect(),
+        ..ChainSpec::development(&validator_config(VALIDATOR_KEYS[0], max_validators))
+    }
+}
+
+/// Validator on the chain founded by the validators of `VALIDATOR_KEYS`
+async fn validator(key: &str, max_validators: u32) -> ConsensusEngine {
+    ConsensusEngine::with_chain_spec(&validator_config(key, max_validators), &founding_spec(max_validators)).await.unwrap()
+}
+
+/// Produce a block minting tokens to `recipient`
+async fn produce(producer: &ConsensusEngine, recipient: &str) -> EnergyBlock {
+    producer.add_transaction(mint_envelope(recipient, EnergyAmount::from_wh(100_000), 0)).await.unwrap();
+    producer.produce_block().await.unwrap().unwrap()
+}
+
+fn account(key: &str) -> AccountId {
+    GridTokenXKeyPair::from_node_key(key).unwrap().account_id().to_string()
+}
+
+fn candidate() -> ValidatorCandidate {
+    let keypair = GridTokenXKeyPair::from_node_key(CANDIDATE_KEY).unwrap();
+    ValidatorCandidate {
+        account_id: keypair.account_id().to_string(),
+        name: CANDIDATE_KEY.to_string(),
+        public_key: keypair.export_public_key_bytes().to_vec(),
+    }
+}
+
+/// Proposal to admit the candidate, paying no fee and staking nothing
+fn add_candidate_proposal() -> EnergyTransactionEnvelope {
+    add_validator_proposal(candidate(), 0)
+}
+
+/// Proposal to admit `candidate`, paying no fee and staking nothing
+fn add_validator_proposal(candidate: ValidatorCandidate, nonce: u64) -> EnergyTransactionEnvelope {
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::GovernanceProposal {
+            proposer: account("proposer"),
+            proposal: GovernanceProposal {
+                id: Uuid::new_v4(),
+                title: format!("Admit {}", candidate.name),
+                description: format!("Add {} to the validator set", candidate.name),
+                proposer: account("proposer"),
+                proposal_type: ProposalType::AddValidator {
+                    account_id: candidate.account_id,
+                    name: candidate.name,
+                    public_key: candidate.public_key,
+                },
+                voting_deadline: Utc::now() + chrono::Duration::hours(1),
+                minimum_voting_power: 0,
+                status: ProposalStatus::Active,
+                created_at: Utc::now(),
+                vote_results: VotingResults {
+                    yes_votes: 0,
+                    no_votes: 0,
+                    abstain_votes: 0,
+                    total_voting_power: 0,
+                    participation_rate: FixedPoint::ZERO,
+                    total_eligible: 0,
+                    turnout_percentage: FixedPoint::ZERO,
+                },
+            },
+            stake_amount: 0,
+            signature: vec![],
+        },
+        nonce,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key("proposer").unwrap());
+    envelope
+}
+
+/// Vote by the validator with `voter_key` in favour of a proposal, paying no fee
+fn approval(voter_key: &str, proposal: &EnergyTransactionEnvelope) -> EnergyTransactionEnvelope {
+    let proposal_id = match &proposal.transaction {
+        EnergyTransaction::GovernanceProposal { proposal, .. } => proposal.id,
+        _ => unreachable!("not a proposal"),
+    };
+    let mut envelope = EnergyTransactionEnvelope::new(
+        EnergyTransaction::Vote {
+            voter: account(voter_key),
+            proposal_id,
+            choice: VoteChoice::For,
+            voting_power: 1,
+            signature: vec![],
+        },
+        0,
+    );
+    envelope.gas_price = 0;
+    envelope.sign(&GridTokenXKeyPair::from_node_key(voter_key).unwrap());
+    envelope
+}
+
+/// Produce block 1 with a proposal to admit the candidate that both founding authorities
+/// approve, then block 2 at the epoch boundary
+async fn run_epoch(max_validators: u32) -> (ConsensusEngine, ConsensusEngine, [EnergyBlock; 2]) {
+    let first = validator(VALIDATOR_KEYS[0], max_validators).await;
+    let second = validator(VALIDATOR_KEYS[1], max_validators).await;
+
+    let proposal = add_candidate_proposal();
+    first.add_transaction(proposal.clone()).await.unwrap();
+    for key in VALIDATOR_KEYS {
+        first.add_transaction(approval(key, &proposal)).await.unwrap();
+    }
+    let proposing = first.produce_block().await.unwrap().unwrap();
+    assert_eq!(second.import_block(proposing.clone()).await.unwrap(), BlockImportResult::Imported);
+    let boundary = produce(&second, "alice").await;
+    assert_eq!(first.import_block(boundary.clone()).await.unwrap(), BlockImportResult::Imported);
+    (first, second, [proposing, boundary])
+}
+
+#[tokio::test]
+async fn test_approved_validator_joins_at_the_epoch_boundary() {
+    let (first, second, [proposing, boundary]) = run_epoch(10).await;
+    assert_eq!(proposing.header.validator_set_change, None);
+    assert_eq!(boundary.header.validator_set_change, Some(ValidatorSetChange {
+        added: vec![candidate()],
+        removed: vec![],
+    }));
+
+    for node in [&first, &second] {
+        let authority = node.get_authority(&candidate().account_id).await.unwrap();
+        assert!(authority.is_active);
+        assert_eq!(node.get_blockchain_state().await.validator_set.proposals().passed_count(), 0);
+    }
+
+    // The candidate joins the rotation after the founding authorities
+    let third = produce(&first, "bob").await;
+    assert_eq!(second.import_block(third.clone()).await.unwrap(), BlockImportResult::Imported);
+    let fourth = produce(&second, "carol").await;
+    assert_eq!(first.import_block(fourth.clone()).await.unwrap(), BlockImportResult::Imported);
+
+    let joined = validator(CANDIDATE_KEY, 10).await;
+    for block in [&proposing, &boundary, &third, &fourth] {
+        assert_eq!(joined.import_block(block.clone()).await.unwrap(), BlockImportResult::Imported);
+    }
+    let block = produce(&joined, "dave").await;
+    assert_eq!(block.header.validator, candidate().account_id);
+    assert_eq!(first.import_block(block).await.unwrap(), BlockImportResult::Imported);
+}
+
+#[tokio::test]
+async fn test_full_validator_set_defers_additions() {
+    let (first, _, [_, boundary]) = run_epoch(2).await;
+    assert_eq!(boundary.header.validator_set_change, None);
+
+    assert!(first.get_authority(&candidate().account_id).await.is_none());
+    assert_eq!(first.get_blockchain_state().await.validator_set.proposals().passed_count(), 1);
+}
+
+#[tokio::test]
+async fn test_block_with_unexpected_validator_set_change_is_rejected() {
+    let producer = validator(VALIDATOR_KEYS[0], 10).await;
+    let observer = validator(VALIDATOR_KEYS[1], 10).await;
+    let mut block = produce(&producer, "alice").await;
+
+    // Block 1 is not an epoch boundary, so it may not change the validator set
+    let keypair = GridTokenXKeyPair::from_node_key(VALIDATOR_KEYS[0]).unwrap();
+    let change = ValidatorSetChange { added: vec![candidate()], removed: vec![] };
+    block.header.validator_set_change = Some(change.clone());
+    block.header.hash = ConsensusEngine::calculate_block_hash(&block);
+    block.validator_signature.signature = keypair.sign(&hex::decode(&block.header.hash).unwrap()).to_bytes().to_vec();
+
+    assert_eq!(
+        observer.import_block(block).await.unwrap(),
+        BlockImportResult::Rejected(BlockRejection::ValidatorSetChangeMismatch { expected: None, found: Some(change) })
+    );
+    assert!(observer.get_authority(&candidate().account_id).await.is_none());
+}
+
+#[tokio::test]
+async fn test_proposal_to_add_a_validator_with_a_foreign_or_invalid_key_fails() {
+    let producer = validator(VALIDATOR_KEYS[0], 10).await;
+    let impostor = ValidatorCandidate { account_id: account("mallory"), ..candidate() };
+    let malformed = ValidatorCandidate { public_key: vec![7; 31], ..candidate() };
+    producer.add_transaction(add_validator_proposal(impostor, 0)).await.unwrap();
+    producer.add_transaction(add_validator_proposal(malformed, 1)).await.unwrap();
+
+    let block = producer.produce_block().await.unwrap().unwrap();
+    assert_eq!(block.receipts.len(), 2);
+    for receipt in &block.receipts {
+        assert_eq!(receipt.status, ReceiptStatus::Failed);
+        assert!(receipt.error.as_deref().unwrap().contains("public key"), "{:?}", receipt.error);
+    }
+    assert_eq!(producer.get_blockchain_state().await.validator_set.proposals().open_count(), 0);
+}
+
+#[tokio::test]
+async fn test_reorg_rolls_back_proposals_of_the_retracted_branch() {
+    // Two competing block 1s by the first authority: one approves the candidate, one doesn't
+    let proposer = validator(VALIDATOR_KEYS[0], 10).await;
+    let proposal = add_candidate_proposal();
+    proposer.add_transaction(proposal.clone()).await.unwrap();
+    for key in VALIDATOR_KEYS {
+        proposer.add_transaction(approval(key, &proposal)).await.unwrap();
+    }
+    let approving = proposer.produce_block().await.unwrap().unwrap();
+    let competing = produce(&validator(VALIDATOR_KEYS[0], 10).await, "alice").await;
+
+    let second = validator(VALIDATOR_KEYS[1], 10).await;
+    assert_eq!(second.import_block(competing.clone()).await.unwrap(), BlockImportResult::Imported);
+    let boundary = produce(&second, "bob").await;
+    assert_eq!(boundary.header.validator_set_change, None);
+
+    let observer = ConsensusEngine::with_chain_spec(
+        &BlockchainConfig { validator: false, ..validator_config("epoch-observer", 10) },
+        &founding_spec(10),
+    ).await.unwrap();
+    for block in [&approving, &competing] {
+        assert_eq!(observer.import_block(block.clone()).await.unwrap(), BlockImportResult::Imported);
+    }
+    assert_eq!(observer.get_blockchain_state().await.validator_set.proposals().passed_count(), 1);
+
+    // The boundary on the competing branch is checked against that branch's proposals, and
+    // the approval is retracted with the block that carried it
+    assert_eq!(observer.import_block(boundary.clone()).await.unwrap(), BlockImportResult::Imported);
+    let state = observer.get_blockchain_state().await;
+    assert_eq!(state.latest_block_hash, boundary.header.hash);
+    assert_eq!(state.validator_set.proposals().passed_count(), 0);
+    assert_eq!(state.validator_set.proposals().open_count(), 0);
+    assert!(observer.get_authority(&candidate().account_id).await.is_none());
+}